@@ -1,3 +1,4 @@
+use serde::de::{Deserialize, Deserializer, Error};
 use serde::ser::{Serialize, Serializer};
 
 /// One of the available colors for a category.
@@ -39,3 +40,26 @@ impl Serialize for CategoryColor {
         }
     }
 }
+
+impl<'de> Deserialize<'de> for CategoryColor {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let color = String::deserialize(deserializer)?;
+        Ok(match color.as_str() {
+            "transparent" => CategoryColor::Transparent,
+            "lightblue" => CategoryColor::LightBlue,
+            "red" => CategoryColor::Red,
+            "lightred" => CategoryColor::LightRed,
+            "orange" => CategoryColor::Orange,
+            "blue" => CategoryColor::Blue,
+            "green" => CategoryColor::Green,
+            "purple" => CategoryColor::Purple,
+            "yellow" => CategoryColor::Yellow,
+            "brown" => CategoryColor::Brown,
+            "magenta" => CategoryColor::Magenta,
+            "lightgreen" => CategoryColor::LightGreen,
+            "grey" | "gray" => CategoryColor::Gray,
+            "darkgray" | "darkgrey" => CategoryColor::DarkGray,
+            _ => return Err(D::Error::custom(format!("unknown category color {color}"))),
+        })
+    }
+}
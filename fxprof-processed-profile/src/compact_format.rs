@@ -0,0 +1,1185 @@
+// The compact profile format is a binary encoding of the processed profile JSON.
+// It encodes the same tree of values, so the converter can expand it back into
+// exactly the JSON which serde_json would have written for the profile.
+//
+// The file starts with the magic bytes "FXPC", followed by the format version
+// as a little-endian u32. Then follows a single tagged value:
+//
+//  - TAG_NULL, TAG_FALSE, TAG_TRUE: no payload.
+//  - TAG_UINT: a varint. TAG_NEG_INT: a varint containing -(value + 1).
+//  - TAG_F64: 8 bytes, little-endian.
+//  - TAG_STRING: a string reference, see below.
+//  - TAG_SEQ: tagged values until TAG_END.
+//  - TAG_MAP: pairs of tagged keys (strings or integers) and tagged values,
+//    until TAG_END.
+//  - TAG_INT_COLUMN, TAG_F64_COLUMN: a sequence of numbers and nulls. This is
+//    what the columns of the profile's tables are encoded as. The payload is
+//    the varint length, a byte which is 1 if the column contains nulls, in that
+//    case a bitmap with one bit per element (1 = null), and then the non-null
+//    values. Integers are stored as zigzag-encoded varint deltas to the
+//    previous non-null value. Float columns have an additional byte before the
+//    values: if it is a decimal exponent e between 0 and 9, every float is
+//    stored like an integer as value * 10^e, which is exact for all values in
+//    the column, e.g. for timestamps in milliseconds with e = 6. If the byte is
+//    RAW_F64, the floats are stored as 8 little-endian bytes each.
+//
+// Varints are LEB128-encoded u64s. A string reference is a varint index into
+// the list of strings seen so far. If the index is equal to the length of that
+// list, the reference introduces a new string, and the varint byte length and
+// the UTF-8 bytes of the string follow.
+//
+// Any change to this encoding needs a new format version.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::io::{Read, Write};
+
+use serde::ser::{
+    Impossible, Serialize, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant,
+    SerializeTuple, SerializeTupleStruct, SerializeTupleVariant, Serializer,
+};
+
+use crate::fast_hash_map::FastHashMap;
+use crate::Profile;
+
+/// The version of the compact profile format which is written by
+/// [`write_compact_profile`]. [`compact_profile_to_json`] only accepts data in
+/// this version.
+pub const COMPACT_PROFILE_FORMAT_VERSION: u32 = 1;
+
+const MAGIC: &[u8] = b"FXPC";
+
+const TAG_NULL: u8 = 0;
+const TAG_FALSE: u8 = 1;
+const TAG_TRUE: u8 = 2;
+const TAG_UINT: u8 = 3;
+const TAG_NEG_INT: u8 = 4;
+const TAG_F64: u8 = 5;
+const TAG_STRING: u8 = 6;
+const TAG_SEQ: u8 = 7;
+const TAG_MAP: u8 = 8;
+const TAG_INT_COLUMN: u8 = 9;
+const TAG_F64_COLUMN: u8 = 10;
+const TAG_END: u8 = 11;
+
+const RAW_F64: u8 = 0xff;
+
+/// How deeply sequences and maps can be nested in the data which is converted by
+/// [`compact_profile_to_json`]. The processed profile JSON is much shallower than this.
+const MAX_NESTING_DEPTH: usize = 128;
+const POWERS_OF_TEN: [f64; 10] = [1e0, 1e1, 1e2, 1e3, 1e4, 1e5, 1e6, 1e7, 1e8, 1e9];
+
+/// An error that can occur when writing or converting a compact profile.
+#[derive(Debug)]
+pub enum CompactProfileError {
+    /// The data could not be read or written.
+    Io(std::io::Error),
+    /// The JSON could not be written.
+    Json(serde_json::Error),
+    /// The data doesn't start with the magic bytes of the compact profile format.
+    NotACompactProfile,
+    /// The data was written in a format version which is not supported.
+    UnsupportedVersion(u32),
+    /// The data is malformed, or the profile could not be serialized.
+    InvalidData(String),
+}
+
+impl fmt::Display for CompactProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompactProfileError::Io(e) => write!(f, "I/O error: {e}"),
+            CompactProfileError::Json(e) => write!(f, "Could not write the profile JSON: {e}"),
+            CompactProfileError::NotACompactProfile => {
+                write!(f, "The data is not in the compact profile format")
+            }
+            CompactProfileError::UnsupportedVersion(version) => write!(
+                f,
+                "Unsupported compact profile format version {version}, expected version {COMPACT_PROFILE_FORMAT_VERSION}"
+            ),
+            CompactProfileError::InvalidData(message) => {
+                write!(f, "Invalid compact profile data: {message}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CompactProfileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CompactProfileError::Io(e) => Some(e),
+            CompactProfileError::Json(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for CompactProfileError {
+    fn from(e: std::io::Error) -> Self {
+        CompactProfileError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for CompactProfileError {
+    fn from(e: serde_json::Error) -> Self {
+        CompactProfileError::Json(e)
+    }
+}
+
+impl serde::ser::Error for CompactProfileError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        CompactProfileError::InvalidData(msg.to_string())
+    }
+}
+
+/// Write the profile in the compact binary profile format.
+///
+/// The compact format is much smaller and faster to write than the JSON
+/// which is written by `serde_json::to_writer(writer, &profile)`. Use
+/// [`compact_profile_to_json`] to expand it into processed profile JSON,
+/// e.g. before loading it in the Firefox Profiler. [`ProfileView`](crate::ProfileView)
+/// can read both formats.
+///
+/// ```
+/// use fxprof_processed_profile::{compact_profile_to_json, write_compact_profile, Profile, SamplingInterval};
+/// use std::time::SystemTime;
+///
+/// # fn convert() -> Result<(), Box<dyn std::error::Error>> {
+/// let profile = Profile::new("My app", SystemTime::now().into(), SamplingInterval::from_millis(1));
+/// let mut compact = Vec::new();
+/// write_compact_profile(&profile, &mut compact)?;
+///
+/// let mut json = Vec::new();
+/// compact_profile_to_json(&compact[..], &mut json)?;
+/// assert_eq!(serde_json::from_slice::<serde_json::Value>(&json)?, serde_json::to_value(&profile)?);
+/// # Ok(())
+/// # }
+/// ```
+pub fn write_compact_profile<W: Write>(
+    profile: &Profile,
+    writer: W,
+) -> Result<(), CompactProfileError> {
+    let mut encoder = Encoder {
+        writer,
+        strings: FastHashMap::default(),
+    };
+    encoder.writer.write_all(MAGIC)?;
+    encoder
+        .writer
+        .write_all(&COMPACT_PROFILE_FORMAT_VERSION.to_le_bytes())?;
+    profile.serialize(&mut encoder)?;
+    encoder.writer.flush()?;
+    Ok(())
+}
+
+/// Whether `data` starts with the magic bytes of the compact profile format.
+pub fn is_compact_profile(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Convert a profile in the compact binary profile format, as written by
+/// [`write_compact_profile`], into processed profile JSON.
+pub fn compact_profile_to_json<R: Read, W: Write>(
+    mut reader: R,
+    writer: W,
+) -> Result<(), CompactProfileError> {
+    // The whole input is read upfront, so that lengths in the data can be checked
+    // against the amount of data which is left.
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    if !is_compact_profile(&data) {
+        return Err(CompactProfileError::NotACompactProfile);
+    }
+    let mut decoder = Decoder {
+        data: &data[MAGIC.len()..],
+        strings: Vec::new(),
+    };
+    let version = u32::from_le_bytes(decoder.read_bytes(4)?.try_into().unwrap());
+    if version != COMPACT_PROFILE_FORMAT_VERSION {
+        return Err(CompactProfileError::UnsupportedVersion(version));
+    }
+
+    let tag = decoder.read_u8()?;
+    let mut serializer = serde_json::Serializer::new(writer);
+    Transcode {
+        decoder: RefCell::new(&mut decoder),
+        tag,
+        depth: 0,
+    }
+    .serialize(&mut serializer)?;
+    serializer.into_inner().flush()?;
+    Ok(())
+}
+
+fn zigzag_encode(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn zigzag_decode(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+/// Returns value * 10^exponent as an integer, if that integer represents the
+/// value exactly. NaN, infinities and -0.0 have no integer representation, so
+/// columns which contain them are stored as raw floats, which keeps them intact.
+fn scale_to_int(value: f64, exponent: u8) -> Option<i64> {
+    if !value.is_finite() || (value == 0.0 && value.is_sign_negative()) {
+        return None;
+    }
+    let scale = POWERS_OF_TEN[exponent as usize];
+    let scaled = (value * scale).round();
+    // Only integers below 2^53 are exactly representable as f64.
+    if scaled.abs() >= 9007199254740992.0 {
+        return None;
+    }
+    if (scaled / scale).to_bits() != value.to_bits() {
+        return None;
+    }
+    Some(scaled as i64)
+}
+
+/// Finds the smallest decimal exponent with which all floats in the column can
+/// be stored as integers.
+fn find_decimal_exponent(column: &[ColumnValue]) -> Option<u8> {
+    (0..POWERS_OF_TEN.len() as u8).find(|&exponent| {
+        column.iter().all(|value| match value {
+            ColumnValue::F64(value) => scale_to_int(*value, exponent).is_some(),
+            _ => true,
+        })
+    })
+}
+
+struct Encoder<W> {
+    writer: W,
+    strings: FastHashMap<String, u64>,
+}
+
+impl<W: Write> Encoder<W> {
+    fn write_u8(&mut self, value: u8) -> Result<(), CompactProfileError> {
+        self.writer.write_all(&[value])?;
+        Ok(())
+    }
+
+    fn write_varint(&mut self, mut value: u64) -> Result<(), CompactProfileError> {
+        let mut buf = [0; 10];
+        let mut len = 0;
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                buf[len] = byte;
+                len += 1;
+                break;
+            }
+            buf[len] = byte | 0x80;
+            len += 1;
+        }
+        self.writer.write_all(&buf[..len])?;
+        Ok(())
+    }
+
+    fn write_f64(&mut self, value: f64) -> Result<(), CompactProfileError> {
+        self.writer.write_all(&value.to_le_bytes())?;
+        Ok(())
+    }
+
+    fn write_string(&mut self, s: &str) -> Result<(), CompactProfileError> {
+        if let Some(index) = self.strings.get(s) {
+            return self.write_varint(*index);
+        }
+        let index = self.strings.len() as u64;
+        self.strings.insert(s.to_owned(), index);
+        self.write_varint(index)?;
+        self.write_varint(s.len() as u64)?;
+        self.writer.write_all(s.as_bytes())?;
+        Ok(())
+    }
+
+    fn write_tagged_string(&mut self, s: &str) -> Result<(), CompactProfileError> {
+        self.write_u8(TAG_STRING)?;
+        self.write_string(s)
+    }
+
+    fn write_tagged_int(&mut self, value: i64) -> Result<(), CompactProfileError> {
+        if value >= 0 {
+            self.write_u8(TAG_UINT)?;
+            self.write_varint(value as u64)
+        } else {
+            self.write_u8(TAG_NEG_INT)?;
+            self.write_varint(!value as u64)
+        }
+    }
+
+    fn write_tagged_column_value(&mut self, value: ColumnValue) -> Result<(), CompactProfileError> {
+        match value {
+            ColumnValue::Null => self.write_u8(TAG_NULL),
+            ColumnValue::Int(value) => self.write_tagged_int(value),
+            ColumnValue::F64(value) => {
+                self.write_u8(TAG_F64)?;
+                self.write_f64(value)
+            }
+        }
+    }
+
+    fn write_column(&mut self, column: Vec<ColumnValue>) -> Result<(), CompactProfileError> {
+        let has_null = column.iter().any(|v| matches!(v, ColumnValue::Null));
+        let has_int = column.iter().any(|v| matches!(v, ColumnValue::Int(_)));
+        let has_f64 = column.iter().any(|v| matches!(v, ColumnValue::F64(_)));
+
+        if has_int && has_f64 {
+            // Keep the distinction between integers and floats, so that the
+            // JSON output is the same.
+            self.write_u8(TAG_SEQ)?;
+            for value in column {
+                self.write_tagged_column_value(value)?;
+            }
+            return self.write_u8(TAG_END);
+        }
+
+        self.write_u8(if has_f64 {
+            TAG_F64_COLUMN
+        } else {
+            TAG_INT_COLUMN
+        })?;
+        self.write_varint(column.len() as u64)?;
+        self.write_u8(has_null as u8)?;
+        if has_null {
+            let mut bitmap = vec![0u8; (column.len() + 7) / 8];
+            for (i, value) in column.iter().enumerate() {
+                if let ColumnValue::Null = value {
+                    bitmap[i / 8] |= 1 << (i % 8);
+                }
+            }
+            self.writer.write_all(&bitmap)?;
+        }
+        let decimal_exponent = if has_f64 {
+            let decimal_exponent = find_decimal_exponent(&column);
+            self.write_u8(decimal_exponent.unwrap_or(RAW_F64))?;
+            decimal_exponent
+        } else {
+            Some(0)
+        };
+        let mut previous = 0i64;
+        for value in column {
+            let value = match (value, decimal_exponent) {
+                (ColumnValue::Null, _) => continue,
+                (ColumnValue::Int(value), _) => value,
+                (ColumnValue::F64(value), Some(exponent)) => scale_to_int(value, exponent).unwrap(),
+                (ColumnValue::F64(value), None) => {
+                    self.write_f64(value)?;
+                    continue;
+                }
+            };
+            self.write_varint(zigzag_encode(value.wrapping_sub(previous)))?;
+            previous = value;
+        }
+        Ok(())
+    }
+}
+
+impl<'a, W: Write> Serializer for &'a mut Encoder<W> {
+    type Ok = ();
+    type Error = CompactProfileError;
+    type SerializeSeq = SeqEncoder<'a, W>;
+    type SerializeTuple = SeqEncoder<'a, W>;
+    type SerializeTupleStruct = SeqEncoder<'a, W>;
+    type SerializeTupleVariant = SeqEncoder<'a, W>;
+    type SerializeMap = MapEncoder<'a, W>;
+    type SerializeStruct = MapEncoder<'a, W>;
+    type SerializeStructVariant = MapEncoder<'a, W>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), CompactProfileError> {
+        self.write_u8(if v { TAG_TRUE } else { TAG_FALSE })
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), CompactProfileError> {
+        self.write_tagged_int(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), CompactProfileError> {
+        self.write_tagged_int(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), CompactProfileError> {
+        self.write_tagged_int(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), CompactProfileError> {
+        self.write_tagged_int(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), CompactProfileError> {
+        self.write_tagged_int(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), CompactProfileError> {
+        self.write_tagged_int(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), CompactProfileError> {
+        self.write_tagged_int(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), CompactProfileError> {
+        self.write_u8(TAG_UINT)?;
+        self.write_varint(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), CompactProfileError> {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), CompactProfileError> {
+        self.write_u8(TAG_F64)?;
+        self.write_f64(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), CompactProfileError> {
+        self.write_tagged_string(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), CompactProfileError> {
+        self.write_tagged_string(v)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), CompactProfileError> {
+        self.collect_seq(v)
+    }
+
+    fn serialize_none(self) -> Result<(), CompactProfileError> {
+        self.write_u8(TAG_NULL)
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), CompactProfileError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), CompactProfileError> {
+        self.write_u8(TAG_NULL)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), CompactProfileError> {
+        self.write_u8(TAG_NULL)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(), CompactProfileError> {
+        self.write_tagged_string(variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), CompactProfileError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), CompactProfileError> {
+        self.write_u8(TAG_MAP)?;
+        self.write_tagged_string(variant)?;
+        value.serialize(&mut *self)?;
+        self.write_u8(TAG_END)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqEncoder<'a, W>, CompactProfileError> {
+        Ok(SeqEncoder::new(self, len, false))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqEncoder<'a, W>, CompactProfileError> {
+        Ok(SeqEncoder::new(self, Some(len), false))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SeqEncoder<'a, W>, CompactProfileError> {
+        Ok(SeqEncoder::new(self, Some(len), false))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SeqEncoder<'a, W>, CompactProfileError> {
+        self.write_u8(TAG_MAP)?;
+        self.write_tagged_string(variant)?;
+        Ok(SeqEncoder::new(self, Some(len), true))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<MapEncoder<'a, W>, CompactProfileError> {
+        self.write_u8(TAG_MAP)?;
+        Ok(MapEncoder {
+            encoder: self,
+            in_variant: false,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<MapEncoder<'a, W>, CompactProfileError> {
+        self.write_u8(TAG_MAP)?;
+        Ok(MapEncoder {
+            encoder: self,
+            in_variant: false,
+        })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<MapEncoder<'a, W>, CompactProfileError> {
+        self.write_u8(TAG_MAP)?;
+        self.write_tagged_string(variant)?;
+        self.write_u8(TAG_MAP)?;
+        Ok(MapEncoder {
+            encoder: self,
+            in_variant: true,
+        })
+    }
+}
+
+/// A value in a sequence which can be stored in a column.
+#[derive(Debug, Clone, Copy)]
+enum ColumnValue {
+    Null,
+    Int(i64),
+    F64(f64),
+}
+
+/// Buffers the elements of a sequence as long as they are all numbers or nulls,
+/// so that they can be written as a column. Once another value is encountered,
+/// the sequence is written as a list of tagged values instead.
+struct SeqEncoder<'a, W> {
+    encoder: &'a mut Encoder<W>,
+    column: Option<Vec<ColumnValue>>,
+    /// Whether this is the content of a tuple variant, i.e. whether the map
+    /// around it needs to be closed at the end.
+    in_variant: bool,
+}
+
+impl<'a, W: Write> SeqEncoder<'a, W> {
+    fn new(encoder: &'a mut Encoder<W>, len: Option<usize>, in_variant: bool) -> Self {
+        Self {
+            encoder,
+            column: Some(Vec::with_capacity(len.unwrap_or(0))),
+            in_variant,
+        }
+    }
+
+    fn element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), CompactProfileError> {
+        if let Some(column) = &mut self.column {
+            if let Ok(column_value) = value.serialize(ColumnValueProbe) {
+                column.push(column_value);
+                return Ok(());
+            }
+            let column = self.column.take().unwrap();
+            self.encoder.write_u8(TAG_SEQ)?;
+            for column_value in column {
+                self.encoder.write_tagged_column_value(column_value)?;
+            }
+        }
+        value.serialize(&mut *self.encoder)
+    }
+
+    fn finish(self) -> Result<(), CompactProfileError> {
+        match self.column {
+            Some(column) => self.encoder.write_column(column)?,
+            None => self.encoder.write_u8(TAG_END)?,
+        }
+        if self.in_variant {
+            self.encoder.write_u8(TAG_END)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a, W: Write> SerializeSeq for SeqEncoder<'a, W> {
+    type Ok = ();
+    type Error = CompactProfileError;
+
+    fn serialize_element<T: ?Sized + Serialize>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), CompactProfileError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), CompactProfileError> {
+        self.finish()
+    }
+}
+
+impl<'a, W: Write> SerializeTuple for SeqEncoder<'a, W> {
+    type Ok = ();
+    type Error = CompactProfileError;
+
+    fn serialize_element<T: ?Sized + Serialize>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), CompactProfileError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), CompactProfileError> {
+        self.finish()
+    }
+}
+
+impl<'a, W: Write> SerializeTupleStruct for SeqEncoder<'a, W> {
+    type Ok = ();
+    type Error = CompactProfileError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), CompactProfileError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), CompactProfileError> {
+        self.finish()
+    }
+}
+
+impl<'a, W: Write> SerializeTupleVariant for SeqEncoder<'a, W> {
+    type Ok = ();
+    type Error = CompactProfileError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), CompactProfileError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), CompactProfileError> {
+        self.finish()
+    }
+}
+
+struct MapEncoder<'a, W> {
+    encoder: &'a mut Encoder<W>,
+    /// Whether this is the content of a struct variant, i.e. whether the map
+    /// around it needs to be closed at the end.
+    in_variant: bool,
+}
+
+impl<'a, W: Write> MapEncoder<'a, W> {
+    fn field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), CompactProfileError> {
+        self.encoder.write_tagged_string(key)?;
+        value.serialize(&mut *self.encoder)
+    }
+
+    fn finish(self) -> Result<(), CompactProfileError> {
+        self.encoder.write_u8(TAG_END)?;
+        if self.in_variant {
+            self.encoder.write_u8(TAG_END)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a, W: Write> SerializeMap for MapEncoder<'a, W> {
+    type Ok = ();
+    type Error = CompactProfileError;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), CompactProfileError> {
+        // Keys are written as tagged values; the converter checks that they
+        // are strings or integers, like serde_json does.
+        key.serialize(&mut *self.encoder)
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), CompactProfileError> {
+        value.serialize(&mut *self.encoder)
+    }
+
+    fn end(self) -> Result<(), CompactProfileError> {
+        self.finish()
+    }
+}
+
+impl<'a, W: Write> SerializeStruct for MapEncoder<'a, W> {
+    type Ok = ();
+    type Error = CompactProfileError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), CompactProfileError> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), CompactProfileError> {
+        self.finish()
+    }
+}
+
+impl<'a, W: Write> SerializeStructVariant for MapEncoder<'a, W> {
+    type Ok = ();
+    type Error = CompactProfileError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), CompactProfileError> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), CompactProfileError> {
+        self.finish()
+    }
+}
+
+/// The error of [`ColumnValueProbe`], for values which can't be stored in a column.
+#[derive(Debug)]
+struct NotAColumnValue;
+
+impl fmt::Display for NotAColumnValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "not a number or null")
+    }
+}
+
+impl std::error::Error for NotAColumnValue {}
+
+impl serde::ser::Error for NotAColumnValue {
+    fn custom<T: fmt::Display>(_msg: T) -> Self {
+        NotAColumnValue
+    }
+}
+
+/// A serializer which only accepts numbers and nulls.
+struct ColumnValueProbe;
+
+impl Serializer for ColumnValueProbe {
+    type Ok = ColumnValue;
+    type Error = NotAColumnValue;
+    type SerializeSeq = Impossible<ColumnValue, NotAColumnValue>;
+    type SerializeTuple = Impossible<ColumnValue, NotAColumnValue>;
+    type SerializeTupleStruct = Impossible<ColumnValue, NotAColumnValue>;
+    type SerializeTupleVariant = Impossible<ColumnValue, NotAColumnValue>;
+    type SerializeMap = Impossible<ColumnValue, NotAColumnValue>;
+    type SerializeStruct = Impossible<ColumnValue, NotAColumnValue>;
+    type SerializeStructVariant = Impossible<ColumnValue, NotAColumnValue>;
+
+    fn serialize_bool(self, _v: bool) -> Result<ColumnValue, NotAColumnValue> {
+        Err(NotAColumnValue)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<ColumnValue, NotAColumnValue> {
+        Ok(ColumnValue::Int(v.into()))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<ColumnValue, NotAColumnValue> {
+        Ok(ColumnValue::Int(v.into()))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<ColumnValue, NotAColumnValue> {
+        Ok(ColumnValue::Int(v.into()))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<ColumnValue, NotAColumnValue> {
+        Ok(ColumnValue::Int(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<ColumnValue, NotAColumnValue> {
+        Ok(ColumnValue::Int(v.into()))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<ColumnValue, NotAColumnValue> {
+        Ok(ColumnValue::Int(v.into()))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<ColumnValue, NotAColumnValue> {
+        Ok(ColumnValue::Int(v.into()))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<ColumnValue, NotAColumnValue> {
+        // Values above i64::MAX don't fit into an integer column.
+        i64::try_from(v)
+            .map(ColumnValue::Int)
+            .map_err(|_| NotAColumnValue)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<ColumnValue, NotAColumnValue> {
+        Ok(ColumnValue::F64(v.into()))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<ColumnValue, NotAColumnValue> {
+        Ok(ColumnValue::F64(v))
+    }
+
+    fn serialize_char(self, _v: char) -> Result<ColumnValue, NotAColumnValue> {
+        Err(NotAColumnValue)
+    }
+
+    fn serialize_str(self, _v: &str) -> Result<ColumnValue, NotAColumnValue> {
+        Err(NotAColumnValue)
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<ColumnValue, NotAColumnValue> {
+        Err(NotAColumnValue)
+    }
+
+    fn serialize_none(self) -> Result<ColumnValue, NotAColumnValue> {
+        Ok(ColumnValue::Null)
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(
+        self,
+        value: &T,
+    ) -> Result<ColumnValue, NotAColumnValue> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<ColumnValue, NotAColumnValue> {
+        Ok(ColumnValue::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<ColumnValue, NotAColumnValue> {
+        Ok(ColumnValue::Null)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<ColumnValue, NotAColumnValue> {
+        Err(NotAColumnValue)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<ColumnValue, NotAColumnValue> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<ColumnValue, NotAColumnValue> {
+        Err(NotAColumnValue)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, NotAColumnValue> {
+        Err(NotAColumnValue)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, NotAColumnValue> {
+        Err(NotAColumnValue)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, NotAColumnValue> {
+        Err(NotAColumnValue)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, NotAColumnValue> {
+        Err(NotAColumnValue)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, NotAColumnValue> {
+        Err(NotAColumnValue)
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, NotAColumnValue> {
+        Err(NotAColumnValue)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, NotAColumnValue> {
+        Err(NotAColumnValue)
+    }
+}
+
+struct Decoder<'a> {
+    /// The data which hasn't been read yet.
+    data: &'a [u8],
+    strings: Vec<String>,
+}
+
+impl<'a> Decoder<'a> {
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], CompactProfileError> {
+        if len > self.data.len() {
+            return Err(CompactProfileError::InvalidData(
+                "data is truncated".to_string(),
+            ));
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, CompactProfileError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn read_varint(&mut self) -> Result<u64, CompactProfileError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.read_u8()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(CompactProfileError::InvalidData(
+            "varint is too long".to_string(),
+        ))
+    }
+
+    fn read_len(&mut self) -> Result<usize, CompactProfileError> {
+        usize::try_from(self.read_varint()?)
+            .map_err(|_| CompactProfileError::InvalidData("length is too large".to_string()))
+    }
+
+    fn read_f64(&mut self) -> Result<f64, CompactProfileError> {
+        let bytes = self.read_bytes(8)?;
+        Ok(f64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn read_neg_int(&mut self) -> Result<i64, CompactProfileError> {
+        let value = self.read_varint()?;
+        i64::try_from(value)
+            .map(|value| !value)
+            .map_err(|_| CompactProfileError::InvalidData("integer is too small".to_string()))
+    }
+
+    /// Reads a string reference and returns the index of the string in `self.strings`.
+    fn read_string(&mut self) -> Result<usize, CompactProfileError> {
+        let index = self.read_len()?;
+        if index < self.strings.len() {
+            return Ok(index);
+        }
+        if index > self.strings.len() {
+            return Err(CompactProfileError::InvalidData(format!(
+                "string index {index} is out of range"
+            )));
+        }
+        let len = self.read_len()?;
+        let bytes = self.read_bytes(len)?;
+        let s = std::str::from_utf8(bytes)
+            .map_err(|_| CompactProfileError::InvalidData("string is not UTF-8".to_string()))?;
+        self.strings.push(s.to_string());
+        Ok(index)
+    }
+
+    /// Reads the length and the null bitmap of a column.
+    fn read_column_header(&mut self) -> Result<(usize, Option<Vec<u8>>), CompactProfileError> {
+        let len = self.read_len()?;
+        let null_bitmap = match self.read_u8()? {
+            0 => {
+                // Every value takes up at least one byte.
+                if len > self.data.len() {
+                    return Err(CompactProfileError::InvalidData(
+                        "column is truncated".to_string(),
+                    ));
+                }
+                None
+            }
+            1 => {
+                let bitmap_len = len.checked_add(7).ok_or_else(|| {
+                    CompactProfileError::InvalidData("column is too long".to_string())
+                })? / 8;
+                let bitmap = self.read_bytes(bitmap_len)?;
+                Some(bitmap.to_vec())
+            }
+            _ => {
+                return Err(CompactProfileError::InvalidData(
+                    "invalid column header".to_string(),
+                ))
+            }
+        };
+        Ok((len, null_bitmap))
+    }
+}
+
+fn is_null(null_bitmap: &Option<Vec<u8>>, index: usize) -> bool {
+    match null_bitmap {
+        Some(bitmap) => bitmap[index / 8] & (1 << (index % 8)) != 0,
+        None => false,
+    }
+}
+
+/// Reads a value with the tag `tag` from the decoder, and serializes it.
+struct Transcode<'a, 'b> {
+    decoder: RefCell<&'a mut Decoder<'b>>,
+    tag: u8,
+    /// The number of sequences and maps which contain the value.
+    depth: usize,
+}
+
+impl<'a, 'b> Serialize for Transcode<'a, 'b> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut decoder = self.decoder.borrow_mut();
+        transcode(&mut decoder, self.tag, self.depth, serializer)
+    }
+}
+
+fn transcode<S: Serializer>(
+    decoder: &mut Decoder,
+    tag: u8,
+    depth: usize,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    use serde::ser::Error;
+
+    match tag {
+        TAG_NULL => serializer.serialize_unit(),
+        TAG_FALSE => serializer.serialize_bool(false),
+        TAG_TRUE => serializer.serialize_bool(true),
+        TAG_UINT => serializer.serialize_u64(decoder.read_varint().map_err(S::Error::custom)?),
+        TAG_NEG_INT => serializer.serialize_i64(decoder.read_neg_int().map_err(S::Error::custom)?),
+        TAG_F64 => serializer.serialize_f64(decoder.read_f64().map_err(S::Error::custom)?),
+        TAG_STRING => {
+            let index = decoder.read_string().map_err(S::Error::custom)?;
+            serializer.serialize_str(&decoder.strings[index])
+        }
+        TAG_SEQ | TAG_MAP if depth >= MAX_NESTING_DEPTH => {
+            Err(S::Error::custom("values are nested too deeply"))
+        }
+        TAG_SEQ => {
+            let mut seq = serializer.serialize_seq(None)?;
+            loop {
+                let tag = decoder.read_u8().map_err(S::Error::custom)?;
+                if tag == TAG_END {
+                    break;
+                }
+                seq.serialize_element(&Transcode {
+                    decoder: RefCell::new(&mut *decoder),
+                    tag,
+                    depth: depth + 1,
+                })?;
+            }
+            seq.end()
+        }
+        TAG_MAP => {
+            let mut map = serializer.serialize_map(None)?;
+            loop {
+                let tag = decoder.read_u8().map_err(S::Error::custom)?;
+                match tag {
+                    TAG_END => break,
+                    TAG_STRING => {
+                        let index = decoder.read_string().map_err(S::Error::custom)?;
+                        map.serialize_key(&decoder.strings[index])?;
+                    }
+                    TAG_UINT => {
+                        let key = decoder.read_varint().map_err(S::Error::custom)?;
+                        map.serialize_key(&key.to_string())?;
+                    }
+                    TAG_NEG_INT => {
+                        let key = decoder.read_neg_int().map_err(S::Error::custom)?;
+                        map.serialize_key(&key.to_string())?;
+                    }
+                    _ => return Err(S::Error::custom("map key must be a string or an integer")),
+                }
+                let tag = decoder.read_u8().map_err(S::Error::custom)?;
+                map.serialize_value(&Transcode {
+                    decoder: RefCell::new(&mut *decoder),
+                    tag,
+                    depth: depth + 1,
+                })?;
+            }
+            map.end()
+        }
+        TAG_INT_COLUMN => {
+            let (len, null_bitmap) = decoder.read_column_header().map_err(S::Error::custom)?;
+            let mut seq = serializer.serialize_seq(Some(len))?;
+            let mut previous = 0i64;
+            for i in 0..len {
+                if is_null(&null_bitmap, i) {
+                    seq.serialize_element(&())?;
+                    continue;
+                }
+                let delta = zigzag_decode(decoder.read_varint().map_err(S::Error::custom)?);
+                previous = previous.wrapping_add(delta);
+                seq.serialize_element(&previous)?;
+            }
+            seq.end()
+        }
+        TAG_F64_COLUMN => {
+            let (len, null_bitmap) = decoder.read_column_header().map_err(S::Error::custom)?;
+            let scale = match decoder.read_u8().map_err(S::Error::custom)? {
+                RAW_F64 => None,
+                exponent => match POWERS_OF_TEN.get(exponent as usize) {
+                    Some(scale) => Some(*scale),
+                    None => {
+                        return Err(S::Error::custom(format!(
+                            "invalid decimal exponent {exponent}"
+                        )))
+                    }
+                },
+            };
+            let mut seq = serializer.serialize_seq(Some(len))?;
+            let mut previous = 0i64;
+            for i in 0..len {
+                if is_null(&null_bitmap, i) {
+                    seq.serialize_element(&())?;
+                    continue;
+                }
+                let value = match scale {
+                    Some(scale) => {
+                        let delta = zigzag_decode(decoder.read_varint().map_err(S::Error::custom)?);
+                        previous = previous.wrapping_add(delta);
+                        previous as f64 / scale
+                    }
+                    None => decoder.read_f64().map_err(S::Error::custom)?,
+                };
+                seq.serialize_element(&value)?;
+            }
+            seq.end()
+        }
+        _ => Err(S::Error::custom(format!("unknown value tag {tag}"))),
+    }
+}
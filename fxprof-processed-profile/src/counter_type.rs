@@ -0,0 +1,43 @@
+/// The kind of track which the Firefox Profiler draws for a counter. Use it with
+/// [`Profile::add_counter_with_type`](crate::Profile::add_counter_with_type).
+///
+/// The profiler picks the track from the counter's category, so each type maps
+/// to a fixed category string. Counters in other categories are kept in the
+/// profile but don't get a track. For all types, the samples contain the change
+/// since the previous sample, and the profiler accumulates them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CounterType {
+    /// A stacked graph of allocated memory. The sample values are the number of
+    /// bytes allocated minus the number of bytes freed, and the number of
+    /// operations is the number of allocation and deallocation calls.
+    Memory,
+    /// A graph of data transfer speed, e.g. for network or disk traffic. The
+    /// sample values are the number of bytes transferred since the previous
+    /// sample, and the profiler draws them as bytes per second.
+    Bandwidth,
+    /// A graph of power usage. The sample values are the energy used since the
+    /// previous sample, in picowatt-hours, and the profiler draws them as watts.
+    Power,
+}
+
+impl CounterType {
+    /// The counter category which selects this type of track in the profiler.
+    pub fn category(&self) -> &'static str {
+        match self {
+            CounterType::Memory => "Memory",
+            CounterType::Bandwidth => "Bandwidth",
+            CounterType::Power => "power",
+        }
+    }
+
+    /// The counter type for a counter category, or `None` if the profiler
+    /// doesn't draw counters in this category.
+    pub fn from_category(category: &str) -> Option<Self> {
+        match category {
+            "Memory" => Some(CounterType::Memory),
+            "Bandwidth" => Some(CounterType::Bandwidth),
+            "power" => Some(CounterType::Power),
+            _ => None,
+        }
+    }
+}
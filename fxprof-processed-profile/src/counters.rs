@@ -1,12 +1,13 @@
 use serde::ser::{Serialize, SerializeMap, Serializer};
 
+use crate::merge::MergeMapping;
 use crate::{ProcessHandle, Timestamp};
 
 /// A counter. Can be created with [`Profile::add_counter`](crate::Profile::add_counter).
 #[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash)]
 pub struct CounterHandle(pub(crate) usize);
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Counter {
     name: String,
     category: String,
@@ -48,6 +49,14 @@ impl Counter {
             .add_sample(timestamp, value_delta, number_of_operations_delta)
     }
 
+    pub fn remap(&mut self, process: ProcessHandle, pid: &str, mapping: &MergeMapping) {
+        self.process = process;
+        self.pid = pid.to_owned();
+        for timestamp in &mut self.samples.time {
+            *timestamp = mapping.timestamp(*timestamp);
+        }
+    }
+
     pub fn as_serializable(&self, main_thread_index: usize) -> impl Serialize + '_ {
         SerializableCounter {
             counter: self,
@@ -89,7 +98,7 @@ impl<'a> Serialize for SerializableCounterSampleGroup<'a> {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct CounterSamples {
     time: Vec<Timestamp>,
     number: Vec<u32>,
@@ -1,4 +1,6 @@
+use serde::de::{Deserialize, Deserializer, Error};
 use serde::ser::{Serialize, Serializer};
+use std::cmp::Ordering;
 use std::time::Duration;
 
 /// The amount of CPU time between thread samples.
@@ -8,52 +10,166 @@ use std::time::Duration;
 /// A thread only runs on one CPU at any time, and can get scheduled off and on
 /// the CPU between two samples. The CPU delta is the accumulation of time it
 /// was running on the CPU.
-#[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash)]
+///
+/// CPU deltas which are created from a duration are converted to the profile's
+/// [`CpuDeltaUnit`] when the profile is serialized. Use [`CpuDelta::from_raw`]
+/// for values which are already in the profile's unit, e.g. CPU cycles.
+///
+/// CPU deltas can only be compared if both are durations or both are raw
+/// values, or if one of them is zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct CpuDelta {
-    micros: u64,
+    value: CpuDeltaValue,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum CpuDeltaValue {
+    Nanos(u64),
+    Raw(u64),
 }
 
 impl From<Duration> for CpuDelta {
     fn from(duration: Duration) -> Self {
-        Self {
-            micros: duration.as_micros() as u64,
-        }
+        Self::from_nanos(u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX))
     }
 }
 
 impl CpuDelta {
     /// A CPU delta of zero.
-    pub const ZERO: Self = Self { micros: 0 };
+    pub const ZERO: Self = Self {
+        value: CpuDeltaValue::Nanos(0),
+    };
 
     /// Create a CPU delta from integer nanoseconds.
     pub fn from_nanos(nanos: u64) -> Self {
         Self {
-            micros: nanos / 1000,
+            value: CpuDeltaValue::Nanos(nanos),
         }
     }
 
     /// Create a CPU delta from integer microseconds.
     pub fn from_micros(micros: u64) -> Self {
-        Self { micros }
+        Self::from_nanos(micros.saturating_mul(1_000))
     }
 
     /// Create a CPU delta from float milliseconds.
     pub fn from_millis(millis: f64) -> Self {
+        Self::from_nanos((millis * 1_000_000.0) as u64)
+    }
+
+    /// Create a CPU delta from a value in the profile's [`CpuDeltaUnit`], for
+    /// example a number of CPU cycles. The value is not converted.
+    pub fn from_raw(value: u64) -> Self {
+        if value == 0 {
+            return Self::ZERO;
+        }
         Self {
-            micros: (millis * 1_000.0) as u64,
+            value: CpuDeltaValue::Raw(value),
+        }
+    }
+
+    /// Create a CPU delta from a value in the given unit, as it was read from a
+    /// profile. Other producers of the processed profile format can write
+    /// fractional values.
+    pub(crate) fn from_value_in_unit(value: f64, unit: CpuDeltaUnit) -> Self {
+        match unit {
+            CpuDeltaUnit::Microseconds => Self::from_nanos((value * 1_000.0).round() as u64),
+            CpuDeltaUnit::Nanoseconds => Self::from_nanos(value.round() as u64),
+            CpuDeltaUnit::VariableCpuCycles => Self::from_raw(value.round() as u64),
+        }
+    }
+
+    /// The value in the given unit. Durations are converted; values which were
+    /// created with [`CpuDelta::from_raw`] are returned unchanged.
+    ///
+    /// Returns `None` for non-zero durations in [`CpuDeltaUnit::VariableCpuCycles`],
+    /// because there is no conversion from durations to CPU cycles. Such CPU
+    /// deltas are serialized as `null`, which the Firefox Profiler treats as a
+    /// missing value.
+    pub fn value_in_unit(&self, unit: CpuDeltaUnit) -> Option<u64> {
+        match (self.value, unit) {
+            (CpuDeltaValue::Nanos(0), _) => Some(0),
+            (CpuDeltaValue::Nanos(nanos), CpuDeltaUnit::Microseconds) => Some(nanos / 1_000),
+            (CpuDeltaValue::Nanos(nanos), CpuDeltaUnit::Nanoseconds) => Some(nanos),
+            (CpuDeltaValue::Nanos(_), CpuDeltaUnit::VariableCpuCycles) => None,
+            (CpuDeltaValue::Raw(value), _) => Some(value),
         }
     }
 
     /// Whether the CPU delta is zero.
     pub fn is_zero(&self) -> bool {
-        self.micros == 0
+        *self == Self::ZERO
+    }
+}
+
+impl PartialOrd for CpuDelta {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self.value, other.value) {
+            (CpuDeltaValue::Nanos(a), CpuDeltaValue::Nanos(b)) => Some(a.cmp(&b)),
+            (CpuDeltaValue::Raw(a), CpuDeltaValue::Raw(b)) => Some(a.cmp(&b)),
+            // Raw values are never zero, see `CpuDelta::from_raw`.
+            (CpuDeltaValue::Nanos(0), CpuDeltaValue::Raw(_)) => Some(Ordering::Less),
+            (CpuDeltaValue::Raw(_), CpuDeltaValue::Nanos(0)) => Some(Ordering::Greater),
+            _ => None,
+        }
     }
 }
 
 impl Serialize for CpuDelta {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-        // CPU deltas are serialized as float microseconds, because
-        // we set profile.meta.sampleUnits.threadCPUDelta to "µs".
-        self.micros.serialize(serializer)
+        // Without a profile, CPU deltas are serialized in the default unit,
+        // microseconds. Raw values are serialized unchanged.
+        self.value_in_unit(CpuDeltaUnit::Microseconds)
+            .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for CpuDelta {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // Other producers of the processed profile format can write fractional
+        // microsecond values.
+        let value = f64::deserialize(deserializer)?;
+        Ok(Self::from_value_in_unit(value, CpuDeltaUnit::Microseconds))
+    }
+}
+
+/// The unit of the [`CpuDelta`] values in a profile. Set it with
+/// [`Profile::set_cpu_delta_unit`](crate::Profile::set_cpu_delta_unit).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CpuDeltaUnit {
+    /// Microseconds. This is the default.
+    Microseconds,
+    /// Nanoseconds.
+    Nanoseconds,
+    /// CPU cycles, or other tick counts whose duration can vary over time, for
+    /// example because of frequency scaling.
+    VariableCpuCycles,
+}
+
+impl Default for CpuDeltaUnit {
+    fn default() -> Self {
+        CpuDeltaUnit::Microseconds
+    }
+}
+
+impl Serialize for CpuDeltaUnit {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            CpuDeltaUnit::Microseconds => "µs".serialize(serializer),
+            CpuDeltaUnit::Nanoseconds => "ns".serialize(serializer),
+            CpuDeltaUnit::VariableCpuCycles => "variable CPU cycles".serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for CpuDeltaUnit {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let unit = String::deserialize(deserializer)?;
+        Ok(match unit.as_str() {
+            "µs" => CpuDeltaUnit::Microseconds,
+            "ns" => CpuDeltaUnit::Nanoseconds,
+            "variable CPU cycles" => CpuDeltaUnit::VariableCpuCycles,
+            _ => return Err(D::Error::custom(format!("unknown CPU delta unit {unit}"))),
+        })
     }
 }
@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::MarkerFieldFormat;
+
+/// A section of key/value entries with additional information about the
+/// profile, for example about the machine on which the profile was recorded.
+///
+/// These sections are displayed in the profile info panel of the Firefox
+/// Profiler. Add them with [`Profile::add_extra_info_section`](crate::Profile::add_extra_info_section).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtraProfileInfoSection {
+    /// The title of the section.
+    pub label: String,
+    /// The entries in this section.
+    pub entries: Vec<ExtraProfileInfoEntry>,
+}
+
+/// A single entry in an [`ExtraProfileInfoSection`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtraProfileInfoEntry {
+    /// The label of the entry.
+    pub label: String,
+    /// How the value should be formatted.
+    pub format: MarkerFieldFormat,
+    /// The value, which should match the format, e.g. a string for
+    /// [`MarkerFieldFormat::String`] or a number for [`MarkerFieldFormat::Bytes`].
+    pub value: Value,
+}
+
+impl ExtraProfileInfoSection {
+    /// Create an empty section with the given title.
+    pub fn new(label: &str) -> Self {
+        Self {
+            label: label.to_string(),
+            entries: Vec::new(),
+        }
+    }
+
+    /// Add an entry with a string value to this section.
+    pub fn add_string_entry(&mut self, label: &str, value: &str) {
+        self.entries.push(ExtraProfileInfoEntry {
+            label: label.to_string(),
+            format: MarkerFieldFormat::String,
+            value: value.into(),
+        });
+    }
+
+    /// Add an entry to this section.
+    pub fn add_entry(&mut self, label: &str, format: MarkerFieldFormat, value: Value) {
+        self.entries.push(ExtraProfileInfoEntry {
+            label: label.to_string(),
+            format,
+            value,
+        });
+    }
+}
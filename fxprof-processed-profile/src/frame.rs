@@ -27,6 +27,30 @@ pub enum Frame {
     RelativeAddressFromReturnAddress(LibraryHandle, u32),
     /// A string, containing an index returned by Profile::intern_string
     Label(StringHandle),
+    /// A function which has a source location but no code address, for example
+    /// a function in an interpreted language.
+    ///
+    /// The function's file, line and column are used in the source view of the
+    /// Firefox Profiler.
+    Function(FunctionFrame),
+}
+
+/// The information about a function in a [`Frame::Function`] frame.
+///
+/// All strings are indexes returned by [`Profile::intern_string`](crate::Profile::intern_string).
+#[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub struct FunctionFrame {
+    /// The name of the function.
+    pub name: StringHandle,
+    /// The path or URL of the file which contains the function.
+    pub file: Option<StringHandle>,
+    /// The line number at which the function starts, 1-based.
+    pub line: Option<u32>,
+    /// The column number at which the function starts, 1-based.
+    pub column: Option<u32>,
+    /// The script URL or module which contains the function. This is displayed
+    /// in the same place as the library name is displayed for native functions.
+    pub resource: Option<StringHandle>,
 }
 
 /// All the information about a single stack frame.
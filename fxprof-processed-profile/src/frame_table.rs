@@ -5,8 +5,9 @@ use crate::category::{
 };
 use crate::fast_hash_map::FastHashMap;
 use crate::frame::FrameFlags;
-use crate::func_table::{FuncIndex, FuncTable};
+use crate::func_table::{FuncIndex, FuncSourceLocation, FuncTable};
 use crate::global_lib_table::{GlobalLibIndex, GlobalLibTable};
+use crate::merge::MergeMapping;
 use crate::native_symbols::{NativeSymbolIndex, NativeSymbols};
 use crate::resource_table::ResourceTable;
 use crate::serialization_helpers::SerializableSingleValueColumn;
@@ -80,9 +81,27 @@ impl FrameTable {
                         (Some(address), s, native_symbol, Some(res))
                     }
                     InternalFrameLocation::Label(string_index) => (None, string_index, None, None),
+                    InternalFrameLocation::Function(function) => {
+                        let res = function
+                            .resource
+                            .map(|name| resource_table.resource_for_name(name));
+                        (None, function.name, None, res)
+                    }
+                };
+                let source_location = match frame.location {
+                    InternalFrameLocation::Function(function) => FuncSourceLocation {
+                        file: function.file,
+                        line: function.line,
+                        column: function.column,
+                    },
+                    _ => FuncSourceLocation::default(),
                 };
-                let func_index =
-                    func_table.index_for_func(location_string_index, resource, frame.flags);
+                let func_index = func_table.index_for_func(
+                    location_string_index,
+                    resource,
+                    source_location,
+                    frame.flags,
+                );
                 let CategoryPairHandle(category, subcategory_index) = frame.category_pair;
                 let subcategory = match subcategory_index {
                     Some(index) => Subcategory::Normal(index),
@@ -97,6 +116,24 @@ impl FrameTable {
             })
     }
 
+    pub fn remap(&mut self, mapping: &MergeMapping) {
+        for (category, subcategory) in self
+            .categories
+            .iter_mut()
+            .zip(self.subcategories.iter_mut())
+        {
+            let (new_category, new_subcategory) =
+                mapping.category_and_subcategory(*category, subcategory);
+            *category = new_category;
+            *subcategory = new_subcategory;
+        }
+        self.internal_frame_to_frame_index =
+            std::mem::take(&mut self.internal_frame_to_frame_index)
+                .into_iter()
+                .map(|(frame, frame_index)| (mapping.frame(frame), frame_index))
+                .collect();
+    }
+
     pub fn as_serializable<'a>(&'a self, categories: &'a [Category]) -> impl Serialize + 'a {
         SerializableFrameTable {
             table: self,
@@ -163,4 +200,15 @@ pub enum InternalFrameLocation {
     UnknownAddress(u64),
     AddressInLib(u32, GlobalLibIndex),
     Label(ThreadInternalStringIndex),
+    Function(InternalFunction),
+}
+
+/// A [`FunctionFrame`](crate::FunctionFrame) with thread-internal string indexes.
+#[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub struct InternalFunction {
+    pub name: ThreadInternalStringIndex,
+    pub file: Option<ThreadInternalStringIndex>,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    pub resource: Option<ThreadInternalStringIndex>,
 }
@@ -3,19 +3,35 @@ use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
 use crate::fast_hash_map::FastHashMap;
 use crate::frame::FrameFlags;
 use crate::resource_table::ResourceIndex;
-use crate::serialization_helpers::SerializableSingleValueColumn;
 use crate::thread_string_table::ThreadInternalStringIndex;
 
 #[derive(Debug, Clone, Default)]
 pub struct FuncTable {
     names: Vec<ThreadInternalStringIndex>,
     resources: Vec<Option<ResourceIndex>>,
+    source_locations: Vec<FuncSourceLocation>,
     flags: Vec<FrameFlags>,
-    func_name_and_resource_and_flags_to_func_index:
-        FastHashMap<(ThreadInternalStringIndex, Option<ResourceIndex>, FrameFlags), usize>,
+    func_key_to_func_index: FastHashMap<FuncKey, usize>,
     contains_js_function: bool,
 }
 
+/// The file, line and column of a function. All fields are `None` for functions
+/// which don't know their source location, such as native functions.
+#[derive(Debug, Clone, Copy, Default, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub struct FuncSourceLocation {
+    pub file: Option<ThreadInternalStringIndex>,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash)]
+struct FuncKey {
+    name: ThreadInternalStringIndex,
+    resource: Option<ResourceIndex>,
+    source_location: FuncSourceLocation,
+    flags: FrameFlags,
+}
+
 impl FuncTable {
     pub fn new() -> Self {
         Default::default()
@@ -25,18 +41,23 @@ impl FuncTable {
         &mut self,
         name: ThreadInternalStringIndex,
         resource: Option<ResourceIndex>,
+        source_location: FuncSourceLocation,
         flags: FrameFlags,
     ) -> FuncIndex {
-        let func_index = *self
-            .func_name_and_resource_and_flags_to_func_index
-            .entry((name, resource, flags))
-            .or_insert_with(|| {
-                let func_index = self.names.len();
-                self.names.push(name);
-                self.resources.push(resource);
-                self.flags.push(flags);
-                func_index
-            });
+        let key = FuncKey {
+            name,
+            resource,
+            source_location,
+            flags,
+        };
+        let func_index = *self.func_key_to_func_index.entry(key).or_insert_with(|| {
+            let func_index = self.names.len();
+            self.names.push(name);
+            self.resources.push(resource);
+            self.source_locations.push(source_location);
+            self.flags.push(flags);
+            func_index
+        });
         if flags.intersects(FrameFlags::IS_JS | FrameFlags::IS_RELEVANT_FOR_JS) {
             self.contains_js_function = true;
         }
@@ -75,9 +96,18 @@ impl Serialize for FuncTable {
             "resource",
             &SerializableFuncTableResourceColumn(&self.resources),
         )?;
-        map.serialize_entry("fileName", &SerializableSingleValueColumn((), len))?;
-        map.serialize_entry("lineNumber", &SerializableSingleValueColumn((), len))?;
-        map.serialize_entry("columnNumber", &SerializableSingleValueColumn((), len))?;
+        map.serialize_entry(
+            "fileName",
+            &SerializableSourceLocationColumn(&self.source_locations, |l| l.file),
+        )?;
+        map.serialize_entry(
+            "lineNumber",
+            &SerializableSourceLocationColumn(&self.source_locations, |l| l.line),
+        )?;
+        map.serialize_entry(
+            "columnNumber",
+            &SerializableSourceLocationColumn(&self.source_locations, |l| l.column),
+        )?;
         map.end()
     }
 }
@@ -97,6 +127,17 @@ impl<'a> Serialize for SerializableFuncTableResourceColumn<'a> {
     }
 }
 
+struct SerializableSourceLocationColumn<'a, T: Serialize>(
+    &'a [FuncSourceLocation],
+    fn(&FuncSourceLocation) -> Option<T>,
+);
+
+impl<'a, T: Serialize> Serialize for SerializableSourceLocationColumn<'a, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter().map(self.1))
+    }
+}
+
 pub struct SerializableFlagColumn<'a>(&'a [FrameFlags], FrameFlags);
 
 impl<'a> Serialize for SerializableFlagColumn<'a> {
@@ -5,7 +5,7 @@ use serde::ser::{Serialize, Serializer};
 use crate::fast_hash_map::FastHashMap;
 use crate::{LibraryInfo, SymbolTable};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct GlobalLibTable {
     /// All libraries added via `Profile::add_lib`. May or may not be used.
     /// Indexed by `LibraryHandle.0`.
@@ -48,6 +48,26 @@ impl GlobalLibTable {
         })
     }
 
+    /// Add all libraries of `other` to this table. Returns the new handles for
+    /// `other`'s `LibraryHandle`s and `GlobalLibIndex`es, indexed by the old ones.
+    pub fn append(&mut self, other: GlobalLibTable) -> (Vec<LibraryHandle>, Vec<GlobalLibIndex>) {
+        let lib_handles: Vec<LibraryHandle> = other
+            .all_libs
+            .into_iter()
+            .map(|lib| self.handle_for_lib(lib))
+            .collect();
+        let lib_indexes = other
+            .used_libs
+            .into_iter()
+            .map(|handle| self.index_for_used_lib(lib_handles[handle.0]))
+            .collect();
+        (lib_handles, lib_indexes)
+    }
+
+    pub fn used_libs(&self) -> impl Iterator<Item = &LibraryInfo> {
+        self.used_libs.iter().map(|handle| &self.all_libs[handle.0])
+    }
+
     pub fn get_lib(&self, index: GlobalLibIndex) -> Option<&LibraryInfo> {
         let handle = self.used_libs.get(index.0)?;
         self.all_libs.get(handle.0)
@@ -56,14 +76,14 @@ impl GlobalLibTable {
 
 impl Serialize for GlobalLibTable {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-        serializer.collect_seq(self.used_libs.iter().map(|handle| &self.all_libs[handle.0]))
+        serializer.collect_seq(self.used_libs())
     }
 }
 
 /// An index for a *used* library, i.e. a library for which there exists at
 /// least one frame in any process's frame table which refers to this lib.
 #[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash)]
-pub struct GlobalLibIndex(usize);
+pub struct GlobalLibIndex(pub(crate) usize);
 
 impl Serialize for GlobalLibIndex {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
@@ -73,4 +93,4 @@ impl Serialize for GlobalLibIndex {
 
 /// The handle for a library, obtained from [`Profile::add_lib`](crate::Profile::add_lib).
 #[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash)]
-pub struct LibraryHandle(usize);
+pub struct LibraryHandle(pub(crate) usize);
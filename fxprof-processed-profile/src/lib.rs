@@ -7,6 +7,12 @@
 //! information into it. To convert it to JSON, use [`serde_json`], for
 //! example [`serde_json::to_writer`] or [`serde_json::to_string`].
 //!
+//! To read a processed profile JSON file back, for example for post-processing,
+//! use [`ProfileView`].
+//!
+//! For large profiles, [`write_compact_profile`] writes a much smaller binary
+//! encoding, which [`compact_profile_to_json`] expands back into JSON.
+//!
 //! ## Example
 //!
 //! ```
@@ -34,8 +40,11 @@ pub use debugid;
 
 mod category;
 mod category_color;
+mod compact_format;
+mod counter_type;
 mod counters;
 mod cpu_delta;
+mod extra_info;
 mod fast_hash_map;
 mod frame;
 mod frame_table;
@@ -45,9 +54,11 @@ mod lib_mappings;
 mod library_info;
 mod marker_table;
 mod markers;
+mod merge;
 mod native_symbols;
 mod process;
 mod profile;
+mod profile_view;
 mod reference_timestamp;
 mod resource_table;
 mod sample_table;
@@ -60,15 +71,27 @@ mod timestamp;
 
 pub use category::{CategoryHandle, CategoryPairHandle};
 pub use category_color::CategoryColor;
+pub use compact_format::{
+    compact_profile_to_json, is_compact_profile, write_compact_profile, CompactProfileError,
+    COMPACT_PROFILE_FORMAT_VERSION,
+};
+pub use counter_type::CounterType;
 pub use counters::CounterHandle;
-pub use cpu_delta::CpuDelta;
-pub use frame::{Frame, FrameFlags, FrameInfo};
+pub use cpu_delta::{CpuDelta, CpuDeltaUnit};
+pub use extra_info::{ExtraProfileInfoEntry, ExtraProfileInfoSection};
+pub use frame::{Frame, FrameFlags, FrameInfo, FunctionFrame};
 pub use global_lib_table::LibraryHandle;
 pub use lib_mappings::LibMappings;
 pub use library_info::{LibraryInfo, Symbol, SymbolTable};
 pub use markers::*;
+pub use merge::{merge_profiles, MergeLayout};
 pub use process::ThreadHandle;
 pub use profile::{Profile, SamplingInterval, StringHandle};
+pub use profile_view::{
+    CategoryView, CounterSampleView, CounterView, FrameView, MarkerView, ProfileReadError,
+    ProfileView, SampleView, StackFrames, ThreadView,
+};
 pub use reference_timestamp::ReferenceTimestamp;
-pub use thread::ProcessHandle;
+pub use sample_table::WeightType;
+pub use thread::{ProcessHandle, ThreadType};
 pub use timestamp::*;
@@ -100,6 +100,35 @@ impl<T> LibMappings<T> {
         self.sorted_mappings.shrink_to_fit();
     }
 
+    /// Adds all mappings of `other`. Existing mappings which overlap with them
+    /// are removed.
+    pub(crate) fn extend(&mut self, other: LibMappings<T>) {
+        for mapping in other.sorted_mappings {
+            self.add_mapping(
+                mapping.start_avma,
+                mapping.end_avma,
+                mapping.relative_address_at_start,
+                mapping.value,
+            );
+        }
+    }
+
+    /// Converts the value of every mapping.
+    pub(crate) fn map_values<U>(self, mut f: impl FnMut(T) -> U) -> LibMappings<U> {
+        LibMappings {
+            sorted_mappings: self
+                .sorted_mappings
+                .into_iter()
+                .map(|mapping| Mapping {
+                    start_avma: mapping.start_avma,
+                    end_avma: mapping.end_avma,
+                    relative_address_at_start: mapping.relative_address_at_start,
+                    value: f(mapping.value),
+                })
+                .collect(),
+        }
+    }
+
     /// Look up the mapping which covers the given address.
     fn lookup(&self, avma: u64) -> Option<&Mapping<T>> {
         let mappings = &self.sorted_mappings[..];
@@ -1,5 +1,5 @@
 use debugid::DebugId;
-use serde::{ser::SerializeMap, Serialize, Serializer};
+use serde::{de::Error, ser::SerializeMap, Deserialize, Deserializer, Serialize, Serializer};
 use std::sync::Arc;
 
 /// A library ("binary" / "module" / "DSO") which is loaded into a process.
@@ -75,6 +75,36 @@ impl Serialize for LibraryInfo {
     }
 }
 
+impl<'de> Deserialize<'de> for LibraryInfo {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct SerializedLibraryInfo {
+            name: String,
+            path: String,
+            debug_name: String,
+            debug_path: String,
+            breakpad_id: String,
+            code_id: Option<String>,
+            arch: Option<String>,
+        }
+
+        let lib = SerializedLibraryInfo::deserialize(deserializer)?;
+        let debug_id = DebugId::from_breakpad(&lib.breakpad_id)
+            .map_err(|_| D::Error::custom(format!("invalid breakpadId {}", lib.breakpad_id)))?;
+        Ok(LibraryInfo {
+            name: lib.name,
+            debug_name: lib.debug_name,
+            path: lib.path,
+            debug_path: lib.debug_path,
+            debug_id,
+            code_id: lib.code_id,
+            arch: lib.arch,
+            symbol_table: None,
+        })
+    }
+}
+
 /// A symbol table which contains a list of [`Symbol`]s, used in [`LibraryInfo`].
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct SymbolTable {
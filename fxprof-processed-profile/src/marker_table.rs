@@ -1,11 +1,13 @@
-use serde::ser::{Serialize, SerializeMap, Serializer};
+use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
 use serde_json::Value;
 
+use crate::fast_hash_map::FastHashMap;
+use crate::merge::MergeMapping;
 use crate::serialization_helpers::{
     SerializableOptionalTimestampColumn, SerializableSingleValueColumn,
 };
 use crate::thread_string_table::ThreadInternalStringIndex;
-use crate::{MarkerTiming, Timestamp};
+use crate::{FlowId, MarkerTiming, Timestamp};
 
 #[derive(Debug, Clone, Default)]
 pub struct MarkerTable {
@@ -14,6 +16,10 @@ pub struct MarkerTable {
     marker_ends: Vec<Option<Timestamp>>,
     marker_phases: Vec<Phase>,
     marker_datas: Vec<Value>,
+    /// The flow ID fields of the marker data, as (marker index, field key, flow ID),
+    /// in marker order. Flow IDs only enter the thread's string table when the
+    /// thread is serialized, so that changing them doesn't leave unused strings.
+    marker_flow_ids: Vec<(usize, String, FlowId)>,
 }
 
 impl MarkerTable {
@@ -26,6 +32,7 @@ impl MarkerTable {
         name: ThreadInternalStringIndex,
         timing: MarkerTiming,
         data: Value,
+        flow_ids: Vec<(String, FlowId)>,
     ) {
         let (s, e, phase) = match timing {
             MarkerTiming::Instant(s) => (Some(s), None, Phase::Instant),
@@ -37,31 +44,111 @@ impl MarkerTable {
         self.marker_starts.push(s);
         self.marker_ends.push(e);
         self.marker_phases.push(phase);
+        let marker_index = self.marker_datas.len();
         self.marker_datas.push(data);
+        self.marker_flow_ids.extend(
+            flow_ids
+                .into_iter()
+                .map(|(key, flow_id)| (marker_index, key, flow_id)),
+        );
     }
+
+    pub fn remap(&mut self, mapping: &MergeMapping) {
+        for timestamp in self
+            .marker_starts
+            .iter_mut()
+            .chain(self.marker_ends.iter_mut())
+            .flatten()
+        {
+            *timestamp = mapping.timestamp(*timestamp);
+        }
+    }
+
+    pub fn flow_ids(&self) -> impl Iterator<Item = FlowId> + '_ {
+        self.marker_flow_ids.iter().map(|(_, _, flow_id)| *flow_id)
+    }
+
+    pub fn flow_ids_mut(&mut self) -> impl Iterator<Item = &mut FlowId> {
+        self.marker_flow_ids
+            .iter_mut()
+            .map(|(_, _, flow_id)| flow_id)
+    }
+
+    /// `flow_id_strings` maps each flow ID to the index of its string in the
+    /// thread's serialized string array.
+    pub fn as_serializable<'a>(
+        &'a self,
+        flow_id_strings: &'a FastHashMap<FlowId, usize>,
+    ) -> impl Serialize + 'a {
+        SerializableMarkerTable {
+            table: self,
+            flow_id_strings,
+        }
+    }
+}
+
+struct SerializableMarkerTable<'a> {
+    table: &'a MarkerTable,
+    flow_id_strings: &'a FastHashMap<FlowId, usize>,
 }
 
-impl Serialize for MarkerTable {
+impl<'a> Serialize for SerializableMarkerTable<'a> {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-        let len = self.marker_name_string_indexes.len();
+        let table = self.table;
+        let len = table.marker_name_string_indexes.len();
         let mut map = serializer.serialize_map(None)?;
         map.serialize_entry("length", &len)?;
         map.serialize_entry("category", &SerializableSingleValueColumn(0, len))?;
-        map.serialize_entry("data", &self.marker_datas)?;
+        map.serialize_entry(
+            "data",
+            &SerializableMarkerDataColumn {
+                table,
+                flow_id_strings: self.flow_id_strings,
+            },
+        )?;
         map.serialize_entry(
             "endTime",
-            &SerializableOptionalTimestampColumn(&self.marker_ends),
+            &SerializableOptionalTimestampColumn(&table.marker_ends),
         )?;
-        map.serialize_entry("name", &self.marker_name_string_indexes)?;
-        map.serialize_entry("phase", &self.marker_phases)?;
+        map.serialize_entry("name", &table.marker_name_string_indexes)?;
+        map.serialize_entry("phase", &table.marker_phases)?;
         map.serialize_entry(
             "startTime",
-            &SerializableOptionalTimestampColumn(&self.marker_starts),
+            &SerializableOptionalTimestampColumn(&table.marker_starts),
         )?;
         map.end()
     }
 }
 
+/// The marker data, with the string index of each flow ID put into its field.
+struct SerializableMarkerDataColumn<'a> {
+    table: &'a MarkerTable,
+    flow_id_strings: &'a FastHashMap<FlowId, usize>,
+}
+
+impl<'a> Serialize for SerializableMarkerDataColumn<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.table.marker_datas.len()))?;
+        let mut flow_ids = self.table.marker_flow_ids.iter().peekable();
+        for (marker_index, data) in self.table.marker_datas.iter().enumerate() {
+            if flow_ids.peek().map(|(index, _, _)| *index) != Some(marker_index) {
+                seq.serialize_element(data)?;
+                continue;
+            }
+            let mut data = data.clone();
+            while let Some((_, key, flow_id)) =
+                flow_ids.next_if(|(index, _, _)| *index == marker_index)
+            {
+                if let Some(value) = data.get_mut(key) {
+                    *value = self.flow_id_strings[flow_id].into();
+                }
+            }
+            seq.serialize_element(&data)?;
+        }
+        seq.end()
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 #[repr(u8)]
 enum Phase {
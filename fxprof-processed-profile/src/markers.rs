@@ -2,7 +2,7 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this file,
  * You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
 use super::timestamp::Timestamp;
@@ -171,7 +171,7 @@ pub struct MarkerDynamicField {
 }
 
 /// The field format of a marker field.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub enum MarkerFieldFormat {
     // ----------------------------------------------------
@@ -221,4 +221,296 @@ pub enum MarkerFieldFormat {
     // Do not use it for time information.
     // "Label: 52.23, 0.0054, 123,456.78"
     Decimal,
+
+    // ----------------------------------------------------
+    // Flow types.
+    /// A [`FlowId`] which connects this marker with all other markers, on any
+    /// thread and in any process, which have the same flow ID.
+    FlowId,
+
+    /// A [`FlowId`] which ends the flow with this marker. Later markers with the
+    /// same flow ID belong to a new flow.
+    TerminatingFlowId,
+}
+
+/// An identifier which connects markers across threads and processes, for
+/// example an IPC message send with the corresponding receive, or the markers
+/// of one request which is handled by multiple threads of a thread pool.
+///
+/// Put it into a marker field with the format [`MarkerFieldFormat::FlowId`] or
+/// [`MarkerFieldFormat::TerminatingFlowId`], or use [`FlowMarker`]. Use an
+/// existing identifier, such as a request ID, or get a new one from
+/// [`Profile::new_flow_id`](crate::Profile::new_flow_id).
+///
+/// In marker data, a flow ID serializes to a hex string. When the profile is
+/// serialized, this string is put into the thread's string table, and the field
+/// value becomes the index of the string, just like in the profiles from Firefox.
+#[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub struct FlowId(pub u64);
+
+impl FlowId {
+    pub(crate) fn to_hex_string(self) -> String {
+        format!("{:016x}", self.0)
+    }
+
+    pub(crate) fn from_hex_string(s: &str) -> Option<Self> {
+        u64::from_str_radix(s, 16).ok().map(FlowId)
+    }
+}
+
+impl Serialize for FlowId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_hex_string().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for FlowId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        FlowId::from_hex_string(&s)
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid flow ID {s}")))
+    }
+}
+
+/// A marker which is part of a flow. Add one with the same [`FlowId`] on each
+/// thread which takes part in the flow, for example one for sending an IPC
+/// message and one for receiving it, and the profiler UI connects them.
+///
+/// ```
+/// use fxprof_processed_profile::{FlowMarker, MarkerTiming, Profile, SamplingInterval, Timestamp};
+/// use std::time::SystemTime;
+///
+/// let mut profile = Profile::new("My app", SystemTime::now().into(), SamplingInterval::from_millis(1));
+/// let process = profile.add_process("App process", 54132, Timestamp::from_millis_since_reference(0.0));
+/// let sender = profile.add_thread(process, 54132, Timestamp::from_millis_since_reference(0.0), true);
+/// let receiver = profile.add_thread(process, 54133, Timestamp::from_millis_since_reference(0.0), false);
+/// let flow_id = profile.new_flow_id();
+/// profile.add_marker(sender, "Send request", FlowMarker::new(flow_id), MarkerTiming::Instant(Timestamp::from_millis_since_reference(1.0)));
+/// profile.add_marker(receiver, "Handle request", FlowMarker::terminating(flow_id), MarkerTiming::Interval(Timestamp::from_millis_since_reference(2.0), Timestamp::from_millis_since_reference(5.0)));
+/// ```
+#[derive(Debug, Clone)]
+pub struct FlowMarker {
+    /// The flow which this marker belongs to.
+    pub flow_id: FlowId,
+    /// Whether this marker ends the flow.
+    pub terminating: bool,
+}
+
+impl FlowMarker {
+    /// A marker which starts or continues the flow.
+    pub fn new(flow_id: FlowId) -> Self {
+        Self {
+            flow_id,
+            terminating: false,
+        }
+    }
+
+    /// A marker which ends the flow.
+    pub fn terminating(flow_id: FlowId) -> Self {
+        Self {
+            flow_id,
+            terminating: true,
+        }
+    }
+}
+
+impl ProfilerMarker for FlowMarker {
+    const MARKER_TYPE_NAME: &'static str = "Flow";
+
+    fn json_marker_data(&self) -> Value {
+        let key = if self.terminating {
+            "terminatingFlow"
+        } else {
+            "flow"
+        };
+        let mut data = serde_json::Map::new();
+        data.insert("type".to_string(), Self::MARKER_TYPE_NAME.into());
+        data.insert(key.to_string(), serde_json::to_value(self.flow_id).unwrap());
+        Value::Object(data)
+    }
+
+    fn schema() -> MarkerSchema {
+        MarkerSchema {
+            type_name: Self::MARKER_TYPE_NAME,
+            locations: vec![MarkerLocation::MarkerChart, MarkerLocation::MarkerTable],
+            chart_label: Some("{marker.name}"),
+            tooltip_label: None,
+            table_label: Some("{marker.name}"),
+            fields: vec![
+                MarkerSchemaField::Dynamic(MarkerDynamicField {
+                    key: "flow",
+                    label: "Flow",
+                    format: MarkerFieldFormat::FlowId,
+                    searchable: true,
+                }),
+                MarkerSchemaField::Dynamic(MarkerDynamicField {
+                    key: "terminatingFlow",
+                    label: "Flow (end)",
+                    format: MarkerFieldFormat::TerminatingFlowId,
+                    searchable: true,
+                }),
+            ],
+        }
+    }
+}
+
+/// The handle for a marker type which was registered at runtime, obtained from
+/// [`Profile::register_marker_type`](crate::Profile::register_marker_type).
+#[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub struct MarkerTypeHandle(pub(crate) usize);
+
+/// Describes a marker type which is only known at runtime, for example a marker
+/// type that is created from the format description of a tracepoint.
+///
+/// This is the same as [`MarkerSchema`], but with owned strings. Register it with
+/// [`Profile::register_marker_type`](crate::Profile::register_marker_type), and then
+/// add markers of this type with [`Profile::add_runtime_marker`](crate::Profile::add_runtime_marker).
+///
+/// ```
+/// use fxprof_processed_profile::{
+///     MarkerFieldFormat, MarkerLocation, MarkerTiming, Profile, RuntimeMarkerDynamicField,
+///     RuntimeMarkerSchema, RuntimeMarkerSchemaField, SamplingInterval, Timestamp,
+/// };
+/// use serde_json::json;
+/// use std::time::SystemTime;
+///
+/// let mut profile = Profile::new("My app", SystemTime::now().into(), SamplingInterval::from_millis(1));
+/// let process = profile.add_process("App process", 54132, Timestamp::from_millis_since_reference(0.0));
+/// let thread = profile.add_thread(process, 54132000, Timestamp::from_millis_since_reference(0.0), true);
+///
+/// let tracepoint_name = "sched:sched_switch".to_string();
+/// let marker_type = profile.register_marker_type(RuntimeMarkerSchema {
+///     type_name: tracepoint_name.clone(),
+///     locations: vec![MarkerLocation::MarkerChart, MarkerLocation::MarkerTable],
+///     chart_label: None,
+///     tooltip_label: Some("{marker.name} - {marker.data.next_comm}".to_string()),
+///     table_label: None,
+///     fields: vec![RuntimeMarkerSchemaField::Dynamic(RuntimeMarkerDynamicField {
+///         key: "next_comm".to_string(),
+///         label: "Next command".to_string(),
+///         format: MarkerFieldFormat::String,
+///         searchable: true,
+///     })],
+/// });
+/// let mut field_values = serde_json::Map::new();
+/// field_values.insert("next_comm".to_string(), json!("swapper/0"));
+/// profile.add_runtime_marker(
+///     thread,
+///     &tracepoint_name,
+///     marker_type,
+///     MarkerTiming::Instant(Timestamp::from_millis_since_reference(1.0)),
+///     field_values,
+/// );
+/// ```
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuntimeMarkerSchema {
+    /// The name of this marker type.
+    #[serde(rename = "name")]
+    pub type_name: String,
+
+    /// List of marker display locations. Empty for SpecialFrontendLocation.
+    #[serde(rename = "display")]
+    pub locations: Vec<MarkerLocation>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chart_label: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tooltip_label: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub table_label: Option<String>,
+
+    /// The marker fields. The values for the dynamic fields are supplied with
+    /// each marker.
+    #[serde(rename = "data")]
+    pub fields: Vec<RuntimeMarkerSchemaField>,
+}
+
+impl RuntimeMarkerSchema {
+    /// The keys of the fields whose values are flow IDs.
+    pub(crate) fn flow_id_keys(&self) -> Vec<String> {
+        self.fields
+            .iter()
+            .filter_map(|field| match field {
+                RuntimeMarkerSchemaField::Dynamic(RuntimeMarkerDynamicField {
+                    key,
+                    format: MarkerFieldFormat::FlowId | MarkerFieldFormat::TerminatingFlowId,
+                    ..
+                }) => Some(key.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// The description of a marker field in a [`RuntimeMarkerSchema`].
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum RuntimeMarkerSchemaField {
+    /// Static fields have the same value on all markers.
+    Static(RuntimeMarkerStaticField),
+
+    /// Dynamic fields have a per-marker value, which is looked up by the field's
+    /// key in the marker's field values.
+    Dynamic(RuntimeMarkerDynamicField),
+}
+
+/// The owned version of [`MarkerStaticField`].
+#[derive(Debug, Clone, Serialize)]
+pub struct RuntimeMarkerStaticField {
+    pub label: String,
+    pub value: String,
+}
+
+/// The owned version of [`MarkerDynamicField`].
+#[derive(Debug, Clone, Serialize)]
+pub struct RuntimeMarkerDynamicField {
+    /// The field key.
+    pub key: String,
+
+    /// The user-visible label of this field.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub label: String,
+
+    /// The format of this field.
+    pub format: MarkerFieldFormat,
+
+    /// Whether this field's value should be matched against search terms.
+    pub searchable: bool,
+}
+
+impl From<MarkerSchema> for RuntimeMarkerSchema {
+    fn from(schema: MarkerSchema) -> Self {
+        RuntimeMarkerSchema {
+            type_name: schema.type_name.to_string(),
+            locations: schema.locations,
+            chart_label: schema.chart_label.map(ToOwned::to_owned),
+            tooltip_label: schema.tooltip_label.map(ToOwned::to_owned),
+            table_label: schema.table_label.map(ToOwned::to_owned),
+            fields: schema.fields.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<MarkerSchemaField> for RuntimeMarkerSchemaField {
+    fn from(field: MarkerSchemaField) -> Self {
+        match field {
+            MarkerSchemaField::Static(field) => {
+                RuntimeMarkerSchemaField::Static(RuntimeMarkerStaticField {
+                    label: field.label.to_string(),
+                    value: field.value.to_string(),
+                })
+            }
+            MarkerSchemaField::Dynamic(field) => {
+                RuntimeMarkerSchemaField::Dynamic(RuntimeMarkerDynamicField {
+                    key: field.key.to_string(),
+                    label: field.label.to_string(),
+                    format: field.format,
+                    searchable: field.searchable,
+                })
+            }
+        }
+    }
 }
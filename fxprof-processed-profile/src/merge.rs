@@ -0,0 +1,153 @@
+use crate::category::{CategoryHandle, CategoryPairHandle, Subcategory, SubcategoryIndex};
+use crate::frame_table::{InternalFrame, InternalFrameLocation};
+use crate::global_lib_table::{GlobalLibIndex, LibraryHandle};
+use crate::{Profile, Timestamp};
+
+/// How [`merge_profiles`] arranges the merged runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MergeLayout {
+    /// Align all runs on a shared timeline: every run starts at the beginning of
+    /// the merged profile, so the runs overlap and can be compared side by side.
+    /// The timestamps of each run are kept relative to the run's own reference
+    /// timestamp.
+    SharedTimeline,
+    /// Keep the wall-clock time of each run, so that runs which were recorded one
+    /// after another are laid out one after another.
+    SeparateProcesses,
+}
+
+/// Merge several profiles, for example multiple recordings of the same workload,
+/// into one profile.
+///
+/// Each run is given as a label and a profile. The processes, threads and counters
+/// of all runs are moved into the merged profile, and the name of each process is
+/// prefixed with the label of its run, so that the runs can be told apart in the
+/// track list. Libraries, categories, strings and marker schemas are deduplicated
+/// across runs. Pids and tids which are used by more than one run get a suffix, just
+/// like when they're added twice to the same profile. The kernel library mappings
+/// of all runs are kept for future calls to [`Profile::add_sample`]; if they overlap,
+/// the ones from later runs win. The flow IDs of each run are offset, so that the
+/// flows of different runs stay separate.
+///
+/// The merged profile takes its product name, sampling interval, CPU delta unit and
+/// metadata, such as the command line, from the first run. Returns `None` if `runs` is empty.
+///
+/// ```
+/// use fxprof_processed_profile::{merge_profiles, MergeLayout, Profile, SamplingInterval, Timestamp};
+/// use std::time::SystemTime;
+///
+/// let runs: Vec<(String, Profile)> = (0..3)
+///     .map(|i| {
+///         let mut profile = Profile::new("My app", SystemTime::now().into(), SamplingInterval::from_millis(1));
+///         let process = profile.add_process("App process", 54132, Timestamp::from_millis_since_reference(0.0));
+///         profile.add_thread(process, 54132, Timestamp::from_millis_since_reference(0.0), true);
+///         (format!("Run {}", i + 1), profile)
+///     })
+///     .collect();
+/// let merged = merge_profiles(runs, MergeLayout::SeparateProcesses).unwrap();
+/// ```
+pub fn merge_profiles<L: AsRef<str>>(
+    runs: impl IntoIterator<Item = (L, Profile)>,
+    layout: MergeLayout,
+) -> Option<Profile> {
+    let runs: Vec<(L, Profile)> = runs.into_iter().collect();
+    let (_, first_profile) = runs.first()?;
+    let reference_timestamp = match layout {
+        MergeLayout::SharedTimeline => first_profile.reference_timestamp,
+        MergeLayout::SeparateProcesses => runs
+            .iter()
+            .map(|(_, profile)| profile.reference_timestamp)
+            .reduce(|a, b| if b < a { b } else { a })
+            .unwrap(),
+    };
+    let mut merged = Profile::new(
+        &first_profile.product,
+        reference_timestamp,
+        first_profile.interval,
+    );
+    merged.arguments = first_profile.arguments.clone();
+    merged.os_cpu = first_profile.os_cpu.clone();
+    merged.platform = first_profile.platform.clone();
+    merged.cpu_name = first_profile.cpu_name.clone();
+    merged.extra_info = first_profile.extra_info.clone();
+    merged.cpu_delta_unit = first_profile.cpu_delta_unit;
+
+    for (label, profile) in runs {
+        let time_offset_nanos = match layout {
+            MergeLayout::SharedTimeline => 0,
+            MergeLayout::SeparateProcesses => {
+                let offset_ms = profile.reference_timestamp.millis_since_unix_epoch()
+                    - reference_timestamp.millis_since_unix_epoch();
+                (offset_ms * 1_000_000.0).round() as u64
+            }
+        };
+        merged.append_profile(profile, time_offset_nanos, label.as_ref());
+    }
+
+    Some(merged)
+}
+
+/// Describes how the indexes of a profile which is being merged into another
+/// profile map to the indexes of the merged profile.
+#[derive(Debug, Clone)]
+pub(crate) struct MergeMapping {
+    /// Indexed by the old `LibraryHandle.0`.
+    pub lib_handles: Vec<LibraryHandle>,
+    /// Indexed by the old `GlobalLibIndex.0`.
+    pub lib_indexes: Vec<GlobalLibIndex>,
+    /// Indexed by the old `CategoryHandle.0`. Contains the new category handle and
+    /// the new indexes of the category's subcategories.
+    pub categories: Vec<(CategoryHandle, Vec<SubcategoryIndex>)>,
+    /// Added to every timestamp.
+    pub time_offset_nanos: u64,
+}
+
+impl MergeMapping {
+    pub fn lib_handle(&self, lib_handle: LibraryHandle) -> LibraryHandle {
+        self.lib_handles[lib_handle.0]
+    }
+
+    pub fn lib_index(&self, lib_index: GlobalLibIndex) -> GlobalLibIndex {
+        self.lib_indexes[lib_index.0]
+    }
+
+    pub fn category_pair(&self, category_pair: CategoryPairHandle) -> CategoryPairHandle {
+        let CategoryPairHandle(category, subcategory) = category_pair;
+        let (new_category, new_subcategories) = &self.categories[category.0 as usize];
+        CategoryPairHandle(
+            *new_category,
+            subcategory.map(|subcategory| new_subcategories[subcategory.0 as usize]),
+        )
+    }
+
+    pub fn category_and_subcategory(
+        &self,
+        category: CategoryHandle,
+        subcategory: &Subcategory,
+    ) -> (CategoryHandle, Subcategory) {
+        let (new_category, new_subcategories) = &self.categories[category.0 as usize];
+        let new_subcategory = match subcategory {
+            Subcategory::Normal(index) => Subcategory::Normal(new_subcategories[index.0 as usize]),
+            Subcategory::Other(_) => Subcategory::Other(*new_category),
+        };
+        (*new_category, new_subcategory)
+    }
+
+    pub fn timestamp(&self, timestamp: Timestamp) -> Timestamp {
+        timestamp.shifted_by_nanos(self.time_offset_nanos)
+    }
+
+    pub fn frame(&self, frame: InternalFrame) -> InternalFrame {
+        let location = match frame.location {
+            InternalFrameLocation::AddressInLib(address, lib_index) => {
+                InternalFrameLocation::AddressInLib(address, self.lib_index(lib_index))
+            }
+            location => location,
+        };
+        InternalFrame {
+            location,
+            category_pair: self.category_pair(frame.category_pair),
+            flags: frame.flags,
+        }
+    }
+}
@@ -4,6 +4,7 @@ use crate::{
     fast_hash_map::FastHashMap,
     global_lib_table::GlobalLibIndex,
     library_info::Symbol,
+    merge::MergeMapping,
     thread_string_table::{ThreadInternalStringIndex, ThreadStringTable},
 };
 
@@ -48,6 +49,19 @@ impl NativeSymbols {
         let name_string_index = names[symbol_index];
         (NativeSymbolIndex(symbol_index as u32), name_string_index)
     }
+
+    pub fn remap(&mut self, mapping: &MergeMapping) {
+        for lib_index in &mut self.lib_indexes {
+            *lib_index = mapping.lib_index(*lib_index);
+        }
+        self.lib_and_symbol_address_to_symbol_index =
+            std::mem::take(&mut self.lib_and_symbol_address_to_symbol_index)
+                .into_iter()
+                .map(|((lib_index, address), symbol_index)| {
+                    ((mapping.lib_index(lib_index), address), symbol_index)
+                })
+                .collect();
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash)]
@@ -4,13 +4,14 @@ use std::hash::Hash;
 use crate::frame_table::InternalFrameLocation;
 use crate::global_lib_table::{GlobalLibTable, LibraryHandle};
 use crate::lib_mappings::LibMappings;
+use crate::merge::MergeMapping;
 use crate::Timestamp;
 
 /// A thread. Can be created with [`Profile::add_thread`](crate::Profile::add_thread).
 #[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash)]
 pub struct ThreadHandle(pub(crate) usize);
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Process {
     pid: String,
     name: String,
@@ -77,6 +78,18 @@ impl Process {
         &self.threads
     }
 
+    /// Adjusts the process after it has been moved into a different profile, in
+    /// which its threads start at `first_thread_index`.
+    pub fn remap(&mut self, pid: String, first_thread_index: usize, mapping: &MergeMapping) {
+        self.pid = pid;
+        for thread in &mut self.threads {
+            thread.0 += first_thread_index;
+        }
+        self.start_time = mapping.timestamp(self.start_time);
+        self.end_time = self.end_time.map(|end_time| mapping.timestamp(end_time));
+        self.libs = std::mem::take(&mut self.libs).map_values(|lib| mapping.lib_handle(lib));
+    }
+
     pub fn convert_address(
         &mut self,
         global_libs: &mut GlobalLibTable,
@@ -4,21 +4,28 @@ use std::time::Duration;
 use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
 use serde_json::json;
 
-use crate::category::{Category, CategoryHandle, CategoryPairHandle};
+use crate::category::{Category, CategoryHandle, CategoryPairHandle, SubcategoryIndex};
 use crate::category_color::CategoryColor;
+use crate::counter_type::CounterType;
 use crate::counters::{Counter, CounterHandle};
-use crate::cpu_delta::CpuDelta;
+use crate::cpu_delta::{CpuDelta, CpuDeltaUnit};
+use crate::extra_info::ExtraProfileInfoSection;
 use crate::fast_hash_map::FastHashMap;
 use crate::frame::{Frame, FrameInfo};
-use crate::frame_table::{InternalFrame, InternalFrameLocation};
+use crate::frame_table::{InternalFrame, InternalFrameLocation, InternalFunction};
 use crate::global_lib_table::{GlobalLibTable, LibraryHandle};
 use crate::lib_mappings::LibMappings;
 use crate::library_info::LibraryInfo;
+use crate::merge::MergeMapping;
 use crate::process::{Process, ThreadHandle};
 use crate::reference_timestamp::ReferenceTimestamp;
+use crate::sample_table::WeightType;
 use crate::string_table::{GlobalStringIndex, GlobalStringTable};
-use crate::thread::{ProcessHandle, Thread};
-use crate::{MarkerSchema, MarkerTiming, ProfilerMarker, SymbolTable, Timestamp};
+use crate::thread::{ProcessHandle, Thread, ThreadType};
+use crate::{
+    FlowId, MarkerTiming, MarkerTypeHandle, ProfilerMarker, RuntimeMarkerSchema, SymbolTable,
+    Timestamp,
+};
 
 /// The sampling interval used during profile recording.
 ///
@@ -98,7 +105,7 @@ pub struct StringHandle(GlobalStringIndex);
 /// # Ok(())
 /// # }
 /// ```
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Profile {
     pub(crate) product: String,
     pub(crate) interval: SamplingInterval,
@@ -109,8 +116,21 @@ pub struct Profile {
     pub(crate) counters: Vec<Counter>,
     pub(crate) threads: Vec<Thread>, // append-only for stable ThreadHandles
     pub(crate) reference_timestamp: ReferenceTimestamp,
+    pub(crate) arguments: Option<String>,
+    pub(crate) os_cpu: Option<String>,
+    pub(crate) platform: Option<String>,
+    pub(crate) cpu_name: Option<String>,
+    pub(crate) extra_info: Vec<ExtraProfileInfoSection>,
+    pub(crate) cpu_delta_unit: CpuDeltaUnit,
+    pub(crate) next_flow_id: u64,
     pub(crate) string_table: GlobalStringTable,
-    pub(crate) marker_schemas: FastHashMap<&'static str, MarkerSchema>,
+    pub(crate) marker_schemas: FastHashMap<String, RuntimeMarkerSchema>,
+    /// The keys of the flow ID fields of the marker types in `marker_schemas`, for
+    /// the marker types which have flow ID fields.
+    pub(crate) flow_id_keys: FastHashMap<String, Vec<String>>,
+    /// The type names of the marker types registered with [`Profile::register_marker_type`].
+    /// Indexed by `MarkerTypeHandle.0`.
+    pub(crate) runtime_marker_types: Vec<String>,
     used_pids: FastHashMap<u32, u32>,
     used_tids: FastHashMap<u32, u32>,
 }
@@ -134,9 +154,18 @@ impl Profile {
             global_libs: GlobalLibTable::new(),
             kernel_libs: LibMappings::new(),
             reference_timestamp,
+            arguments: None,
+            os_cpu: None,
+            platform: None,
+            cpu_name: None,
+            extra_info: Vec::new(),
+            cpu_delta_unit: CpuDeltaUnit::Microseconds,
+            next_flow_id: 0,
             processes: Vec::new(),
             string_table: GlobalStringTable::new(),
             marker_schemas: FastHashMap::default(),
+            flow_id_keys: FastHashMap::default(),
+            runtime_marker_types: Vec::new(),
             categories: vec![Category {
                 name: "Other".to_string(),
                 color: CategoryColor::Gray,
@@ -163,6 +192,46 @@ impl Profile {
         self.product = product.to_string();
     }
 
+    /// Set the command line of the profiled program, or of the command which
+    /// recorded the profile.
+    pub fn set_arguments(&mut self, arguments: &str) {
+        self.arguments = Some(arguments.to_string());
+    }
+
+    /// Set the name and version of the operating system on which the profile was
+    /// recorded, for example "Linux 6.2.0" or "macOS 13.4".
+    pub fn set_os_cpu(&mut self, os_cpu: &str) {
+        self.os_cpu = Some(os_cpu.to_string());
+    }
+
+    /// Set the platform on which the profile was recorded, for example "Linux"
+    /// or "Macintosh".
+    pub fn set_platform(&mut self, platform: &str) {
+        self.platform = Some(platform.to_string());
+    }
+
+    /// Set the name of the CPU model of the machine on which the profile was recorded.
+    pub fn set_cpu_name(&mut self, cpu_name: &str) {
+        self.cpu_name = Some(cpu_name.to_string());
+    }
+
+    /// Add a section of additional information about the profile. Sections are
+    /// displayed in the order in which they were added.
+    pub fn add_extra_info_section(&mut self, section: ExtraProfileInfoSection) {
+        self.extra_info.push(section);
+    }
+
+    /// Set the unit in which the [`CpuDelta`] values of all samples in the profile
+    /// are stored. The default is microseconds. CPU deltas which were created from
+    /// a duration are converted to this unit.
+    ///
+    /// For example, for a profile which was imported from a format that records
+    /// CPU cycles per sample, use [`CpuDeltaUnit::VariableCpuCycles`] and create
+    /// the CPU deltas with [`CpuDelta::from_raw`].
+    pub fn set_cpu_delta_unit(&mut self, unit: CpuDeltaUnit) {
+        self.cpu_delta_unit = unit;
+    }
+
     /// Add a category and return its handle.
     ///
     /// Categories are used for stack frames and markers, as part of a "category pair".
@@ -217,9 +286,22 @@ impl Profile {
         }
     }
 
+    /// Makes a pid or tid from a different profile unique in this profile. The id
+    /// may already have a ".1" style suffix from the other profile, in which case
+    /// the suffix is recomputed for this profile.
+    fn make_unique_pid_or_tid_for_merge(map: &mut FastHashMap<u32, u32>, id: &str) -> String {
+        match id.split('.').next().and_then(|id| id.parse().ok()) {
+            Some(id) => Self::make_unique_pid_or_tid(map, id),
+            None => id.to_string(),
+        }
+    }
+
     /// Create a counter. Counters let you make graphs with a time axis and a Y axis. One example of a
     /// counter is memory usage.
     ///
+    /// The Firefox Profiler only draws counters in the categories of the
+    /// [`CounterType`] variants; use [`Profile::add_counter_with_type`] to pick one.
+    ///
     /// # Example
     ///
     /// ```
@@ -251,6 +333,30 @@ impl Profile {
         handle
     }
 
+    /// Create a counter which the Firefox Profiler draws as a track of the given
+    /// type. This is the same as calling [`Profile::add_counter`] with the
+    /// category from [`CounterType::category`].
+    ///
+    /// ```
+    /// use fxprof_processed_profile::{CounterType, Profile, SamplingInterval, Timestamp};
+    /// use std::time::SystemTime;
+    ///
+    /// let mut profile = Profile::new("My app", SystemTime::now().into(), SamplingInterval::from_millis(1));
+    /// let process = profile.add_process("App process", 54132, Timestamp::from_millis_since_reference(0.0));
+    /// let network_counter = profile.add_counter_with_type(process, "net", CounterType::Bandwidth, "Received bytes");
+    /// profile.add_counter_sample(network_counter, Timestamp::from_millis_since_reference(0.0), 0.0, 0);
+    /// profile.add_counter_sample(network_counter, Timestamp::from_millis_since_reference(1.0), 1500.0, 1);
+    /// ```
+    pub fn add_counter_with_type(
+        &mut self,
+        process: ProcessHandle,
+        name: &str,
+        counter_type: CounterType,
+        description: &str,
+    ) -> CounterHandle {
+        self.add_counter(process, name, counter_type.category(), description)
+    }
+
     /// Change the start time of a process.
     pub fn set_process_start_time(&mut self, process: ProcessHandle, start_time: Timestamp) {
         self.processes[process.0].set_start_time(start_time);
@@ -275,6 +381,12 @@ impl Profile {
         self.global_libs.handle_for_lib(library)
     }
 
+    /// The libraries which are referred to by the frames in the profile, in the
+    /// order in which they're serialized.
+    pub fn libs(&self) -> impl Iterator<Item = &LibraryInfo> {
+        self.global_libs.used_libs()
+    }
+
     /// Set the symbol table for a library.
     ///
     /// This symbol table can also be specified in the [`LibraryInfo`] which is given to
@@ -369,6 +481,48 @@ impl Profile {
         self.threads[thread.0].set_name(name);
     }
 
+    /// Set the unit of the sample weights of a thread. By default, weights are
+    /// sample counts.
+    ///
+    /// All samples of a thread share the same weight type. For example, for an
+    /// allocation profile, use [`WeightType::Bytes`] and pass the allocation size
+    /// as the weight to [`Profile::add_sample`].
+    pub fn set_thread_samples_weight_type(
+        &mut self,
+        thread: ThreadHandle,
+        weight_type: WeightType,
+    ) {
+        self.threads[thread.0].set_samples_weight_type(weight_type);
+    }
+
+    /// Set what kind of work a thread does, e.g. to mark it as a thread which
+    /// represents the idle time of a CPU.
+    ///
+    /// Like [`Profile::set_thread_description`] and
+    /// [`Profile::set_thread_sampling_interval`], this is stored in a thread key
+    /// which the Firefox Profiler doesn't use yet. Tools which read the profile
+    /// with [`ProfileView`](crate::ProfileView) can use it, e.g. to leave idle
+    /// threads out of their reports.
+    pub fn set_thread_type(&mut self, thread: ThreadHandle, thread_type: ThreadType) {
+        self.threads[thread.0].set_thread_type(thread_type);
+    }
+
+    /// Set a user-visible description of a thread, for example what the thread
+    /// is used for.
+    pub fn set_thread_description(&mut self, thread: ThreadHandle, description: &str) {
+        self.threads[thread.0].set_description(description);
+    }
+
+    /// Set the sampling interval of a thread, if it was sampled at a different
+    /// rate than the profile's sampling interval.
+    pub fn set_thread_sampling_interval(
+        &mut self,
+        thread: ThreadHandle,
+        interval: SamplingInterval,
+    ) {
+        self.threads[thread.0].set_interval(interval);
+    }
+
     /// Change the start time of a thread.
     pub fn set_thread_start_time(&mut self, thread: ThreadHandle, start_time: Timestamp) {
         self.threads[thread.0].set_start_time(start_time);
@@ -421,7 +575,7 @@ impl Profile {
         timestamp: Timestamp,
         frames: impl Iterator<Item = FrameInfo>,
         cpu_delta: CpuDelta,
-        weight: i32,
+        weight: i64,
     ) {
         let stack_index = self.stack_index_for_frames(thread, frames);
         self.threads[thread.0].add_sample(timestamp, stack_index, cpu_delta, weight);
@@ -434,7 +588,7 @@ impl Profile {
         &mut self,
         thread: ThreadHandle,
         timestamp: Timestamp,
-        weight: i32,
+        weight: i64,
     ) {
         self.threads[thread.0].add_sample_same_stack_zero_cpu(timestamp, weight);
     }
@@ -447,10 +601,18 @@ impl Profile {
         marker: T,
         timing: MarkerTiming,
     ) {
-        self.marker_schemas
-            .entry(T::MARKER_TYPE_NAME)
-            .or_insert_with(T::schema);
-        self.threads[thread.0].add_marker(name, marker, timing, None);
+        self.ensure_marker_schema::<T>();
+        let flow_id_keys = self
+            .flow_id_keys
+            .get(T::MARKER_TYPE_NAME)
+            .map_or(&[][..], Vec::as_slice);
+        self.threads[thread.0].add_marker(
+            name,
+            marker.json_marker_data(),
+            timing,
+            None,
+            flow_id_keys,
+        );
     }
 
     /// Add a marker to the given thread, with a stack.
@@ -462,11 +624,106 @@ impl Profile {
         timing: MarkerTiming,
         stack_frames: impl Iterator<Item = FrameInfo>,
     ) {
-        self.marker_schemas
-            .entry(T::MARKER_TYPE_NAME)
-            .or_insert_with(T::schema);
+        self.ensure_marker_schema::<T>();
         let stack_index = self.stack_index_for_frames(thread, stack_frames);
-        self.threads[thread.0].add_marker(name, marker, timing, stack_index);
+        let flow_id_keys = self
+            .flow_id_keys
+            .get(T::MARKER_TYPE_NAME)
+            .map_or(&[][..], Vec::as_slice);
+        self.threads[thread.0].add_marker(
+            name,
+            marker.json_marker_data(),
+            timing,
+            stack_index,
+            flow_id_keys,
+        );
+    }
+
+    fn ensure_marker_schema<T: ProfilerMarker>(&mut self) {
+        if !self.marker_schemas.contains_key(T::MARKER_TYPE_NAME) {
+            self.insert_marker_schema(T::schema().into());
+        }
+    }
+
+    fn insert_marker_schema(&mut self, schema: RuntimeMarkerSchema) {
+        let flow_id_keys = schema.flow_id_keys();
+        if flow_id_keys.is_empty() {
+            self.flow_id_keys.remove(&schema.type_name);
+        } else {
+            self.flow_id_keys
+                .insert(schema.type_name.clone(), flow_id_keys);
+        }
+        self.marker_schemas.insert(schema.type_name.clone(), schema);
+    }
+
+    /// Return a new [`FlowId`] which is different from all flow IDs previously
+    /// returned by this method. Use it for markers which should be connected
+    /// across threads and processes, e.g. with [`FlowMarker`](crate::FlowMarker).
+    pub fn new_flow_id(&mut self) -> FlowId {
+        let flow_id = FlowId(self.next_flow_id);
+        self.next_flow_id += 1;
+        flow_id
+    }
+
+    /// Register a marker type whose schema is only known at runtime, and return
+    /// its handle. Markers of this type can be added with [`Profile::add_runtime_marker`].
+    ///
+    /// If a marker type with the same type name already exists, its schema is replaced.
+    pub fn register_marker_type(&mut self, schema: RuntimeMarkerSchema) -> MarkerTypeHandle {
+        let handle = MarkerTypeHandle(self.runtime_marker_types.len());
+        self.runtime_marker_types.push(schema.type_name.clone());
+        self.insert_marker_schema(schema);
+        handle
+    }
+
+    /// Add a marker of a type which was registered with [`Profile::register_marker_type`].
+    ///
+    /// `field_values` contains the values of the schema's dynamic fields, keyed
+    /// by the field key. The `type` property is filled in automatically.
+    pub fn add_runtime_marker(
+        &mut self,
+        thread: ThreadHandle,
+        name: &str,
+        marker_type: MarkerTypeHandle,
+        timing: MarkerTiming,
+        field_values: serde_json::Map<String, serde_json::Value>,
+    ) {
+        let data = self.runtime_marker_data(marker_type, field_values);
+        let flow_id_keys = self
+            .flow_id_keys
+            .get(&self.runtime_marker_types[marker_type.0])
+            .map_or(&[][..], Vec::as_slice);
+        self.threads[thread.0].add_marker(name, data, timing, None, flow_id_keys);
+    }
+
+    /// Add a marker of a type which was registered with [`Profile::register_marker_type`],
+    /// with a stack.
+    pub fn add_runtime_marker_with_stack(
+        &mut self,
+        thread: ThreadHandle,
+        name: &str,
+        marker_type: MarkerTypeHandle,
+        timing: MarkerTiming,
+        field_values: serde_json::Map<String, serde_json::Value>,
+        stack_frames: impl Iterator<Item = FrameInfo>,
+    ) {
+        let data = self.runtime_marker_data(marker_type, field_values);
+        let stack_index = self.stack_index_for_frames(thread, stack_frames);
+        let flow_id_keys = self
+            .flow_id_keys
+            .get(&self.runtime_marker_types[marker_type.0])
+            .map_or(&[][..], Vec::as_slice);
+        self.threads[thread.0].add_marker(name, data, timing, stack_index, flow_id_keys);
+    }
+
+    fn runtime_marker_data(
+        &self,
+        marker_type: MarkerTypeHandle,
+        mut field_values: serde_json::Map<String, serde_json::Value>,
+    ) -> serde_json::Value {
+        let type_name = &self.runtime_marker_types[marker_type.0];
+        field_values.insert("type".to_string(), type_name.clone().into());
+        serde_json::Value::Object(field_values)
     }
 
     /// Add a data point to a counter. For a memory counter, `value_delta` is the number
@@ -474,6 +731,8 @@ impl Profile {
     /// `number_of_operations` is the number of `malloc` / `free` calls since the previous
     /// counter sample. Both numbers are deltas.
     ///
+    /// See [`CounterType`] for the meaning of the values for the other types of counters.
+    ///
     /// The graph in the profiler UI will connect subsequent data points with diagonal lines.
     /// Counters are intended for values that are measured at a certain sample rate. You can
     /// also use them for instrumented events and emit a new data point at every discrete change,
@@ -523,6 +782,18 @@ impl Profile {
                         thread.convert_string_index(&self.string_table, string_index.0);
                     InternalFrameLocation::Label(thread_string_index)
                 }
+                Frame::Function(function) => {
+                    let mut convert = |string_index: StringHandle| {
+                        thread.convert_string_index(&self.string_table, string_index.0)
+                    };
+                    InternalFrameLocation::Function(InternalFunction {
+                        name: convert(function.name),
+                        file: function.file.map(&mut convert),
+                        line: function.line,
+                        column: function.column,
+                        resource: function.resource.map(&mut convert),
+                    })
+                }
             };
             let internal_frame = InternalFrame {
                 location,
@@ -536,6 +807,104 @@ impl Profile {
         prefix
     }
 
+    /// Move all processes, threads and counters of `other` into this profile, for
+    /// [`merge_profiles`](crate::merge_profiles). All of `other`'s timestamps are
+    /// shifted by `time_offset_nanos`, and its process names are prefixed with
+    /// `label`, if given.
+    pub(crate) fn append_profile(
+        &mut self,
+        mut other: Profile,
+        time_offset_nanos: u64,
+        label: &str,
+    ) {
+        let (lib_handles, lib_indexes) = self.global_libs.append(other.global_libs);
+        let categories = other
+            .categories
+            .into_iter()
+            .map(|category| self.merge_category(category))
+            .collect();
+        let mapping = MergeMapping {
+            lib_handles,
+            lib_indexes,
+            categories,
+            time_offset_nanos,
+        };
+        // Kernel library mappings only matter for future calls to add_sample.
+        self.kernel_libs
+            .extend(other.kernel_libs.map_values(|lib| mapping.lib_handle(lib)));
+
+        // Offset the flow IDs of the appended profile, so that they don't collide
+        // with the flow IDs of the profiles which were appended before it.
+        let flow_id_offset = self.next_flow_id;
+        let mut other_next_flow_id = other.next_flow_id;
+        for thread in &mut other.threads {
+            thread.for_each_flow_id(|flow_id| {
+                other_next_flow_id = other_next_flow_id.max(flow_id.0.saturating_add(1));
+                *flow_id = FlowId(flow_id.0.wrapping_add(flow_id_offset));
+            });
+        }
+        self.next_flow_id = flow_id_offset.saturating_add(other_next_flow_id);
+
+        let first_process_index = self.processes.len();
+        let first_thread_index = self.threads.len();
+        for mut process in other.processes {
+            let pid = Self::make_unique_pid_or_tid_for_merge(&mut self.used_pids, process.pid());
+            process.remap(pid, first_thread_index, &mapping);
+            let name = format!("{label}: {}", process.name());
+            process.set_name(&name);
+            self.processes.push(process);
+        }
+        for mut thread in other.threads {
+            let process = ProcessHandle(thread.process().0 + first_process_index);
+            let tid = Self::make_unique_pid_or_tid_for_merge(&mut self.used_tids, thread.tid());
+            thread.remap(
+                process,
+                tid,
+                &mapping,
+                &other.string_table,
+                &mut self.string_table,
+            );
+            self.threads.push(thread);
+        }
+        for mut counter in other.counters {
+            let process = ProcessHandle(counter.process().0 + first_process_index);
+            counter.remap(process, self.processes[process.0].pid(), &mapping);
+            self.counters.push(counter);
+        }
+        for (type_name, schema) in other.marker_schemas {
+            if !self.marker_schemas.contains_key(&type_name) {
+                self.insert_marker_schema(schema);
+            }
+        }
+    }
+
+    /// Returns the handle of the category with the same name and color as
+    /// `category`, adding it if needed, and the indexes of its subcategories.
+    fn merge_category(&mut self, category: Category) -> (CategoryHandle, Vec<SubcategoryIndex>) {
+        let handle = match self
+            .categories
+            .iter()
+            .position(|c| c.name == category.name && c.color == category.color)
+        {
+            Some(index) => CategoryHandle(index as u16),
+            None => self.add_category(&category.name, category.color),
+        };
+        let merged_category = &mut self.categories[handle.0 as usize];
+        let mut subcategories = Vec::with_capacity(category.subcategories.len());
+        for name in category.subcategories {
+            let index = match merged_category
+                .subcategories
+                .iter()
+                .position(|s| *s == name)
+            {
+                Some(index) => SubcategoryIndex(index as u8),
+                None => merged_category.add_subcategory(name),
+            };
+            subcategories.push(index);
+        }
+        (handle, subcategories)
+    }
+
     /// Returns a flattened list of `ThreadHandle`s in the right order.
     ///
     // The processed profile format has all threads from all processes in a flattened threads list.
@@ -576,6 +945,7 @@ impl Profile {
             threads: &self.threads,
             processes: &self.processes,
             categories: &self.categories,
+            cpu_delta_unit: self.cpu_delta_unit,
             sorted_threads,
         }
     }
@@ -637,7 +1007,7 @@ impl<'a> Serialize for SerializableProfileMeta<'a> {
             &json!({
                 "time": "ms",
                 "eventDelay": "ms",
-                "threadCPUDelta": "µs",
+                "threadCPUDelta": self.0.cpu_delta_unit,
             }),
         )?;
         map.serialize_entry("startTime", &self.0.reference_timestamp)?;
@@ -648,11 +1018,27 @@ impl<'a> Serialize for SerializableProfileMeta<'a> {
         map.serialize_entry("doesNotUseFrameImplementation", &true)?;
         map.serialize_entry("sourceCodeIsNotOnSearchfox", &true)?;
 
-        let mut marker_schemas: Vec<MarkerSchema> =
-            self.0.marker_schemas.values().cloned().collect();
-        marker_schemas.sort_by_key(|schema| schema.type_name);
+        let mut marker_schemas: Vec<&RuntimeMarkerSchema> =
+            self.0.marker_schemas.values().collect();
+        marker_schemas.sort_by_key(|schema| &schema.type_name);
         map.serialize_entry("markerSchema", &marker_schemas)?;
 
+        if let Some(arguments) = &self.0.arguments {
+            map.serialize_entry("arguments", arguments)?;
+        }
+        if let Some(os_cpu) = &self.0.os_cpu {
+            map.serialize_entry("oscpu", os_cpu)?;
+        }
+        if let Some(platform) = &self.0.platform {
+            map.serialize_entry("platform", platform)?;
+        }
+        if let Some(cpu_name) = &self.0.cpu_name {
+            map.serialize_entry("CPUName", cpu_name)?;
+        }
+        if !self.0.extra_info.is_empty() {
+            map.serialize_entry("extra", &self.0.extra_info)?;
+        }
+
         map.end()
     }
 }
@@ -661,6 +1047,7 @@ struct SerializableProfileThreadsProperty<'a> {
     threads: &'a [Thread],
     processes: &'a [Process],
     categories: &'a [Category],
+    cpu_delta_unit: CpuDeltaUnit,
     sorted_threads: &'a [ThreadHandle],
 }
 
@@ -672,7 +1059,12 @@ impl<'a> Serialize for SerializableProfileThreadsProperty<'a> {
             let categories = &self.categories;
             let thread = &self.threads[thread.0];
             let process = &self.processes[thread.process().0];
-            seq.serialize_element(&SerializableProfileThread(process, thread, categories))?;
+            seq.serialize_element(&SerializableProfileThread(
+                process,
+                thread,
+                categories,
+                self.cpu_delta_unit,
+            ))?;
         }
 
         seq.end()
@@ -697,22 +1089,11 @@ impl<'a> Serialize for SerializableProfileCountersProperty<'a> {
     }
 }
 
-struct SerializableProfileThread<'a>(&'a Process, &'a Thread, &'a [Category]);
+struct SerializableProfileThread<'a>(&'a Process, &'a Thread, &'a [Category], CpuDeltaUnit);
 
 impl<'a> Serialize for SerializableProfileThread<'a> {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-        let SerializableProfileThread(process, thread, categories) = self;
-        let process_start_time = process.start_time();
-        let process_end_time = process.end_time();
-        let process_name = process.name();
-        let pid = process.pid();
-        thread.serialize_with(
-            serializer,
-            categories,
-            process_start_time,
-            process_end_time,
-            process_name,
-            pid,
-        )
+        let SerializableProfileThread(process, thread, categories, cpu_delta_unit) = self;
+        thread.serialize_with(serializer, categories, *cpu_delta_unit, process)
     }
 }
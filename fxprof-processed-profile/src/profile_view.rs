@@ -0,0 +1,936 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::io::Read;
+
+use debugid::DebugId;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::category::{CategoryHandle, CategoryPairHandle, SubcategoryIndex};
+use crate::compact_format::{compact_profile_to_json, is_compact_profile, CompactProfileError};
+use crate::{
+    CategoryColor, CounterType, CpuDelta, CpuDeltaUnit, ExtraProfileInfoSection, FlowId,
+    FrameFlags, LibraryInfo, MarkerTiming, ReferenceTimestamp, SamplingInterval, ThreadType,
+    Timestamp, WeightType,
+};
+
+/// An error that can occur when reading a profile with [`ProfileView`].
+#[derive(Debug)]
+pub enum ProfileReadError {
+    /// The profile data could not be read or decompressed.
+    Io(std::io::Error),
+    /// The profile data is not valid JSON, or doesn't have the structure of a
+    /// processed profile.
+    Json(serde_json::Error),
+    /// The profile data is in the compact profile format, but could not be
+    /// converted to JSON.
+    Compact(CompactProfileError),
+    /// A column in one of the thread's tables has the wrong length, or contains
+    /// an index that is out of range.
+    InvalidThreadData {
+        thread_index: usize,
+        column: &'static str,
+    },
+    /// A counter's `mainThreadIndex` is out of range, or the columns of its
+    /// sample table have different lengths.
+    InvalidCounterData { counter_index: usize },
+}
+
+impl fmt::Display for ProfileReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProfileReadError::Io(e) => write!(f, "Could not read the profile: {e}"),
+            ProfileReadError::Json(e) => write!(f, "Could not parse the profile JSON: {e}"),
+            ProfileReadError::Compact(e) => write!(f, "Could not read the compact profile: {e}"),
+            ProfileReadError::InvalidThreadData {
+                thread_index,
+                column,
+            } => write!(
+                f,
+                "The {column} column of thread {thread_index} has an invalid length or contains an invalid index"
+            ),
+            ProfileReadError::InvalidCounterData { counter_index } => write!(
+                f,
+                "Counter {counter_index} has an invalid mainThreadIndex or invalid samples"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ProfileReadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ProfileReadError::Io(e) => Some(e),
+            ProfileReadError::Json(e) => Some(e),
+            ProfileReadError::Compact(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ProfileReadError {
+    fn from(e: std::io::Error) -> Self {
+        ProfileReadError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for ProfileReadError {
+    fn from(e: serde_json::Error) -> Self {
+        ProfileReadError::Json(e)
+    }
+}
+
+/// A read-only view of a profile in the processed profile format, for example
+/// one that was written by serializing a [`Profile`](crate::Profile).
+///
+/// All indexes in the profile are checked when the profile is loaded, so the
+/// accessors on this type and on the types it hands out don't fail.
+///
+/// ```
+/// use fxprof_processed_profile::ProfileView;
+///
+/// # fn print_stacks(profile_bytes: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+/// let profile = ProfileView::from_slice(profile_bytes)?;
+/// for thread in profile.threads() {
+///     println!("Thread {} ({})", thread.name(), thread.tid());
+///     for sample in thread.samples() {
+///         let function_names: Vec<&str> =
+///             thread.stack_frames(sample.stack_index).map(|frame| frame.name).collect();
+///         println!("  {:?}: {}", sample.timestamp, function_names.join(" <- "));
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ProfileView {
+    profile: SerializedProfile,
+}
+
+impl ProfileView {
+    /// Read a profile from JSON bytes. If the bytes are gzip-compressed, they
+    /// are decompressed first. Profiles in the compact profile format, see
+    /// [`write_compact_profile`](crate::write_compact_profile), are converted
+    /// to JSON first.
+    pub fn from_slice(data: &[u8]) -> Result<Self, ProfileReadError> {
+        const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+        if data.starts_with(GZIP_MAGIC) {
+            let decompressed = decompress_gzip(data)?;
+            return Self::from_slice(&decompressed);
+        }
+        if is_compact_profile(data) {
+            let mut json = Vec::new();
+            compact_profile_to_json(data, &mut json).map_err(ProfileReadError::Compact)?;
+            return Self::from_slice(&json);
+        }
+        let profile: SerializedProfile = serde_json::from_slice(data)?;
+        profile.validate()?;
+        Ok(Self { profile })
+    }
+
+    /// Read a profile from a reader, for example from a [`std::fs::File`]. The
+    /// data can be plain JSON or gzip-compressed JSON.
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Self, ProfileReadError> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        Self::from_slice(&data)
+    }
+
+    /// The name of the profiled product.
+    pub fn product(&self) -> &str {
+        &self.profile.meta.product
+    }
+
+    /// The sampling interval which was used during recording.
+    pub fn interval(&self) -> SamplingInterval {
+        SamplingInterval::from_nanos((self.profile.meta.interval * 1_000_000.0).round() as u64)
+    }
+
+    /// The unit of the CPU deltas of all samples in the profile.
+    pub fn cpu_delta_unit(&self) -> CpuDeltaUnit {
+        self.profile.meta.cpu_delta_unit()
+    }
+
+    /// The absolute timestamp which all other timestamps in the profile are relative to.
+    pub fn reference_timestamp(&self) -> ReferenceTimestamp {
+        self.profile.meta.start_time
+    }
+
+    /// The profile's categories. The index of each category matches the
+    /// [`CategoryHandle`] in the frames and markers which use it.
+    pub fn categories(&self) -> impl ExactSizeIterator<Item = CategoryView<'_>> + '_ {
+        self.profile.meta.categories.iter().map(|category| {
+            // The last subcategory is the implicit "Other" subcategory.
+            let subcategory_count = category.subcategories.len().saturating_sub(1);
+            CategoryView {
+                name: &category.name,
+                color: category.color,
+                subcategories: &category.subcategories[..subcategory_count],
+            }
+        })
+    }
+
+    /// The libraries which are referred to by the frames in the profile.
+    pub fn libs(&self) -> &[LibraryInfo] {
+        &self.profile.libs
+    }
+
+    /// The marker schemas, as JSON objects. These have the structure of a
+    /// serialized [`MarkerSchema`](crate::MarkerSchema).
+    pub fn marker_schemas(&self) -> &[Value] {
+        &self.profile.meta.marker_schema
+    }
+
+    /// The command line, if the profile has one.
+    pub fn arguments(&self) -> Option<&str> {
+        self.profile.meta.arguments.as_deref()
+    }
+
+    /// The operating system name and version, if the profile has them.
+    pub fn os_cpu(&self) -> Option<&str> {
+        self.profile.meta.oscpu.as_deref()
+    }
+
+    /// The platform, if the profile has one.
+    pub fn platform(&self) -> Option<&str> {
+        self.profile.meta.platform.as_deref()
+    }
+
+    /// The CPU model name, if the profile has one.
+    pub fn cpu_name(&self) -> Option<&str> {
+        self.profile.meta.cpu_name.as_deref()
+    }
+
+    /// The sections of additional information about the profile.
+    pub fn extra_info_sections(&self) -> &[ExtraProfileInfoSection] {
+        &self.profile.meta.extra
+    }
+
+    /// The threads in the profile, in the order in which they appear in the JSON.
+    pub fn threads(&self) -> impl ExactSizeIterator<Item = ThreadView<'_>> + '_ {
+        self.profile.threads.iter().map(move |thread| ThreadView {
+            profile: &self.profile,
+            thread,
+        })
+    }
+
+    /// The thread at index `thread_index` in the profile's thread list.
+    pub fn thread(&self, thread_index: usize) -> Option<ThreadView<'_>> {
+        let thread = self.profile.threads.get(thread_index)?;
+        Some(ThreadView {
+            profile: &self.profile,
+            thread,
+        })
+    }
+
+    /// The addresses of the native frames in the stacks of all samples, grouped
+    /// by library. This is the set of addresses which needs to be looked up to
+    /// symbolicate the profile. The addresses are relative to the library base
+    /// address, and they are sorted.
+    pub fn native_frame_addresses(&self) -> BTreeMap<DebugId, (&LibraryInfo, BTreeSet<u32>)> {
+        let mut addresses_per_lib = BTreeMap::new();
+        for thread in self.threads() {
+            let stack_indexes: BTreeSet<usize> =
+                thread.samples().filter_map(|s| s.stack_index).collect();
+            for stack_index in stack_indexes {
+                for frame in thread.stack_frames(Some(stack_index)) {
+                    if let (Some(lib), Some(address)) = (frame.lib, frame.address) {
+                        addresses_per_lib
+                            .entry(lib.debug_id)
+                            .or_insert_with(|| (lib, BTreeSet::new()))
+                            .1
+                            .insert(address);
+                    }
+                }
+            }
+        }
+        addresses_per_lib
+    }
+
+    /// The counters in the profile.
+    pub fn counters(&self) -> impl ExactSizeIterator<Item = CounterView<'_>> + '_ {
+        self.profile
+            .counters
+            .iter()
+            .map(|counter| CounterView { counter })
+    }
+}
+
+#[cfg(feature = "gzip")]
+fn decompress_gzip(data: &[u8]) -> Result<Vec<u8>, ProfileReadError> {
+    let mut decompressed = Vec::new();
+    flate2::read::GzDecoder::new(data).read_to_end(&mut decompressed)?;
+    Ok(decompressed)
+}
+
+#[cfg(not(feature = "gzip"))]
+fn decompress_gzip(_data: &[u8]) -> Result<Vec<u8>, ProfileReadError> {
+    Err(ProfileReadError::Io(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        "the profile is gzip-compressed, but the gzip feature of fxprof-processed-profile is disabled",
+    )))
+}
+
+/// The information about a category in a [`ProfileView`].
+#[derive(Debug, Clone, Copy)]
+pub struct CategoryView<'a> {
+    pub name: &'a str,
+    pub color: CategoryColor,
+    /// The names of the subcategories, without the implicit "Other" subcategory.
+    pub subcategories: &'a [String],
+}
+
+/// A thread in a [`ProfileView`].
+#[derive(Debug, Clone, Copy)]
+pub struct ThreadView<'a> {
+    profile: &'a SerializedProfile,
+    thread: &'a SerializedThread,
+}
+
+impl<'a> ThreadView<'a> {
+    /// The thread name. For main threads, this is the process name.
+    pub fn name(&self) -> &'a str {
+        &self.thread.name
+    }
+
+    pub fn tid(&self) -> &'a str {
+        &self.thread.tid
+    }
+
+    pub fn pid(&self) -> &'a str {
+        &self.thread.pid
+    }
+
+    pub fn process_name(&self) -> &'a str {
+        &self.thread.process_name
+    }
+
+    pub fn is_main_thread(&self) -> bool {
+        self.thread.is_main_thread
+    }
+
+    /// What kind of work the thread does.
+    pub fn thread_type(&self) -> ThreadType {
+        self.thread.thread_type
+    }
+
+    /// The thread's user-visible description, if it has one.
+    pub fn description(&self) -> Option<&'a str> {
+        self.thread.description.as_deref()
+    }
+
+    /// The thread's sampling interval, if it differs from the profile's
+    /// sampling interval.
+    pub fn sampling_interval(&self) -> Option<SamplingInterval> {
+        self.thread
+            .interval
+            .map(|interval| SamplingInterval::from_nanos((interval * 1_000_000.0).round() as u64))
+    }
+
+    pub fn register_time(&self) -> Timestamp {
+        self.thread.register_time
+    }
+
+    pub fn unregister_time(&self) -> Option<Timestamp> {
+        self.thread.unregister_time
+    }
+
+    pub fn process_startup_time(&self) -> Timestamp {
+        self.thread.process_startup_time
+    }
+
+    pub fn process_shutdown_time(&self) -> Option<Timestamp> {
+        self.thread.process_shutdown_time
+    }
+
+    /// The unit of the weights of the thread's samples.
+    pub fn weight_type(&self) -> WeightType {
+        self.thread.samples.weight_type
+    }
+
+    /// The thread's samples, in the order in which they were added.
+    pub fn samples(&self) -> impl ExactSizeIterator<Item = SampleView> + 'a {
+        let samples = &self.thread.samples;
+        let cpu_delta_unit = self.profile.meta.cpu_delta_unit();
+        (0..samples.time.len()).map(move |i| SampleView {
+            timestamp: samples.time[i],
+            stack_index: samples.stack[i],
+            weight: samples.weight.as_ref().map_or(1, |weights| weights[i]),
+            cpu_delta: samples
+                .thread_cpu_delta
+                .as_ref()
+                .and_then(|cpu_deltas| cpu_deltas[i])
+                .map(|value| CpuDelta::from_value_in_unit(value, cpu_delta_unit)),
+        })
+    }
+
+    /// The frames of the stack with the given index, starting with the leaf
+    /// frame and ending with the root frame. The iterator is empty if `stack_index`
+    /// is `None` or out of range.
+    pub fn stack_frames(&self, stack_index: Option<usize>) -> StackFrames<'a> {
+        StackFrames {
+            thread: *self,
+            next_stack_index: stack_index,
+        }
+    }
+
+    /// The thread's markers, in the order in which they were added.
+    pub fn markers(&self) -> impl ExactSizeIterator<Item = MarkerView<'a>> + 'a {
+        let thread = self.thread;
+        let markers = &thread.markers;
+        (0..markers.name.len()).map(move |i| {
+            let start = markers.start_time[i].unwrap_or(Timestamp::from_nanos_since_reference(0));
+            let end = markers.end_time[i].unwrap_or(Timestamp::from_nanos_since_reference(0));
+            let timing = match markers.phase[i] {
+                0 => MarkerTiming::Instant(start),
+                1 => MarkerTiming::Interval(start, end),
+                2 => MarkerTiming::IntervalStart(start),
+                _ => MarkerTiming::IntervalEnd(end),
+            };
+            MarkerView {
+                name: thread.string(markers.name[i]),
+                timing,
+                category: CategoryHandle(markers.category[i]),
+                data: &markers.data[i],
+                stack_count: thread.stack_table.prefix.len(),
+                strings: &thread.string_array,
+            }
+        })
+    }
+
+    fn frame_view(&self, stack_index: usize) -> FrameView<'a> {
+        let thread = self.thread;
+        let stack_table = &thread.stack_table;
+        let frame_table = &thread.frame_table;
+        let func_table = &thread.func_table;
+
+        let frame_index = stack_table.frame[stack_index];
+        let func_index = frame_table.func[frame_index];
+        let category = stack_table.category[stack_index];
+        let subcategory = stack_table.subcategory[stack_index];
+        // The last subcategory of each category is the implicit "Other" subcategory.
+        let other_subcategory = self.profile.meta.categories[usize::from(category)]
+            .subcategories
+            .len()
+            .saturating_sub(1);
+        let subcategory = if usize::from(subcategory) < other_subcategory {
+            Some(SubcategoryIndex(subcategory))
+        } else {
+            None
+        };
+
+        let mut flags = FrameFlags::empty();
+        if func_table.is_js[func_index] {
+            flags |= FrameFlags::IS_JS;
+        }
+        if func_table.relevant_for_js[func_index] {
+            flags |= FrameFlags::IS_RELEVANT_FOR_JS;
+        }
+
+        let resource_index = usize::try_from(func_table.resource[func_index]).ok();
+        let lib = resource_index
+            .and_then(|resource_index| thread.resource_table.lib[resource_index])
+            .map(|lib_index| &self.profile.libs[lib_index]);
+        let resource_name = resource_index
+            .map(|resource_index| thread.string(thread.resource_table.name[resource_index]));
+        let native_symbol_name = frame_table.native_symbol[frame_index]
+            .map(|symbol_index| thread.string(thread.native_symbols.name[symbol_index]));
+
+        FrameView {
+            name: thread.string(func_table.name[func_index]),
+            address: u32::try_from(frame_table.address[frame_index]).ok(),
+            lib,
+            resource_name,
+            file: func_table.file_name[func_index].map(|file| thread.string(file)),
+            line: func_table.line_number[func_index],
+            column: func_table.column_number[func_index],
+            native_symbol_name,
+            category_pair: CategoryPairHandle(CategoryHandle(category), subcategory),
+            flags,
+        }
+    }
+}
+
+/// An iterator over the frames of a stack, from the leaf to the root. Created
+/// with [`ThreadView::stack_frames`].
+#[derive(Debug, Clone)]
+pub struct StackFrames<'a> {
+    thread: ThreadView<'a>,
+    next_stack_index: Option<usize>,
+}
+
+impl<'a> Iterator for StackFrames<'a> {
+    type Item = FrameView<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let stack_index = self.next_stack_index?;
+        // The prefixes of valid stacks are checked when the profile is read,
+        // but the initial stack index comes from the caller.
+        self.next_stack_index = *self.thread.thread.stack_table.prefix.get(stack_index)?;
+        Some(self.thread.frame_view(stack_index))
+    }
+}
+
+/// A sample in a [`ThreadView`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SampleView {
+    pub timestamp: Timestamp,
+    /// The index of the sampled stack. Use [`ThreadView::stack_frames`] to get
+    /// the frames of the stack.
+    pub stack_index: Option<usize>,
+    /// The sample weight, in the unit given by [`ThreadView::weight_type`].
+    pub weight: i64,
+    /// The CPU delta, if the profile has CPU deltas.
+    pub cpu_delta: Option<CpuDelta>,
+}
+
+/// A resolved stack frame, returned by [`StackFrames`].
+#[derive(Debug, Clone, Copy)]
+pub struct FrameView<'a> {
+    /// The function name. For frames which have not been symbolicated, this is
+    /// the address as a hex string.
+    pub name: &'a str,
+    /// The address of the frame, relative to the library base address.
+    pub address: Option<u32>,
+    /// The library which contains this frame's address.
+    pub lib: Option<&'a LibraryInfo>,
+    /// The name of the function's resource: the library name for native
+    /// functions, or the script URL or module for [`Frame::Function`](crate::Frame::Function) frames.
+    pub resource_name: Option<&'a str>,
+    /// The file which contains the function, if known.
+    pub file: Option<&'a str>,
+    /// The line number at which the function starts, if known.
+    pub line: Option<u32>,
+    /// The column number at which the function starts, if known.
+    pub column: Option<u32>,
+    /// The name of the native symbol, if the library had a symbol table.
+    pub native_symbol_name: Option<&'a str>,
+    pub category_pair: CategoryPairHandle,
+    pub flags: FrameFlags,
+}
+
+/// A marker in a [`ThreadView`].
+#[derive(Debug, Clone)]
+pub struct MarkerView<'a> {
+    pub name: &'a str,
+    pub timing: MarkerTiming,
+    pub category: CategoryHandle,
+    /// The marker's data, as returned by [`ProfilerMarker::json_marker_data`](crate::ProfilerMarker::json_marker_data).
+    pub data: &'a Value,
+    stack_count: usize,
+    strings: &'a [String],
+}
+
+impl<'a> MarkerView<'a> {
+    /// The index of the marker's stack, if the marker was added with a stack
+    /// and the stack index is valid.
+    pub fn stack_index(&self) -> Option<usize> {
+        let stack_index = self.data.get("cause")?.get("stack")?.as_u64()?;
+        usize::try_from(stack_index)
+            .ok()
+            .filter(|stack_index| *stack_index < self.stack_count)
+    }
+
+    /// The flow ID in the data field with the key `key`, if the field contains a
+    /// flow ID, i.e. the index of a flow ID string in the thread's string table.
+    pub fn flow_id(&self, key: &str) -> Option<FlowId> {
+        let index = usize::try_from(self.data.get(key)?.as_u64()?).ok()?;
+        FlowId::from_hex_string(self.strings.get(index)?)
+    }
+}
+
+/// A counter in a [`ProfileView`].
+#[derive(Debug, Clone, Copy)]
+pub struct CounterView<'a> {
+    counter: &'a SerializedCounter,
+}
+
+impl<'a> CounterView<'a> {
+    pub fn name(&self) -> &'a str {
+        &self.counter.name
+    }
+
+    pub fn category(&self) -> &'a str {
+        &self.counter.category
+    }
+
+    pub fn description(&self) -> &'a str {
+        &self.counter.description
+    }
+
+    pub fn pid(&self) -> &'a str {
+        &self.counter.pid
+    }
+
+    /// The index of the main thread of the counter's process in [`ProfileView::threads`].
+    pub fn main_thread_index(&self) -> usize {
+        self.counter.main_thread_index
+    }
+
+    /// The kind of track which the Firefox Profiler draws for this counter,
+    /// based on its category.
+    pub fn counter_type(&self) -> Option<CounterType> {
+        CounterType::from_category(&self.counter.category)
+    }
+
+    /// The counter's samples, in the order in which they were added.
+    pub fn samples(&self) -> impl Iterator<Item = CounterSampleView> + 'a {
+        self.counter.sample_groups.iter().flat_map(|group| {
+            let samples = &group.samples;
+            (0..samples.time.len()).map(move |i| CounterSampleView {
+                timestamp: samples.time[i],
+                value_delta: samples.count[i],
+                number_of_operations_delta: samples.number.as_ref().map_or(0, |number| number[i]),
+            })
+        })
+    }
+}
+
+/// A counter sample, see [`Profile::add_counter_sample`](crate::Profile::add_counter_sample).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CounterSampleView {
+    pub timestamp: Timestamp,
+    pub value_delta: f64,
+    pub number_of_operations_delta: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SerializedProfile {
+    meta: SerializedMeta,
+    libs: Vec<LibraryInfo>,
+    threads: Vec<SerializedThread>,
+    #[serde(default)]
+    counters: Vec<SerializedCounter>,
+}
+
+impl SerializedProfile {
+    fn validate(&self) -> Result<(), ProfileReadError> {
+        for (thread_index, thread) in self.threads.iter().enumerate() {
+            thread
+                .validate(self.libs.len(), &self.meta.categories)
+                .map_err(|column| ProfileReadError::InvalidThreadData {
+                    thread_index,
+                    column,
+                })?;
+        }
+        for (counter_index, counter) in self.counters.iter().enumerate() {
+            let samples_are_valid = counter.sample_groups.iter().all(|group| {
+                let samples = &group.samples;
+                samples.count.len() == samples.time.len()
+                    && samples
+                        .number
+                        .as_ref()
+                        .map_or(true, |number| number.len() == samples.time.len())
+            });
+            if counter.main_thread_index >= self.threads.len() || !samples_are_valid {
+                return Err(ProfileReadError::InvalidCounterData { counter_index });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SerializedMeta {
+    categories: Vec<SerializedCategory>,
+    interval: f64,
+    product: String,
+    start_time: ReferenceTimestamp,
+    #[serde(default)]
+    marker_schema: Vec<Value>,
+    arguments: Option<String>,
+    oscpu: Option<String>,
+    platform: Option<String>,
+    #[serde(rename = "CPUName")]
+    cpu_name: Option<String>,
+    #[serde(default)]
+    extra: Vec<ExtraProfileInfoSection>,
+    sample_units: Option<SerializedSampleUnits>,
+}
+
+impl SerializedMeta {
+    fn cpu_delta_unit(&self) -> CpuDeltaUnit {
+        self.sample_units
+            .as_ref()
+            .and_then(|sample_units| sample_units.thread_cpu_delta)
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct SerializedSampleUnits {
+    #[serde(rename = "threadCPUDelta")]
+    thread_cpu_delta: Option<CpuDeltaUnit>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct SerializedCategory {
+    name: String,
+    color: CategoryColor,
+    subcategories: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SerializedThread {
+    name: String,
+    tid: String,
+    pid: String,
+    process_name: String,
+    is_main_thread: bool,
+    #[serde(default)]
+    thread_type: ThreadType,
+    description: Option<String>,
+    interval: Option<f64>,
+    register_time: Timestamp,
+    unregister_time: Option<Timestamp>,
+    process_startup_time: Timestamp,
+    process_shutdown_time: Option<Timestamp>,
+    frame_table: SerializedFrameTable,
+    func_table: SerializedFuncTable,
+    markers: SerializedMarkerTable,
+    native_symbols: SerializedNativeSymbols,
+    resource_table: SerializedResourceTable,
+    samples: SerializedSampleTable,
+    stack_table: SerializedStackTable,
+    string_array: Vec<String>,
+}
+
+impl SerializedThread {
+    fn string(&self, index: usize) -> &str {
+        &self.string_array[index]
+    }
+
+    /// Checks the lengths of all table columns and that all indexes are in range.
+    /// Returns the name of the first invalid column.
+    fn validate(
+        &self,
+        lib_count: usize,
+        categories: &[SerializedCategory],
+    ) -> Result<(), &'static str> {
+        fn check(condition: bool, column: &'static str) -> Result<(), &'static str> {
+            if condition {
+                Ok(())
+            } else {
+                Err(column)
+            }
+        }
+        fn all_below(column: &[usize], len: usize) -> bool {
+            column.iter().all(|index| *index < len)
+        }
+
+        let string_count = self.string_array.len();
+
+        let samples = &self.samples;
+        let sample_count = samples.time.len();
+        let stack_count = self.stack_table.category.len();
+        check(
+            samples.stack.len() == sample_count
+                && samples.stack.iter().flatten().all(|s| *s < stack_count),
+            "samples.stack",
+        )?;
+        check(
+            samples
+                .weight
+                .as_ref()
+                .map_or(true, |weight| weight.len() == sample_count),
+            "samples.weight",
+        )?;
+        check(
+            samples
+                .thread_cpu_delta
+                .as_ref()
+                .map_or(true, |cpu_delta| cpu_delta.len() == sample_count),
+            "samples.threadCPUDelta",
+        )?;
+
+        let stacks = &self.stack_table;
+        let frame_count = self.frame_table.func.len();
+        check(
+            stacks.prefix.len() == stack_count
+                && stacks
+                    .prefix
+                    .iter()
+                    .enumerate()
+                    .all(|(i, prefix)| prefix.map_or(true, |prefix| prefix < i)),
+            "stackTable.prefix",
+        )?;
+        check(
+            stacks.frame.len() == stack_count && all_below(&stacks.frame, frame_count),
+            "stackTable.frame",
+        )?;
+        check(
+            stacks.subcategory.len() == stack_count
+                && stacks
+                    .category
+                    .iter()
+                    .all(|category| usize::from(*category) < categories.len()),
+            "stackTable.category",
+        )?;
+
+        let frames = &self.frame_table;
+        let func_count = self.func_table.name.len();
+        let native_symbol_count = self.native_symbols.name.len();
+        check(frames.address.len() == frame_count, "frameTable.address")?;
+        check(all_below(&frames.func, func_count), "frameTable.func")?;
+        check(
+            frames.native_symbol.len() == frame_count
+                && frames
+                    .native_symbol
+                    .iter()
+                    .flatten()
+                    .all(|s| *s < native_symbol_count),
+            "frameTable.nativeSymbol",
+        )?;
+
+        let funcs = &self.func_table;
+        let resource_count = self.resource_table.lib.len();
+        check(all_below(&funcs.name, string_count), "funcTable.name")?;
+        check(
+            funcs.is_js.len() == func_count && funcs.relevant_for_js.len() == func_count,
+            "funcTable.isJS",
+        )?;
+        check(
+            funcs.resource.len() == func_count
+                && funcs
+                    .resource
+                    .iter()
+                    .all(|r| *r < 0 || (*r as usize) < resource_count),
+            "funcTable.resource",
+        )?;
+        check(
+            funcs.file_name.len() == func_count
+                && funcs.file_name.iter().flatten().all(|s| *s < string_count),
+            "funcTable.fileName",
+        )?;
+        check(
+            funcs.line_number.len() == func_count && funcs.column_number.len() == func_count,
+            "funcTable.lineNumber",
+        )?;
+
+        let resources = &self.resource_table;
+        check(
+            resources.lib.iter().flatten().all(|lib| *lib < lib_count),
+            "resourceTable.lib",
+        )?;
+        check(
+            resources.name.len() == resource_count && all_below(&resources.name, string_count),
+            "resourceTable.name",
+        )?;
+
+        let native_symbols = &self.native_symbols;
+        check(
+            all_below(&native_symbols.name, string_count),
+            "nativeSymbols.name",
+        )?;
+
+        let markers = &self.markers;
+        let marker_count = markers.name.len();
+        check(all_below(&markers.name, string_count), "markers.name")?;
+        check(
+            markers.start_time.len() == marker_count && markers.end_time.len() == marker_count,
+            "markers.startTime",
+        )?;
+        check(
+            markers.phase.len() == marker_count && markers.phase.iter().all(|phase| *phase <= 3),
+            "markers.phase",
+        )?;
+        check(
+            markers.category.len() == marker_count && markers.data.len() == marker_count,
+            "markers.data",
+        )?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SerializedSampleTable {
+    stack: Vec<Option<usize>>,
+    time: Vec<Timestamp>,
+    weight: Option<Vec<i64>>,
+    #[serde(default)]
+    weight_type: WeightType,
+    #[serde(rename = "threadCPUDelta")]
+    thread_cpu_delta: Option<Vec<Option<f64>>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct SerializedStackTable {
+    prefix: Vec<Option<usize>>,
+    frame: Vec<usize>,
+    category: Vec<u16>,
+    subcategory: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SerializedFrameTable {
+    /// -1 for frames without an address.
+    address: Vec<i64>,
+    func: Vec<usize>,
+    native_symbol: Vec<Option<usize>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SerializedFuncTable {
+    name: Vec<usize>,
+    #[serde(rename = "isJS")]
+    is_js: Vec<bool>,
+    #[serde(rename = "relevantForJS")]
+    relevant_for_js: Vec<bool>,
+    /// -1 for functions without a resource.
+    resource: Vec<i64>,
+    file_name: Vec<Option<usize>>,
+    line_number: Vec<Option<u32>>,
+    column_number: Vec<Option<u32>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct SerializedResourceTable {
+    lib: Vec<Option<usize>>,
+    name: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct SerializedNativeSymbols {
+    name: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SerializedMarkerTable {
+    name: Vec<usize>,
+    start_time: Vec<Option<Timestamp>>,
+    end_time: Vec<Option<Timestamp>>,
+    phase: Vec<u8>,
+    category: Vec<u16>,
+    data: Vec<Value>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SerializedCounter {
+    name: String,
+    category: String,
+    description: String,
+    pid: String,
+    main_thread_index: usize,
+    sample_groups: Vec<SerializedCounterSampleGroup>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct SerializedCounterSampleGroup {
+    samples: SerializedCounterSamples,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct SerializedCounterSamples {
+    time: Vec<Timestamp>,
+    count: Vec<f64>,
+    number: Option<Vec<u32>>,
+}
@@ -1,3 +1,4 @@
+use serde::de::{Deserialize, Deserializer};
 use serde::ser::{Serialize, Serializer};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
@@ -24,6 +25,10 @@ impl ReferenceTimestamp {
         }
     }
 
+    pub(crate) fn millis_since_unix_epoch(&self) -> f64 {
+        self.ms_since_unix_epoch
+    }
+
     /// Create a reference timestamp from a [`SystemTime`].
     pub fn from_system_time(system_time: SystemTime) -> Self {
         Self::from_duration_since_unix_epoch(system_time.duration_since(UNIX_EPOCH).unwrap())
@@ -41,3 +46,11 @@ impl Serialize for ReferenceTimestamp {
         self.ms_since_unix_epoch.serialize(serializer)
     }
 }
+
+impl<'de> Deserialize<'de> for ReferenceTimestamp {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Self::from_millis_since_unix_epoch(f64::deserialize(
+            deserializer,
+        )?))
+    }
+}
@@ -2,15 +2,18 @@ use serde::ser::{Serialize, SerializeMap, Serializer};
 
 use crate::fast_hash_map::FastHashMap;
 use crate::global_lib_table::{GlobalLibIndex, GlobalLibTable};
+use crate::merge::MergeMapping;
 use crate::serialization_helpers::SerializableSingleValueColumn;
 use crate::thread_string_table::ThreadInternalStringIndex;
 use crate::thread_string_table::ThreadStringTable;
 
 #[derive(Debug, Clone, Default)]
 pub struct ResourceTable {
-    resource_libs: Vec<GlobalLibIndex>,
+    /// `None` for resources which are not libraries, e.g. script URLs.
+    resource_libs: Vec<Option<GlobalLibIndex>>,
     resource_names: Vec<ThreadInternalStringIndex>,
     lib_to_resource: FastHashMap<GlobalLibIndex, ResourceIndex>,
+    name_to_resource: FastHashMap<ThreadInternalStringIndex, ResourceIndex>,
 }
 
 impl ResourceTable {
@@ -29,30 +32,61 @@ impl ResourceTable {
         *self.lib_to_resource.entry(lib_index).or_insert_with(|| {
             let resource = ResourceIndex(resource_libs.len() as u32);
             let lib_name = &global_libs.get_lib(lib_index).unwrap().name;
-            resource_libs.push(lib_index);
+            resource_libs.push(Some(lib_index));
             resource_names.push(thread_string_table.index_for_string(lib_name));
             resource
         })
     }
+
+    /// Returns the resource for a script URL or module which is not a library.
+    pub fn resource_for_name(&mut self, name: ThreadInternalStringIndex) -> ResourceIndex {
+        let resource_libs = &mut self.resource_libs;
+        let resource_names = &mut self.resource_names;
+        *self.name_to_resource.entry(name).or_insert_with(|| {
+            let resource = ResourceIndex(resource_libs.len() as u32);
+            resource_libs.push(None);
+            resource_names.push(name);
+            resource
+        })
+    }
+
+    pub fn remap(&mut self, mapping: &MergeMapping) {
+        for lib_index in self.resource_libs.iter_mut().flatten() {
+            *lib_index = mapping.lib_index(*lib_index);
+        }
+        self.lib_to_resource = std::mem::take(&mut self.lib_to_resource)
+            .into_iter()
+            .map(|(lib_index, resource)| (mapping.lib_index(lib_index), resource))
+            .collect();
+    }
 }
 
 impl Serialize for ResourceTable {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-        const RESOURCE_TYPE_LIB: u32 = 1;
         let len = self.resource_libs.len();
         let mut map = serializer.serialize_map(None)?;
         map.serialize_entry("length", &len)?;
         map.serialize_entry("lib", &self.resource_libs)?;
         map.serialize_entry("name", &self.resource_names)?;
         map.serialize_entry("host", &SerializableSingleValueColumn((), len))?;
-        map.serialize_entry(
-            "type",
-            &SerializableSingleValueColumn(RESOURCE_TYPE_LIB, len),
-        )?;
+        map.serialize_entry("type", &SerializableResourceTypeColumn(&self.resource_libs))?;
         map.end()
     }
 }
 
+struct SerializableResourceTypeColumn<'a>(&'a [Option<GlobalLibIndex>]);
+
+impl<'a> Serialize for SerializableResourceTypeColumn<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        const RESOURCE_TYPE_LIB: u32 = 1;
+        const RESOURCE_TYPE_URL: u32 = 5;
+        serializer.collect_seq(self.0.iter().map(|lib| match lib {
+            Some(_) => RESOURCE_TYPE_LIB,
+            None => RESOURCE_TYPE_URL,
+        }))
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash)]
 pub struct ResourceIndex(u32);
 
@@ -1,11 +1,14 @@
+use serde::de::{Deserialize, Deserializer, Error};
 use serde::ser::{Serialize, SerializeMap, Serializer};
 
-use crate::cpu_delta::CpuDelta;
+use crate::cpu_delta::{CpuDelta, CpuDeltaUnit};
+use crate::merge::MergeMapping;
 use crate::Timestamp;
 
 #[derive(Debug, Clone, Default)]
 pub struct SampleTable {
-    sample_weights: Vec<i32>,
+    sample_weight_type: WeightType,
+    sample_weights: Vec<i64>,
     sample_timestamps: Vec<Timestamp>,
     sample_stack_indexes: Vec<Option<usize>>,
     sample_cpu_deltas: Vec<CpuDelta>,
@@ -16,12 +19,16 @@ impl SampleTable {
         Default::default()
     }
 
+    pub fn set_weight_type(&mut self, weight_type: WeightType) {
+        self.sample_weight_type = weight_type;
+    }
+
     pub fn add_sample(
         &mut self,
         timestamp: Timestamp,
         stack_index: Option<usize>,
         cpu_delta: CpuDelta,
-        weight: i32,
+        weight: i64,
     ) {
         self.sample_weights.push(weight);
         self.sample_timestamps.push(timestamp);
@@ -29,22 +36,106 @@ impl SampleTable {
         self.sample_cpu_deltas.push(cpu_delta);
     }
 
-    pub fn modify_last_sample(&mut self, timestamp: Timestamp, weight: i32) {
+    pub fn modify_last_sample(&mut self, timestamp: Timestamp, weight: i64) {
         *self.sample_weights.last_mut().unwrap() += weight;
         *self.sample_timestamps.last_mut().unwrap() = timestamp;
     }
+
+    pub fn remap(&mut self, mapping: &MergeMapping) {
+        for timestamp in &mut self.sample_timestamps {
+            *timestamp = mapping.timestamp(*timestamp);
+        }
+    }
+
+    pub fn as_serializable(&self, cpu_delta_unit: CpuDeltaUnit) -> impl Serialize + '_ {
+        SerializableSampleTable {
+            table: self,
+            cpu_delta_unit,
+        }
+    }
 }
 
-impl Serialize for SampleTable {
+struct SerializableSampleTable<'a> {
+    table: &'a SampleTable,
+    /// The unit in which the CPU deltas are serialized.
+    cpu_delta_unit: CpuDeltaUnit,
+}
+
+impl<'a> Serialize for SerializableSampleTable<'a> {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-        let len = self.sample_timestamps.len();
+        let table = self.table;
+        let len = table.sample_timestamps.len();
         let mut map = serializer.serialize_map(None)?;
         map.serialize_entry("length", &len)?;
-        map.serialize_entry("stack", &self.sample_stack_indexes)?;
-        map.serialize_entry("time", &self.sample_timestamps)?;
-        map.serialize_entry("weight", &self.sample_weights)?;
-        map.serialize_entry("weightType", &"samples")?;
-        map.serialize_entry("threadCPUDelta", &self.sample_cpu_deltas)?;
+        map.serialize_entry("stack", &table.sample_stack_indexes)?;
+        map.serialize_entry("time", &table.sample_timestamps)?;
+        map.serialize_entry("weight", &table.sample_weights)?;
+        map.serialize_entry("weightType", &table.sample_weight_type)?;
+        map.serialize_entry(
+            "threadCPUDelta",
+            &SerializableCpuDeltaColumn(&table.sample_cpu_deltas, self.cpu_delta_unit),
+        )?;
         map.end()
     }
 }
+
+struct SerializableCpuDeltaColumn<'a>(&'a [CpuDelta], CpuDeltaUnit);
+
+impl<'a> Serialize for SerializableCpuDeltaColumn<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(
+            self.0
+                .iter()
+                .map(|cpu_delta| cpu_delta.value_in_unit(self.1)),
+        )
+    }
+}
+
+/// The unit of the sample weights in a thread's sample table.
+///
+/// The call tree in the Firefox Profiler sums up the sample weights, so the
+/// weight type determines what the numbers in the call tree mean. Set it with
+/// [`Profile::set_thread_samples_weight_type`](crate::Profile::set_thread_samples_weight_type).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WeightType {
+    /// Each weight is a number of samples. This is the default.
+    Samples,
+    /// Each weight is a duration in milliseconds, for example the duration of
+    /// an instrumented function call.
+    TracingMs,
+    /// Each weight is a number of bytes, for example the size of an allocation.
+    /// Use negative weights for deallocations.
+    Bytes,
+}
+
+impl Default for WeightType {
+    fn default() -> Self {
+        WeightType::Samples
+    }
+}
+
+impl Serialize for WeightType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            WeightType::Samples => "samples".serialize(serializer),
+            WeightType::TracingMs => "tracing-ms".serialize(serializer),
+            WeightType::Bytes => "bytes".serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for WeightType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let weight_type = String::deserialize(deserializer)?;
+        Ok(match weight_type.as_str() {
+            "samples" => WeightType::Samples,
+            "tracing-ms" => WeightType::TracingMs,
+            "bytes" => WeightType::Bytes,
+            _ => {
+                return Err(D::Error::custom(format!(
+                    "unknown weight type {weight_type}"
+                )))
+            }
+        })
+    }
+}
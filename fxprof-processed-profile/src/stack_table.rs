@@ -4,6 +4,7 @@ use crate::category::{
     Category, CategoryHandle, CategoryPairHandle, SerializableSubcategoryColumn, Subcategory,
 };
 use crate::fast_hash_map::FastHashMap;
+use crate::merge::MergeMapping;
 
 #[derive(Debug, Clone, Default)]
 pub struct StackTable {
@@ -47,6 +48,19 @@ impl StackTable {
         }
     }
 
+    pub fn remap(&mut self, mapping: &MergeMapping) {
+        for (category, subcategory) in self
+            .stack_categories
+            .iter_mut()
+            .zip(self.stack_subcategories.iter_mut())
+        {
+            let (new_category, new_subcategory) =
+                mapping.category_and_subcategory(*category, subcategory);
+            *category = new_category;
+            *subcategory = new_subcategory;
+        }
+    }
+
     pub fn serialize_with_categories<'a>(
         &'a self,
         categories: &'a [Category],
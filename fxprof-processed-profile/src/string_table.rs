@@ -5,7 +5,7 @@ use serde::{Serialize, Serializer};
 use crate::fast_hash_map::FastHashMap;
 
 #[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash)]
-pub struct StringIndex(u32);
+pub struct StringIndex(pub(crate) u32);
 
 #[derive(Debug, Clone, Default)]
 pub struct StringTable {
@@ -29,6 +29,19 @@ impl StringTable {
     pub fn get_string(&self, index: StringIndex) -> Option<&str> {
         self.strings.get(index.0 as usize).map(Deref::deref)
     }
+
+    /// The index of `s`, if the table contains it, without adding it.
+    pub fn find_string(&self, s: &str) -> Option<StringIndex> {
+        self.index.get(s).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn strings(&self) -> &[String] {
+        &self.strings
+    }
 }
 
 impl Serialize for StringTable {
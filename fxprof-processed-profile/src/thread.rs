@@ -1,22 +1,27 @@
 use std::borrow::Cow;
 use std::cmp::Ordering;
 
-use serde::ser::{SerializeMap, Serializer};
+use serde::de::{Deserializer, Error};
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use serde::Deserialize;
 use serde_json::json;
 
 use crate::category::{Category, CategoryPairHandle};
-use crate::cpu_delta::CpuDelta;
+use crate::cpu_delta::{CpuDelta, CpuDeltaUnit};
+use crate::fast_hash_map::FastHashMap;
 use crate::frame_table::{FrameTable, InternalFrame};
 use crate::func_table::FuncTable;
 use crate::global_lib_table::GlobalLibTable;
 use crate::marker_table::MarkerTable;
+use crate::merge::MergeMapping;
 use crate::native_symbols::NativeSymbols;
+use crate::process::Process;
 use crate::resource_table::ResourceTable;
-use crate::sample_table::SampleTable;
+use crate::sample_table::{SampleTable, WeightType};
 use crate::stack_table::StackTable;
 use crate::string_table::{GlobalStringIndex, GlobalStringTable};
 use crate::thread_string_table::{ThreadInternalStringIndex, ThreadStringTable};
-use crate::{MarkerTiming, ProfilerMarker, Timestamp};
+use crate::{FlowId, MarkerTiming, SamplingInterval, Timestamp};
 
 /// A process. Can be created with [`Profile::add_process`](crate::Profile::add_process).
 #[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash)]
@@ -25,7 +30,52 @@ pub struct ProcessHandle(pub(crate) usize);
 #[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash)]
 pub struct CounterHandle(pub(crate) usize);
 
-#[derive(Debug)]
+/// What kind of work a thread does. Set it with
+/// [`Profile::set_thread_type`](crate::Profile::set_thread_type).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ThreadType {
+    /// A regular thread. This is the default.
+    Default,
+    /// A thread which represents the time in which a CPU was idle, for example
+    /// the "swapper" thread in Linux profiles.
+    Idle,
+    /// A kernel thread, which only runs kernel code.
+    Kernel,
+}
+
+impl Default for ThreadType {
+    fn default() -> Self {
+        ThreadType::Default
+    }
+}
+
+impl Serialize for ThreadType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            ThreadType::Default => "default".serialize(serializer),
+            ThreadType::Idle => "idle".serialize(serializer),
+            ThreadType::Kernel => "kernel".serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ThreadType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let thread_type = String::deserialize(deserializer)?;
+        Ok(match thread_type.as_str() {
+            "default" => ThreadType::Default,
+            "idle" => ThreadType::Idle,
+            "kernel" => ThreadType::Kernel,
+            _ => {
+                return Err(D::Error::custom(format!(
+                    "unknown thread type {thread_type}"
+                )))
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Thread {
     process: ProcessHandle,
     tid: String,
@@ -33,6 +83,9 @@ pub struct Thread {
     start_time: Timestamp,
     end_time: Option<Timestamp>,
     is_main: bool,
+    thread_type: ThreadType,
+    description: Option<String>,
+    interval: Option<SamplingInterval>,
     stack_table: StackTable,
     frame_table: FrameTable,
     func_table: FuncTable,
@@ -54,6 +107,9 @@ impl Thread {
             start_time,
             end_time: None,
             is_main,
+            thread_type: ThreadType::Default,
+            description: None,
+            interval: None,
             stack_table: StackTable::new(),
             frame_table: FrameTable::new(),
             func_table: FuncTable::new(),
@@ -79,10 +135,60 @@ impl Thread {
         self.end_time = Some(end_time);
     }
 
+    pub fn set_thread_type(&mut self, thread_type: ThreadType) {
+        self.thread_type = thread_type;
+    }
+
+    pub fn set_description(&mut self, description: &str) {
+        self.description = Some(description.to_string());
+    }
+
+    pub fn set_interval(&mut self, interval: SamplingInterval) {
+        self.interval = Some(interval);
+    }
+
+    pub fn set_samples_weight_type(&mut self, weight_type: WeightType) {
+        self.samples.set_weight_type(weight_type);
+    }
+
     pub fn process(&self) -> ProcessHandle {
         self.process
     }
 
+    pub fn tid(&self) -> &str {
+        &self.tid
+    }
+
+    /// Adjusts the thread after it has been moved from the profile with the global
+    /// string table `old_strings` into the profile with the global string table
+    /// `new_strings`.
+    pub fn remap(
+        &mut self,
+        process: ProcessHandle,
+        tid: String,
+        mapping: &MergeMapping,
+        old_strings: &GlobalStringTable,
+        new_strings: &mut GlobalStringTable,
+    ) {
+        self.process = process;
+        self.tid = tid;
+        self.start_time = mapping.timestamp(self.start_time);
+        self.end_time = self.end_time.map(|end_time| mapping.timestamp(end_time));
+        self.stack_table.remap(mapping);
+        self.frame_table.remap(mapping);
+        self.samples.remap(mapping);
+        self.markers.remap(mapping);
+        self.resources.remap(mapping);
+        self.native_symbols.remap(mapping);
+        self.string_table
+            .remap_global_strings(old_strings, new_strings);
+    }
+
+    /// Calls `f` with every flow ID in the thread's markers, so that it can be changed.
+    pub fn for_each_flow_id(&mut self, f: impl FnMut(&mut FlowId)) {
+        self.markers.flow_ids_mut().for_each(f);
+    }
+
     pub fn convert_string_index(
         &mut self,
         global_table: &GlobalStringTable,
@@ -122,15 +228,15 @@ impl Thread {
         timestamp: Timestamp,
         stack_index: Option<usize>,
         cpu_delta: CpuDelta,
-        weight: i32,
+        weight: i64,
     ) {
         self.samples
             .add_sample(timestamp, stack_index, cpu_delta, weight);
         self.last_sample_stack = stack_index;
-        self.last_sample_was_zero_cpu = cpu_delta == CpuDelta::ZERO;
+        self.last_sample_was_zero_cpu = cpu_delta.is_zero();
     }
 
-    pub fn add_sample_same_stack_zero_cpu(&mut self, timestamp: Timestamp, weight: i32) {
+    pub fn add_sample_same_stack_zero_cpu(&mut self, timestamp: Timestamp, weight: i64) {
         if self.last_sample_was_zero_cpu {
             self.samples.modify_last_sample(timestamp, weight);
         } else {
@@ -141,21 +247,32 @@ impl Thread {
         }
     }
 
-    pub fn add_marker<T: ProfilerMarker>(
+    /// Adds a marker. The values of the fields in `flow_id_keys` are flow IDs, which
+    /// are kept in the marker table and only put into the string table when the
+    /// thread is serialized.
+    pub fn add_marker(
         &mut self,
         name: &str,
-        marker: T,
+        mut data: serde_json::Value,
         timing: MarkerTiming,
         stack_index: Option<usize>,
+        flow_id_keys: &[String],
     ) {
         let name_string_index = self.string_table.index_for_string(name);
-        let mut data = marker.json_marker_data();
+        let flow_ids = flow_id_keys
+            .iter()
+            .filter_map(|key| {
+                let flow_id = FlowId::deserialize(data.get(key)?).ok()?;
+                Some((key.clone(), flow_id))
+            })
+            .collect();
         if let Some(stack_index) = stack_index {
             if let Some(obj) = data.as_object_mut() {
                 obj.insert("cause".to_string(), json!({ "stack": stack_index }));
             }
         }
-        self.markers.add_marker(name_string_index, timing, data);
+        self.markers
+            .add_marker(name_string_index, timing, data, flow_ids);
     }
 
     pub fn contains_js_function(&self) -> bool {
@@ -183,11 +300,13 @@ impl Thread {
         &self,
         serializer: S,
         categories: &[Category],
-        process_start_time: Timestamp,
-        process_end_time: Option<Timestamp>,
-        process_name: &str,
-        pid: &str,
+        cpu_delta_unit: CpuDeltaUnit,
+        process: &Process,
     ) -> Result<S::Ok, S::Error> {
+        let process_start_time = process.start_time();
+        let process_end_time = process.end_time();
+        let process_name = process.name();
+        let pid = process.pid();
         let thread_name: Cow<str> = match (self.is_main, &self.name) {
             (true, _) => process_name.into(),
             (false, Some(name)) => name.into(),
@@ -197,12 +316,36 @@ impl Thread {
         let thread_register_time = self.start_time;
         let thread_unregister_time = self.end_time;
 
+        // Flow IDs are stored as strings, which are added to the string array here.
+        let flow_ids: Vec<FlowId> = self.markers.flow_ids().collect();
+        let flow_id_strings: Vec<String> = flow_ids
+            .iter()
+            .map(|flow_id| flow_id.to_hex_string())
+            .collect();
+        let (flow_id_string_indexes, extra_strings) = self
+            .string_table
+            .indexes_for_extra_strings(flow_id_strings.iter().map(String::as_str));
+        let flow_id_string_indexes: FastHashMap<FlowId, usize> =
+            flow_ids.into_iter().zip(flow_id_string_indexes).collect();
+
         let mut map = serializer.serialize_map(None)?;
         map.serialize_entry("frameTable", &self.frame_table.as_serializable(categories))?;
         map.serialize_entry("funcTable", &self.func_table)?;
-        map.serialize_entry("markers", &self.markers)?;
+        map.serialize_entry(
+            "markers",
+            &self.markers.as_serializable(&flow_id_string_indexes),
+        )?;
         map.serialize_entry("name", &thread_name)?;
         map.serialize_entry("isMainThread", &self.is_main)?;
+        if self.thread_type != ThreadType::Default {
+            map.serialize_entry("threadType", &self.thread_type)?;
+        }
+        if let Some(description) = &self.description {
+            map.serialize_entry("description", description)?;
+        }
+        if let Some(interval) = &self.interval {
+            map.serialize_entry("interval", &(interval.as_secs_f64() * 1000.0))?;
+        }
         map.serialize_entry("nativeSymbols", &self.native_symbols)?;
         map.serialize_entry("pausedRanges", &[] as &[()])?;
         map.serialize_entry("pid", &pid)?;
@@ -212,12 +355,17 @@ impl Thread {
         map.serialize_entry("processType", &"default")?;
         map.serialize_entry("registerTime", &thread_register_time)?;
         map.serialize_entry("resourceTable", &self.resources)?;
-        map.serialize_entry("samples", &self.samples)?;
+        map.serialize_entry("samples", &self.samples.as_serializable(cpu_delta_unit))?;
         map.serialize_entry(
             "stackTable",
             &self.stack_table.serialize_with_categories(categories),
         )?;
-        map.serialize_entry("stringArray", &self.string_table)?;
+        map.serialize_entry(
+            "stringArray",
+            &self
+                .string_table
+                .as_serializable_with_extra_strings(&extra_strings),
+        )?;
         map.serialize_entry("tid", &self.tid)?;
         map.serialize_entry("unregisterTime", &thread_unregister_time)?;
         map.end()
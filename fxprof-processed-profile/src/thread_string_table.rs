@@ -27,6 +27,40 @@ impl ThreadStringTable {
         ThreadInternalStringIndex(self.table.index_for_string(s))
     }
 
+    /// Returns the index that each of `strings` will have in the serialized string
+    /// array, and the strings which need to be appended to the table's strings
+    /// for this. Used for strings which are only added when the thread is
+    /// serialized.
+    pub fn indexes_for_extra_strings<'a>(
+        &self,
+        strings: impl IntoIterator<Item = &'a str>,
+    ) -> (Vec<usize>, Vec<&'a str>) {
+        let mut extra_strings: Vec<&str> = Vec::new();
+        let mut extra_string_indexes: FastHashMap<&str, usize> = FastHashMap::default();
+        let indexes = strings
+            .into_iter()
+            .map(|s| match self.table.find_string(s) {
+                Some(index) => index.0 as usize,
+                None => *extra_string_indexes.entry(s).or_insert_with(|| {
+                    extra_strings.push(s);
+                    self.table.len() + extra_strings.len() - 1
+                }),
+            })
+            .collect();
+        (indexes, extra_strings)
+    }
+
+    /// Serializes the table's strings, followed by `extra_strings`.
+    pub fn as_serializable_with_extra_strings<'a>(
+        &'a self,
+        extra_strings: &'a [&'a str],
+    ) -> impl Serialize + 'a {
+        SerializableThreadStringTable {
+            table: self,
+            extra_strings,
+        }
+    }
+
     pub fn index_for_global_string(
         &mut self,
         global_index: GlobalStringIndex,
@@ -41,10 +75,33 @@ impl ThreadStringTable {
                 ThreadInternalStringIndex(table.index_for_string(s))
             })
     }
+
+    /// Re-keys the global-to-local string mapping after the thread has been moved
+    /// from the profile with the global string table `old_global_table` into the
+    /// profile with the global string table `new_global_table`.
+    pub fn remap_global_strings(
+        &mut self,
+        old_global_table: &GlobalStringTable,
+        new_global_table: &mut GlobalStringTable,
+    ) {
+        self.global_to_local_string = std::mem::take(&mut self.global_to_local_string)
+            .into_iter()
+            .map(|(old_global_index, local_index)| {
+                let s = old_global_table.get_string(old_global_index).unwrap();
+                (new_global_table.index_for_string(s), local_index)
+            })
+            .collect();
+    }
+}
+
+struct SerializableThreadStringTable<'a> {
+    table: &'a ThreadStringTable,
+    extra_strings: &'a [&'a str],
 }
 
-impl Serialize for ThreadStringTable {
+impl<'a> Serialize for SerializableThreadStringTable<'a> {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-        self.table.serialize(serializer)
+        let strings = self.table.table.strings().iter().map(String::as_str);
+        serializer.collect_seq(strings.chain(self.extra_strings.iter().copied()))
     }
 }
@@ -1,3 +1,4 @@
+use serde::de::{Deserialize, Deserializer};
 use serde::ser::{Serialize, Serializer};
 
 /// The type used for sample and marker timestamps.
@@ -18,6 +19,12 @@ impl Timestamp {
             nanos: (millis * 1_000_000.0) as u64,
         }
     }
+
+    pub(crate) fn shifted_by_nanos(self, nanos: u64) -> Self {
+        Self {
+            nanos: self.nanos + nanos,
+        }
+    }
 }
 
 impl Serialize for Timestamp {
@@ -27,3 +34,14 @@ impl Serialize for Timestamp {
         serializer.serialize_f64((self.nanos as f64) / 1_000_000.0)
     }
 }
+
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // Round to the nearest nanosecond so that serializing and deserializing
+        // a timestamp gives back the same value.
+        let millis = f64::deserialize(deserializer)?;
+        Ok(Self {
+            nanos: (millis * 1_000_000.0).round() as u64,
+        })
+    }
+}
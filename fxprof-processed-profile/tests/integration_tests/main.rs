@@ -3,10 +3,14 @@ use debugid::DebugId;
 use serde_json::json;
 
 use fxprof_processed_profile::{
-    CategoryColor, CpuDelta, Frame, FrameFlags, FrameInfo, LibraryInfo, MarkerDynamicField,
-    MarkerFieldFormat, MarkerLocation, MarkerSchema, MarkerSchemaField, MarkerStaticField,
-    MarkerTiming, Profile, ProfilerMarker, ReferenceTimestamp, SamplingInterval, Symbol,
-    SymbolTable, Timestamp,
+    compact_profile_to_json, is_compact_profile, merge_profiles, write_compact_profile,
+    CategoryColor, CompactProfileError, CounterType, CpuDelta, CpuDeltaUnit,
+    ExtraProfileInfoSection, FlowId, FlowMarker, Frame, FrameFlags, FrameInfo, FunctionFrame,
+    LibraryInfo, MarkerDynamicField, MarkerFieldFormat, MarkerLocation, MarkerSchema,
+    MarkerSchemaField, MarkerStaticField, MarkerTiming, MergeLayout, Profile, ProfileView,
+    ProfilerMarker, ReferenceTimestamp, RuntimeMarkerDynamicField, RuntimeMarkerSchema,
+    RuntimeMarkerSchemaField, RuntimeMarkerStaticField, SamplingInterval, Symbol, SymbolTable,
+    ThreadType, Timestamp, WeightType,
 };
 
 use std::sync::Arc;
@@ -1592,3 +1596,1171 @@ fn profile_counters_with_sorted_processes() {
         )
     )
 }
+
+#[test]
+fn read_back_profile() {
+    let mut profile = Profile::new(
+        "test",
+        ReferenceTimestamp::from_millis_since_unix_epoch(1636162232627.0),
+        SamplingInterval::from_millis(2),
+    );
+    let process = profile.add_process("test", 123, Timestamp::from_millis_since_reference(0.5));
+    let thread = profile.add_thread(
+        process,
+        12345,
+        Timestamp::from_millis_since_reference(0.5),
+        true,
+    );
+    let lib = profile.add_lib(LibraryInfo {
+        name: "libtest.so".to_string(),
+        debug_name: "libtest.so".to_string(),
+        path: "/usr/lib/libtest.so".to_string(),
+        code_id: Some("f0fc29165cbe6088c0e1adf03b0048fbecbc003a".to_string()),
+        debug_path: "/usr/lib/libtest.so".to_string(),
+        debug_id: DebugId::from_breakpad("1629FCF0BE5C8860C0E1ADF03B0048FB0").unwrap(),
+        arch: Some("x86_64".to_string()),
+        symbol_table: Some(Arc::new(SymbolTable::new(vec![Symbol {
+            address: 0x1000,
+            size: Some(0x100),
+            name: "test_function".to_string(),
+        }]))),
+    });
+    let category = profile.add_category("Regular", CategoryColor::Blue);
+    let subcategory = profile.add_subcategory(category, "Sub");
+    let root_label = profile.intern_string("Root");
+    let frames = vec![
+        FrameInfo {
+            frame: Frame::Label(root_label),
+            category_pair: category.into(),
+            flags: FrameFlags::IS_JS,
+        },
+        FrameInfo {
+            frame: Frame::RelativeAddressFromReturnAddress(lib, 0x1011),
+            category_pair: subcategory,
+            flags: FrameFlags::empty(),
+        },
+        FrameInfo {
+            frame: Frame::RelativeAddressFromInstructionPointer(lib, 0x2000),
+            category_pair: category.into(),
+            flags: FrameFlags::empty(),
+        },
+    ];
+    profile.add_sample(
+        thread,
+        Timestamp::from_nanos_since_reference(1_000_001),
+        frames.clone().into_iter(),
+        CpuDelta::from_micros(700),
+        1,
+    );
+    profile.add_sample(
+        thread,
+        Timestamp::from_millis_since_reference(3.0),
+        vec![].into_iter(),
+        CpuDelta::ZERO,
+        3,
+    );
+    profile.add_marker_with_stack(
+        thread,
+        "Marker",
+        TextMarker("Hello world!".to_string()),
+        MarkerTiming::Interval(
+            Timestamp::from_millis_since_reference(1.0),
+            Timestamp::from_millis_since_reference(2.5),
+        ),
+        frames.into_iter().take(1),
+    );
+    profile.add_marker(
+        thread,
+        "End",
+        TextMarker("Bye".to_string()),
+        MarkerTiming::IntervalEnd(Timestamp::from_millis_since_reference(4.0)),
+    );
+    let counter = profile.add_counter(process, "malloc", "Memory", "Amount of allocated memory");
+    profile.add_counter_sample(
+        counter,
+        Timestamp::from_millis_since_reference(1.0),
+        1000.0,
+        2,
+    );
+
+    let json = serde_json::to_vec(&profile).unwrap();
+    let view = ProfileView::from_slice(&json).unwrap();
+
+    assert_eq!(view.product(), "test");
+    assert_eq!(view.interval(), SamplingInterval::from_millis(2));
+    assert_eq!(
+        view.reference_timestamp(),
+        ReferenceTimestamp::from_millis_since_unix_epoch(1636162232627.0)
+    );
+    let categories: Vec<_> = view.categories().collect();
+    assert_eq!(categories.len(), 2);
+    assert_eq!(categories[1].name, "Regular");
+    assert_eq!(categories[1].color, CategoryColor::Blue);
+    assert_eq!(categories[1].subcategories, &["Sub".to_string()]);
+    assert_eq!(view.libs().len(), 1);
+    assert_eq!(view.libs()[0].debug_name, "libtest.so");
+    assert_eq!(view.libs()[0].arch.as_deref(), Some("x86_64"));
+    assert_eq!(view.marker_schemas().len(), 1);
+
+    assert_eq!(view.threads().len(), 1);
+    let thread = view.thread(0).unwrap();
+    assert_eq!(thread.name(), "test");
+    assert_eq!(thread.pid(), "123");
+    assert_eq!(thread.tid(), "12345");
+    assert!(thread.is_main_thread());
+    assert_eq!(
+        thread.register_time(),
+        Timestamp::from_millis_since_reference(0.5)
+    );
+    assert_eq!(thread.unregister_time(), None);
+
+    let samples: Vec<_> = thread.samples().collect();
+    assert_eq!(samples.len(), 2);
+    assert_eq!(
+        samples[0].timestamp,
+        Timestamp::from_nanos_since_reference(1_000_001)
+    );
+    assert_eq!(samples[0].cpu_delta, Some(CpuDelta::from_micros(700)));
+    assert_eq!(samples[1].weight, 3);
+    assert_eq!(samples[1].stack_index, None);
+    assert_eq!(thread.stack_frames(samples[1].stack_index).count(), 0);
+
+    let frames: Vec<_> = thread.stack_frames(samples[0].stack_index).collect();
+    assert_eq!(frames.len(), 3);
+    assert_eq!(frames[0].name, "0x2000");
+    assert_eq!(frames[0].address, Some(0x2000));
+    assert_eq!(frames[0].native_symbol_name, None);
+    assert_eq!(frames[0].category_pair, category.into());
+    // Return addresses are nudged into the calling instruction.
+    assert_eq!(frames[1].name, "test_function");
+    assert_eq!(frames[1].address, Some(0x1010));
+    assert_eq!(frames[1].native_symbol_name, Some("test_function"));
+    assert_eq!(frames[1].lib.unwrap().name, "libtest.so");
+    assert_eq!(frames[1].category_pair, subcategory);
+    assert_eq!(frames[2].name, "Root");
+    assert_eq!(frames[2].address, None);
+    assert!(frames[2].lib.is_none());
+    assert_eq!(frames[2].flags, FrameFlags::IS_JS);
+
+    let native_frame_addresses = view.native_frame_addresses();
+    assert_eq!(native_frame_addresses.len(), 1);
+    let (lib, addresses) = &native_frame_addresses[&view.libs()[0].debug_id];
+    assert_eq!(lib.name, "libtest.so");
+    assert_eq!(addresses.iter().copied().collect::<Vec<_>>(), [0x1010, 0x2000]);
+
+    let markers: Vec<_> = thread.markers().collect();
+    assert_eq!(markers.len(), 2);
+    assert_eq!(markers[0].name, "Marker");
+    assert!(matches!(
+        markers[0].timing,
+        MarkerTiming::Interval(start, end)
+            if start == Timestamp::from_millis_since_reference(1.0)
+                && end == Timestamp::from_millis_since_reference(2.5)
+    ));
+    assert_eq!(markers[0].data["name"], "Hello world!");
+    let marker_frames: Vec<_> = thread
+        .stack_frames(markers[0].stack_index())
+        .map(|frame| frame.name)
+        .collect();
+    assert_eq!(marker_frames, vec!["Root"]);
+    assert!(matches!(
+        markers[1].timing,
+        MarkerTiming::IntervalEnd(end) if end == Timestamp::from_millis_since_reference(4.0)
+    ));
+    assert_eq!(markers[1].stack_index(), None);
+    assert_eq!(thread.stack_frames(Some(usize::MAX)).count(), 0);
+
+    // Marker data isn't validated when the profile is read.
+    let mut json_value: serde_json::Value = serde_json::from_slice(&json).unwrap();
+    json_value["threads"][0]["markers"]["data"][0]["cause"]["stack"] = 1000.into();
+    let json_with_invalid_stack = serde_json::to_vec(&json_value).unwrap();
+    let view_with_invalid_stack = ProfileView::from_slice(&json_with_invalid_stack).unwrap();
+    let thread_with_invalid_stack = view_with_invalid_stack.thread(0).unwrap();
+    let marker = thread_with_invalid_stack.markers().next().unwrap();
+    assert_eq!(marker.stack_index(), None);
+
+    let counters: Vec<_> = view.counters().collect();
+    assert_eq!(counters.len(), 1);
+    assert_eq!(counters[0].name(), "malloc");
+    assert_eq!(counters[0].main_thread_index(), 0);
+    let counter_samples: Vec<_> = counters[0].samples().collect();
+    assert_eq!(counter_samples.len(), 1);
+    assert_eq!(counter_samples[0].value_delta, 1000.0);
+    assert_eq!(counter_samples[0].number_of_operations_delta, 2);
+}
+
+#[test]
+fn runtime_marker_schema() {
+    let mut profile = Profile::new(
+        "test",
+        ReferenceTimestamp::from_millis_since_unix_epoch(1636162232627.0),
+        SamplingInterval::from_millis(1),
+    );
+    let process = profile.add_process("test", 123, Timestamp::from_millis_since_reference(0.0));
+    let thread = profile.add_thread(
+        process,
+        12345,
+        Timestamp::from_millis_since_reference(0.0),
+        true,
+    );
+
+    let type_name = format!("{}:{}", "sched", "sched_switch");
+    let marker_type = profile.register_marker_type(RuntimeMarkerSchema {
+        type_name: type_name.clone(),
+        locations: vec![MarkerLocation::MarkerChart, MarkerLocation::MarkerTable],
+        chart_label: None,
+        tooltip_label: Some("{marker.data.next_comm}".to_string()),
+        table_label: None,
+        fields: vec![
+            RuntimeMarkerSchemaField::Dynamic(RuntimeMarkerDynamicField {
+                key: "next_comm".to_string(),
+                label: "Next command".to_string(),
+                format: MarkerFieldFormat::String,
+                searchable: true,
+            }),
+            RuntimeMarkerSchemaField::Static(RuntimeMarkerStaticField {
+                label: "Description".to_string(),
+                value: "A context switch".to_string(),
+            }),
+        ],
+    });
+    let mut field_values = serde_json::Map::new();
+    field_values.insert("next_comm".to_string(), json!("swapper/0"));
+    profile.add_runtime_marker(
+        thread,
+        &type_name,
+        marker_type,
+        MarkerTiming::Instant(Timestamp::from_millis_since_reference(1.0)),
+        field_values,
+    );
+    profile.add_marker(
+        thread,
+        "Text",
+        TextMarker("Hello".to_string()),
+        MarkerTiming::Instant(Timestamp::from_millis_since_reference(2.0)),
+    );
+
+    let profile_json = serde_json::to_value(&profile).unwrap();
+    assert_json_eq!(
+        profile_json["meta"]["markerSchema"],
+        json!([
+            {
+                "name": "Text",
+                "display": ["marker-chart", "marker-table"],
+                "chartLabel": "{marker.data.name}",
+                "tableLabel": "{marker.name} - {marker.data.name}",
+                "data": [
+                    {
+                        "key": "name",
+                        "label": "Details",
+                        "format": "string",
+                        "searchable": true
+                    }
+                ]
+            },
+            {
+                "name": "sched:sched_switch",
+                "display": ["marker-chart", "marker-table"],
+                "tooltipLabel": "{marker.data.next_comm}",
+                "data": [
+                    {
+                        "key": "next_comm",
+                        "label": "Next command",
+                        "format": "string",
+                        "searchable": true
+                    },
+                    {
+                        "label": "Description",
+                        "value": "A context switch"
+                    }
+                ]
+            }
+        ])
+    );
+    assert_json_eq!(
+        profile_json["threads"][0]["markers"]["data"],
+        json!([
+            {
+                "type": "sched:sched_switch",
+                "next_comm": "swapper/0"
+            },
+            {
+                "type": "Text",
+                "name": "Hello"
+            }
+        ])
+    );
+}
+
+#[test]
+fn byte_weights() {
+    let mut profile = Profile::new(
+        "test",
+        ReferenceTimestamp::from_millis_since_unix_epoch(1636162232627.0),
+        SamplingInterval::from_millis(1),
+    );
+    let process = profile.add_process("test", 123, Timestamp::from_millis_since_reference(0.0));
+    let thread = profile.add_thread(
+        process,
+        12345,
+        Timestamp::from_millis_since_reference(0.0),
+        true,
+    );
+    profile.set_thread_samples_weight_type(thread, WeightType::Bytes);
+    let label = profile.intern_string("malloc");
+    let category = profile.add_category("Regular", CategoryColor::Blue);
+    let frames = vec![FrameInfo {
+        frame: Frame::Label(label),
+        category_pair: category.into(),
+        flags: FrameFlags::empty(),
+    }];
+    profile.add_sample(
+        thread,
+        Timestamp::from_millis_since_reference(1.0),
+        frames.clone().into_iter(),
+        CpuDelta::ZERO,
+        5_000_000_000,
+    );
+    profile.add_sample(
+        thread,
+        Timestamp::from_millis_since_reference(2.0),
+        frames.into_iter(),
+        CpuDelta::ZERO,
+        -4096,
+    );
+
+    let json = serde_json::to_value(&profile).unwrap();
+    assert_eq!(json["threads"][0]["samples"]["weightType"], "bytes");
+    assert_eq!(
+        json["threads"][0]["samples"]["weight"],
+        json!([5_000_000_000i64, -4096])
+    );
+
+    let view = ProfileView::from_slice(&serde_json::to_vec(&profile).unwrap()).unwrap();
+    let thread = view.thread(0).unwrap();
+    assert_eq!(thread.weight_type(), WeightType::Bytes);
+    let weights: Vec<i64> = thread.samples().map(|sample| sample.weight).collect();
+    assert_eq!(weights, vec![5_000_000_000, -4096]);
+}
+
+fn profile_for_merging(reference_ms: f64, extra_category: bool) -> Profile {
+    let mut profile = Profile::new(
+        "test",
+        ReferenceTimestamp::from_millis_since_unix_epoch(reference_ms),
+        SamplingInterval::from_millis(1),
+    );
+    if extra_category {
+        // Make sure that the category indexes differ between the merged profiles.
+        profile.add_category("Kernel", CategoryColor::Orange);
+    }
+    let process = profile.add_process("app", 123, Timestamp::from_millis_since_reference(0.0));
+    let thread = profile.add_thread(
+        process,
+        123,
+        Timestamp::from_millis_since_reference(0.0),
+        true,
+    );
+    let lib = profile.add_lib(LibraryInfo {
+        name: "libapp.so".to_string(),
+        debug_name: "libapp.so".to_string(),
+        path: "/usr/lib/libapp.so".to_string(),
+        code_id: None,
+        debug_path: "/usr/lib/libapp.so".to_string(),
+        debug_id: DebugId::nil(),
+        arch: None,
+        symbol_table: None,
+    });
+    let category = profile.add_category("Regular", CategoryColor::Blue);
+    let subcategory = profile.add_subcategory(category, "Sub");
+    let label = profile.intern_string("main");
+    let frames = vec![
+        FrameInfo {
+            frame: Frame::Label(label),
+            category_pair: category.into(),
+            flags: FrameFlags::empty(),
+        },
+        FrameInfo {
+            frame: Frame::RelativeAddressFromInstructionPointer(lib, 0x1000),
+            category_pair: subcategory,
+            flags: FrameFlags::empty(),
+        },
+    ];
+    profile.add_sample(
+        thread,
+        Timestamp::from_millis_since_reference(1.0),
+        frames.into_iter(),
+        CpuDelta::ZERO,
+        1,
+    );
+    profile.add_marker(
+        thread,
+        "Marker",
+        TextMarker("Hello".to_string()),
+        MarkerTiming::Instant(Timestamp::from_millis_since_reference(2.0)),
+    );
+    let counter = profile.add_counter(process, "malloc", "Memory", "Amount of allocated memory");
+    profile.add_counter_sample(
+        counter,
+        Timestamp::from_millis_since_reference(3.0),
+        1000.0,
+        1,
+    );
+    profile
+}
+
+#[test]
+fn merge_profiles_separate_processes() {
+    let runs = vec![
+        ("Run 1", profile_for_merging(1636162232627.0, false)),
+        ("Run 2", profile_for_merging(1636162232637.0, true)),
+    ];
+    let merged = merge_profiles(runs, MergeLayout::SeparateProcesses).unwrap();
+    let json = serde_json::to_vec(&merged).unwrap();
+    let view = ProfileView::from_slice(&json).unwrap();
+
+    assert_eq!(
+        view.reference_timestamp(),
+        ReferenceTimestamp::from_millis_since_unix_epoch(1636162232627.0)
+    );
+    assert_eq!(view.libs().len(), 1);
+    let category_names: Vec<_> = view.categories().map(|c| c.name).collect();
+    assert_eq!(category_names, vec!["Other", "Regular", "Kernel"]);
+    assert_eq!(view.categories().nth(1).unwrap().subcategories, &["Sub"]);
+
+    let threads: Vec<_> = view.threads().collect();
+    assert_eq!(threads.len(), 2);
+    assert_eq!(threads[0].process_name(), "Run 1: app");
+    assert_eq!(threads[0].pid(), "123");
+    assert_eq!(threads[1].process_name(), "Run 2: app");
+    assert_eq!(threads[1].pid(), "123.1");
+    assert_eq!(threads[1].tid(), "123.1");
+    assert_eq!(
+        threads[1].process_startup_time(),
+        Timestamp::from_millis_since_reference(10.0)
+    );
+
+    let mut leaf_frames = Vec::new();
+    for thread in &threads {
+        let samples: Vec<_> = thread.samples().collect();
+        assert_eq!(samples.len(), 1);
+        let frames: Vec<_> = thread.stack_frames(samples[0].stack_index).collect();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].address, Some(0x1000));
+        assert_eq!(frames[0].lib.unwrap().name, "libapp.so");
+        assert_eq!(frames[1].name, "main");
+        leaf_frames.push(frames[0]);
+    }
+    assert_eq!(leaf_frames[0].category_pair, leaf_frames[1].category_pair);
+
+    assert_eq!(
+        threads[1].samples().next().unwrap().timestamp,
+        Timestamp::from_millis_since_reference(11.0)
+    );
+    assert!(matches!(
+        threads[1].markers().next().unwrap().timing,
+        MarkerTiming::Instant(start) if start == Timestamp::from_millis_since_reference(12.0)
+    ));
+
+    let counters: Vec<_> = view.counters().collect();
+    assert_eq!(counters.len(), 2);
+    assert_eq!(counters[1].pid(), "123.1");
+    assert_eq!(
+        counters[1].samples().next().unwrap().timestamp,
+        Timestamp::from_millis_since_reference(13.0)
+    );
+}
+
+#[test]
+fn merge_profiles_shared_timeline() {
+    let mut run_with_kernel = profile_for_merging(1636162232637.0, false);
+    let kernel_lib = run_with_kernel.add_lib(LibraryInfo {
+        name: "vmlinux".to_string(),
+        debug_name: "vmlinux".to_string(),
+        path: "vmlinux".to_string(),
+        code_id: None,
+        debug_path: "vmlinux".to_string(),
+        debug_id: DebugId::nil(),
+        arch: None,
+        symbol_table: None,
+    });
+    run_with_kernel.add_kernel_lib_mapping(
+        kernel_lib,
+        0xffff_0000_0000_0000,
+        0xffff_0000_0010_0000,
+        0,
+    );
+    let runs = vec![
+        ("Run 1", profile_for_merging(1636162232627.0, false)),
+        ("Run 2", run_with_kernel),
+    ];
+    let mut merged = merge_profiles(runs, MergeLayout::SharedTimeline).unwrap();
+
+    // The kernel mappings still apply to samples which are added after merging.
+    let process = merged.add_process("later", 456, Timestamp::from_millis_since_reference(0.0));
+    let thread = merged.add_thread(
+        process,
+        456,
+        Timestamp::from_millis_since_reference(0.0),
+        true,
+    );
+    let category = merged.add_category("Regular", CategoryColor::Blue);
+    merged.add_sample(
+        thread,
+        Timestamp::from_millis_since_reference(1.0),
+        vec![FrameInfo {
+            frame: Frame::InstructionPointer(0xffff_0000_0000_1234),
+            category_pair: category.into(),
+            flags: FrameFlags::empty(),
+        }]
+        .into_iter(),
+        CpuDelta::ZERO,
+        1,
+    );
+
+    let json = serde_json::to_vec(&merged).unwrap();
+    let view = ProfileView::from_slice(&json).unwrap();
+
+    let threads: Vec<_> = view.threads().collect();
+    assert_eq!(threads.len(), 3);
+    assert_eq!(threads[0].process_name(), "Run 1: app");
+    assert_eq!(threads[1].process_name(), "Run 2: app");
+    for thread in &threads[..2] {
+        assert_eq!(
+            thread.samples().next().unwrap().timestamp,
+            Timestamp::from_millis_since_reference(1.0)
+        );
+    }
+    let later_sample = threads[2].samples().next().unwrap();
+    let later_frame = threads[2]
+        .stack_frames(later_sample.stack_index)
+        .next()
+        .unwrap();
+    assert_eq!(later_frame.lib.unwrap().name, "vmlinux");
+    assert_eq!(later_frame.address, Some(0x1234));
+    assert!(merge_profiles(Vec::<(&str, Profile)>::new(), MergeLayout::SharedTimeline).is_none());
+}
+
+#[test]
+fn function_frames() {
+    let mut profile = Profile::new(
+        "test",
+        ReferenceTimestamp::from_millis_since_unix_epoch(1636162232627.0),
+        SamplingInterval::from_millis(1),
+    );
+    let process = profile.add_process("python", 123, Timestamp::from_millis_since_reference(0.0));
+    let thread = profile.add_thread(
+        process,
+        123,
+        Timestamp::from_millis_since_reference(0.0),
+        true,
+    );
+    let category = profile.add_category("Python", CategoryColor::Yellow);
+    let main_function = FunctionFrame {
+        name: profile.intern_string("main"),
+        file: Some(profile.intern_string("/home/user/app.py")),
+        line: Some(10),
+        column: Some(1),
+        resource: Some(profile.intern_string("app")),
+    };
+    let loads_function = FunctionFrame {
+        name: profile.intern_string("loads"),
+        file: Some(profile.intern_string("/usr/lib/python3/json/__init__.py")),
+        line: Some(299),
+        column: None,
+        resource: Some(profile.intern_string("json")),
+    };
+    let frames = vec![
+        FrameInfo {
+            frame: Frame::Function(main_function),
+            category_pair: category.into(),
+            flags: FrameFlags::empty(),
+        },
+        FrameInfo {
+            frame: Frame::Function(loads_function),
+            category_pair: category.into(),
+            flags: FrameFlags::empty(),
+        },
+    ];
+    profile.add_sample(
+        thread,
+        Timestamp::from_millis_since_reference(1.0),
+        frames.into_iter(),
+        CpuDelta::ZERO,
+        1,
+    );
+
+    let json = serde_json::to_value(&profile).unwrap();
+    let thread_json = &json["threads"][0];
+    assert_json_eq!(
+        thread_json["funcTable"],
+        json!({
+            "length": 2,
+            "name": [0, 3],
+            "isJS": [false, false],
+            "relevantForJS": [false, false],
+            "resource": [0, 1],
+            "fileName": [1, 4],
+            "lineNumber": [10, 299],
+            "columnNumber": [1, null]
+        })
+    );
+    assert_json_eq!(
+        thread_json["resourceTable"],
+        json!({
+            "length": 2,
+            "lib": [null, null],
+            "name": [2, 5],
+            "host": [null, null],
+            "type": [5, 5]
+        })
+    );
+    assert_json_eq!(thread_json["frameTable"]["address"], json!([-1, -1]));
+
+    let view = ProfileView::from_slice(&serde_json::to_vec(&profile).unwrap()).unwrap();
+    let thread = view.thread(0).unwrap();
+    let sample = thread.samples().next().unwrap();
+    let frames: Vec<_> = thread.stack_frames(sample.stack_index).collect();
+    assert_eq!(frames[0].name, "loads");
+    assert_eq!(frames[0].file, Some("/usr/lib/python3/json/__init__.py"));
+    assert_eq!(frames[0].line, Some(299));
+    assert_eq!(frames[0].column, None);
+    assert_eq!(frames[0].resource_name, Some("json"));
+    assert!(frames[0].lib.is_none());
+    assert_eq!(frames[1].name, "main");
+    assert_eq!(frames[1].line, Some(10));
+}
+
+#[test]
+fn profile_meta_info() {
+    let mut profile = Profile::new(
+        "test",
+        ReferenceTimestamp::from_millis_since_unix_epoch(1636162232627.0),
+        SamplingInterval::from_millis(1),
+    );
+    profile.set_arguments("./app --iterations 5");
+    profile.set_os_cpu("Linux 6.2.0");
+    profile.set_platform("Linux");
+    profile.set_cpu_name("AMD Ryzen 9 5950X");
+    let mut section = ExtraProfileInfoSection::new("Recording");
+    section.add_string_entry("Hostname", "buildbox");
+    section.add_entry("Memory", MarkerFieldFormat::Bytes, json!(34359738368u64));
+    profile.add_extra_info_section(section);
+
+    let json = serde_json::to_value(&profile).unwrap();
+    let meta = &json["meta"];
+    assert_eq!(meta["arguments"], "./app --iterations 5");
+    assert_eq!(meta["oscpu"], "Linux 6.2.0");
+    assert_eq!(meta["platform"], "Linux");
+    assert_eq!(meta["CPUName"], "AMD Ryzen 9 5950X");
+    assert_json_eq!(
+        meta["extra"],
+        json!([
+            {
+                "label": "Recording",
+                "entries": [
+                    { "label": "Hostname", "format": "string", "value": "buildbox" },
+                    { "label": "Memory", "format": "bytes", "value": 34359738368u64 }
+                ]
+            }
+        ])
+    );
+
+    let view = ProfileView::from_slice(&serde_json::to_vec(&profile).unwrap()).unwrap();
+    assert_eq!(view.arguments(), Some("./app --iterations 5"));
+    assert_eq!(view.os_cpu(), Some("Linux 6.2.0"));
+    assert_eq!(view.platform(), Some("Linux"));
+    assert_eq!(view.cpu_name(), Some("AMD Ryzen 9 5950X"));
+    let sections = view.extra_info_sections();
+    assert_eq!(sections.len(), 1);
+    assert_eq!(sections[0].entries[0].value, "buildbox");
+}
+
+#[test]
+fn counter_types() {
+    let mut profile = Profile::new(
+        "test",
+        ReferenceTimestamp::from_millis_since_unix_epoch(1636162232627.0),
+        SamplingInterval::from_millis(1),
+    );
+    let process = profile.add_process("test", 123, Timestamp::from_millis_since_reference(0.0));
+    profile.add_thread(
+        process,
+        123,
+        Timestamp::from_millis_since_reference(0.0),
+        true,
+    );
+    profile.add_counter_with_type(
+        process,
+        "malloc",
+        CounterType::Memory,
+        "Amount of allocated memory",
+    );
+    let network_counter =
+        profile.add_counter_with_type(process, "net", CounterType::Bandwidth, "Received bytes");
+    profile.add_counter_with_type(process, "rapl", CounterType::Power, "Package power");
+    profile.add_counter(process, "queue", "Queue", "Pending jobs");
+    profile.add_counter_sample(
+        network_counter,
+        Timestamp::from_millis_since_reference(1.0),
+        1500.0,
+        1,
+    );
+
+    let json = serde_json::to_value(&profile).unwrap();
+    let categories: Vec<_> = json["counters"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|counter| counter["category"].as_str().unwrap())
+        .collect();
+    assert_eq!(categories, ["Memory", "Bandwidth", "power", "Queue"]);
+    assert!(json["counters"][1].get("display").is_none());
+
+    let view = ProfileView::from_slice(&serde_json::to_vec(&profile).unwrap()).unwrap();
+    let counter_types: Vec<_> = view.counters().map(|c| c.counter_type()).collect();
+    assert_eq!(
+        counter_types,
+        [
+            Some(CounterType::Memory),
+            Some(CounterType::Bandwidth),
+            Some(CounterType::Power),
+            None
+        ]
+    );
+    let counters: Vec<_> = view.counters().collect();
+    assert_eq!(counters[1].samples().next().unwrap().value_delta, 1500.0);
+}
+
+#[test]
+fn cpu_delta_units() {
+    let mut profile = Profile::new(
+        "test",
+        ReferenceTimestamp::from_millis_since_unix_epoch(1636162232627.0),
+        SamplingInterval::from_millis(1),
+    );
+    profile.set_cpu_delta_unit(CpuDeltaUnit::Nanoseconds);
+    let process = profile.add_process("test", 123, Timestamp::from_millis_since_reference(0.0));
+    let thread = profile.add_thread(
+        process,
+        123,
+        Timestamp::from_millis_since_reference(0.0),
+        true,
+    );
+    profile.add_sample(
+        thread,
+        Timestamp::from_millis_since_reference(1.0),
+        std::iter::empty(),
+        CpuDelta::from_micros(700),
+        1,
+    );
+    profile.add_sample(
+        thread,
+        Timestamp::from_millis_since_reference(2.0),
+        std::iter::empty(),
+        CpuDelta::from_millis(0.25),
+        1,
+    );
+
+    let json = serde_json::to_value(&profile).unwrap();
+    assert_eq!(json["meta"]["sampleUnits"]["threadCPUDelta"], "ns");
+    assert_eq!(
+        json["threads"][0]["samples"]["threadCPUDelta"],
+        json!([700000, 250000])
+    );
+    let view = ProfileView::from_slice(&serde_json::to_vec(&profile).unwrap()).unwrap();
+    assert_eq!(view.cpu_delta_unit(), CpuDeltaUnit::Nanoseconds);
+    let cpu_deltas: Vec<_> = view
+        .thread(0)
+        .unwrap()
+        .samples()
+        .map(|sample| sample.cpu_delta)
+        .collect();
+    assert_eq!(
+        cpu_deltas,
+        vec![
+            Some(CpuDelta::from_micros(700)),
+            Some(CpuDelta::from_micros(250))
+        ]
+    );
+
+    profile.set_cpu_delta_unit(CpuDeltaUnit::Microseconds);
+    let json = serde_json::to_value(&profile).unwrap();
+    assert_eq!(
+        json["threads"][0]["samples"]["threadCPUDelta"],
+        json!([700, 250])
+    );
+
+    let mut profile = Profile::new(
+        "test",
+        ReferenceTimestamp::from_millis_since_unix_epoch(1636162232627.0),
+        SamplingInterval::from_millis(1),
+    );
+    profile.set_cpu_delta_unit(CpuDeltaUnit::VariableCpuCycles);
+    let process = profile.add_process("test", 123, Timestamp::from_millis_since_reference(0.0));
+    let thread = profile.add_thread(
+        process,
+        123,
+        Timestamp::from_millis_since_reference(0.0),
+        true,
+    );
+    profile.add_sample(
+        thread,
+        Timestamp::from_millis_since_reference(1.0),
+        std::iter::empty(),
+        CpuDelta::from_raw(2500000),
+        1,
+    );
+    // Durations can't be converted to CPU cycles, so they become missing values.
+    profile.add_sample(
+        thread,
+        Timestamp::from_millis_since_reference(2.0),
+        std::iter::empty(),
+        CpuDelta::from_micros(700),
+        1,
+    );
+    profile.add_sample(
+        thread,
+        Timestamp::from_millis_since_reference(3.0),
+        std::iter::empty(),
+        CpuDelta::ZERO,
+        1,
+    );
+
+    let json = serde_json::to_value(&profile).unwrap();
+    assert_eq!(
+        json["meta"]["sampleUnits"]["threadCPUDelta"],
+        "variable CPU cycles"
+    );
+    assert_eq!(
+        json["threads"][0]["samples"]["threadCPUDelta"],
+        json!([2500000, null, 0])
+    );
+
+    let view = ProfileView::from_slice(&serde_json::to_vec(&profile).unwrap()).unwrap();
+    assert_eq!(view.cpu_delta_unit(), CpuDeltaUnit::VariableCpuCycles);
+    let cpu_deltas: Vec<_> = view
+        .thread(0)
+        .unwrap()
+        .samples()
+        .map(|sample| sample.cpu_delta)
+        .collect();
+    assert_eq!(
+        cpu_deltas,
+        vec![
+            Some(CpuDelta::from_raw(2500000)),
+            None,
+            Some(CpuDelta::ZERO)
+        ]
+    );
+}
+
+#[test]
+fn cpu_delta_values() {
+    assert_eq!(
+        CpuDelta::from_micros(u64::MAX),
+        CpuDelta::from_nanos(u64::MAX)
+    );
+    assert_eq!(
+        CpuDelta::from(Duration::from_secs(u64::MAX)),
+        CpuDelta::from_nanos(u64::MAX)
+    );
+    assert_eq!(CpuDelta::from_raw(0), CpuDelta::ZERO);
+
+    // Durations and raw values are in different units and can't be compared,
+    // unless one of them is zero.
+    assert!(CpuDelta::from_micros(2) > CpuDelta::from_nanos(1500));
+    assert!(CpuDelta::from_raw(2) > CpuDelta::from_raw(1));
+    assert!(CpuDelta::ZERO < CpuDelta::from_raw(1));
+    assert_eq!(
+        CpuDelta::from_nanos(5).partial_cmp(&CpuDelta::from_raw(5)),
+        None
+    );
+    assert_ne!(CpuDelta::from_nanos(5), CpuDelta::from_raw(5));
+
+    // Outside of a profile, CPU deltas are serialized in microseconds.
+    assert_eq!(
+        serde_json::to_value(CpuDelta::from_nanos(2500)).unwrap(),
+        json!(2)
+    );
+    assert_eq!(
+        serde_json::from_value::<CpuDelta>(json!(2.5)).unwrap(),
+        CpuDelta::from_nanos(2500)
+    );
+}
+
+#[test]
+fn thread_metadata() {
+    let mut profile = Profile::new(
+        "test",
+        ReferenceTimestamp::from_millis_since_unix_epoch(1636162232627.0),
+        SamplingInterval::from_millis(1),
+    );
+    let process = profile.add_process("test", 123, Timestamp::from_millis_since_reference(0.0));
+    profile.add_thread(
+        process,
+        123,
+        Timestamp::from_millis_since_reference(0.0),
+        true,
+    );
+    let idle_thread = profile.add_thread(
+        process,
+        0,
+        Timestamp::from_millis_since_reference(0.0),
+        false,
+    );
+    profile.set_thread_type(idle_thread, ThreadType::Idle);
+    profile.set_thread_description(idle_thread, "Time in which the CPU was idle");
+    profile.set_thread_sampling_interval(idle_thread, SamplingInterval::from_millis(4));
+
+    let json = serde_json::to_value(&profile).unwrap();
+    assert!(json["threads"][0].get("threadType").is_none());
+    assert!(json["threads"][0].get("description").is_none());
+    assert!(json["threads"][0].get("interval").is_none());
+    assert_eq!(json["threads"][1]["threadType"], "idle");
+    assert_eq!(
+        json["threads"][1]["description"],
+        "Time in which the CPU was idle"
+    );
+    assert_eq!(json["threads"][1]["interval"], 4.0);
+
+    let view = ProfileView::from_slice(&serde_json::to_vec(&profile).unwrap()).unwrap();
+    let threads: Vec<_> = view.threads().collect();
+    assert_eq!(threads[0].thread_type(), ThreadType::Default);
+    assert_eq!(threads[0].description(), None);
+    assert_eq!(threads[0].sampling_interval(), None);
+    assert_eq!(threads[1].thread_type(), ThreadType::Idle);
+    assert_eq!(
+        threads[1].description(),
+        Some("Time in which the CPU was idle")
+    );
+    assert_eq!(
+        threads[1].sampling_interval(),
+        Some(SamplingInterval::from_millis(4))
+    );
+}
+
+#[test]
+fn compact_profile_format() {
+    let mut profile = Profile::new(
+        "test",
+        ReferenceTimestamp::from_millis_since_unix_epoch(1636162232627.0),
+        SamplingInterval::from_millis(1),
+    );
+    profile.set_arguments("./app --iterations 5");
+    let process = profile.add_process("test", 123, Timestamp::from_millis_since_reference(0.0));
+    let thread = profile.add_thread(
+        process,
+        123,
+        Timestamp::from_millis_since_reference(0.0),
+        true,
+    );
+    let category = profile.add_category("Regular", CategoryColor::Blue);
+    for i in 0..1000u64 {
+        let stack = ["main", "run", if i % 3 == 0 { "compute" } else { "wait" }]
+            .iter()
+            .map(|name| FrameInfo {
+                frame: Frame::Label(profile.intern_string(name)),
+                category_pair: category.into(),
+                flags: FrameFlags::empty(),
+            })
+            .collect::<Vec<_>>();
+        profile.add_sample(
+            thread,
+            Timestamp::from_millis_since_reference(i as f64 * 1.25),
+            stack.into_iter(),
+            CpuDelta::from_micros(i * 7),
+            -(i as i64),
+        );
+    }
+    profile.add_marker(
+        thread,
+        "Text",
+        TextMarker("Hello world".to_string()),
+        MarkerTiming::Instant(Timestamp::from_millis_since_reference(3.0)),
+    );
+    let counter = profile.add_counter(process, "malloc", "Memory", "Amount of allocated memory");
+    profile.add_counter_sample(
+        counter,
+        Timestamp::from_millis_since_reference(1.0),
+        1000.5,
+        2,
+    );
+
+    let mut compact = Vec::new();
+    write_compact_profile(&profile, &mut compact).unwrap();
+    assert!(is_compact_profile(&compact));
+
+    let expected_json = serde_json::to_string(&profile).unwrap();
+    assert!(compact.len() < expected_json.len() / 2);
+
+    let mut json = Vec::new();
+    compact_profile_to_json(&compact[..], &mut json).unwrap();
+    assert_eq!(String::from_utf8(json).unwrap(), expected_json);
+
+    let view = ProfileView::from_slice(&compact).unwrap();
+    assert_eq!(view.arguments(), Some("./app --iterations 5"));
+    assert_eq!(view.threads().next().unwrap().samples().len(), 1000);
+
+    let mut unsupported_version = compact.clone();
+    unsupported_version[4] = 2;
+    assert!(matches!(
+        compact_profile_to_json(&unsupported_version[..], &mut Vec::new()),
+        Err(CompactProfileError::UnsupportedVersion(2))
+    ));
+    assert!(matches!(
+        compact_profile_to_json(expected_json.as_bytes(), &mut Vec::new()),
+        Err(CompactProfileError::NotACompactProfile)
+    ));
+    assert!(compact_profile_to_json(&compact[..compact.len() / 2], &mut Vec::new()).is_err());
+
+    // A column whose length is u64::MAX, with a null bitmap.
+    let header = &compact[..8];
+    let mut huge_column = header.to_vec();
+    huge_column.push(9);
+    huge_column.extend_from_slice(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]);
+    huge_column.push(1);
+    assert!(compact_profile_to_json(&huge_column[..], &mut Vec::new()).is_err());
+
+    // Sequences which are nested too deeply.
+    let mut deeply_nested = header.to_vec();
+    deeply_nested.extend(std::iter::repeat(7).take(100_000));
+    deeply_nested.extend(std::iter::repeat(11).take(100_000));
+    assert!(compact_profile_to_json(&deeply_nested[..], &mut Vec::new()).is_err());
+}
+
+#[test]
+fn compact_profile_special_floats() {
+    let mut profile = Profile::new(
+        "test",
+        ReferenceTimestamp::from_millis_since_unix_epoch(1636162232627.0),
+        SamplingInterval::from_millis(1),
+    );
+    let process = profile.add_process("test", 123, Timestamp::from_millis_since_reference(0.0));
+    let counter = profile.add_counter(process, "malloc", "Memory", "Amount of allocated memory");
+    for (i, value) in [1.5, -0.0, f64::NAN, 0.0].into_iter().enumerate() {
+        profile.add_counter_sample(
+            counter,
+            Timestamp::from_millis_since_reference(i as f64),
+            value,
+            1,
+        );
+    }
+
+    let mut compact = Vec::new();
+    write_compact_profile(&profile, &mut compact).unwrap();
+    let mut json = Vec::new();
+    compact_profile_to_json(&compact[..], &mut json).unwrap();
+    // serde_json writes NaN as null, and keeps the sign of -0.0.
+    let json = String::from_utf8(json).unwrap();
+    assert_eq!(json, serde_json::to_string(&profile).unwrap());
+    assert!(json.contains(r#""count":[1.5,-0.0,null,0.0]"#));
+}
+
+#[test]
+fn flow_markers() {
+    let mut profile = Profile::new(
+        "test",
+        ReferenceTimestamp::from_millis_since_unix_epoch(1636162232627.0),
+        SamplingInterval::from_millis(1),
+    );
+    let parent = profile.add_process("parent", 123, Timestamp::from_millis_since_reference(0.0));
+    let parent_thread = profile.add_thread(
+        parent,
+        123,
+        Timestamp::from_millis_since_reference(0.0),
+        true,
+    );
+    let child = profile.add_process("child", 456, Timestamp::from_millis_since_reference(0.0));
+    let child_thread = profile.add_thread(
+        child,
+        456,
+        Timestamp::from_millis_since_reference(0.0),
+        true,
+    );
+
+    let first_flow = profile.new_flow_id();
+    let flow = profile.new_flow_id();
+    assert_ne!(first_flow, flow);
+    profile.add_marker(
+        parent_thread,
+        "IPC send",
+        FlowMarker::new(flow),
+        MarkerTiming::Instant(Timestamp::from_millis_since_reference(1.0)),
+    );
+    profile.add_marker(
+        child_thread,
+        "IPC receive",
+        FlowMarker::terminating(flow),
+        MarkerTiming::Instant(Timestamp::from_millis_since_reference(2.0)),
+    );
+
+    let json = serde_json::to_value(&profile).unwrap();
+    assert_json_eq!(
+        json["meta"]["markerSchema"][0]["data"],
+        json!([
+            { "key": "flow", "label": "Flow", "format": "flow-id", "searchable": true },
+            {
+                "key": "terminatingFlow",
+                "label": "Flow (end)",
+                "format": "terminating-flow-id",
+                "searchable": true
+            }
+        ])
+    );
+    // Flow IDs are stored in the thread's string table, like unique strings.
+    for (thread_index, key) in [(0, "flow"), (1, "terminatingFlow")] {
+        let thread = &json["threads"][thread_index];
+        let data = &thread["markers"]["data"][0];
+        assert_eq!(data["type"], "Flow");
+        let string_index = data[key].as_u64().unwrap() as usize;
+        assert_eq!(thread["stringArray"][string_index], "0000000000000001");
+    }
+
+    let view = ProfileView::from_slice(&serde_json::to_vec(&profile).unwrap()).unwrap();
+    let flow_ids: Vec<FlowId> = view
+        .threads()
+        .flat_map(|thread| thread.markers().collect::<Vec<_>>())
+        .map(|marker| {
+            marker
+                .flow_id("flow")
+                .or_else(|| marker.flow_id("terminatingFlow"))
+                .unwrap()
+        })
+        .collect();
+    assert_eq!(flow_ids, vec![flow, flow]);
+
+    // The flows of different runs stay separate when the runs are merged.
+    let second_run = profile.clone();
+    let mut merged = merge_profiles(
+        vec![("Run 1", profile), ("Run 2", second_run)],
+        MergeLayout::SeparateProcesses,
+    )
+    .unwrap();
+    let new_flow = merged.new_flow_id();
+    let view = ProfileView::from_slice(&serde_json::to_vec(&merged).unwrap()).unwrap();
+    let mut flow_ids: Vec<FlowId> = view
+        .threads()
+        .flat_map(|thread| thread.markers().collect::<Vec<_>>())
+        .map(|marker| {
+            marker
+                .flow_id("flow")
+                .or_else(|| marker.flow_id("terminatingFlow"))
+                .unwrap()
+        })
+        .collect();
+    flow_ids.sort();
+    assert_eq!(flow_ids, vec![FlowId(1), FlowId(1), FlowId(3), FlowId(3)]);
+    assert_eq!(new_flow, FlowId(4));
+
+    // Offsetting the flow IDs of the second run doesn't leave the old flow ID
+    // strings behind in its string tables.
+    let json = serde_json::to_value(&merged).unwrap();
+    for thread in json["threads"].as_array().unwrap() {
+        let strings = thread["stringArray"].as_array().unwrap();
+        let flow_id_strings = strings
+            .iter()
+            .filter(|s| s.as_str().unwrap().len() == 16)
+            .count();
+        assert_eq!(flow_id_strings, 1);
+    }
+}
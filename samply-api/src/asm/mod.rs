@@ -2,7 +2,7 @@ use std::str::FromStr;
 
 use samply_symbols::{
     debugid::DebugId, object, CodeByteReadingError, CodeId, FileAndPathHelper,
-    FileAndPathHelperError, LibraryInfo, SymbolManager,
+    FileAndPathHelperError, LibraryInfo, SymbolInfo, SymbolManager,
 };
 use serde_json::json;
 use yaxpeax_arch::{Arch, DecodeError, LengthedInstruction, Reader, U8Reader};
@@ -98,13 +98,58 @@ impl<'a, 'h: 'a, H: FileAndPathHelper<'h>> AsmApi<'a, 'h, H> {
             .await
             .map_err(AsmError::LoadBinaryError)?;
 
+        let architecture = binary_image.arch();
+        let instruction_set = match architecture.and_then(InstructionSet::for_arch) {
+            Some(instruction_set) => instruction_set,
+            None => {
+                return Err(AsmError::UnrecognizedArch(
+                    architecture.map_or_else(|| "unknown".to_string(), |a| a.to_string()),
+                ))
+            }
+        };
+
+        // Only look up the function symbol if we need it, i.e. if we need to
+        // know where the function ends, or if we can't tell from the start
+        // address whether 32-bit ARM code is in thumb mode.
+        let needs_symbol = *continue_until_function_end
+            || (instruction_set == InstructionSet::Arm
+                && start_address & 0b1 == 0
+                && !is_thumb_only_arch(architecture));
+        let function_symbol = if needs_symbol {
+            self.lookup_function_symbol(&library_info, *start_address)
+                .await
+        } else {
+            None
+        };
+
+        // On 32-bit ARM, the same binary can contain both ARM code and thumb code,
+        // in different functions. Addresses of thumb functions have the lowest bit
+        // set (the "thumb bit"), so we check the requested address and the address
+        // of the containing function symbol. If neither tells us anything, we
+        // assume thumb, because that's what most Android code is compiled to.
+        let instruction_set = match instruction_set {
+            InstructionSet::Arm if !is_thumb_only_arch(architecture) => {
+                let is_arm_mode = start_address & 0b1 == 0
+                    && function_symbol
+                        .as_ref()
+                        .map_or(false, |symbol| symbol.address & 0b1 == 0);
+                if is_arm_mode {
+                    InstructionSet::Arm
+                } else {
+                    InstructionSet::Thumb
+                }
+            }
+            InstructionSet::Arm => InstructionSet::Thumb,
+            other => other,
+        };
+
         let mut disassembly_len = *size;
 
         if *continue_until_function_end {
-            if let Some(function_end_address) = self
-                .get_function_end_address(&library_info, *start_address)
-                .await
-            {
+            let function_end_address = function_symbol
+                .as_ref()
+                .and_then(|symbol| symbol.address.checked_add(symbol.size?));
+            if let Some(function_end_address) = function_end_address {
                 if function_end_address >= *start_address
                     && function_end_address - *start_address > *size
                 {
@@ -117,15 +162,10 @@ impl<'a, 'h: 'a, H: FileAndPathHelper<'h>> AsmApi<'a, 'h, H> {
         // For example, on ARM, you might be looking for the instructions of a
         // function whose function symbol has address 0x2001. But this address is
         // really two pieces of information: 0x2000 is the address of the function's
-        // first instruction (ARM instructions are two-byte aligned), and the 0x1 bit
+        // first instruction (thumb instructions are two-byte aligned), and the 0x1 bit
         // is the "thumb" bit, meaning that the instructions need to be decoded
         // with the thumb decoder.
-        let architecture = binary_image.arch();
-        let rel_address = match architecture {
-            Some("arm64" | "arm64e") => start_address & !0b11,
-            Some("arm") => start_address & !0b1,
-            _ => *start_address,
-        };
+        let rel_address = start_address & !(instruction_set.instruction_alignment() - 1);
 
         // Pad out the number of bytes we read a little, to allow for reading one
         // more instruction.
@@ -136,11 +176,11 @@ impl<'a, 'h: 'a, H: FileAndPathHelper<'h>> AsmApi<'a, 'h, H> {
         // decode the entire instruction, so we need all of its bytes.
         // We have another check later to make sure we don't return instructions whose
         // address is beyond the requested range.
-        const MAX_INSTR_LEN: u32 = 15; // TODO: Get the correct max length for this arch
+        let max_instruction_len = instruction_set.max_instruction_len();
 
         // Now read the instruction bytes from the file.
         let bytes = binary_image
-            .read_bytes_at_relative_address(rel_address, disassembly_len + MAX_INSTR_LEN)
+            .read_bytes_at_relative_address(rel_address, disassembly_len + max_instruction_len)
             .map_err(|e| match e {
                 CodeByteReadingError::AddressNotFound => AsmError::AddressNotFound,
                 CodeByteReadingError::ObjectParseError(e) => AsmError::ObjectParseError(e),
@@ -148,62 +188,139 @@ impl<'a, 'h: 'a, H: FileAndPathHelper<'h>> AsmApi<'a, 'h, H> {
                 CodeByteReadingError::FileIO(e) => AsmError::FileIO(e),
             })?;
 
-        decode_arch(bytes, architecture, rel_address, disassembly_len)
+        Ok(decode_instruction_set(
+            bytes,
+            instruction_set,
+            rel_address,
+            disassembly_len,
+        ))
     }
 
-    async fn get_function_end_address(
+    async fn lookup_function_symbol(
         &self,
         library_info: &LibraryInfo,
         address_within_function: u32,
-    ) -> Option<u32> {
+    ) -> Option<SymbolInfo> {
         let symbol_map_res = self.symbol_manager.load_symbol_map(library_info).await;
         let symbol = symbol_map_res
             .ok()?
             .lookup_relative_address(address_within_function)?
             .symbol;
-        symbol.address.checked_add(symbol.size?)
+        Some(symbol)
     }
 }
 
-fn decode_arch(
-    bytes: &[u8],
-    arch: Option<&str>,
-    rel_address: u32,
-    decode_len: u32,
-) -> Result<Response, AsmError> {
-    Ok(match arch {
-        Some("x86") => decode::<yaxpeax_x86::protected_mode::Arch>(bytes, rel_address, decode_len),
-        Some("x86_64" | "x86_64h") => {
-            decode::<yaxpeax_x86::amd64::Arch>(bytes, rel_address, decode_len)
+/// The instruction encoding of the code that should be disassembled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum InstructionSet {
+    X86,
+    X86_64,
+    Aarch64,
+    /// 32-bit ARM code in ARM mode, with fixed-size four-byte instructions.
+    Arm,
+    /// 32-bit ARM code in thumb mode, with two- or four-byte instructions.
+    Thumb,
+}
+
+impl InstructionSet {
+    /// Returns the instruction set for the architecture string of a binary, e.g. "x86_64" or "arm64e".
+    ///
+    /// For 32-bit ARM, this returns `InstructionSet::Arm`, and the caller needs to
+    /// check whether the code is actually thumb code.
+    fn for_arch(arch: &str) -> Option<Self> {
+        match arch {
+            "x86" | "i386" | "i686" => Some(InstructionSet::X86),
+            "x86_64" | "x86_64h" => Some(InstructionSet::X86_64),
+            "arm64" | "arm64e" | "arm64v8" | "arm64_32" | "arm64_32v8" | "aarch64" => {
+                Some(InstructionSet::Aarch64)
+            }
+            _ if arch.starts_with("arm") => Some(InstructionSet::Arm),
+            _ => None,
         }
-        Some("arm64" | "arm64e") => {
-            decode::<yaxpeax_arm::armv8::a64::ARMv8>(bytes, rel_address, decode_len)
+    }
+
+    /// The alignment of instruction addresses, in bytes.
+    ///
+    /// This is also the size of the smallest instruction, so it's the number
+    /// of bytes we skip when we encounter an invalid instruction.
+    fn instruction_alignment(self) -> u32 {
+        match self {
+            InstructionSet::X86 | InstructionSet::X86_64 => 1,
+            InstructionSet::Aarch64 | InstructionSet::Arm => 4,
+            InstructionSet::Thumb => 2,
         }
-        Some("arm") => decode::<yaxpeax_arm::armv7::ARMv7>(bytes, rel_address, decode_len),
-        _ => {
-            return Err(AsmError::UnrecognizedArch(
-                arch.map_or_else(|| "unknown".to_string(), |a| a.to_string()),
-            ))
+    }
+
+    /// The length of the longest instruction, in bytes.
+    fn max_instruction_len(self) -> u32 {
+        match self {
+            InstructionSet::X86 | InstructionSet::X86_64 => 15,
+            InstructionSet::Aarch64 | InstructionSet::Arm | InstructionSet::Thumb => 4,
         }
-    })
+    }
+}
+
+/// Returns whether this is a 32-bit ARM architecture which only supports thumb
+/// instructions, i.e. one of the M-profile architectures.
+fn is_thumb_only_arch(arch: Option<&str>) -> bool {
+    matches!(arch, Some("armv6m" | "armv7m" | "armv7em"))
+}
+
+fn decode_instruction_set(
+    bytes: &[u8],
+    instruction_set: InstructionSet,
+    rel_address: u32,
+    decode_len: u32,
+) -> Response {
+    let adjust_by_after_error = instruction_set.instruction_alignment() as usize;
+    match instruction_set {
+        InstructionSet::X86 => decode::<yaxpeax_x86::protected_mode::Arch>(
+            bytes,
+            rel_address,
+            decode_len,
+            yaxpeax_x86::protected_mode::InstDecoder::default(),
+            adjust_by_after_error,
+        ),
+        InstructionSet::X86_64 => decode::<yaxpeax_x86::amd64::Arch>(
+            bytes,
+            rel_address,
+            decode_len,
+            yaxpeax_x86::amd64::InstDecoder::default(),
+            adjust_by_after_error,
+        ),
+        InstructionSet::Aarch64 => decode::<yaxpeax_arm::armv8::a64::ARMv8>(
+            bytes,
+            rel_address,
+            decode_len,
+            yaxpeax_arm::armv8::a64::InstDecoder::default(),
+            adjust_by_after_error,
+        ),
+        InstructionSet::Arm => decode::<yaxpeax_arm::armv7::ARMv7>(
+            bytes,
+            rel_address,
+            decode_len,
+            yaxpeax_arm::armv7::InstDecoder::default(),
+            adjust_by_after_error,
+        ),
+        InstructionSet::Thumb => decode::<yaxpeax_arm::armv7::ARMv7>(
+            bytes,
+            rel_address,
+            decode_len,
+            yaxpeax_arm::armv7::InstDecoder::default_thumb(),
+            adjust_by_after_error,
+        ),
+    }
 }
 
 trait InstructionDecoding: Arch {
     const ARCH_NAME: &'static str;
     const SYNTAX: &'static [&'static str];
-    const ADJUST_BY_AFTER_ERROR: usize;
-    fn make_decoder() -> Self::Decoder;
     fn stringify_inst(rel_address: u32, offset: u32, inst: Self::Instruction) -> DecodedInstruction;
 }
 
 impl InstructionDecoding for yaxpeax_x86::amd64::Arch {
     const ARCH_NAME: &'static str = "x86_64";
     const SYNTAX: &'static [&'static str] = &["Intel", "C style"];
-    const ADJUST_BY_AFTER_ERROR: usize = 1;
-
-    fn make_decoder() -> Self::Decoder {
-        yaxpeax_x86::amd64::InstDecoder::default()
-    }
 
     fn stringify_inst(rel_address: u32, offset: u32, inst: Self::Instruction) -> DecodedInstruction {
         let (mut intel_insn, mut c_insn) = (
@@ -267,11 +384,6 @@ impl InstructionDecoding for yaxpeax_x86::amd64::Arch {
 impl InstructionDecoding for yaxpeax_x86::protected_mode::Arch {
     const ARCH_NAME: &'static str = "i686";
     const SYNTAX: &'static [&'static str] = &["Intel"];
-    const ADJUST_BY_AFTER_ERROR: usize = 1;
-
-    fn make_decoder() -> Self::Decoder {
-        yaxpeax_x86::protected_mode::InstDecoder::default()
-    }
 
     fn stringify_inst(_rel_address: u32, offset: u32, inst: Self::Instruction) -> DecodedInstruction {
         DecodedInstruction {
@@ -284,11 +396,6 @@ impl InstructionDecoding for yaxpeax_x86::protected_mode::Arch {
 impl InstructionDecoding for yaxpeax_arm::armv8::a64::ARMv8 {
     const ARCH_NAME: &'static str = "aarch64";
     const SYNTAX: &'static [&'static str] = &["ARM"];
-    const ADJUST_BY_AFTER_ERROR: usize = 4;
-
-    fn make_decoder() -> Self::Decoder {
-        yaxpeax_arm::armv8::a64::InstDecoder::default()
-    }
 
     fn stringify_inst(_rel_address: u32, offset: u32, inst: Self::Instruction) -> DecodedInstruction {
         DecodedInstruction {
@@ -301,22 +408,6 @@ impl InstructionDecoding for yaxpeax_arm::armv8::a64::ARMv8 {
 impl InstructionDecoding for yaxpeax_arm::armv7::ARMv7 {
     const ARCH_NAME: &'static str = "arm";
     const SYNTAX: &'static [&'static str] = &["ARM"];
-    const ADJUST_BY_AFTER_ERROR: usize = 2;
-
-    fn make_decoder() -> Self::Decoder {
-        // TODO: Detect whether the instructions in the requested address range
-        // use thumb or non-thumb mode.
-        // I'm not quite sure how to do this. The same object file can contain both
-        // types of code in different functions. We basically have two options:
-        //  1. Have the API caller tell us whether to use thumb, or
-        //  2. Detect the mode based on the content in the file.
-        // For 2., we could look up the closest symbol to the start address and
-        // check whether its symbol address has the thumb bit set. But the function
-        // may not have a symbol in the binary that we have access to here.
-        //
-        // For now we just always assume thumb.
-        yaxpeax_arm::armv7::InstDecoder::default_thumb()
-    }
 
     fn stringify_inst(_rel_address: u32, offset: u32, inst: Self::Instruction) -> DecodedInstruction {
         DecodedInstruction {
@@ -330,6 +421,8 @@ fn decode<'a, A: InstructionDecoding>(
     bytes: &'a [u8],
     rel_address: u32,
     decode_len: u32,
+    decoder: A::Decoder,
+    adjust_by_after_error: usize,
 ) -> Response
 where
     u64: From<A::Address>,
@@ -337,7 +430,6 @@ where
 {
     use yaxpeax_arch::Decoder;
     let mut reader = yaxpeax_arch::U8Reader::new(bytes);
-    let decoder = A::make_decoder();
     let mut instructions = Vec::new();
     let mut offset = 0;
     loop {
@@ -359,13 +451,13 @@ where
                 let remaining_bytes = &bytes[offset as usize..];
                 let s = remaining_bytes
                     .iter()
-                    .take(A::ADJUST_BY_AFTER_ERROR)
+                    .take(adjust_by_after_error)
                     .map(|b| format!("{b:#02x}"))
                     .collect::<Vec<_>>()
                     .join(", ");
                 let s2 = remaining_bytes
                     .iter()
-                    .take(A::ADJUST_BY_AFTER_ERROR)
+                    .take(adjust_by_after_error)
                     .map(|b| format!("{b:02X}"))
                     .collect::<Vec<_>>()
                     .join(" ");
@@ -377,14 +469,16 @@ where
                         .map(|_| {
                             format!(
                                 ".byte {s:width$} # Invalid instruction {s2}: {e}",
-                                width = A::ADJUST_BY_AFTER_ERROR * 6
+                                width = adjust_by_after_error * 6
                             )
                         })
                         .collect(),
                 });
 
-                offset += A::ADJUST_BY_AFTER_ERROR as u32;
-                let Some(reader_bytes) = bytes.get(offset as usize..) else { break };
+                offset += adjust_by_after_error as u32;
+                let Some(reader_bytes) = bytes.get(offset as usize..) else {
+                    break;
+                };
                 reader = U8Reader::new(reader_bytes);
             }
         }
@@ -401,3 +495,61 @@ where
         instructions,
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{decode_instruction_set, InstructionSet};
+
+    fn decoded_offsets(bytes: &[u8], instruction_set: InstructionSet) -> Vec<u32> {
+        let response = decode_instruction_set(bytes, instruction_set, 0x1000, bytes.len() as u32);
+        assert!(response
+            .instructions
+            .iter()
+            .all(|inst| !inst.decoded_string_per_syntax[0].contains("Invalid instruction")));
+        response
+            .instructions
+            .iter()
+            .map(|inst| inst.offset)
+            .collect()
+    }
+
+    #[test]
+    fn instruction_set_for_arch() {
+        assert_eq!(
+            InstructionSet::for_arch("arm64e"),
+            Some(InstructionSet::Aarch64)
+        );
+        assert_eq!(InstructionSet::for_arch("armv7"), Some(InstructionSet::Arm));
+        assert_eq!(
+            InstructionSet::for_arch("x86_64h"),
+            Some(InstructionSet::X86_64)
+        );
+        assert_eq!(InstructionSet::for_arch("riscv64"), None);
+    }
+
+    #[test]
+    fn decode_aarch64() {
+        // stp x29, x30, [sp, #-0x10]!; mov x29, sp; ret
+        let bytes = [
+            0xfd, 0x7b, 0xbf, 0xa9, 0xfd, 0x03, 0x00, 0x91, 0xc0, 0x03, 0x5f, 0xd6,
+        ];
+        assert_eq!(
+            decoded_offsets(&bytes, InstructionSet::Aarch64),
+            vec![0, 4, 8]
+        );
+    }
+
+    #[test]
+    fn decode_arm_and_thumb() {
+        // bx lr, in ARM mode
+        let arm_bytes = [0x1e, 0xff, 0x2f, 0xe1];
+        assert_eq!(decoded_offsets(&arm_bytes, InstructionSet::Arm), vec![0]);
+
+        // movs r0, #0; bx lr, in thumb mode
+        let thumb_bytes = [0x00, 0x20, 0x70, 0x47];
+        assert_eq!(
+            decoded_offsets(&thumb_bytes, InstructionSet::Thumb),
+            vec![0, 2]
+        );
+    }
+}
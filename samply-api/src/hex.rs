@@ -35,3 +35,15 @@ where
     };
     u32::from_str_radix(s, 16).map_err(serde::de::Error::custom)
 }
+
+pub fn from_optional_prefixed_hex_str<'de, D>(deserializer: D) -> Result<Option<u32>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::Deserialize;
+    #[derive(Deserialize)]
+    struct Wrapper(#[serde(deserialize_with = "from_prefixed_hex_str")] u32);
+
+    let v = Option::<Wrapper>::deserialize(deserializer)?;
+    Ok(v.map(|Wrapper(v)| v))
+}
@@ -150,6 +150,7 @@ use debugid::DebugId;
 use serde_json::json;
 use source::SourceApi;
 use symbolicate::SymbolicateApi;
+use symbols::SymbolsApi;
 
 mod api_file_path;
 mod asm;
@@ -157,6 +158,11 @@ mod error;
 mod hex;
 mod source;
 mod symbolicate;
+mod symbols;
+
+/// The parsed form of a `/symbolicate/v5` request, for callers which need to
+/// inspect or split up requests before passing them to [`Api::query_api`].
+pub use symbolicate::request_json::{Job, JobIterator, Lib, Request, Stack, StackFrame};
 
 pub(crate) fn to_debug_id(breakpad_id: &str) -> Result<DebugId, samply_symbols::Error> {
     DebugId::from_breakpad(breakpad_id)
@@ -189,6 +195,8 @@ impl<'a, 'h: 'a, H: FileAndPathHelper<'h>> Api<'a, 'h, H> {
     ///    symbol information for that address.
     ///  - `/asm/v1`: Experimental API. Symbolicates an address and lets you read one of the files in the
     ///    symbol information for that address.
+    ///  - `/symbols/v1`: Experimental API. Lists the symbols of a library, with their addresses and sizes.
+    ///    The list can be filtered by name (substring, exact or regex match) and by address range.
     pub async fn query_api(self, request_url: &str, request_json_data: &str) -> String {
         if request_url == "/symbolicate/v5" {
             let symbolicate_api = SymbolicateApi::new(self.symbol_manager);
@@ -199,6 +207,9 @@ impl<'a, 'h: 'a, H: FileAndPathHelper<'h>> Api<'a, 'h, H> {
         } else if request_url == "/asm/v1" {
             let asm_api = AsmApi::new(self.symbol_manager);
             asm_api.query_api_json(request_json_data).await
+        } else if request_url == "/symbols/v1" {
+            let symbols_api = SymbolsApi::new(self.symbol_manager);
+            symbols_api.query_api_json(request_json_data).await
         } else {
             json!({ "error": format!("Unrecognized URL {request_url}") }).to_string()
         }
@@ -0,0 +1,143 @@
+use crate::to_debug_id;
+use regex::Regex;
+use samply_symbols::{FileAndPathHelper, LibraryInfo, SymbolManager};
+use serde_json::json;
+
+use self::request_json::SearchMode;
+
+mod request_json;
+mod response_json;
+
+#[derive(thiserror::Error, Debug)]
+enum SymbolsError {
+    #[error("Couldn't parse request: {0}")]
+    ParseRequestErrorSerde(#[from] serde_json::error::Error),
+
+    #[error("Could not obtain symbols for the requested library: {0}")]
+    NoSymbols(#[from] samply_symbols::Error),
+
+    #[error("Invalid search regex: {0}")]
+    InvalidRegex(#[from] regex::Error),
+}
+
+pub struct SymbolsApi<'a, 'h: 'a, H: FileAndPathHelper<'h>> {
+    symbol_manager: &'a SymbolManager<'h, H>,
+}
+
+impl<'a, 'h: 'a, H: FileAndPathHelper<'h>> SymbolsApi<'a, 'h, H> {
+    /// Create a [`SymbolsApi`] instance which uses the provided [`SymbolManager`].
+    pub fn new(symbol_manager: &'a SymbolManager<'h, H>) -> Self {
+        Self { symbol_manager }
+    }
+
+    pub async fn query_api_json(&self, request_json: &str) -> String {
+        match self.query_api_fallible_json(request_json).await {
+            Ok(response_json) => response_json,
+            Err(err) => json!({ "error": err.to_string() }).to_string(),
+        }
+    }
+
+    async fn query_api_fallible_json(&self, request_json: &str) -> Result<String, SymbolsError> {
+        let request: request_json::Request = serde_json::from_str(request_json)?;
+        let response = self.query_api(&request).await?;
+        Ok(serde_json::to_string(&response)?)
+    }
+
+    async fn query_api(
+        &self,
+        request: &request_json::Request,
+    ) -> Result<response_json::Response, SymbolsError> {
+        let request_json::Request {
+            debug_name,
+            debug_id,
+            search,
+            search_mode,
+            start_address,
+            end_address,
+            limit,
+        } = request;
+        let debug_id = to_debug_id(debug_id)?;
+        let matcher = match search {
+            Some(search) => Some(NameMatcher::new(search, *search_mode)?),
+            None => None,
+        };
+
+        let info = LibraryInfo {
+            debug_name: Some(debug_name.to_string()),
+            debug_id: Some(debug_id),
+            ..Default::default()
+        };
+        let symbol_map = self.symbol_manager.load_symbol_map(&info).await?;
+
+        let mut all_symbols: Vec<(u32, _)> = symbol_map.iter_demangled_symbols().collect();
+        all_symbols.sort_by_key(|(address, _)| *address);
+        let total_symbol_count = all_symbols.len();
+
+        let mut symbols = Vec::new();
+        let mut truncated = false;
+        for (address, name) in &all_symbols {
+            if start_address.map_or(false, |start| *address < start)
+                || end_address.map_or(false, |end| *address >= end)
+            {
+                continue;
+            }
+            if let Some(matcher) = &matcher {
+                if !matcher.matches(name) {
+                    continue;
+                }
+            }
+            if limit.map_or(false, |limit| symbols.len() >= limit) {
+                truncated = true;
+                break;
+            }
+            // The symbol map knows the symbol's size, either from the debug info
+            // or from the address of the next symbol.
+            let size = symbol_map
+                .lookup_relative_address(*address)
+                .filter(|info| info.symbol.address == *address)
+                .and_then(|info| info.symbol.size);
+            symbols.push(response_json::Symbol {
+                name: name.to_string(),
+                address: *address,
+                size,
+            });
+        }
+
+        Ok(response_json::Response {
+            total_symbol_count,
+            truncated,
+            symbols,
+        })
+    }
+}
+
+enum NameMatcher<'a> {
+    Substring(&'a str),
+    SubstringIgnoreCase(String),
+    Exact(&'a str),
+    Regex(Regex),
+}
+
+impl<'a> NameMatcher<'a> {
+    fn new(search: &'a str, search_mode: SearchMode) -> Result<Self, regex::Error> {
+        Ok(match search_mode {
+            SearchMode::Substring => NameMatcher::Substring(search),
+            SearchMode::SubstringIgnoreCase => {
+                NameMatcher::SubstringIgnoreCase(search.to_ascii_lowercase())
+            }
+            SearchMode::Exact => NameMatcher::Exact(search),
+            SearchMode::Regex => NameMatcher::Regex(Regex::new(search)?),
+        })
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        match self {
+            NameMatcher::Substring(search) => name.contains(search),
+            NameMatcher::SubstringIgnoreCase(search) => {
+                name.to_ascii_lowercase().contains(search.as_str())
+            }
+            NameMatcher::Exact(search) => name == *search,
+            NameMatcher::Regex(regex) => regex.is_match(name),
+        }
+    }
+}
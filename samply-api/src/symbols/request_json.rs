@@ -0,0 +1,108 @@
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Request {
+    /// The debugName of the library whose symbols should be listed.
+    pub debug_name: String,
+
+    /// The debugId / "breakpadId" of the library whose symbols should be listed.
+    pub debug_id: String,
+
+    /// If present, only symbols whose name matches this string are returned.
+    /// How the string is matched is determined by `searchMode`.
+    #[serde(default)]
+    pub search: Option<String>,
+
+    /// How `search` is matched against the symbol names. This field is optional
+    /// and defaults to "substring".
+    #[serde(default)]
+    pub search_mode: SearchMode,
+
+    /// If present, only symbols whose address is at or after this address
+    /// are returned. This is a "0x"-prefixed hex string, interpreted as a
+    /// library-relative offset in bytes.
+    #[serde(
+        default,
+        deserialize_with = "crate::hex::from_optional_prefixed_hex_str"
+    )]
+    pub start_address: Option<u32>,
+
+    /// If present, only symbols whose address is before this address
+    /// are returned. This is a "0x"-prefixed hex string, interpreted as a
+    /// library-relative offset in bytes.
+    #[serde(
+        default,
+        deserialize_with = "crate::hex::from_optional_prefixed_hex_str"
+    )]
+    pub end_address: Option<u32>,
+
+    /// The maximum number of symbols to return. This field is optional; if
+    /// it's not specified, all matching symbols are returned.
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum SearchMode {
+    /// The symbol name needs to contain the search string.
+    Substring,
+    /// The symbol name needs to contain the search string, ignoring ASCII case.
+    SubstringIgnoreCase,
+    /// The symbol name needs to be equal to the search string.
+    Exact,
+    /// The search string is a regular expression which needs to match the symbol name.
+    Regex,
+}
+
+impl Default for SearchMode {
+    fn default() -> Self {
+        SearchMode::Substring
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{Request, SearchMode};
+    use serde_json::Result;
+
+    #[test]
+    fn parse_minimal_request() -> Result<()> {
+        let data = r#"
+        {
+          "debugName": "xul.pdb",
+          "debugId": "A14CAFD390A3E1884C4C44205044422E1"
+        }"#;
+
+        let r: Request = serde_json::from_str(data)?;
+        assert_eq!(r.search, None);
+        assert_eq!(r.search_mode, SearchMode::Substring);
+        assert_eq!(r.start_address, None);
+        assert_eq!(r.limit, None);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_search_request() -> Result<()> {
+        let data = r#"
+        {
+          "debugName": "xul.pdb",
+          "debugId": "A14CAFD390A3E1884C4C44205044422E1",
+          "search": "^mozilla::.*::Run$",
+          "searchMode": "regex",
+          "startAddress": "0x1d04742",
+          "endAddress": "0x1e00000",
+          "limit": 20
+        }"#;
+
+        let r: Request = serde_json::from_str(data)?;
+        assert_eq!(r.search.as_deref(), Some("^mozilla::.*::Run$"));
+        assert_eq!(r.search_mode, SearchMode::Regex);
+        assert_eq!(r.start_address, Some(30426946));
+        assert_eq!(r.end_address, Some(0x1e00000));
+        assert_eq!(r.limit, Some(20));
+        Ok(())
+    }
+}
@@ -0,0 +1,78 @@
+use serde::Serialize;
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Response {
+    /// The total number of symbols in the library, before any filtering.
+    pub total_symbol_count: usize,
+
+    /// Whether more symbols matched the request than were returned, because
+    /// of the requested `limit`.
+    pub truncated: bool,
+
+    /// The matching symbols, sorted by address.
+    pub symbols: Vec<Symbol>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Symbol {
+    /// The function name, demangled.
+    pub name: String,
+
+    /// The start address of the function, as a "0x"-prefixed hex string,
+    /// interpreted as a library-relative offset in bytes.
+    #[serde(serialize_with = "crate::hex::as_hex_string")]
+    pub address: u32,
+
+    /// The size of the function in bytes, as a "0x"-prefixed hex string.
+    /// This is computed from the address of the next symbol, so it is null
+    /// for the last symbol in the library.
+    #[serde(serialize_with = "crate::hex::as_optional_hex_string")]
+    pub size: Option<u32>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Response, Symbol};
+    use serde_json::Result;
+
+    #[test]
+    fn serialize_correctly() -> Result<()> {
+        let response = Response {
+            total_symbol_count: 1234,
+            truncated: false,
+            symbols: vec![
+                Symbol {
+                    name: "main".to_string(),
+                    address: 0x1b30,
+                    size: Some(0x38),
+                },
+                Symbol {
+                    name: "_fini".to_string(),
+                    address: 0x1b68,
+                    size: None,
+                },
+            ],
+        };
+        let response = serde_json::to_string_pretty(&response)?;
+        let expected = r#"{
+  "totalSymbolCount": 1234,
+  "truncated": false,
+  "symbols": [
+    {
+      "name": "main",
+      "address": "0x1b30",
+      "size": "0x38"
+    },
+    {
+      "name": "_fini",
+      "address": "0x1b68",
+      "size": null
+    }
+  ]
+}"#;
+        assert_eq!(response, expected);
+        Ok(())
+    }
+}
@@ -390,6 +390,69 @@ fn win_exe() {
     );
 }
 
+#[test]
+fn symbols_search() {
+    let query = |request_json: &str| -> serde_json::Value {
+        let output = futures::executor::block_on(crate::query_api(
+            "/symbols/v1",
+            request_json,
+            fixtures_dir().join("win64-ci"),
+        ));
+        serde_json::from_str(&output).unwrap()
+    };
+
+    let substring_result = query(
+        r#"{
+            "debugName": "WriteArgument.pdb",
+            "debugId": "DF5016E17D4C30144C4C44205044422E1",
+            "search": "main"
+        }"#,
+    );
+    let symbols = substring_result["symbols"].as_array().unwrap();
+    assert!(!symbols.is_empty());
+    assert!(symbols.iter().any(|s| s["address"] == "0x1000"));
+    // One leading underscore is stripped from the names in PDB files.
+    assert!(symbols
+        .iter()
+        .any(|s| s["name"] == "_scrt_common_main_seh()"));
+    assert!(substring_result["totalSymbolCount"].as_u64().unwrap() > symbols.len() as u64);
+
+    let regex_result = query(
+        r#"{
+            "debugName": "WriteArgument.pdb",
+            "debugId": "DF5016E17D4C30144C4C44205044422E1",
+            "search": "^main\\(",
+            "searchMode": "regex",
+            "startAddress": "0x1000",
+            "endAddress": "0x1001"
+        }"#,
+    );
+    let symbols = regex_result["symbols"].as_array().unwrap();
+    assert_eq!(symbols.len(), 1);
+    assert_eq!(symbols[0]["address"], "0x1000");
+    assert!(symbols[0]["size"].is_string());
+
+    let limited_result = query(
+        r#"{
+            "debugName": "WriteArgument.pdb",
+            "debugId": "DF5016E17D4C30144C4C44205044422E1",
+            "limit": 3
+        }"#,
+    );
+    assert_eq!(limited_result["symbols"].as_array().unwrap().len(), 3);
+    assert_eq!(limited_result["truncated"], true);
+
+    let invalid_regex_result = query(
+        r#"{
+            "debugName": "WriteArgument.pdb",
+            "debugId": "DF5016E17D4C30144C4C44205044422E1",
+            "search": "(unclosed",
+            "searchMode": "regex"
+        }"#,
+    );
+    assert!(invalid_regex_result["error"].is_string());
+}
+
 #[test]
 fn asm_with_continue() {
     // This tests the following:
@@ -297,8 +297,8 @@ impl<F: FileContents> BinaryImageInner<F> {
     }
 }
 
-struct PeInfo {
-    code_id: CodeId,
+pub(crate) struct PeInfo {
+    pub(crate) code_id: CodeId,
     pdb_path: Option<String>,
     pdb_name: Option<String>,
 }
@@ -309,7 +309,7 @@ impl PeInfo {
     }
 }
 
-fn pe_info<'a, Pe: ImageNtHeaders, R: ReadRef<'a>>(pe: &PeFile<'a, Pe, R>) -> PeInfo {
+pub(crate) fn pe_info<'a, Pe: ImageNtHeaders, R: ReadRef<'a>>(pe: &PeFile<'a, Pe, R>) -> PeInfo {
     // The code identifier consists of the `time_date_stamp` field id the COFF header, followed by
     // the `size_of_image` field in the optional header. If the optional PE header is not present,
     // this identifier is `None`.
@@ -1,39 +1,297 @@
-use super::demangle_ocaml;
+use super::{demangle_d, demangle_ocaml, demangle_swift};
+use crate::FrameDebugInfo;
 use msvc_demangler::DemangleFlags;
 
-pub fn demangle_any(name: &str) -> String {
+/// Controls how symbol names are demangled.
+///
+/// Mangled names are recognized for C++ (both the Itanium ABI and MSVC),
+/// Rust, Swift, D and OCaml. Zig does not mangle its symbol names, so Zig
+/// symbols are returned unchanged (apart from the leading underscore on
+/// macOS, see [`DemangleOptions::strip_leading_underscore`]).
+///
+/// The default options produce short, readable names: no return types, but
+/// with template arguments, and without Rust symbol hashes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DemangleOptions {
+    return_types: bool,
+    template_args: bool,
+    rust_hashes: bool,
+    strip_leading_underscore: bool,
+}
+
+impl Default for DemangleOptions {
+    fn default() -> Self {
+        Self {
+            return_types: false,
+            template_args: true,
+            rust_hashes: false,
+            strip_leading_underscore: true,
+        }
+    }
+}
+
+impl DemangleOptions {
+    /// Create a new `DemangleOptions` in its default state.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether to include the return type of C++ functions. Off by default.
+    pub fn return_types(mut self, return_types: bool) -> Self {
+        self.return_types = return_types;
+        self
+    }
+
+    /// Whether to include template arguments in C++ function names, e.g.
+    /// `std::vector<int>::push_back(int&&)` vs `std::vector::push_back(int&&)`.
+    /// On by default.
+    pub fn template_args(mut self, template_args: bool) -> Self {
+        self.template_args = template_args;
+        self
+    }
+
+    /// Whether to include the hash suffix of Rust symbols, e.g.
+    /// `core::ptr::drop_in_place::h1b9b3e0b9f5e7f4c`. Off by default.
+    pub fn rust_hashes(mut self, rust_hashes: bool) -> Self {
+        self.rust_hashes = rust_hashes;
+        self
+    }
+
+    /// Whether to strip a leading underscore from names which could not be
+    /// demangled. On macOS, the names of C functions (and of all other
+    /// functions with C linkage, e.g. from Zig) are prefixed with an underscore,
+    /// and so are the names of C functions in 32-bit Windows PDB files.
+    /// On by default.
+    pub fn strip_leading_underscore(mut self, strip_leading_underscore: bool) -> Self {
+        self.strip_leading_underscore = strip_leading_underscore;
+        self
+    }
+}
+
+pub fn demangle_any(name: &str, options: &DemangleOptions) -> String {
     if name.starts_with('?') {
-        let flags = DemangleFlags::NO_ACCESS_SPECIFIERS
-            | DemangleFlags::NO_FUNCTION_RETURNS
-            | DemangleFlags::NO_MEMBER_TYPE
-            | DemangleFlags::NO_MS_KEYWORDS
-            | DemangleFlags::NO_THISTYPE
-            | DemangleFlags::NO_CLASS_TYPE
-            | DemangleFlags::SPACE_AFTER_COMMA
-            | DemangleFlags::HUG_TYPE;
-        return msvc_demangler::demangle(name, flags).unwrap_or_else(|_| name.to_string());
+        return demangle_msvc(name, options);
     }
 
     if let Ok(demangled_symbol) = rustc_demangle::try_demangle(name) {
-        return format!("{demangled_symbol:#}");
+        return if options.rust_hashes {
+            format!("{demangled_symbol}")
+        } else {
+            format!("{demangled_symbol:#}")
+        };
+    }
+
+    if let Some(symbol) = demangle_swift::demangle(name) {
+        return symbol;
     }
 
     if name.starts_with('_') {
-        let options = cpp_demangle::DemangleOptions::default().no_return_type();
+        let mut cpp_options = cpp_demangle::DemangleOptions::default();
+        if !options.return_types {
+            cpp_options = cpp_options.no_return_type();
+        }
         if let Ok(symbol) = cpp_demangle::Symbol::new(name) {
-            if let Ok(demangled_string) = symbol.demangle(&options) {
+            if let Ok(demangled_string) = symbol.demangle(&cpp_options) {
+                if !options.template_args {
+                    return strip_template_args(&demangled_string);
+                }
                 return demangled_string;
             }
         }
     }
 
+    if let Some(symbol) = demangle_d::demangle(name) {
+        return symbol;
+    }
+
     if let Some(symbol) = demangle_ocaml::demangle(name) {
         return symbol;
     }
 
-    if name.starts_with('_') {
+    if options.strip_leading_underscore && name.starts_with('_') {
         return name.split_at(1).1.to_owned();
     }
 
     name.to_owned()
 }
+
+/// Demangles a function name from a PDB file. pdb-addr2line already returns
+/// demangled names for functions with debug info, so only the MSVC-mangled
+/// names of public symbols are demangled here. Like [`demangle_any`], this
+/// strips one leading underscore from all other names, unless disabled with
+/// [`DemangleOptions::strip_leading_underscore`].
+pub fn demangle_pdb_name(name: &str, options: &DemangleOptions) -> String {
+    if name.starts_with('?') {
+        return demangle_msvc(name, options);
+    }
+    let name = match name.strip_prefix('_') {
+        Some(stripped) if options.strip_leading_underscore => stripped,
+        _ => name,
+    };
+    if !options.template_args {
+        return strip_template_args(name);
+    }
+    name.to_owned()
+}
+
+fn demangle_msvc(name: &str, options: &DemangleOptions) -> String {
+    let mut flags = DemangleFlags::NO_ACCESS_SPECIFIERS
+        | DemangleFlags::NO_MEMBER_TYPE
+        | DemangleFlags::NO_MS_KEYWORDS
+        | DemangleFlags::NO_THISTYPE
+        | DemangleFlags::NO_CLASS_TYPE
+        | DemangleFlags::SPACE_AFTER_COMMA
+        | DemangleFlags::HUG_TYPE;
+    if !options.return_types {
+        flags |= DemangleFlags::NO_FUNCTION_RETURNS;
+    }
+    match msvc_demangler::demangle(name, flags) {
+        Ok(demangled) if !options.template_args => strip_template_args(&demangled),
+        Ok(demangled) => demangled,
+        Err(_) => name.to_string(),
+    }
+}
+
+/// Demangles the function names of the given frames in place.
+pub fn demangle_frames(frames: &mut [FrameDebugInfo], demangle: impl Fn(&str) -> String) {
+    for frame in frames {
+        if let Some(function) = &mut frame.function {
+            *function = demangle(function);
+        }
+    }
+}
+
+/// Removes all `<...>` template argument lists from a demangled C++ name,
+/// leaving `operator<`, `operator<<` etc. intact.
+fn strip_template_args(name: &str) -> String {
+    let mut result = String::with_capacity(name.len());
+    let mut depth = 0;
+    let mut rest = name;
+    while let Some(c) = rest.chars().next() {
+        if depth == 0 {
+            if let Some(after_operator) = rest.strip_prefix("operator") {
+                // An operator name is followed by its template argument list or
+                // its parameter list, so in `operator<<Foo>` the operator is `<`.
+                let op_len = ["<=>", "<<=", ">>=", "<<", ">>", "<=", ">=", "->", "<", ">"]
+                    .iter()
+                    .find(|op| match after_operator.strip_prefix(*op) {
+                        Some(after_op) => {
+                            !after_op.starts_with(|c: char| c.is_alphanumeric() || c == '_')
+                        }
+                        None => false,
+                    })
+                    .map_or(0, |op| op.len());
+                let len = "operator".len() + op_len;
+                result.push_str(&rest[..len]);
+                rest = &rest[len..];
+                continue;
+            }
+        }
+        match c {
+            '<' => depth += 1,
+            '>' if depth > 0 => depth -= 1,
+            _ if depth == 0 => result.push(c),
+            _ => {}
+        }
+        rest = &rest[c.len_utf8()..];
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::{demangle_any, demangle_pdb_name, strip_template_args, DemangleOptions};
+
+    #[test]
+    fn options() {
+        let default = DemangleOptions::default();
+        let itanium = "_Z3maxIiET_S0_S0_";
+        assert_eq!(demangle_any(itanium, &default), "max<int>(int, int)");
+        assert_eq!(
+            demangle_any(itanium, &default.return_types(true)),
+            "int max<int>(int, int)"
+        );
+        assert_eq!(
+            demangle_any(itanium, &default.template_args(false)),
+            "max(int, int)"
+        );
+        assert_eq!(
+            demangle_any(
+                "_ZN7mozilla5ArrayIiEltERKS1_",
+                &default.template_args(false)
+            ),
+            "mozilla::Array::operator<(mozilla::Array const&)"
+        );
+
+        let rust = "_ZN4core3ptr13drop_in_place17h1b9b3e0b9f5e7f4cE";
+        assert_eq!(demangle_any(rust, &default), "core::ptr::drop_in_place");
+        assert_eq!(
+            demangle_any(rust, &default.rust_hashes(true)),
+            "core::ptr::drop_in_place::h1b9b3e0b9f5e7f4c"
+        );
+
+        assert_eq!(demangle_any("_main", &default), "main");
+        assert_eq!(
+            demangle_any("_main", &default.strip_leading_underscore(false)),
+            "_main"
+        );
+        assert_eq!(demangle_any("std.debug.print", &default), "std.debug.print");
+    }
+
+    #[test]
+    fn template_args() {
+        assert_eq!(
+            strip_template_args("std::vector<std::pair<int, int> >::push_back(int&&)"),
+            "std::vector::push_back(int&&)"
+        );
+        assert_eq!(
+            strip_template_args("bool operator<<Foo>(Foo const&, Foo const&)"),
+            "bool operator<(Foo const&, Foo const&)"
+        );
+        assert_eq!(
+            strip_template_args("Stream::operator<<(int)"),
+            "Stream::operator<<(int)"
+        );
+        assert_eq!(
+            strip_template_args("Set<int>::operator<=>(Set<int> const&)"),
+            "Set::operator<=>(Set const&)"
+        );
+        assert_eq!(
+            strip_template_args("Ptr<T>::operator->()"),
+            "Ptr::operator->()"
+        );
+    }
+
+    #[test]
+    fn pdb_names() {
+        let default = DemangleOptions::default();
+        assert_eq!(demangle_pdb_name("_purecall", &default), "purecall");
+        assert_eq!(
+            demangle_pdb_name("_purecall", &default.strip_leading_underscore(false)),
+            "_purecall"
+        );
+        assert_eq!(
+            demangle_pdb_name("__scrt_common_main_seh()", &default),
+            "_scrt_common_main_seh()"
+        );
+        assert_eq!(
+            demangle_pdb_name("?Foo@Bar@@QEAAXH@Z", &default),
+            "Bar::Foo(int)"
+        );
+        assert_eq!(
+            demangle_pdb_name("std::vector<int>::size()", &default.template_args(false)),
+            "std::vector::size()"
+        );
+    }
+
+    #[test]
+    fn other_languages() {
+        let default = DemangleOptions::default();
+        assert_eq!(demangle_any("_$s4main3fooyyF", &default), "main.foo()");
+        assert_eq!(demangle_any("_D3foo3barFZv", &default), "foo.bar");
+        assert_eq!(
+            demangle_any("camlStdlib__array__map_154", &default),
+            "Stdlib.array.map_154"
+        );
+    }
+}
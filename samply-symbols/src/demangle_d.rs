@@ -0,0 +1,395 @@
+/// Demangles the qualified name of a D symbol, e.g. `_D3foo3barFZv` to `foo.bar`,
+/// or `_D3std5stdio__T7writelnTAyaZQnFNfQjZv` to
+/// `std.stdio.writeln!(immutable(char)[]).writeln`.
+///
+/// Template arguments are included in the result, parameter and return types
+/// are not. If any part of the name isn't understood, e.g. for nested functions
+/// or for template value arguments which aren't integers or booleans, `None` is
+/// returned and the mangled name is left alone.
+pub fn demangle(name: &str) -> Option<String> {
+    if name == "_Dmain" {
+        return Some("D main".to_string());
+    }
+
+    let mut demangler = Demangler::new(name.strip_prefix("_D")?.as_bytes());
+    let qualified_name = demangler.qualified_name()?;
+    if !demangler.is_at_end() {
+        // The type of the symbol, which is a function type for functions.
+        // Member functions have an `M` in front of it.
+        if demangler.peek() == Some(b'M') {
+            demangler.pos += 1;
+        }
+        demangler.type_()?;
+    }
+    if !demangler.is_at_end() {
+        return None;
+    }
+    Some(qualified_name)
+}
+
+/// The maximum nesting depth of types and back references.
+const MAX_DEPTH: usize = 64;
+
+struct Demangler<'a> {
+    s: &'a [u8],
+    pos: usize,
+    depth: usize,
+}
+
+impl<'a> Demangler<'a> {
+    fn new(s: &'a [u8]) -> Self {
+        Self {
+            s,
+            pos: 0,
+            depth: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.s.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn is_at_end(&self) -> bool {
+        self.pos == self.s.len()
+    }
+
+    fn starts_with(&self, prefix: &[u8]) -> bool {
+        self.s[self.pos..].starts_with(prefix)
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        let (number, end) = parse_number(self.s, self.pos)?;
+        self.pos = end;
+        Some(number)
+    }
+
+    /// Runs `f` with the position temporarily set to `pos`, for back references.
+    fn at<T>(&mut self, pos: usize, f: impl FnOnce(&mut Self) -> Option<T>) -> Option<T> {
+        if self.depth >= MAX_DEPTH {
+            return None;
+        }
+        let saved_pos = self.pos;
+        self.pos = pos;
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        self.pos = saved_pos;
+        result
+    }
+
+    /// The position which the back reference at the current position refers to,
+    /// and the position after the back reference.
+    fn back_reference(&self) -> Option<(usize, usize)> {
+        let (offset, end) = parse_back_reference(self.s, self.pos + 1)?;
+        Some((self.pos.checked_sub(offset)?, end))
+    }
+
+    /// A dot-separated sequence of identifiers and template instances.
+    fn qualified_name(&mut self) -> Option<String> {
+        let mut parts = Vec::new();
+        loop {
+            let part = match self.peek() {
+                Some(b'0'..=b'9') => self.lname()?,
+                Some(b'_') if self.starts_with(b"__T") || self.starts_with(b"__U") => {
+                    self.pos += 3;
+                    self.template_instance()?
+                }
+                Some(b'Q') => {
+                    // A back reference to an identifier earlier in the mangled name.
+                    // Back references to types are left for the caller.
+                    let (target, end) = self.back_reference()?;
+                    if !self.s.get(target)?.is_ascii_digit() {
+                        break;
+                    }
+                    let part = self.at(target, Self::lname)?;
+                    self.pos = end;
+                    part
+                }
+                _ => break,
+            };
+            parts.push(part);
+        }
+        if parts.is_empty() {
+            return None;
+        }
+        Some(parts.join("."))
+    }
+
+    /// A length-prefixed identifier. Before D 2.077, template instances were
+    /// mangled as length-prefixed identifiers, too.
+    fn lname(&mut self) -> Option<String> {
+        let len = self.number()?;
+        let identifier = self.s.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        match identifier
+            .strip_prefix(b"__T")
+            .or_else(|| identifier.strip_prefix(b"__U"))
+        {
+            Some(template_instance) => {
+                let mut demangler = Demangler::new(template_instance);
+                demangler.depth = self.depth + 1;
+                let name = demangler.template_instance()?;
+                if !demangler.is_at_end() {
+                    return None;
+                }
+                Some(name)
+            }
+            None => Some(std::str::from_utf8(identifier).ok()?.to_string()),
+        }
+    }
+
+    /// A template instance after its `__T` prefix: the template name, the
+    /// arguments and a `Z`.
+    fn template_instance(&mut self) -> Option<String> {
+        let name = match self.peek()? {
+            b'Q' => {
+                let (target, end) = self.back_reference()?;
+                let name = self.at(target, Self::lname)?;
+                self.pos = end;
+                name
+            }
+            _ => self.lname()?,
+        };
+        let mut args = Vec::new();
+        while self.peek()? != b'Z' {
+            args.push(self.template_arg()?);
+        }
+        self.pos += 1;
+        Some(format!("{name}!({})", args.join(", ")))
+    }
+
+    fn template_arg(&mut self) -> Option<String> {
+        // A marker for arguments which were deduced from a template parameter.
+        if self.peek() == Some(b'H') {
+            self.pos += 1;
+        }
+        match self.next()? {
+            b'T' => self.type_(),
+            b'V' => {
+                let value_type = self.type_()?;
+                match self.next()? {
+                    b'i' => {
+                        let value = self.number()?;
+                        match value_type.as_str() {
+                            "bool" if value <= 1 => Some((value == 1).to_string()),
+                            "bool" => None,
+                            _ => Some(value.to_string()),
+                        }
+                    }
+                    b'N' => Some(format!("-{}", self.number()?)),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn type_(&mut self) -> Option<String> {
+        if self.depth >= MAX_DEPTH {
+            return None;
+        }
+        self.depth += 1;
+        let result = self.type_inner();
+        self.depth -= 1;
+        result
+    }
+
+    fn type_inner(&mut self) -> Option<String> {
+        let c = self.next()?;
+        if let Some(basic_type) = basic_type(c) {
+            return Some(basic_type.to_string());
+        }
+        Some(match c {
+            b'A' => format!("{}[]", self.type_()?),
+            b'P' => format!("{}*", self.type_()?),
+            b'G' => {
+                let len = self.number()?;
+                format!("{}[{len}]", self.type_()?)
+            }
+            b'H' => {
+                let key = self.type_()?;
+                format!("{}[{key}]", self.type_()?)
+            }
+            b'x' => format!("const({})", self.type_()?),
+            b'y' => format!("immutable({})", self.type_()?),
+            b'O' => format!("shared({})", self.type_()?),
+            b'N' if self.peek() == Some(b'g') => {
+                self.pos += 1;
+                format!("inout({})", self.type_()?)
+            }
+            // Classes, structs, enums, interfaces and typedefs.
+            b'C' | b'S' | b'E' | b'I' | b'T' => self.qualified_name()?,
+            b'F' | b'U' | b'W' | b'V' | b'R' => self.function_type("function")?,
+            b'D' => {
+                if !matches!(self.next()?, b'F' | b'U' | b'W' | b'V' | b'R') {
+                    return None;
+                }
+                self.function_type("delegate")?
+            }
+            b'Q' => {
+                // A back reference to a type earlier in the mangled name.
+                self.pos -= 1;
+                let (target, end) = self.back_reference()?;
+                let type_ = self.at(target, Self::type_)?;
+                self.pos = end;
+                type_
+            }
+            _ => return None,
+        })
+    }
+
+    /// A function type after its calling convention: the attributes, the
+    /// parameters, a `Z` (or `X` or `Y` for variadic functions) and the return type.
+    fn function_type(&mut self, keyword: &str) -> Option<String> {
+        // Attributes, e.g. `Na` for pure or `Nf` for @safe.
+        while self.peek() == Some(b'N')
+            && matches!(
+                self.s.get(self.pos + 1),
+                Some(b'a'..=b'f' | b'i' | b'j' | b'l' | b'm')
+            )
+        {
+            self.pos += 2;
+        }
+        let mut params = Vec::new();
+        let variadic = loop {
+            match self.peek()? {
+                b'Z' => break "",
+                b'X' => break "...",
+                b'Y' => break ", ...",
+                _ => {}
+            }
+            let mut storage_classes = String::new();
+            loop {
+                let storage_class = match self.peek()? {
+                    b'I' => "in ",
+                    b'J' => "out ",
+                    b'K' => "ref ",
+                    b'L' => "lazy ",
+                    b'M' => "scope ",
+                    b'N' if self.s.get(self.pos + 1) == Some(&b'k') => {
+                        self.pos += 1;
+                        "return "
+                    }
+                    _ => break,
+                };
+                self.pos += 1;
+                storage_classes.push_str(storage_class);
+            }
+            params.push(format!("{storage_classes}{}", self.type_()?));
+        };
+        self.pos += 1;
+        let return_type = self.type_()?;
+        Some(format!(
+            "{return_type} {keyword}({}{variadic})",
+            params.join(", ")
+        ))
+    }
+}
+
+fn basic_type(c: u8) -> Option<&'static str> {
+    Some(match c {
+        b'v' => "void",
+        b'g' => "byte",
+        b'h' => "ubyte",
+        b's' => "short",
+        b't' => "ushort",
+        b'i' => "int",
+        b'k' => "uint",
+        b'l' => "long",
+        b'm' => "ulong",
+        b'f' => "float",
+        b'd' => "double",
+        b'e' => "real",
+        b'o' => "ifloat",
+        b'p' => "idouble",
+        b'j' => "ireal",
+        b'q' => "cfloat",
+        b'r' => "cdouble",
+        b'c' => "creal",
+        b'a' => "char",
+        b'u' => "wchar",
+        b'w' => "dchar",
+        b'b' => "bool",
+        b'n' => "typeof(null)",
+        _ => return None,
+    })
+}
+
+fn parse_number(s: &[u8], start: usize) -> Option<(usize, usize)> {
+    let digit_count = s
+        .get(start..)?
+        .iter()
+        .take_while(|c| c.is_ascii_digit())
+        .count();
+    if digit_count == 0 {
+        return None;
+    }
+    let end = start + digit_count;
+    let number = std::str::from_utf8(&s[start..end]).ok()?.parse().ok()?;
+    Some((number, end))
+}
+
+/// Back references are encoded in base 26, with upper case letters for all
+/// digits except the last one, which is a lower case letter.
+fn parse_back_reference(s: &[u8], start: usize) -> Option<(usize, usize)> {
+    let mut value: usize = 0;
+    for (i, c) in s.get(start..)?.iter().enumerate() {
+        match c {
+            b'A'..=b'Z' => value = value.checked_mul(26)? + usize::from(c - b'A'),
+            b'a'..=b'z' => {
+                value = value.checked_mul(26)? + usize::from(c - b'a');
+                return Some((value, start + i + 1));
+            }
+            _ => return None,
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::demangle;
+
+    #[test]
+    fn demangle_d() {
+        assert_eq!(demangle("main"), None);
+        assert_eq!(demangle("_D"), None);
+        assert_eq!(demangle("_Dmain"), Some("D main".to_string()));
+        assert_eq!(demangle("_D3foo3barFZv"), Some("foo.bar".to_string()));
+        assert_eq!(
+            demangle("_D3std5stdio4File5closeMFNeZv"),
+            Some("std.stdio.File.close".to_string())
+        );
+        assert_eq!(
+            demangle("_D3std5stdio__T7writelnTAyaZQnFNfQjZv"),
+            Some("std.stdio.writeln!(immutable(char)[]).writeln".to_string())
+        );
+        assert_eq!(
+            demangle("_D3std6format17__T6formatTaTiTiZ6formatFNaNfxAaiiZAya"),
+            Some("std.format.format!(char, int, int).format".to_string())
+        );
+        assert_eq!(
+            demangle("_D3foo__T3BarVii3Vbi1ZQnFKPxS3foo3BazZv"),
+            Some("foo.Bar!(3, true).Bar".to_string())
+        );
+        assert_eq!(demangle("_D3fooQe"), Some("foo.foo".to_string()));
+    }
+
+    #[test]
+    fn partly_understood() {
+        // Nested functions.
+        assert_eq!(demangle("_D3foo3barFZ3bazFZv"), None);
+        // String template value arguments.
+        assert_eq!(demangle("_D3foo__T3barVAyaa3_616263ZQnFZv"), None);
+        // Garbage after the type.
+        assert_eq!(demangle("_D3foo3barFZvX"), None);
+        // Unterminated template arguments.
+        assert_eq!(demangle("_D3foo__T3barTi"), None);
+    }
+}
@@ -0,0 +1,336 @@
+/// Demangles the names of common Swift symbols, e.g. `$s4main6MyTypeV6method1xySi_tF`
+/// to `main.MyType.method(x:)`.
+///
+/// This only understands a small subset of the Swift mangling grammar: functions,
+/// initializers and property accessors in module and nominal type contexts, whose
+/// parameter and result types are standard library types, nominal types or
+/// tuples, arrays and optionals of those. Types are not included in the result.
+/// If any part of the name isn't understood, `None` is returned and the mangled
+/// name is left alone.
+pub fn demangle(name: &str) -> Option<String> {
+    // On macOS, all symbol names have an extra leading underscore.
+    let mangled = ["$s", "_$s", "$S", "_$S", "$e", "_$e", "_T0", "__T0"]
+        .iter()
+        .find_map(|prefix| name.strip_prefix(prefix))?;
+    Demangler::new(mangled.as_bytes()).demangle_entity()
+}
+
+/// The maximum number of words that can be referenced by word substitutions.
+const MAX_WORDS: usize = 26;
+
+/// The kinds of entities which are demangled.
+enum EntityKind {
+    Function,
+    Initializer,
+    Getter,
+    Setter,
+    Modify,
+}
+
+impl EntityKind {
+    /// The kind of the entity whose mangled name ends with `rest`, if `rest` is
+    /// just the kind. Static functions end with an additional `Z`.
+    fn from_suffix(rest: &[u8]) -> Option<Self> {
+        Some(match rest {
+            b"F" | b"FZ" => EntityKind::Function,
+            b"fC" | b"fc" => EntityKind::Initializer,
+            b"vg" => EntityKind::Getter,
+            b"vs" => EntityKind::Setter,
+            b"vM" => EntityKind::Modify,
+            _ => return None,
+        })
+    }
+}
+
+struct Demangler<'a> {
+    s: &'a [u8],
+    pos: usize,
+    words: Vec<&'a [u8]>,
+}
+
+impl<'a> Demangler<'a> {
+    fn new(s: &'a [u8]) -> Self {
+        Self {
+            s,
+            pos: 0,
+            words: Vec::new(),
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.s.get(self.pos).copied()
+    }
+
+    fn rest(&self) -> &'a [u8] {
+        &self.s[self.pos..]
+    }
+
+    fn demangle_entity(mut self) -> Option<String> {
+        let mut context = vec![self.demangle_module()?];
+
+        // Nominal type contexts are an identifier followed by a kind character.
+        // The first identifier which isn't followed by a kind character is the
+        // name of the entity.
+        let name = loop {
+            let identifier = self.demangle_identifier()?;
+            match self.peek() {
+                Some(b'V' | b'C' | b'O' | b'P') => {
+                    self.pos += 1;
+                    context.push(identifier);
+                }
+                _ => break identifier,
+            }
+        };
+
+        let mut labels = Vec::new();
+        while let Some(c) = self.peek() {
+            match c {
+                b'_' => {
+                    self.pos += 1;
+                    labels.push("_".to_string());
+                }
+                b'0'..=b'9' => labels.push(self.demangle_identifier()?),
+                _ => break,
+            }
+        }
+        if matches!(self.peek(), Some(b'V' | b'C' | b'O' | b'P')) {
+            // What we took for labels was actually a type from another module.
+            return None;
+        }
+
+        // The entity's type, followed by its kind, which must be the end of the name.
+        let kind = loop {
+            if let Some(kind) = EntityKind::from_suffix(self.rest()) {
+                break kind;
+            }
+            self.skip_type_token()?;
+        };
+
+        let path = context.join(".");
+        let labels_string: String = labels.iter().map(|label| format!("{label}:")).collect();
+        Some(match kind {
+            EntityKind::Initializer => {
+                // Initializers have no name, so what we took for the name is the first label.
+                let labels_string: String = std::iter::once(&name)
+                    .chain(labels.iter())
+                    .map(|label| format!("{label}:"))
+                    .collect();
+                format!("{path}.init({labels_string})")
+            }
+            EntityKind::Function => format!("{path}.{name}({labels_string})"),
+            EntityKind::Getter => format!("{path}.{name}.getter"),
+            EntityKind::Setter => format!("{path}.{name}.setter"),
+            EntityKind::Modify => format!("{path}.{name}.modify"),
+        })
+    }
+
+    /// Skips one token of a type: a standard library type or a nominal type, a
+    /// substitution, or one of the operators which build tuples, functions,
+    /// optionals and generic types from the types before them.
+    fn skip_type_token(&mut self) -> Option<()> {
+        match self.peek()? {
+            // Empty list, list separator, tuple, bound generic type, function type,
+            // throws, inout and variadic.
+            b'y' | b'_' | b't' | b'G' | b'c' | b'K' | b'n' | b'd' => self.pos += 1,
+            // Standard library types and sugar, e.g. `Si` for `Int` or `Sg` for
+            // optionals. `So` is the module of imported Objective-C types.
+            b'S' => {
+                if !self.rest().get(1)?.is_ascii_alphabetic() {
+                    return None;
+                }
+                self.pos += 2;
+            }
+            b'A' => {
+                self.pos += 1;
+                self.skip_substitution_index()?;
+            }
+            b'0'..=b'9' => {
+                self.demangle_identifier()?;
+                if matches!(self.peek(), Some(b'V' | b'C' | b'O' | b'P')) {
+                    self.pos += 1;
+                }
+            }
+            _ => return None,
+        }
+        Some(())
+    }
+
+    /// Skips the index of a substitution which refers to an earlier type, e.g.
+    /// `C` in `AC`, `b` and `C` in `AbC`, or `3_` in `A3_`.
+    fn skip_substitution_index(&mut self) -> Option<()> {
+        loop {
+            while self.peek()?.is_ascii_digit() {
+                self.pos += 1;
+            }
+            let c = self.peek()?;
+            self.pos += 1;
+            match c {
+                b'a'..=b'z' => continue,
+                b'A'..=b'Z' | b'_' => return Some(()),
+                _ => return None,
+            }
+        }
+    }
+
+    fn demangle_module(&mut self) -> Option<String> {
+        if self.peek()? == b's' {
+            // The standard library module, "Swift".
+            self.pos += 1;
+            return Some("Swift".to_string());
+        }
+        self.demangle_identifier()
+    }
+
+    fn demangle_natural(&mut self) -> Option<usize> {
+        let digit_count = self
+            .rest()
+            .iter()
+            .take_while(|c| c.is_ascii_digit())
+            .count();
+        if digit_count == 0 {
+            return None;
+        }
+        let number = std::str::from_utf8(&self.rest()[..digit_count])
+            .ok()?
+            .parse()
+            .ok()?;
+        self.pos += digit_count;
+        Some(number)
+    }
+
+    /// Demangles an identifier, which is either a length-prefixed string, or a
+    /// `0`-prefixed sequence of word substitutions and length-prefixed strings.
+    fn demangle_identifier(&mut self) -> Option<String> {
+        let mut has_word_substitutions = false;
+        if self.peek()? == b'0' {
+            self.pos += 1;
+            if self.peek()? == b'0' {
+                // Punycode-encoded identifiers are not supported.
+                return None;
+            }
+            has_word_substitutions = true;
+        }
+
+        let mut identifier = Vec::new();
+        loop {
+            while has_word_substitutions && self.peek()?.is_ascii_alphabetic() {
+                let c = self.peek()?;
+                self.pos += 1;
+                let word_index = if c.is_ascii_lowercase() {
+                    usize::from(c - b'a')
+                } else {
+                    has_word_substitutions = false;
+                    usize::from(c - b'A')
+                };
+                identifier.extend_from_slice(self.words.get(word_index)?);
+            }
+            if self.peek() == Some(b'0') {
+                self.pos += 1;
+                break;
+            }
+            if !has_word_substitutions && !identifier.is_empty() {
+                break;
+            }
+            let len = self.demangle_natural()?;
+            let slice = self.rest().get(..len)?;
+            identifier.extend_from_slice(slice);
+            self.add_words(slice);
+            self.pos += len;
+            if !has_word_substitutions {
+                break;
+            }
+        }
+        String::from_utf8(identifier).ok()
+    }
+
+    /// Splits a literal identifier part into words which can be referenced by
+    /// later word substitutions, following the rules of the Swift mangler.
+    fn add_words(&mut self, slice: &'a [u8]) {
+        let is_word_start = |c: u8| !c.is_ascii_digit() && c != b'_' && c != 0;
+        let is_word_end = |c: u8, prev: u8| {
+            c == b'_' || c == 0 || (!prev.is_ascii_uppercase() && c.is_ascii_uppercase())
+        };
+        let mut word_start = None;
+        for i in 0..=slice.len() {
+            let c = slice.get(i).copied().unwrap_or(0);
+            if let Some(start) = word_start {
+                if is_word_end(c, slice[i - 1]) {
+                    if i - start >= 2 && self.words.len() < MAX_WORDS {
+                        self.words.push(&slice[start..i]);
+                    }
+                    word_start = None;
+                }
+            }
+            if word_start.is_none() && is_word_start(c) {
+                word_start = Some(i);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::demangle;
+
+    #[test]
+    fn demangle_swift() {
+        assert_eq!(demangle("main"), None);
+        assert_eq!(demangle("$s4main3fooyyF"), Some("main.foo()".to_string()));
+        assert_eq!(demangle("_$s4main3fooyyF"), Some("main.foo()".to_string()));
+        assert_eq!(
+            demangle("$s4main3foo1x1yySi_SitF"),
+            Some("main.foo(x:y:)".to_string())
+        );
+        assert_eq!(
+            demangle("$s4main6MyTypeV6methodyyF"),
+            Some("main.MyType.method()".to_string())
+        );
+        assert_eq!(
+            demangle("$s4main6MyTypeV5countSivg"),
+            Some("main.MyType.count.getter".to_string())
+        );
+        assert_eq!(
+            demangle("$s4main6MyTypeV5valueACSi_tcfC"),
+            Some("main.MyType.init(value:)".to_string())
+        );
+        // "main" and "MyClass" are split into the words "main", "My" and "Class",
+        // and the method name "myClassMethod" reuses "Class" via the word index "c".
+        assert_eq!(
+            demangle("$s4main7MyClassC02myc6Method0yyF"),
+            Some("main.MyClass.myClassMethod()".to_string())
+        );
+        assert_eq!(
+            demangle("$s4main3foo1xySaySiG_tF"),
+            Some("main.foo(x:)".to_string())
+        );
+        assert_eq!(
+            demangle("$s4main3foo1xySiSg_tF"),
+            Some("main.foo(x:)".to_string())
+        );
+        assert_eq!(
+            demangle("$s4main6MyTypeV6create4fromAC10Foundation4DataV_tKFZ"),
+            Some("main.MyType.create(from:)".to_string())
+        );
+        assert_eq!(demangle("$sSa4mainE3fooyyF"), None);
+    }
+
+    #[test]
+    fn not_swift() {
+        // The prefixes are checked first, so C and C++ names are never demangled as Swift.
+        assert_eq!(demangle("_main"), None);
+        assert_eq!(demangle("_ZN4main3fooEv"), None);
+        assert_eq!(demangle("$sfoo"), None);
+        assert_eq!(demangle("$s4main"), None);
+        // A type from another module after the name is not mistaken for argument labels.
+        assert_eq!(demangle("$s4main3foo10Foundation4DataVyF"), None);
+        // Entities other than functions and accessors are not supported.
+        assert_eq!(demangle("$s4main3fooSivp"), None);
+        // Punycode identifiers are not supported.
+        assert_eq!(demangle("$s4main00foo_yyF"), None);
+        // Names which are only partly understood are left alone, e.g. a local
+        // function inside a function, generic functions, and specializations.
+        assert_eq!(demangle("$s4main3fooyyF3barL_yyF"), None);
+        assert_eq!(demangle("$s4main3fooyyxlF"), None);
+        assert_eq!(demangle("$s4main3fooyyFTf4_n"), None);
+    }
+}
@@ -1,8 +1,8 @@
 use std::marker::PhantomData;
 
-use crate::path_mapper::PathMapper;
+use crate::path_mapper::{ExtraPathMapper, PathMapper};
 use crate::shared::FrameDebugInfo;
-use crate::{demangle, Error, SourceFilePath};
+use crate::{Error, SourceFilePath};
 use addr2line::fallible_iterator;
 use addr2line::gimli;
 use elsa::sync::FrozenVec;
@@ -11,10 +11,10 @@ use gimli::{EndianSlice, Reader, RunTimeEndian, SectionId};
 use object::read::ReadRef;
 use object::{CompressedFileRange, CompressionFormat};
 
-pub fn get_frames<R: Reader>(
+pub fn get_frames<R: Reader, E: ExtraPathMapper>(
     address: u64,
     context: Option<&addr2line::Context<R>>,
-    path_mapper: &mut PathMapper<()>,
+    path_mapper: &mut PathMapper<E>,
 ) -> Option<Vec<FrameDebugInfo>> {
     let frame_iter = context?.find_frames(address).skip_all_loads().ok()?;
     let frames: Vec<_> = frame_iter
@@ -29,14 +29,14 @@ pub fn get_frames<R: Reader>(
     }
 }
 
-pub fn convert_stack_frame<R: gimli::Reader>(
+pub fn convert_stack_frame<R: gimli::Reader, E: ExtraPathMapper>(
     frame: addr2line::Frame<R>,
-    path_mapper: &mut PathMapper<()>,
+    path_mapper: &mut PathMapper<E>,
 ) -> FrameDebugInfo {
     let function = match frame.function {
         Some(function_name) => {
             if let Ok(name) = function_name.raw_name() {
-                Some(demangle::demangle_any(&name))
+                Some(name.into_owned())
             } else {
                 None
             }
@@ -4,6 +4,7 @@ use object::{read::archive::ArchiveFile, File, FileKind, ReadRef};
 use yoke::{Yoke, Yokeable};
 
 use crate::{
+    demangle::{self, DemangleOptions},
     dwarf::{get_frames, Addr2lineContextData},
     macho,
     path_mapper::PathMapper,
@@ -32,7 +33,10 @@ where
         file,
         external_file_ref.arch.as_deref(),
     )?;
-    Ok(ExternalFileSymbolMap(Box::new(symbol_map)))
+    Ok(ExternalFileSymbolMap(
+        Box::new(symbol_map),
+        DemangleOptions::default(),
+    ))
 }
 
 struct ExternalFileMemberContext<'a> {
@@ -162,14 +166,22 @@ impl<F: FileContents + 'static> ExternalFileSymbolMapTrait for ExternalFileSymbo
 /// A symbol map for an external object file. You usually don't need this because
 /// you usually call `SymbolManager::lookup_external`.
 #[cfg(feature = "send_futures")]
-pub struct ExternalFileSymbolMap(Box<dyn ExternalFileSymbolMapTrait + Send + Sync>);
+pub struct ExternalFileSymbolMap(
+    Box<dyn ExternalFileSymbolMapTrait + Send + Sync>,
+    DemangleOptions,
+);
 
 /// A symbol map for an external object file. You usually don't need this because
 /// you usually call `SymbolManager::lookup_external`.
 #[cfg(not(feature = "send_futures"))]
-pub struct ExternalFileSymbolMap(Box<dyn ExternalFileSymbolMapTrait>);
+pub struct ExternalFileSymbolMap(Box<dyn ExternalFileSymbolMapTrait>, DemangleOptions);
 
 impl ExternalFileSymbolMap {
+    pub(crate) fn with_demangle_options(mut self, demangle_options: DemangleOptions) -> Self {
+        self.1 = demangle_options;
+        self
+    }
+
     /// The string which identifies this external file. This is usually an absolute
     /// path. (XXX does this contain the `archive.a(membername)` stuff or no?)
     pub fn name(&self) -> &str {
@@ -188,7 +200,9 @@ impl ExternalFileSymbolMap {
         &self,
         external_file_address: &ExternalFileAddressInFileRef,
     ) -> Option<Vec<FrameDebugInfo>> {
-        self.0.lookup(external_file_address)
+        let mut frames = self.0.lookup(external_file_address)?;
+        demangle::demangle_frames(&mut frames, |name| demangle::demangle_any(name, &self.1));
+        Some(frames)
     }
 }
 
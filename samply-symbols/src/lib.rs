@@ -239,7 +239,9 @@ mod chunked_read_buffer_manager;
 mod compact_symbol_table;
 mod debugid_util;
 mod demangle;
+mod demangle_d;
 mod demangle_ocaml;
+mod demangle_swift;
 mod dwarf;
 mod elf;
 mod error;
@@ -249,8 +251,10 @@ mod macho;
 mod mapped_path;
 mod path_mapper;
 mod shared;
+mod source_link;
 mod symbol_map;
 mod symbol_map_object;
+mod symbol_store;
 mod windows;
 
 pub use crate::binary_image::{BinaryImage, CodeByteReadingError};
@@ -259,7 +263,8 @@ pub use crate::breakpad::{
 };
 pub use crate::cache::{FileByteSource, FileContentsWithChunkedCaching};
 pub use crate::compact_symbol_table::CompactSymbolTable;
-pub use crate::debugid_util::{debug_id_for_object, DebugIdExt};
+pub use crate::debugid_util::{code_id_for_object, debug_id_for_object, DebugIdExt};
+pub use crate::demangle::DemangleOptions;
 pub use crate::error::Error;
 pub use crate::external_file::{load_external_file, ExternalFileSymbolMap};
 pub use crate::jitdump::debug_id_and_code_id_for_jitdump;
@@ -273,10 +278,12 @@ pub use crate::shared::{
     OptionallySendFuture, PeCodeId, SourceFilePath, SymbolInfo,
 };
 pub use crate::symbol_map::SymbolMap;
+pub use crate::symbol_store::{symbol_store_paths, SymbolStoreLayout};
 
 pub struct SymbolManager<'h, H: FileAndPathHelper<'h>> {
     helper: &'h H,
     cached_external_file: Mutex<Option<ExternalFileSymbolMap>>,
+    demangle_options: DemangleOptions,
 }
 
 impl<'h, H, F, FL> SymbolManager<'h, H>
@@ -290,6 +297,7 @@ where
         Self {
             helper,
             cached_external_file: Mutex::new(None),
+            demangle_options: DemangleOptions::default(),
         }
     }
 
@@ -298,14 +306,41 @@ where
         self.helper
     }
 
+    /// Sets the options which are used to demangle function names in the
+    /// symbol maps and external files returned by this `SymbolManager`.
+    pub fn set_demangle_options(&mut self, demangle_options: DemangleOptions) {
+        self.demangle_options = demangle_options;
+    }
+
     pub async fn load_source_file(
         &self,
         debug_file_location: &H::FL,
         source_file_path: &SourceFilePath,
     ) -> Result<String, Error> {
-        let source_file_location = debug_file_location
-            .location_for_source_file(source_file_path.raw_path())
-            .ok_or(Error::FileLocationRefusedSourceFileLocation)?;
+        let result = match debug_file_location.location_for_source_file(source_file_path.raw_path())
+        {
+            Some(location) => self.load_source_file_at_location(location).await,
+            None => Err(Error::FileLocationRefusedSourceFileLocation),
+        };
+        if result.is_ok() {
+            return result;
+        }
+
+        // Fall back to downloading the file, if we know a URL for it.
+        match source_file_path
+            .mapped_path()
+            .and_then(MappedPath::to_url)
+            .and_then(|url| debug_file_location.location_for_source_file_url(&url))
+        {
+            Some(location) => self.load_source_file_at_location(location).await,
+            None => result,
+        }
+    }
+
+    async fn load_source_file_at_location(
+        &self,
+        source_file_location: FL,
+    ) -> Result<String, Error> {
         let file_contents = self
             .helper
             .load_file(source_file_location.clone())
@@ -356,6 +391,7 @@ where
                 } => {
                     macho::load_symbol_map_for_dyld_cache(dyld_cache_path, dylib_path, self.helper)
                         .await
+                        .map(|symbol_map| symbol_map.with_demangle_options(self.demangle_options))
                 }
             };
 
@@ -389,7 +425,10 @@ where
         debug_file_location: &H::FL,
         external_file_ref: &ExternalFileRef,
     ) -> Result<ExternalFileSymbolMap, Error> {
-        external_file::load_external_file(self.helper, debug_file_location, external_file_ref).await
+        let external_file =
+            external_file::load_external_file(self.helper, debug_file_location, external_file_ref)
+                .await?;
+        Ok(external_file.with_demangle_options(self.demangle_options))
     }
 
     /// Resolve a debug info lookup for which `SymbolMap::lookup_*` returned a
@@ -561,7 +600,8 @@ where
                 dylib_path.to_owned(),
                 self.helper,
             )
-            .await;
+            .await
+            .map(|symbol_map| symbol_map.with_demangle_options(self.demangle_options));
             match (&multi_arch_disambiguator, symbol_map_res) {
                 (Some(MultiArchDisambiguator::DebugId(expected_debug_id)), Ok(symbol_map)) => {
                     if &symbol_map.debug_id() == expected_debug_id {
@@ -583,6 +623,17 @@ where
         &self,
         file_location: FL,
         multi_arch_disambiguator: Option<MultiArchDisambiguator>,
+    ) -> Result<SymbolMap<FL>, Error> {
+        let symbol_map = self
+            .load_symbol_map_from_location_impl(file_location, multi_arch_disambiguator)
+            .await?;
+        Ok(symbol_map.with_demangle_options(self.demangle_options))
+    }
+
+    async fn load_symbol_map_from_location_impl(
+        &self,
+        file_location: FL,
+        multi_arch_disambiguator: Option<MultiArchDisambiguator>,
     ) -> Result<SymbolMap<FL>, Error> {
         let file_contents = self
             .helper
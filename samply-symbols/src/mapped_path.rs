@@ -95,6 +95,29 @@ impl MappedPath {
         }
     }
 
+    /// The URL from which the raw contents of this file can be downloaded, if known.
+    ///
+    /// This is the inverse of [`MappedPath::from_url`].
+    pub fn to_url(&self) -> Option<String> {
+        match self {
+            MappedPath::Git { repo, path, rev } => {
+                let repo = repo.strip_prefix("github.com/")?;
+                Some(format!(
+                    "https://raw.githubusercontent.com/{repo}/{rev}/{path}"
+                ))
+            }
+            MappedPath::Hg { repo, path, rev } => {
+                Some(format!("https://{repo}/raw-file/{rev}/{path}"))
+            }
+            MappedPath::S3 {
+                bucket,
+                digest,
+                path,
+            } => Some(format!("https://{bucket}.s3.amazonaws.com/{digest}/{path}")),
+            MappedPath::Cargo { .. } => None,
+        }
+    }
+
     /// Create a short, display-friendly form of this path.
     pub fn display_path(&self) -> String {
         match self {
@@ -324,6 +347,23 @@ mod test {
         );
     }
 
+    #[test]
+    fn to_url_roundtrip() {
+        for url in [
+            "https://raw.githubusercontent.com/baldurk/renderdoc/v1.15/renderdoc/data/glsl/gl_texsample.h",
+            "https://hg.mozilla.org/mozilla-central/raw-file/1706d4d54ec68fae1280305b70a02cb24c16ff68/mozglue/baseprofiler/core/ProfilerBacktrace.cpp",
+            "https://gecko-generated-sources.s3.amazonaws.com/7a1db5dfd0061d0e/x86_64-pc-windows-msvc/release/build/swgl-59e3a0e09f56f4ea/out/brush_solid_DEBUG_OVERDRAW.h",
+        ] {
+            let mapped_path = MappedPath::from_url(url).unwrap();
+            assert_eq!(mapped_path.to_url().as_deref(), Some(url));
+        }
+        let gitiles_path = MappedPath::from_special_path_str(
+            "git:chromium.googlesource.com/chromium/src:content/gpu/gpu_main.cc:4dac2548d4812df2aa4a90ac1fc8912363f4d59c",
+        )
+        .unwrap();
+        assert_eq!(gitiles_path.to_url(), None);
+    }
+
     #[test]
     fn parse_s3_paths() {
         assert_eq!(
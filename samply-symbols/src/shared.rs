@@ -463,6 +463,18 @@ pub trait FileLocation: Clone + Display {
     /// those relative paths relative to the current working directory.
     fn location_for_source_file(&self, source_file_path: &str) -> Option<Self>;
 
+    /// Called on the location of a debug file in order to create a location for
+    /// a source file which can be downloaded from `url`. This is used if the source
+    /// file couldn't be loaded from the location returned by `location_for_source_file`.
+    ///
+    /// The URL comes from the mapped path of the source file, which can be derived
+    /// from the srcsrv stream or the SourceLink mappings in the debug file.
+    ///
+    /// The default implementation returns `None`, i.e. source files are not downloaded.
+    fn location_for_source_file_url(&self, _url: &str) -> Option<Self> {
+        None
+    }
+
     /// Called on the location of a Breakpad sym file, to get a location for its
     /// corresponding symindex file.
     fn location_for_breakpad_symindex(&self) -> Option<Self>;
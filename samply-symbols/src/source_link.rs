@@ -0,0 +1,151 @@
+use crate::path_mapper::ExtraPathMapper;
+use crate::MappedPath;
+
+/// The URL mappings from a [SourceLink](https://github.com/dotnet/designs/blob/main/accepted/2020/diagnostics/source-link.md)
+/// JSON document.
+///
+/// Such a document is embedded in Windows PDB files (as the `sourcelink` stream, when
+/// linking with `/SOURCELINK`) and in ELF / mach-O files (as the `.debug_sourcelink`
+/// section). It maps local source paths on the build machine to URLs, for example:
+///
+/// ```json
+/// {
+///   "documents": {
+///     "C:\\build\\project\\*": "https://raw.githubusercontent.com/org/project/75e5f7a9d4b1fbc5a4fdbaba4f87ea6c2d6dc1e4/*"
+///   }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLinkMappings {
+    /// (path pattern, URL pattern), sorted by descending pattern length so that
+    /// the most specific pattern is checked first.
+    documents: Vec<(SourceLinkPattern, String)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SourceLinkPattern {
+    /// Matches a single path exactly.
+    Exact(String),
+    /// Matches all paths starting with this prefix. The rest of the path is
+    /// substituted for the `*` in the URL pattern.
+    Prefix(String),
+}
+
+impl SourceLinkPattern {
+    fn len(&self) -> usize {
+        match self {
+            SourceLinkPattern::Exact(s) | SourceLinkPattern::Prefix(s) => s.len(),
+        }
+    }
+}
+
+impl SourceLinkMappings {
+    /// Parse a SourceLink JSON document. Returns `None` if the document is malformed
+    /// or doesn't contain any mappings.
+    pub fn parse(json: &[u8]) -> Option<Self> {
+        let value: serde_json::Value = serde_json::from_slice(json).ok()?;
+        let mut documents: Vec<_> = value
+            .get("documents")?
+            .as_object()?
+            .iter()
+            .filter_map(|(path_pattern, url_pattern)| {
+                let url_pattern = url_pattern.as_str()?;
+                let path_pattern = match path_pattern.strip_suffix('*') {
+                    Some(prefix) if url_pattern.contains('*') => {
+                        SourceLinkPattern::Prefix(prefix.to_ascii_lowercase())
+                    }
+                    Some(_) => return None,
+                    None => SourceLinkPattern::Exact(path_pattern.to_ascii_lowercase()),
+                };
+                Some((path_pattern, url_pattern.to_string()))
+            })
+            .collect();
+        if documents.is_empty() {
+            return None;
+        }
+        documents.sort_by_key(|(path_pattern, _)| std::cmp::Reverse(path_pattern.len()));
+        Some(Self { documents })
+    }
+
+    /// Returns the URL for the source file at `path`, if any of the mappings
+    /// match it. Paths are matched case-insensitively.
+    pub fn url_for_path(&self, path: &str) -> Option<String> {
+        let lowercase_path = path.to_ascii_lowercase();
+        self.documents
+            .iter()
+            .find_map(|(path_pattern, url_pattern)| match path_pattern {
+                SourceLinkPattern::Exact(exact) if *exact == lowercase_path => {
+                    Some(url_pattern.clone())
+                }
+                SourceLinkPattern::Prefix(prefix) if lowercase_path.starts_with(prefix) => {
+                    let rest = path[prefix.len()..].replace('\\', "/");
+                    Some(url_pattern.replacen('*', &rest, 1))
+                }
+                _ => None,
+            })
+    }
+}
+
+impl ExtraPathMapper for SourceLinkMappings {
+    fn map_path(&mut self, path: &str) -> Option<MappedPath> {
+        MappedPath::from_url(&self.url_for_path(path)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn url_for_path() {
+        let mappings = SourceLinkMappings::parse(
+            br#"{
+                "documents": {
+                    "C:\\build\\project\\*": "https://raw.githubusercontent.com/org/project/75e5f7a9/*",
+                    "C:\\build\\project\\third_party\\lib\\*": "https://raw.githubusercontent.com/org/lib/v1.2/*",
+                    "C:\\build\\generated.h": "https://example.com/generated.h"
+                }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            mappings.url_for_path(r"C:\build\project\src\main.cpp"),
+            Some("https://raw.githubusercontent.com/org/project/75e5f7a9/src/main.cpp".into())
+        );
+        assert_eq!(
+            mappings.url_for_path(r"c:\Build\Project\third_party\lib\Lib.cpp"),
+            Some("https://raw.githubusercontent.com/org/lib/v1.2/Lib.cpp".into())
+        );
+        assert_eq!(
+            mappings.url_for_path(r"C:\build\generated.h"),
+            Some("https://example.com/generated.h".into())
+        );
+        assert_eq!(mappings.url_for_path(r"C:\other\main.cpp"), None);
+    }
+
+    #[test]
+    fn map_path() {
+        let mut mappings = SourceLinkMappings::parse(
+            br#"{"documents": {"/home/user/project/*": "https://raw.githubusercontent.com/org/project/v1.0/*"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            mappings.map_path("/home/user/project/src/lib.rs"),
+            Some(MappedPath::Git {
+                repo: "github.com/org/project".into(),
+                path: "src/lib.rs".into(),
+                rev: "v1.0".into(),
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_documents() {
+        assert_eq!(SourceLinkMappings::parse(b"not json"), None);
+        assert_eq!(SourceLinkMappings::parse(br#"{"documents": {}}"#), None);
+        assert_eq!(
+            SourceLinkMappings::parse(br#"{"documents": {"C:\\src\\*": "https://example.com/"}}"#),
+            None
+        );
+    }
+}
@@ -3,11 +3,16 @@ use std::borrow::Cow;
 use debugid::DebugId;
 use yoke::{Yoke, Yokeable};
 
-use crate::{shared::AddressInfo, Error, FileLocation};
+use crate::{
+    demangle::{self, DemangleOptions},
+    shared::AddressInfo,
+    Error, FileLocation, FramesLookupResult,
+};
 
 pub struct SymbolMap<FL: FileLocation> {
     debug_file_location: FL,
     pub(crate) inner: Box<dyn SymbolMapTrait>,
+    demangle_options: DemangleOptions,
 }
 
 impl<FL: FileLocation> SymbolMap<FL> {
@@ -15,9 +20,15 @@ impl<FL: FileLocation> SymbolMap<FL> {
         Self {
             debug_file_location,
             inner,
+            demangle_options: DemangleOptions::default(),
         }
     }
 
+    pub(crate) fn with_demangle_options(mut self, demangle_options: DemangleOptions) -> Self {
+        self.demangle_options = demangle_options;
+        self
+    }
+
     pub fn debug_file_location(&self) -> &FL {
         &self.debug_file_location
     }
@@ -30,20 +41,66 @@ impl<FL: FileLocation> SymbolMap<FL> {
         self.inner.symbol_count()
     }
 
+    /// The options which are used to demangle the function names returned by
+    /// the lookup methods and by [`SymbolMap::iter_demangled_symbols`].
+    pub fn demangle_options(&self) -> &DemangleOptions {
+        &self.demangle_options
+    }
+
+    /// Iterates over all symbols, with their raw (possibly mangled) names.
     pub fn iter_symbols(&self) -> Box<dyn Iterator<Item = (u32, Cow<'_, str>)> + '_> {
         self.inner.iter_symbols()
     }
 
+    /// Iterates over all symbols, with their names demangled according to
+    /// this symbol map's [`DemangleOptions`].
+    pub fn iter_demangled_symbols(&self) -> Box<dyn Iterator<Item = (u32, Cow<'_, str>)> + '_> {
+        let demangle = match self.demangle_fn() {
+            Some(demangle) => demangle,
+            None => return self.inner.iter_symbols(),
+        };
+        let options = self.demangle_options;
+        Box::new(
+            self.inner
+                .iter_symbols()
+                .map(move |(address, name)| (address, Cow::Owned(demangle(&name, &options)))),
+        )
+    }
+
     pub fn lookup_relative_address(&self, address: u32) -> Option<AddressInfo> {
-        self.inner.lookup_relative_address(address)
+        let info = self.inner.lookup_relative_address(address)?;
+        Some(self.demangle_address_info(info))
     }
 
     pub fn lookup_svma(&self, svma: u64) -> Option<AddressInfo> {
-        self.inner.lookup_svma(svma)
+        let info = self.inner.lookup_svma(svma)?;
+        Some(self.demangle_address_info(info))
     }
 
     pub fn lookup_offset(&self, offset: u64) -> Option<AddressInfo> {
-        self.inner.lookup_offset(offset)
+        let info = self.inner.lookup_offset(offset)?;
+        Some(self.demangle_address_info(info))
+    }
+
+    fn demangle_address_info(&self, mut info: AddressInfo) -> AddressInfo {
+        if let Some(demangle) = self.demangle_fn() {
+            let options = &self.demangle_options;
+            info.symbol.name = demangle(&info.symbol.name, options);
+            if let FramesLookupResult::Available(frames) = &mut info.frames {
+                demangle::demangle_frames(frames, |name| demangle(name, options));
+            }
+        }
+        info
+    }
+
+    fn demangle_fn(&self) -> Option<fn(&str, &DemangleOptions) -> String> {
+        if self.inner.has_pdb_names() {
+            Some(demangle::demangle_pdb_name)
+        } else if self.inner.has_mangled_names() {
+            Some(demangle::demangle_any)
+        } else {
+            None
+        }
     }
 }
 
@@ -52,6 +109,17 @@ pub trait SymbolMapTrait {
 
     fn symbol_count(&self) -> usize;
 
+    /// Whether the names returned by this symbol map still need to be demangled.
+    fn has_mangled_names(&self) -> bool {
+        false
+    }
+
+    /// Whether the names returned by this symbol map come from a PDB file.
+    /// These are demangled with [`demangle::demangle_pdb_name`].
+    fn has_pdb_names(&self) -> bool {
+        false
+    }
+
     fn iter_symbols(&self) -> Box<dyn Iterator<Item = (u32, Cow<'_, str>)> + '_>;
 
     fn lookup_relative_address(&self, address: u32) -> Option<AddressInfo>;
@@ -113,6 +181,14 @@ impl<SMDO: SymbolMapDataOuterTrait> SymbolMapTrait for GenericSymbolMap<SMDO> {
         self.0.get().0.symbol_count()
     }
 
+    fn has_mangled_names(&self) -> bool {
+        self.0.get().0.has_mangled_names()
+    }
+
+    fn has_pdb_names(&self) -> bool {
+        self.0.get().0.has_pdb_names()
+    }
+
     fn iter_symbols(&self) -> Box<dyn Iterator<Item = (u32, Cow<'_, str>)> + '_> {
         self.0.get().0.iter_symbols()
     }
@@ -8,13 +8,13 @@ use object::{
 
 use crate::ExternalFileAddressRef;
 use crate::{
-    demangle,
     dwarf::{get_frames, Addr2lineContextData},
     path_mapper::PathMapper,
     shared::{
         relative_address_base, AddressInfo, ExternalFileAddressInFileRef, ExternalFileRef,
         SymbolInfo,
     },
+    source_link::SourceLinkMappings,
     symbol_map::{SymbolMapDataMidTrait, SymbolMapInnerWrapper, SymbolMapTrait},
     Error, FramesLookupResult,
 };
@@ -175,7 +175,7 @@ where
     entries: Vec<(u32, FullSymbolListEntry<'data, Symbol>)>,
     debug_id: DebugId,
     arch: Option<&'static str>,
-    path_mapper: Mutex<PathMapper<()>>,
+    path_mapper: Mutex<PathMapper<SourceLinkMappings>>,
     object_map: ObjectMap<'data>,
     context: Option<addr2line::Context<gimli::EndianSlice<'file, gimli::RunTimeEndian>>>,
     svma_file_ranges: Vec<SvmaFileRange>,
@@ -368,7 +368,13 @@ where
             .make_context(data, object_file, sup_data, sup_object_file)
             .ok();
 
-        let path_mapper = Mutex::new(PathMapper::new());
+        let source_link_mappings = object_file
+            .section_by_name(".debug_sourcelink")
+            .and_then(|section| section.uncompressed_data().ok())
+            .and_then(|data| SourceLinkMappings::parse(&data));
+        let path_mapper = Mutex::new(PathMapper::new_with_maybe_extra_mapper(
+            source_link_mappings,
+        ));
 
         let mut svma_file_ranges: Vec<SvmaFileRange> = object_file
             .segments()
@@ -430,6 +436,10 @@ where
             .count()
     }
 
+    fn has_mangled_names(&self) -> bool {
+        true
+    }
+
     fn iter_symbols(&self) -> Box<dyn Iterator<Item = (u32, Cow<'_, str>)> + '_> {
         Box::new(SymbolMapIter {
             inner: self.entries.iter(),
@@ -495,12 +505,11 @@ where
                 }
             };
 
-            let name = demangle::demangle_any(&name);
             Some(AddressInfo {
                 symbol: SymbolInfo {
                     address: *start_addr,
                     size: Some(function_size),
-                    name,
+                    name: name.into_owned(),
                 },
                 frames,
             })
@@ -0,0 +1,168 @@
+use debugid::DebugId;
+use object::read::pe::{PeFile32, PeFile64};
+use object::{FileKind, Object, ObjectSection};
+use pdb_addr2line::pdb::PDB;
+
+use crate::binary_image::pe_info;
+use crate::breakpad::is_breakpad_file;
+use crate::debugid_util::code_id_for_object;
+use crate::error::{Context, Error};
+use crate::shared::{CodeId, FileContents, FileContentsWrapper};
+use crate::windows::is_pdb_file;
+
+/// The directory layout of a symbol store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SymbolStoreLayout {
+    /// The layout used by Microsoft symbol servers: PDB files are stored at
+    /// `<name>/<GUIDAGE>/<name>`, and PE binaries are stored at
+    /// `<name>/<TIMESTAMPSIZE>/<name>`.
+    Symsrv,
+    /// The layout used by Breakpad symbol servers: `.sym` files are stored at
+    /// `<debug name>/<debug ID>/<debug name without .pdb>.sym`.
+    Breakpad,
+    /// The layout used by debuginfod servers: ELF files are stored at
+    /// `buildid/<build ID>/debuginfo` if they contain DWARF debug info, and at
+    /// `buildid/<build ID>/executable` if they contain code.
+    Debuginfod,
+}
+
+/// Computes the paths at which the given file should be stored in a symbol store
+/// with the given layout.
+///
+/// `file_name` is the file name of the file, e.g. `xul.pdb` or `xul.dll`. It
+/// becomes part of the path in the symsrv layout. The returned paths are relative
+/// to the root of the symbol store and use `/` as the separator.
+///
+/// An empty `Vec` is returned if the file doesn't belong in this kind of symbol
+/// store, for example for a PDB file in the debuginfod layout, or for an ELF file
+/// without a build ID.
+pub fn symbol_store_paths<F: FileContents>(
+    file_contents: F,
+    file_name: &str,
+    layout: SymbolStoreLayout,
+) -> Result<Vec<String>, Error> {
+    let file_contents = FileContentsWrapper::new(file_contents);
+    match layout {
+        SymbolStoreLayout::Symsrv => symsrv_paths(&file_contents, file_name),
+        SymbolStoreLayout::Breakpad => breakpad_paths(&file_contents, file_name),
+        SymbolStoreLayout::Debuginfod => debuginfod_paths(&file_contents),
+    }
+}
+
+fn symsrv_paths<F: FileContents>(
+    file_contents: &FileContentsWrapper<F>,
+    file_name: &str,
+) -> Result<Vec<String>, Error> {
+    if is_breakpad_file(file_contents) {
+        return Ok(Vec::new());
+    }
+
+    if is_pdb_file(file_contents) {
+        let mut pdb = PDB::open(file_contents)?;
+        let info = pdb.pdb_information().context("pdb_information")?;
+        let dbi = pdb.debug_information()?;
+        let age = dbi.age().unwrap_or(info.age);
+        let debug_id = DebugId::from_parts(info.guid, age);
+        return Ok(vec![format!(
+            "{file_name}/{}/{file_name}",
+            debug_id.breakpad()
+        )]);
+    }
+
+    let code_id = match FileKind::parse(file_contents) {
+        Ok(FileKind::Pe32) => {
+            let pe = PeFile32::parse(file_contents)
+                .map_err(|e| Error::ObjectParseError(FileKind::Pe32, e))?;
+            pe_info(&pe).code_id
+        }
+        Ok(FileKind::Pe64) => {
+            let pe = PeFile64::parse(file_contents)
+                .map_err(|e| Error::ObjectParseError(FileKind::Pe64, e))?;
+            pe_info(&pe).code_id
+        }
+        _ => return Ok(Vec::new()),
+    };
+    Ok(vec![format!("{file_name}/{code_id}/{file_name}")])
+}
+
+fn breakpad_paths<F: FileContents>(
+    file_contents: &FileContentsWrapper<F>,
+    file_name: &str,
+) -> Result<Vec<String>, Error> {
+    if !is_breakpad_file(file_contents) {
+        return Ok(Vec::new());
+    }
+
+    // The first line has the form "MODULE <os> <arch> <debug ID> <debug name>".
+    let len = file_contents.len().min(1024);
+    let first_bytes = file_contents
+        .read_bytes_at(0, len)
+        .map_err(|e| Error::HelperErrorDuringFileReading(file_name.to_string(), e))?;
+    let first_line = first_bytes
+        .split(|b| *b == b'\n')
+        .next()
+        .and_then(|line| std::str::from_utf8(line).ok())
+        .ok_or(Error::InvalidInputError(
+            "The MODULE line of the Breakpad file is not valid UTF-8",
+        ))?;
+    let mut fields = first_line.trim_end().splitn(5, ' ').skip(3);
+    let (debug_id, debug_name) = match (fields.next(), fields.next()) {
+        (Some(debug_id), Some(debug_name)) if !debug_name.is_empty() => (debug_id, debug_name),
+        _ => {
+            return Err(Error::InvalidInputError(
+                "The MODULE line of the Breakpad file is incomplete",
+            ))
+        }
+    };
+    // The debug name becomes a path component, so it must not be able to
+    // escape the symbol store directory.
+    if debug_name.contains(['/', '\\']) || debug_name == "." || debug_name == ".." {
+        return Err(Error::InvalidInputError(
+            "The debug name in the MODULE line of the Breakpad file is not a file name",
+        ));
+    }
+    let debug_id = DebugId::from_breakpad(debug_id)
+        .map_err(|_| Error::InvalidBreakpadId(debug_id.to_string()))?;
+    let sym_file_stem = match debug_name.len().checked_sub(4) {
+        Some(stem_len)
+            if debug_name.is_char_boundary(stem_len)
+                && debug_name[stem_len..].eq_ignore_ascii_case(".pdb") =>
+        {
+            &debug_name[..stem_len]
+        }
+        _ => debug_name,
+    };
+    Ok(vec![format!(
+        "{debug_name}/{}/{sym_file_stem}.sym",
+        debug_id.breakpad(),
+    )])
+}
+
+fn debuginfod_paths<F: FileContents>(
+    file_contents: &FileContentsWrapper<F>,
+) -> Result<Vec<String>, Error> {
+    let file_kind = match FileKind::parse(file_contents) {
+        Ok(file_kind @ (FileKind::Elf32 | FileKind::Elf64)) => file_kind,
+        _ => return Ok(Vec::new()),
+    };
+    let obj =
+        object::File::parse(file_contents).map_err(|e| Error::ObjectParseError(file_kind, e))?;
+    let build_id = match code_id_for_object(&obj) {
+        Some(CodeId::ElfBuildId(build_id)) => build_id,
+        _ => return Ok(Vec::new()),
+    };
+
+    let mut paths = Vec::new();
+    if obj.section_by_name(".debug_info").is_some() {
+        paths.push(format!("buildid/{build_id}/debuginfo"));
+    }
+    // Debug files created with `objcopy --only-keep-debug` still have a .text
+    // section header, but its contents are not present in the file.
+    let has_code = obj.sections().any(|section| {
+        section.kind() == object::SectionKind::Text && section.file_range().is_some()
+    });
+    if has_code {
+        paths.push(format!("buildid/{build_id}/executable"));
+    }
+    Ok(paths)
+}
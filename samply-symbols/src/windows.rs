@@ -5,12 +5,13 @@ use crate::shared::{
     AddressInfo, FileAndPathHelper, FileContents, FileContentsWrapper, FrameDebugInfo,
     FramesLookupResult, SymbolInfo,
 };
+use crate::source_link::SourceLinkMappings;
 use crate::symbol_map::{
     GenericSymbolMap, SymbolMap, SymbolMapDataMidTrait, SymbolMapDataOuterTrait,
     SymbolMapInnerWrapper, SymbolMapTrait,
 };
 use crate::symbol_map_object::{FunctionAddressesComputer, ObjectSymbolMapDataMid};
-use crate::{demangle, FileLocation, MappedPath, SourceFilePath};
+use crate::{FileLocation, MappedPath, SourceFilePath};
 use debugid::DebugId;
 use nom::bytes::complete::{tag, take_until1};
 use nom::combinator::eof;
@@ -146,19 +147,27 @@ struct PdbObject<'data, FC: FileContents + 'static> {
     context_data: pdb_addr2line::ContextPdbData<'data, 'data, &'data FileContentsWrapper<FC>>,
     debug_id: DebugId,
     srcsrv_stream: Option<Box<dyn Deref<Target = [u8]> + 'data>>,
+    sourcelink_stream: Option<Box<dyn Deref<Target = [u8]> + 'data>>,
 }
 
 impl<'data, FC: FileContents + 'static> SymbolMapDataMidTrait for PdbObject<'data, FC> {
     fn make_symbol_map_inner(&self) -> Result<SymbolMapInnerWrapper<'_>, Error> {
         let context = self.make_context()?;
 
-        let path_mapper = match &self.srcsrv_stream {
+        let srcsrv = match &self.srcsrv_stream {
             Some(srcsrv_stream) => Some(SrcSrvPathMapper::new(srcsrv::SrcSrvStream::parse(
                 srcsrv_stream.deref(),
             )?)),
             None => None,
         };
-        let path_mapper = PathMapper::new_with_maybe_extra_mapper(path_mapper);
+        let source_link = self
+            .sourcelink_stream
+            .as_ref()
+            .and_then(|sourcelink_stream| SourceLinkMappings::parse(sourcelink_stream.deref()));
+        let path_mapper = PathMapper::new_with_maybe_extra_mapper(Some(PdbPathMapper {
+            srcsrv,
+            source_link,
+        }));
 
         let symbol_map = PdbSymbolMapInner {
             context,
@@ -207,7 +216,7 @@ impl<'a, 's> PdbAddr2lineContextTrait for pdb_addr2line::Context<'a, 's> {
 struct PdbSymbolMapInner<'object> {
     context: Box<dyn PdbAddr2lineContextTrait + 'object>,
     debug_id: DebugId,
-    path_mapper: Mutex<PathMapper<SrcSrvPathMapper<'object>>>,
+    path_mapper: Mutex<PathMapper<PdbPathMapper<'object>>>,
 }
 
 impl<'object> SymbolMapTrait for PdbSymbolMapInner<'object> {
@@ -219,6 +228,10 @@ impl<'object> SymbolMapTrait for PdbSymbolMapInner<'object> {
         self.context.function_count()
     }
 
+    fn has_pdb_names(&self) -> bool {
+        true
+    }
+
     fn iter_symbols(&self) -> Box<dyn Iterator<Item = (u32, Cow<'_, str>)> + '_> {
         let iter = self.context.functions().map(|f| {
             let start_rva = f.start_rva;
@@ -234,7 +247,7 @@ impl<'object> SymbolMapTrait for PdbSymbolMapInner<'object> {
         let function_frames = self.context.find_frames(address).ok()??;
         let symbol_address = function_frames.start_rva;
         let symbol_name = match &function_frames.frames.last().unwrap().function {
-            Some(name) => demangle::demangle_any(name),
+            Some(name) => name.clone(),
             None => "unknown".to_string(),
         };
         let function_size = function_frames
@@ -304,6 +317,13 @@ impl<T: FileContents + 'static> SymbolMapDataOuterTrait for PdbSymbolData<T> {
             Err(e) => return Err(Error::PdbError("pdb.named_stream(srcsrv)", e)),
         };
 
+        // The stream which is written by the MSVC linker's /SOURCELINK option.
+        let sourcelink_stream = match pdb.named_stream(b"sourcelink") {
+            Ok(stream) => Some(box_stream(stream)),
+            Err(pdb::Error::StreamNameNotFound | pdb::Error::StreamNotFound(_)) => None,
+            Err(e) => return Err(Error::PdbError("pdb.named_stream(sourcelink)", e)),
+        };
+
         let context_data = pdb_addr2line::ContextPdbData::try_from_pdb(pdb)
             .context("ContextConstructionData::try_from_pdb")?;
 
@@ -311,6 +331,7 @@ impl<T: FileContents + 'static> SymbolMapDataOuterTrait for PdbSymbolData<T> {
             context_data,
             debug_id,
             srcsrv_stream,
+            sourcelink_stream,
         }))
     }
 }
@@ -327,6 +348,26 @@ where
     Ok(SymbolMap::new(debug_file_location, Box::new(symbol_map)))
 }
 
+/// Map raw file paths to special "permalink" paths, using the srcsrv stream or,
+/// if the srcsrv stream has no information about a path, the SourceLink mappings.
+struct PdbPathMapper<'a> {
+    srcsrv: Option<SrcSrvPathMapper<'a>>,
+    source_link: Option<SourceLinkMappings>,
+}
+
+impl<'a> ExtraPathMapper for PdbPathMapper<'a> {
+    fn map_path(&mut self, path: &str) -> Option<MappedPath> {
+        if let Some(mapped_path) = self
+            .srcsrv
+            .as_mut()
+            .and_then(|srcsrv| srcsrv.map_path(path))
+        {
+            return Some(mapped_path);
+        }
+        self.source_link.as_mut()?.map_path(path)
+    }
+}
+
 /// Map raw file paths to special "permalink" paths, using the srcsrv stream.
 /// This allows finding source code for applications that were not compiled on this
 /// machine, for example when using PDBs that were downloaded from a symbol server.
@@ -2,7 +2,7 @@ use samply_symbols::debugid::DebugId;
 use samply_symbols::{
     self, CandidatePathInfo, CompactSymbolTable, Error, FileAndPathHelper, FileAndPathHelperResult,
     FileLocation, LibraryInfo, MultiArchDisambiguator, OptionallySendFuture, SymbolManager,
-    SymbolMap,
+    SymbolMap, SymbolStoreLayout,
 };
 use std::fs::File;
 use std::io::{BufWriter, Read, Write};
@@ -569,3 +569,69 @@ fn compare_snapshot() {
 
     assert_eq!(output, expected);
 }
+
+#[test]
+fn symbol_store_paths() {
+    fn paths(path: &Path, layout: SymbolStoreLayout) -> Vec<String> {
+        let file_name = path.file_name().unwrap().to_str().unwrap();
+        let data = std::fs::read(path).unwrap();
+        samply_symbols::symbol_store_paths(data, file_name, layout).unwrap()
+    }
+
+    let pdb_path = fixtures_dir().join("win64-ci").join("WriteArgument.pdb");
+    assert_eq!(
+        paths(&pdb_path, SymbolStoreLayout::Symsrv),
+        vec!["WriteArgument.pdb/DF5016E17D4C30144C4C44205044422E1/WriteArgument.pdb"]
+    );
+    assert!(paths(&pdb_path, SymbolStoreLayout::Debuginfod).is_empty());
+
+    let exe_path = fixtures_dir().join("win64-ci").join("WriteArgument.exe");
+    assert_eq!(
+        paths(&exe_path, SymbolStoreLayout::Symsrv),
+        vec!["WriteArgument.exe/5EBA81A37000/WriteArgument.exe"]
+    );
+    assert!(paths(&exe_path, SymbolStoreLayout::Breakpad).is_empty());
+
+    let elf_path = fixtures_dir().join("other").join("example-linux");
+    assert_eq!(
+        paths(&elf_path, SymbolStoreLayout::Debuginfod),
+        vec![
+            "buildid/6c974ebe5232ee469d6b7847a670b2a956f8aede/debuginfo",
+            "buildid/6c974ebe5232ee469d6b7847a670b2a956f8aede/executable"
+        ]
+    );
+    assert!(paths(&elf_path, SymbolStoreLayout::Symsrv).is_empty());
+
+    let sym_file = b"MODULE windows x86_64 DF5016E17D4C30144C4C44205044422E1 WriteArgument.pdb\nINFO CODE_ID 5EBA81A37000 WriteArgument.exe\n";
+    assert_eq!(
+        samply_symbols::symbol_store_paths(
+            &sym_file[..],
+            "WriteArgument.sym",
+            SymbolStoreLayout::Breakpad
+        )
+        .unwrap(),
+        vec!["WriteArgument.pdb/DF5016E17D4C30144C4C44205044422E1/WriteArgument.sym"]
+    );
+
+    let sym_file = b"MODULE windows x86_64 DF5016E17D4C30144C4C44205044422E1 WriteArgument.PDB\n";
+    assert_eq!(
+        samply_symbols::symbol_store_paths(
+            &sym_file[..],
+            "WriteArgument.sym",
+            SymbolStoreLayout::Breakpad
+        )
+        .unwrap(),
+        vec!["WriteArgument.PDB/DF5016E17D4C30144C4C44205044422E1/WriteArgument.sym"]
+    );
+
+    for debug_name in ["../../evil.pdb", "dir/evil.pdb", "dir\\evil.pdb", ".."] {
+        let sym_file =
+            format!("MODULE windows x86_64 DF5016E17D4C30144C4C44205044422E1 {debug_name}\n");
+        assert!(samply_symbols::symbol_store_paths(
+            sym_file.as_bytes(),
+            "evil.sym",
+            SymbolStoreLayout::Breakpad
+        )
+        .is_err());
+    }
+}
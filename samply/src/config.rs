@@ -0,0 +1,513 @@
+use serde_derive::Deserialize;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// The name of the samply config file. A user-level config file is read from
+/// `samply/samply.toml` in the platform's config directory, e.g.
+/// `~/.config/samply/samply.toml` on Linux, and a project-level config file is
+/// read from the current directory or the closest ancestor directory which has
+/// one. Values from the project-level file take precedence, except for the
+/// settings which a project must not control, see [`Config::remove_untrusted_settings`].
+pub const CONFIG_FILE_NAME: &str = "samply.toml";
+
+/// The contents of a samply config file.
+///
+/// Command line arguments take precedence over the values in this file.
+#[derive(Deserialize, Default, Clone, Debug)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub server: ServerConfig,
+
+    /// Symbol locations which are used by all commands.
+    #[serde(default)]
+    pub symbols: SymbolsConfig,
+
+    /// Named sets of recording settings, selected with `samply record --preset <name>`.
+    #[serde(default)]
+    pub presets: HashMap<String, Preset>,
+
+    /// Whether to use all settings from project-level config files, including
+    /// the ones which are ignored by default because a checked-out repository
+    /// shouldn't control them. Only read from the user-level config file.
+    #[serde(default)]
+    pub trust_project_config: bool,
+}
+
+/// The `[server]` table of the config file.
+#[derive(Deserialize, Default, Clone, Debug)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ServerConfig {
+    /// A directory containing a static build of the profiler front-end. If
+    /// set, the front-end is served by the local server instead of being
+    /// loaded from profiler.firefox.com. Relative paths are resolved relative
+    /// to the directory of the config file.
+    pub ui_dir: Option<PathBuf>,
+}
+
+/// A `[presets.<name>]` table of the config file.
+#[derive(Deserialize, Default, Clone, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Preset {
+    pub event_source: Option<EventSource>,
+    /// Sampling rate, in Hz.
+    pub rate: Option<f64>,
+    /// Recording time limit, in seconds.
+    pub duration: Option<f64>,
+    pub iteration_count: Option<u32>,
+    pub merge_threads: Option<bool>,
+    pub fold_recursive_prefix: Option<bool>,
+    /// The output filename, with `{command}`, `{preset}` and `{timestamp}`
+    /// placeholders. See [`expand_output_template`].
+    pub output: Option<String>,
+    #[serde(default)]
+    pub symbols: SymbolsConfig,
+}
+
+/// The perf event which drives sampling on Linux.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum EventSource {
+    /// The hardware CPU cycles counter. Not available in some VMs.
+    CpuCycles,
+    /// The software CPU clock.
+    CpuClock,
+}
+
+/// Where the symbolication server looks for symbols, in addition to the
+/// default locations. Used for the `[symbols]` table and for the
+/// `[presets.<name>.symbols]` tables of the config file.
+#[derive(Deserialize, Default, Clone, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct SymbolsConfig {
+    /// Local symbol stores with the directory layout of a Windows symbol
+    /// server, i.e. `name.pdb/<id>/name.pdb`.
+    #[serde(default)]
+    pub symbol_dirs: Vec<PathBuf>,
+    /// Directories with breakpad symbol files.
+    #[serde(default)]
+    pub breakpad_dirs: Vec<PathBuf>,
+    /// Breakpad symbol servers, e.g. `https://symbols.mozilla.org/`.
+    #[serde(default)]
+    pub breakpad_servers: Vec<String>,
+    /// Windows symbol servers, e.g. `https://msdl.microsoft.com/download/symbols`.
+    #[serde(default)]
+    pub windows_symbol_servers: Vec<String>,
+    /// debuginfod servers, e.g. `https://debuginfod.elfutils.org/`.
+    #[serde(default)]
+    pub debuginfod_servers: Vec<String>,
+    /// Whether to use the debuginfod servers from the `DEBUGINFOD_URLS`
+    /// environment variable. Defaults to whether `SAMPLY_USE_DEBUGINFOD` is set.
+    pub use_debuginfod: Option<bool>,
+    /// The directory for files downloaded from symbol servers. Defaults to `~/sym`.
+    pub cache_dir: Option<PathBuf>,
+    /// Whether to download source files from the srcsrv and SourceLink URLs in
+    /// the debug info. Off by default, because these URLs can point to any host.
+    pub download_source_files: Option<bool>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ConfigError {
+    #[error("Could not read the config file {0:?}: {1}")]
+    Io(PathBuf, #[source] std::io::Error),
+
+    #[error("Could not parse the config file {0:?}: {1}")]
+    Parse(PathBuf, #[source] toml::de::Error),
+}
+
+impl Config {
+    /// The path of the user-level config file, if there is a config directory
+    /// on this platform.
+    pub fn user_config_path() -> Option<PathBuf> {
+        Some(dirs::config_dir()?.join("samply").join(CONFIG_FILE_NAME))
+    }
+
+    /// The path of the project-level config file: the samply.toml in the
+    /// current directory or in the closest ancestor directory which has one.
+    /// The search stops at the root of the repository, i.e. at a directory
+    /// which contains `.git`, and at the home directory.
+    pub fn project_config_path() -> Option<PathBuf> {
+        let current_dir = std::env::current_dir().ok()?;
+        find_project_config(&current_dir, dirs::home_dir().as_deref())
+    }
+
+    /// Loads the user-level and the project-level config files and merges
+    /// them. Missing files are not an error and result in the default config.
+    pub fn load() -> Result<Self, ConfigError> {
+        let mut config = match Self::user_config_path() {
+            Some(path) if path.exists() => Self::load_from_file(&path)?,
+            _ => Self::default(),
+        };
+        if let Some(path) = Self::project_config_path() {
+            if Some(&path) != Self::user_config_path().as_ref() {
+                let project_config = Self::load_from_file(&path)?;
+                if config.merge_project_config(project_config) {
+                    eprintln!(
+                        "Warning: Ignoring the ui-dir, symbol server, use-debuginfod, cache-dir and download-source-files settings in {path:?}."
+                    );
+                    eprintln!(
+                        "To use them, set trust-project-config = true in the user config file."
+                    );
+                }
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn load_from_file(path: &Path) -> Result<Self, ConfigError> {
+        let contents =
+            std::fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_owned(), e))?;
+        let mut config: Config =
+            toml::from_str(&contents).map_err(|e| ConfigError::Parse(path.to_owned(), e))?;
+        if let Some(config_dir) = path.parent() {
+            config.resolve_relative_paths(config_dir);
+        }
+        Ok(config)
+    }
+
+    /// Merges a project-level config file into the user-level config `self`.
+    /// Unless the user config trusts project config files, the settings which
+    /// are removed by [`Config::remove_untrusted_settings`] are ignored.
+    /// Returns whether any settings were ignored.
+    fn merge_project_config(&mut self, mut project_config: Config) -> bool {
+        let ignored_settings =
+            !self.trust_project_config && project_config.remove_untrusted_settings();
+        self.merge(project_config);
+        ignored_settings
+    }
+
+    /// Removes the settings which a config file from a checked-out repository
+    /// must not control: which front-end code is served, which hosts symbols
+    /// and source files are downloaded from, and where downloaded files are
+    /// written. Local symbol directories are kept. Returns whether any of these
+    /// settings were set.
+    fn remove_untrusted_settings(&mut self) -> bool {
+        let mut removed = self.server.ui_dir.take().is_some();
+        removed |= self.symbols.remove_untrusted_settings();
+        for preset in self.presets.values_mut() {
+            removed |= preset.symbols.remove_untrusted_settings();
+        }
+        removed
+    }
+
+    /// Overrides the values in `self` with the values which are set in `other`.
+    /// Presets are replaced as a whole if `other` has a preset with the same name.
+    /// The symbol locations from both are used, the ones from `other` first.
+    /// `trust_project_config` is kept, because only the user config sets it.
+    fn merge(&mut self, other: Config) {
+        if other.server.ui_dir.is_some() {
+            self.server.ui_dir = other.server.ui_dir;
+        }
+        self.presets.extend(other.presets);
+        let user_symbols = std::mem::take(&mut self.symbols);
+        self.symbols = other.symbols;
+        self.symbols.append(user_symbols);
+    }
+
+    fn resolve_relative_paths(&mut self, config_dir: &Path) {
+        if let Some(ui_dir) = &mut self.server.ui_dir {
+            resolve_relative_path(ui_dir, config_dir);
+        }
+        self.symbols.resolve_relative_paths(config_dir);
+        for preset in self.presets.values_mut() {
+            preset.symbols.resolve_relative_paths(config_dir);
+        }
+    }
+}
+
+impl SymbolsConfig {
+    /// Adds the locations from `other` after the ones in `self`, so that the
+    /// ones in `self` are searched first. Options which are already set in
+    /// `self` are kept.
+    pub fn append(&mut self, other: SymbolsConfig) {
+        self.symbol_dirs.extend(other.symbol_dirs);
+        self.breakpad_dirs.extend(other.breakpad_dirs);
+        self.breakpad_servers.extend(other.breakpad_servers);
+        self.windows_symbol_servers
+            .extend(other.windows_symbol_servers);
+        self.debuginfod_servers.extend(other.debuginfod_servers);
+        self.use_debuginfod = self.use_debuginfod.or(other.use_debuginfod);
+        self.cache_dir = self.cache_dir.take().or(other.cache_dir);
+        self.download_source_files = self.download_source_files.or(other.download_source_files);
+    }
+
+    /// See [`Config::remove_untrusted_settings`].
+    fn remove_untrusted_settings(&mut self) -> bool {
+        let removed = !self.breakpad_servers.is_empty()
+            || !self.windows_symbol_servers.is_empty()
+            || !self.debuginfod_servers.is_empty()
+            || self.use_debuginfod.is_some()
+            || self.cache_dir.is_some()
+            || self.download_source_files.is_some();
+        self.breakpad_servers.clear();
+        self.windows_symbol_servers.clear();
+        self.debuginfod_servers.clear();
+        self.use_debuginfod = None;
+        self.cache_dir = None;
+        self.download_source_files = None;
+        removed
+    }
+
+    fn resolve_relative_paths(&mut self, config_dir: &Path) {
+        for dir in &mut self.symbol_dirs {
+            resolve_relative_path(dir, config_dir);
+        }
+        for dir in &mut self.breakpad_dirs {
+            resolve_relative_path(dir, config_dir);
+        }
+        if let Some(cache_dir) = &mut self.cache_dir {
+            resolve_relative_path(cache_dir, config_dir);
+        }
+    }
+}
+
+/// Finds the samply.toml in `start_dir` or in the closest ancestor which has
+/// one, without looking past a repository root or `home_dir`.
+fn find_project_config(start_dir: &Path, home_dir: Option<&Path>) -> Option<PathBuf> {
+    for dir in start_dir.ancestors() {
+        let path = dir.join(CONFIG_FILE_NAME);
+        if path.is_file() {
+            return Some(path);
+        }
+        if dir.join(".git").exists() || Some(dir) == home_dir {
+            return None;
+        }
+    }
+    None
+}
+
+fn resolve_relative_path(path: &mut PathBuf, config_dir: &Path) {
+    if path.is_relative() {
+        *path = config_dir.join(&path);
+    }
+}
+
+/// Replaces the placeholders in an output filename template: `{command}` with
+/// the file name of the recorded command, `{preset}` with the name of the
+/// preset, and `{timestamp}` with the current time in seconds since the Unix
+/// epoch.
+pub fn expand_output_template(
+    template: &str,
+    command: &str,
+    preset: &str,
+    timestamp: u64,
+) -> PathBuf {
+    let command = Path::new(command)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    PathBuf::from(
+        template
+            .replace("{command}", &command)
+            .replace("{preset}", preset)
+            .replace("{timestamp}", &timestamp.to_string()),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_config() {
+        let config: Config = toml::from_str("").unwrap();
+        assert!(config.server.ui_dir.is_none());
+
+        let mut config: Config = toml::from_str(
+            r#"
+            [server]
+            ui-dir = "profiler/dist"
+            "#,
+        )
+        .unwrap();
+        config.resolve_relative_paths(Path::new("/home/user/.config/samply"));
+        assert_eq!(
+            config.server.ui_dir.as_deref(),
+            Some(Path::new("/home/user/.config/samply/profiler/dist"))
+        );
+
+        assert!(toml::from_str::<Config>("[server]\nui-directory = \"x\"").is_err());
+    }
+
+    #[test]
+    fn parse_presets() {
+        let mut config: Config = toml::from_str(
+            r#"
+            [presets.ci]
+            event-source = "cpu-clock"
+            rate = 4000
+            merge-threads = true
+            output = "profiles/{command}-{timestamp}.json"
+
+            [presets.ci.symbols]
+            breakpad-dirs = ["syms"]
+            breakpad-servers = ["https://symbols.mozilla.org/"]
+            "#,
+        )
+        .unwrap();
+        config.resolve_relative_paths(Path::new("/project"));
+        let preset = &config.presets["ci"];
+        assert_eq!(preset.event_source, Some(EventSource::CpuClock));
+        assert_eq!(preset.rate, Some(4000.0));
+        assert_eq!(preset.merge_threads, Some(true));
+        assert_eq!(preset.fold_recursive_prefix, None);
+        assert_eq!(
+            preset.symbols.breakpad_dirs,
+            vec![PathBuf::from("/project/syms")]
+        );
+        assert_eq!(
+            preset.symbols.breakpad_servers,
+            vec!["https://symbols.mozilla.org/".to_string()]
+        );
+
+        assert!(toml::from_str::<Config>("[presets.ci]\nevent-source = \"cycles\"").is_err());
+        assert!(toml::from_str::<Config>("[presets.ci]\nfrequency = 1000").is_err());
+    }
+
+    #[test]
+    fn project_config_search() {
+        let root = tempfile::tempdir().unwrap();
+        let repo = root.path().join("repo");
+        let subdir = repo.join("src").join("module");
+        std::fs::create_dir_all(&subdir).unwrap();
+        std::fs::write(root.path().join(CONFIG_FILE_NAME), "").unwrap();
+        assert_eq!(
+            find_project_config(&subdir, None),
+            Some(root.path().join(CONFIG_FILE_NAME))
+        );
+        assert_eq!(find_project_config(&subdir, Some(&repo)), None);
+
+        std::fs::create_dir(repo.join(".git")).unwrap();
+        assert_eq!(find_project_config(&subdir, None), None);
+
+        std::fs::write(repo.join(CONFIG_FILE_NAME), "").unwrap();
+        assert_eq!(
+            find_project_config(&subdir, None),
+            Some(repo.join(CONFIG_FILE_NAME))
+        );
+    }
+
+    #[test]
+    fn merge_configs() {
+        let mut config: Config = toml::from_str(
+            r#"
+            trust-project-config = true
+            [server]
+            ui-dir = "/user/profiler"
+            [symbols]
+            breakpad-servers = ["https://user/"]
+            cache-dir = "/user/sym"
+            [presets.fast]
+            rate = 10000
+            [presets.slow]
+            rate = 100
+            "#,
+        )
+        .unwrap();
+        let project_config: Config = toml::from_str(
+            r#"
+            [symbols]
+            breakpad-servers = ["https://project/"]
+            [presets.slow]
+            rate = 10
+            duration = 60
+            "#,
+        )
+        .unwrap();
+        assert!(!config.merge_project_config(project_config));
+        assert_eq!(
+            config.symbols.breakpad_servers,
+            vec!["https://project/".to_string(), "https://user/".to_string()]
+        );
+        assert_eq!(
+            config.symbols.cache_dir.as_deref(),
+            Some(Path::new("/user/sym"))
+        );
+        assert_eq!(
+            config.server.ui_dir.as_deref(),
+            Some(Path::new("/user/profiler"))
+        );
+        assert_eq!(config.presets["fast"].rate, Some(10000.0));
+        assert_eq!(config.presets["slow"].rate, Some(10.0));
+        assert_eq!(config.presets["slow"].duration, Some(60.0));
+    }
+
+    #[test]
+    fn untrusted_project_config() {
+        let user_config = r#"
+            [server]
+            ui-dir = "/user/profiler"
+            [symbols]
+            breakpad-servers = ["https://user/"]
+            cache-dir = "/user/sym"
+            download-source-files = false
+            "#;
+        let project_config = r#"
+            trust-project-config = true
+            [server]
+            ui-dir = "/tmp/evil-profiler"
+            [symbols]
+            breakpad-dirs = ["/project/syms"]
+            breakpad-servers = ["https://evil.example.com/"]
+            windows-symbol-servers = ["https://evil.example.com/"]
+            debuginfod-servers = ["https://evil.example.com/"]
+            use-debuginfod = true
+            cache-dir = "/home/user/.ssh"
+            download-source-files = true
+            [presets.ci]
+            rate = 4000
+            [presets.ci.symbols]
+            breakpad-servers = ["https://evil.example.com/"]
+            cache-dir = "/home/user/.ssh"
+            download-source-files = true
+            "#;
+
+        let mut config: Config = toml::from_str(user_config).unwrap();
+        assert!(config.merge_project_config(toml::from_str(project_config).unwrap()));
+        assert!(!config.trust_project_config);
+        assert_eq!(
+            config.server.ui_dir.as_deref(),
+            Some(Path::new("/user/profiler"))
+        );
+        assert_eq!(
+            config.symbols,
+            SymbolsConfig {
+                breakpad_dirs: vec![PathBuf::from("/project/syms")],
+                breakpad_servers: vec!["https://user/".to_string()],
+                cache_dir: Some(PathBuf::from("/user/sym")),
+                download_source_files: Some(false),
+                ..Default::default()
+            }
+        );
+        assert_eq!(config.presets["ci"].rate, Some(4000.0));
+        assert_eq!(config.presets["ci"].symbols, SymbolsConfig::default());
+
+        // Project config files without these settings are merged silently.
+        let mut config: Config = toml::from_str(user_config).unwrap();
+        assert!(!config.merge_project_config(toml::from_str("[presets.ci]\nrate = 1").unwrap()));
+
+        // If the user trusts project config files, all settings are used.
+        let mut config: Config =
+            toml::from_str(&format!("trust-project-config = true\n{user_config}")).unwrap();
+        assert!(!config.merge_project_config(toml::from_str(project_config).unwrap()));
+        assert_eq!(
+            config.server.ui_dir.as_deref(),
+            Some(Path::new("/tmp/evil-profiler"))
+        );
+        assert_eq!(config.symbols.download_source_files, Some(true));
+    }
+
+    #[test]
+    fn output_template() {
+        assert_eq!(
+            expand_output_template(
+                "profiles/{command}-{preset}-{timestamp}.json",
+                "./target/release/my-app",
+                "ci",
+                1700000000
+            ),
+            PathBuf::from("profiles/my-app-ci-1700000000.json")
+        );
+    }
+}
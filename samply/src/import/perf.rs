@@ -1,5 +1,5 @@
 use framehop::{Module, Unwinder};
-use fxprof_processed_profile::Profile;
+use fxprof_processed_profile::{ExtraProfileInfoSection, Profile};
 use linux_perf_data::linux_perf_event_reader;
 use linux_perf_data::{DsoInfo, DsoKey, PerfFileReader, PerfFileRecord};
 use linux_perf_event_reader::EventRecord;
@@ -98,8 +98,19 @@ where
         .to_owned();
     let linux_version = perf_file.os_release().unwrap();
     let attributes = perf_file.event_attributes();
-    if let Ok(Some(cmd_line)) = perf_file.cmdline() {
-        eprintln!("cmd line: {}", cmd_line.join(" "));
+    let cmd_line = perf_file
+        .cmdline()
+        .ok()
+        .flatten()
+        .map(|cmd_line| cmd_line.join(" "));
+    if let Some(cmd_line) = &cmd_line {
+        eprintln!("cmd line: {cmd_line}");
+    }
+    let mut extra_info = ExtraProfileInfoSection::new("perf.data");
+    extra_info.add_string_entry("Host", &host);
+    extra_info.add_string_entry("perf version", &perf_version);
+    if let Some(arch) = perf_file.arch().ok().flatten() {
+        extra_info.add_string_entry("Architecture", arch);
     }
     for event_name in attributes.iter().filter_map(|attr| attr.name()) {
         eprintln!("event {event_name}");
@@ -123,6 +134,16 @@ where
         fold_recursive_prefix,
     );
 
+    let profile = converter.profile_mut();
+    if let Some(cmd_line) = &cmd_line {
+        profile.set_arguments(cmd_line);
+    }
+    if let Some(linux_version) = linux_version {
+        profile.set_os_cpu(&format!("Linux {linux_version}"));
+    }
+    profile.set_platform("Linux");
+    profile.add_extra_info_section(extra_info);
+
     let mut last_timestamp = 0;
 
     while let Ok(Some(record)) = record_iter.next_record(&mut perf_file) {
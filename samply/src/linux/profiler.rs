@@ -6,9 +6,7 @@ use linux_perf_data::linux_perf_event_reader::{
 
 use std::collections::HashMap;
 use std::ffi::OsString;
-use std::fs::File;
-use std::io::BufWriter;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::ExitStatus;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
@@ -19,8 +17,12 @@ use super::perf_event::EventSource;
 use super::perf_group::{AttachMode, PerfGroup};
 use super::proc_maps;
 use super::process::SuspendedLaunchedProcess;
+use crate::config;
 use crate::linux_shared::{ConvertRegs, Converter, EventInterpretation, MmapRangeOrVec};
-use crate::server::{start_server_main, ServerProps};
+use crate::server::{start_server_main, ServedProfile, ServerProps};
+use crate::shared::live_profile::{LiveProfile, LiveProfileRecorder};
+use crate::shared::profile_file::save_profile;
+use crate::shared::recording_metadata::add_recording_metadata;
 use crate::ConversionArgs;
 
 #[cfg(target_arch = "x86_64")]
@@ -39,6 +41,8 @@ pub fn start_recording(
     server_props: Option<ServerProps>,
     conversion_args: &ConversionArgs,
     iteration_count: u32,
+    event_source: Option<config::EventSource>,
+    live_profile: Option<LiveProfileRecorder>,
 ) -> Result<ExitStatus, ()> {
     // Ignore SIGINT while the subcommand is running. The signal still reaches the process
     // under observation while we continue to record it. (ctrl+c will send the SIGINT signal
@@ -65,12 +69,20 @@ pub fn start_recording(
         crossbeam_channel::bounded(2);
 
     // Launch the observer thread. This thread will manage the perf events.
-    let output_file_copy = output_file.to_owned();
+    let served_live_profile = live_profile.as_ref().map(LiveProfileRecorder::live_profile);
+    let output = ProfileOutput {
+        path: output_file.to_owned(),
+        live_profile,
+    };
     let command_name_copy = command_name.to_string_lossy().to_string();
+    let command_line: Vec<OsString> = std::iter::once(command_name.clone())
+        .chain(command_args.iter().cloned())
+        .collect();
     let conversion_args = conversion_args.clone();
     let observer_thread = thread::spawn(move || {
         let product = command_name_copy;
         let mut converter = make_converter(interval, &product, &conversion_args);
+        add_recording_metadata(converter.profile_mut(), Some(&command_line));
 
         // Wait for the initial pid to profile.
         let SamplerRequest::StartProfilingAnotherProcess(pid, attach_mode) =
@@ -79,7 +91,7 @@ pub fn start_recording(
             };
 
         // Create the perf events, setting ENABLE_ON_EXEC.
-        let perf_group = init_profiler(interval, pid, attach_mode, &mut converter);
+        let perf_group = init_profiler(interval, pid, attach_mode, event_source, &mut converter);
 
         // Tell the main thread to tell the child process to begin executing.
         profile_another_pid_reply_sender.send(true).unwrap();
@@ -95,7 +107,7 @@ pub fn start_recording(
         run_profiler(
             perf_group,
             converter,
-            &output_file_copy,
+            output,
             time_limit,
             profile_another_pid_request_receiver,
             profile_another_pid_reply_sender,
@@ -105,6 +117,10 @@ pub fn start_recording(
 
     // We're on the main thread here and the observer thread has just been launched.
 
+    // For live profiles, the server runs during the recording.
+    let (server_props, server_thread) =
+        start_live_server(output_file, server_props, served_live_profile);
+
     // Request profiling of our process and wait for profiler initialization.
     profile_another_pid_request_sender
         .send(SamplerRequest::StartProfilingAnotherProcess(
@@ -183,12 +199,16 @@ pub fn start_recording(
         .expect("couldn't join observer thread");
 
     if let Some(server_props) = server_props {
-        start_server_main(output_file, server_props);
+        start_server_main(&[ServedProfile::from_path(output_file)], server_props);
+    }
+    if let Some(server_thread) = server_thread {
+        server_thread.join().expect("couldn't join server thread");
     }
 
     Ok(exit_status)
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn start_profiling_pid(
     output_file: &Path,
     pid: u32,
@@ -196,6 +216,8 @@ pub fn start_profiling_pid(
     interval: Duration,
     server_props: Option<ServerProps>,
     conversion_args: &ConversionArgs,
+    event_source: Option<config::EventSource>,
+    live_profile: Option<LiveProfileRecorder>,
 ) {
     // When the first Ctrl+C is received, stop recording.
     // The server launches after the recording finishes. On the second Ctrl+C, terminate the server.
@@ -214,18 +236,24 @@ pub fn start_profiling_pid(
     let (profile_another_pid_reply_sender, profile_another_pid_reply_receiver) =
         crossbeam_channel::bounded(2);
 
-    let output_file_copy = output_file.to_owned();
+    let served_live_profile = live_profile.as_ref().map(LiveProfileRecorder::live_profile);
+    let output = ProfileOutput {
+        path: output_file.to_owned(),
+        live_profile,
+    };
     let product = format!("PID {pid}");
     let conversion_args = conversion_args.clone();
     let observer_thread = thread::spawn({
         let stop = stop.clone();
         move || {
             let mut converter = make_converter(interval, &product, &conversion_args);
+            add_recording_metadata(converter.profile_mut(), None);
             let SamplerRequest::StartProfilingAnotherProcess(pid, attach_mode) =
                 profile_another_pid_request_receiver.recv().unwrap() else {
                     panic!("The first message should be a StartProfilingAnotherProcess")
                 };
-            let perf_group = init_profiler(interval, pid, attach_mode, &mut converter);
+            let perf_group =
+                init_profiler(interval, pid, attach_mode, event_source, &mut converter);
 
             // Tell the main thread that we are now executing.
             profile_another_pid_reply_sender.send(true).unwrap();
@@ -233,7 +261,7 @@ pub fn start_profiling_pid(
             run_profiler(
                 perf_group,
                 converter,
-                &output_file_copy,
+                output,
                 time_limit,
                 profile_another_pid_request_receiver,
                 profile_another_pid_reply_sender,
@@ -244,6 +272,10 @@ pub fn start_profiling_pid(
 
     // We're on the main thread here and the observer thread has just been launched.
 
+    // For live profiles, the server runs during the recording.
+    let (server_props, server_thread) =
+        start_live_server(output_file, server_props, served_live_profile);
+
     // Request profiling of our process and wait for profiler initialization.
     profile_another_pid_request_sender
         .send(SamplerRequest::StartProfilingAnotherProcess(
@@ -272,7 +304,28 @@ pub fn start_profiling_pid(
     stop.store(true, Ordering::SeqCst);
 
     if let Some(server_props) = server_props {
-        start_server_main(output_file, server_props);
+        start_server_main(&[ServedProfile::from_path(output_file)], server_props);
+    }
+    if let Some(server_thread) = server_thread {
+        server_thread.join().expect("couldn't join server thread");
+    }
+}
+
+/// If the profile should be served while it's being recorded, starts the
+/// server on a separate thread. Otherwise, returns the server props so that
+/// the server can be started after the recording.
+fn start_live_server(
+    output_file: &Path,
+    server_props: Option<ServerProps>,
+    live_profile: Option<Arc<LiveProfile>>,
+) -> (Option<ServerProps>, Option<thread::JoinHandle<()>>) {
+    match (server_props, live_profile) {
+        (Some(server_props), Some(live_profile)) => {
+            let profile = ServedProfile::live(output_file, live_profile);
+            let server_thread = thread::spawn(move || start_server_main(&[profile], server_props));
+            (None, Some(server_thread))
+        }
+        (server_props, _) => (server_props, None),
     }
 }
 
@@ -330,6 +383,7 @@ fn init_profiler(
     interval: Duration,
     pid: u32,
     attach_mode: AttachMode,
+    event_source: Option<config::EventSource>,
     converter: &mut Converter<
         framehop::UnwinderNative<MmapRangeOrVec, framehop::MayAllocateDuringUnwind>,
     >,
@@ -344,11 +398,17 @@ fn init_profiler(
     let stack_size = 32000;
     let regs_mask = ConvertRegsNative::regs_mask();
 
+    // Unless an event source was requested explicitly, start with the hardware
+    // CPU cycles event and fall back to the software CPU clock event below.
+    let first_event_source = match event_source {
+        Some(config::EventSource::CpuClock) => EventSource::SwCpuClock,
+        Some(config::EventSource::CpuCycles) | None => EventSource::HwCpuCycles,
+    };
     let perf = PerfGroup::open(
         pid,
         frequency,
         stack_size,
-        EventSource::HwCpuCycles,
+        first_event_source,
         regs_mask,
         attach_mode,
     );
@@ -369,6 +429,10 @@ fn init_profiler(
                     eprintln!();
                     std::process::exit(1);
                 }
+                _ if event_source.is_some() => {
+                    eprintln!("Failed to start profiling with the requested event source: {error}");
+                    std::process::exit(1);
+                }
                 _ => {
                     // Permission denied even though parania was probably not the reason.
                     // Another reason for the error could be the type of perf event:
@@ -472,12 +536,19 @@ enum SamplerRequest {
     StopProfilingOncePerfEventsExhausted,
 }
 
+/// Where the profile goes: the final profile is written to `path`, and, for
+/// live profiles, snapshots are shared with the server during the recording.
+struct ProfileOutput {
+    path: PathBuf,
+    live_profile: Option<LiveProfileRecorder>,
+}
+
 fn run_profiler(
     mut perf: PerfGroup,
     mut converter: Converter<
         framehop::UnwinderNative<MmapRangeOrVec, framehop::MayAllocateDuringUnwind>,
     >,
-    output_filename: &Path,
+    mut output: ProfileOutput,
     _time_limit: Option<Duration>,
     more_processes_request_receiver: Receiver<SamplerRequest>,
     more_processes_reply_sender: Sender<bool>,
@@ -541,6 +612,13 @@ fn run_profiler(
             break;
         }
 
+        if let Some(live_profile) = &mut output.live_profile {
+            live_profile.snapshot_if_due(|| {
+                let snapshot = converter.snapshot();
+                move || snapshot.into_profile()
+            });
+        }
+
         if wait {
             wait = false;
             perf.wait();
@@ -617,9 +695,11 @@ fn run_profiler(
 
     let profile = converter.finish();
 
-    let output_file = File::create(output_filename).unwrap();
-    let writer = BufWriter::new(output_file);
-    serde_json::to_writer(writer, &profile).expect("Couldn't write JSON");
+    save_profile(&profile, &output.path).expect("Couldn't write the profile");
+
+    if let Some(live_profile) = output.live_profile {
+        live_profile.finish(profile);
+    }
 }
 
 pub fn read_string_lossy<P: AsRef<Path>>(path: P) -> std::io::Result<String> {
@@ -34,12 +34,12 @@ use super::event_interpretation::EventInterpretation;
 use super::injected_jit_object::{correct_bad_perf_jit_so_file, jit_function_name};
 use super::kernel_symbols::{kernel_module_build_id, KernelSymbols};
 use super::mmap_range_or_vec::MmapRangeOrVec;
-use super::processes::Processes;
+use super::processes::{flush_process_sample_datas, Processes};
 use super::rss_stat::{RssStat, MM_ANONPAGES, MM_FILEPAGES, MM_SHMEMPAGES, MM_SWAPENTS};
 use super::svma_file_range::compute_vma_bias;
 
 use crate::shared::jit_category_manager::JitCategoryManager;
-use crate::shared::process_sample_data::RssStatMember;
+use crate::shared::process_sample_data::{ProcessSampleData, RssStatMember};
 use crate::shared::timestamp_converter::TimestampConverter;
 use crate::shared::types::{StackFrame, StackMode};
 use crate::shared::unresolved_samples::{
@@ -49,6 +49,29 @@ use crate::shared::utils::open_file_with_fallback;
 
 pub type BoxedProductNameGenerator = Box<dyn FnOnce(&str) -> String>;
 
+/// The data of a profile which is still being recorded, see [`Converter::snapshot`].
+pub struct ProfileSnapshot {
+    profile: Profile,
+    process_sample_datas: Vec<ProcessSampleData>,
+    unresolved_stacks: UnresolvedStacks,
+    event_names: Vec<String>,
+}
+
+impl ProfileSnapshot {
+    /// Adds the samples to the profile. This doesn't need the converter, so it
+    /// can run on a different thread than the recording.
+    pub fn into_profile(self) -> Profile {
+        let mut profile = self.profile;
+        flush_process_sample_datas(
+            self.process_sample_datas,
+            &mut profile,
+            &self.unresolved_stacks,
+            &self.event_names,
+        );
+        profile
+    }
+}
+
 /// See [`Converter::check_for_pe_mapping`].
 #[derive(Debug, Clone)]
 struct SuspectedPeMapping {
@@ -73,7 +96,7 @@ where
     extra_binary_artifact_dir: Option<PathBuf>,
     context_switch_handler: ContextSwitchHandler,
     unresolved_stacks: UnresolvedStacks,
-    off_cpu_weight_per_sample: i32,
+    off_cpu_weight_per_sample: i64,
     have_context_switches: bool,
     event_names: Vec<String>,
     kernel_symbols: Option<KernelSymbols>,
@@ -158,6 +181,27 @@ where
         }
     }
 
+    /// The profile which is being built, for example to set profile metadata.
+    pub fn profile_mut(&mut self) -> &mut Profile {
+        &mut self.profile
+    }
+
+    /// Takes a snapshot of the data recorded so far, without stopping the
+    /// recording. This is used to look at a profile while it's being recorded.
+    ///
+    /// This only copies the profile without its samples, and shares the
+    /// recorded samples and stacks, so it's cheap enough for the recording
+    /// thread. The expensive part, resolving the stacks of the samples, happens
+    /// in [`ProfileSnapshot::into_profile`].
+    pub fn snapshot(&self) -> ProfileSnapshot {
+        ProfileSnapshot {
+            profile: self.profile.clone(),
+            process_sample_datas: self.processes.snapshot_sample_datas(),
+            unresolved_stacks: self.unresolved_stacks.snapshot(),
+            event_names: self.event_names.clone(),
+        }
+    }
+
     pub fn finish(mut self) -> Profile {
         let mut profile = self.profile;
         self.processes.finish(
@@ -1203,7 +1247,7 @@ fn process_off_cpu_sample_group(
     thread_handle: ThreadHandle,
     cpu_delta_ns: u64,
     timestamp_converter: &TimestampConverter,
-    off_cpu_weight_per_sample: i32,
+    off_cpu_weight_per_sample: i64,
     off_cpu_stack: UnresolvedStackHandle,
     samples: &mut UnresolvedSamples,
 ) {
@@ -1232,7 +1276,7 @@ fn process_off_cpu_sample_group(
     if sample_count > 1 {
         // Emit a "rest sample" with a CPU delta of zero covering the rest of the paused range.
         let cpu_delta = CpuDelta::from_nanos(0);
-        let weight = i32::try_from(sample_count - 1).unwrap_or(0) * off_cpu_weight_per_sample;
+        let weight = i64::try_from(sample_count - 1).unwrap_or(0) * off_cpu_weight_per_sample;
         let profile_timestamp = timestamp_converter.convert_time(end_timestamp);
         samples.add_sample(
             thread_handle,
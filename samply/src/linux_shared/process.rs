@@ -130,6 +130,18 @@ where
         profile.set_process_end_time(self.profile_process, end_time);
     }
 
+    /// The sample data collected so far, for a snapshot of a profile which is
+    /// still being recorded. Unlike [`Process::finish`], this doesn't read
+    /// perf map files, so functions from perf maps stay unsymbolicated.
+    pub fn snapshot_sample_data(&self) -> ProcessSampleData {
+        ProcessSampleData::new(
+            self.unresolved_samples.clone(),
+            self.lib_mapping_ops.clone(),
+            self.jitdump_manager.snapshot_lib_mapping_ops(),
+            None,
+        )
+    }
+
     pub fn finish(
         mut self,
         profile: &mut Profile,
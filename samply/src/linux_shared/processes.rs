@@ -1,5 +1,5 @@
 use framehop::Unwinder;
-use fxprof_processed_profile::{CategoryColor, Profile, Timestamp};
+use fxprof_processed_profile::{CategoryColor, Profile, ThreadType, Timestamp};
 
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
@@ -86,6 +86,10 @@ where
                 if let Some(name) = name.as_deref() {
                     profile.set_thread_name(main_thread_handle, name);
                 }
+                if pid == 0 {
+                    // pid 0 is the kernel's idle task ("swapper").
+                    profile.set_thread_type(main_thread_handle, ThreadType::Idle);
+                }
                 let process = Process::new(
                     pid,
                     process_handle,
@@ -107,6 +111,9 @@ where
                 profile.add_process(&format!("<{pid}>"), pid as u32, fake_start_time);
             let main_thread_handle =
                 profile.add_thread(process_handle, pid as u32, fake_start_time, true);
+            if pid == 0 {
+                profile.set_thread_type(main_thread_handle, ThreadType::Idle);
+            }
             Process::new(
                 pid,
                 process_handle,
@@ -192,18 +199,47 @@ where
             }
         }
 
-        let user_category = profile.add_category("User", CategoryColor::Yellow).into();
-        let kernel_category = profile.add_category("Kernel", CategoryColor::Orange).into();
-        let mut stack_frame_scratch_buf = Vec::new();
-        for process_sample_data in self.process_sample_datas {
-            process_sample_data.flush_samples_to_profile(
-                profile,
-                user_category,
-                kernel_category,
-                &mut stack_frame_scratch_buf,
-                unresolved_stacks,
-                event_names,
-            );
+        flush_process_sample_datas(
+            self.process_sample_datas,
+            profile,
+            unresolved_stacks,
+            event_names,
+        );
+    }
+
+    /// Adds the samples collected so far to `profile`, which should be a copy
+    /// of the profile that is being built. Recording can continue afterwards.
+    /// The sample data of all processes, for a snapshot of a profile which is
+    /// still being recorded. The samples themselves are shared, not copied.
+    pub fn snapshot_sample_datas(&self) -> Vec<ProcessSampleData> {
+        let mut process_sample_datas = self.process_sample_datas.clone();
+        for process in self.processes_by_pid.values() {
+            let process_sample_data = process.snapshot_sample_data();
+            if !process_sample_data.is_empty() {
+                process_sample_datas.push(process_sample_data);
+            }
         }
+        process_sample_datas
+    }
+}
+
+pub fn flush_process_sample_datas(
+    process_sample_datas: Vec<ProcessSampleData>,
+    profile: &mut Profile,
+    unresolved_stacks: &UnresolvedStacks,
+    event_names: &[String],
+) {
+    let user_category = profile.add_category("User", CategoryColor::Yellow).into();
+    let kernel_category = profile.add_category("Kernel", CategoryColor::Orange).into();
+    let mut stack_frame_scratch_buf = Vec::new();
+    for process_sample_data in process_sample_datas {
+        process_sample_data.flush_samples_to_profile(
+            profile,
+            user_category,
+            kernel_category,
+            &mut stack_frame_scratch_buf,
+            unresolved_stacks,
+            event_names,
+        );
     }
 }
@@ -1,11 +1,8 @@
 use crossbeam_channel::unbounded;
-use serde_json::to_writer;
 
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::ffi::OsString;
-use std::fs::File;
-use std::io::BufWriter;
 use std::path::Path;
 use std::process::ExitStatus;
 use std::sync::atomic::AtomicBool;
@@ -17,9 +14,14 @@ use super::error::SamplingError;
 use super::process_launcher::{MachError, ReceivedStuff, TaskAccepter};
 use super::sampler::{Sampler, TaskInit};
 use super::time::get_monotonic_timestamp;
-use crate::server::{start_server_main, ServerProps};
+use crate::config::EventSource;
+use crate::server::{start_server_main, ServedProfile, ServerProps};
+use crate::shared::live_profile::LiveProfileRecorder;
+use crate::shared::profile_file::save_profile;
+use crate::shared::recording_metadata::add_recording_metadata;
 use crate::ConversionArgs;
 
+#[allow(clippy::too_many_arguments)]
 pub fn start_profiling_pid(
     _output_file: &Path,
     _pid: u32,
@@ -27,6 +29,8 @@ pub fn start_profiling_pid(
     _interval: Duration,
     _server_props: Option<ServerProps>,
     _conversion_args: &ConversionArgs,
+    _event_source: Option<EventSource>,
+    _live_profile: Option<LiveProfileRecorder>,
 ) {
     eprintln!("Profiling existing processes is currently not supported on macOS.");
     eprintln!("You can only profile processes which you launch via samply.");
@@ -43,7 +47,16 @@ pub fn start_recording(
     server_props: Option<ServerProps>,
     conversion_args: &ConversionArgs,
     iteration_count: u32,
+    event_source: Option<EventSource>,
+    live_profile: Option<LiveProfileRecorder>,
 ) -> Result<ExitStatus, MachError> {
+    if event_source.is_some() {
+        eprintln!("Choosing the event source is currently not supported on macOS, ignoring it.");
+    }
+    if live_profile.is_some() {
+        eprintln!("Live profiles are currently not supported on macOS.");
+        eprintln!("The profile will be served once the recording has finished.");
+    }
     let (task_sender, task_receiver) = unbounded();
     let command_name_copy = command_name.to_string_lossy().to_string();
     let conversion_args = conversion_args.clone();
@@ -157,7 +170,7 @@ pub fn start_recording(
     // or until the time limit has elapsed.
     let profile_result = sampler_thread.join().expect("couldn't join sampler thread");
 
-    let profile = match profile_result {
+    let mut profile = match profile_result {
         Ok(profile) => profile,
         Err(SamplingError::CouldNotObtainRootTask) => {
             eprintln!("Profiling failed: Could not obtain the root task.");
@@ -173,12 +186,15 @@ pub fn start_recording(
         }
     };
 
-    let file = File::create(output_file).unwrap();
-    let writer = BufWriter::new(file);
-    to_writer(writer, &profile).expect("Couldn't write JSON");
+    let command_line: Vec<OsString> = std::iter::once(command_name)
+        .chain(command_args.iter().cloned())
+        .collect();
+    add_recording_metadata(&mut profile, Some(&command_line));
+
+    save_profile(&profile, output_file).expect("Couldn't write the profile");
 
     if let Some(server_props) = server_props {
-        start_server_main(output_file, server_props);
+        start_server_main(&[ServedProfile::from_path(output_file)], server_props);
     }
 
     Ok(exit_status)
@@ -53,6 +53,7 @@ impl Sampler {
         let ConversionArgs {
             merge_threads,
             fold_recursive_prefix,
+            ..
         } = *conversion_args;
 
         Sampler {
@@ -4,16 +4,21 @@ mod mac;
 #[cfg(target_os = "linux")]
 mod linux;
 
+mod config;
 mod import;
 mod linux_shared;
+mod report;
 mod server;
 mod shared;
+mod symbolication_batcher;
+mod upload;
 
 use clap::{Args, Parser, Subcommand};
 use tempfile::NamedTempFile;
 
 use std::fs::File;
 use std::io::{BufReader, BufWriter};
+use std::net::IpAddr;
 use std::path::{Path, PathBuf};
 
 // To avoid warnings about unused declarations
@@ -25,7 +30,9 @@ use linux::profiler;
 #[cfg(target_os = "macos")]
 use mac::profiler;
 
-use server::{start_server_main, PortSelection, ServerProps};
+use config::{Config, SymbolsConfig};
+use server::{start_server_main, PortSelection, ServedProfile, ServerProps};
+use shared::profile_file::convert_compact_profile;
 
 #[derive(Debug, Parser)]
 #[command(
@@ -44,12 +51,34 @@ EXAMPLES:
     # On Linux, you can also profile existing processes by pid:
     samply record -p 12345 # Linux only
 
+    # View the profile while it is being recorded, with a new snapshot every 5 seconds:
+    samply record --live --snapshot-interval 5 ./yourservice # Linux only
+
+    # Serve a profile to other machines over HTTPS, with a fixed token and basic auth:
+    samply load --address 0.0.0.0 --token-file ~/.samply-token --basic-auth me:secret \
+        --tls-cert cert.pem --tls-key key.pem --ui-dir ~/profiler/dist prof.json
+
     # Alternative usage: Save profile to file for later viewing, and then load it.
     samply record --save-only -o prof.json -- ./yourcommand yourargs
     samply load prof.json # Opens in the browser and supplies symbols
 
+    # Save the profile in the compact binary format, which is converted to JSON when loading:
+    samply record --save-only -o prof.fxpc -- ./yourcommand yourargs
+    samply load prof.fxpc
+
     # Import perf.data files from Linux perf:
     samply load perf.data
+
+    # Print a report with the top functions and the call tree, e.g. over SSH:
+    samply report --top 10 --threshold 5 prof.json
+    samply record --report ./yourcommand yourargs
+
+    # Symbolicate a profile, upload it to an HTTP server with PUT, and print the profiler link:
+    samply upload --symbolicate --strip prof.json --to http://profiles.example.com/archive/
+
+    # Serve multiple profiles, or all profiles in a directory, with an index page:
+    samply load before.json after.json
+    samply load profiles/
 "#
 )]
 struct Opt {
@@ -65,13 +94,56 @@ enum Action {
     #[cfg(any(target_os = "macos", target_os = "linux"))]
     /// Record a profile and display it.
     Record(RecordArgs),
+
+    /// Print a report of a profile to the terminal, without opening the profiler UI.
+    Report(ReportArgs),
+
+    /// Upload a profile with an HTTP PUT request, and print a link which opens it in the profiler.
+    Upload(UploadArgs),
 }
 
 #[derive(Debug, Args)]
-struct LoadArgs {
-    /// Path to the file that should be loaded.
+struct ReportArgs {
+    /// Path to the profile file.
     file: PathBuf,
 
+    #[command(flatten)]
+    report_options: ReportOptions,
+
+    #[command(flatten)]
+    conversion_args: ConversionArgs,
+
+    #[command(flatten)]
+    symbol_args: SymbolArgs,
+}
+
+#[derive(Debug, Args)]
+struct UploadArgs {
+    /// Path to the profile file.
+    file: PathBuf,
+
+    /// The URL to upload the gzipped profile to. If it ends with a slash, the
+    /// file name of the profile is appended.
+    #[arg(long, value_name = "URL")]
+    to: String,
+
+    #[command(flatten)]
+    upload_options: UploadOptions,
+
+    #[command(flatten)]
+    conversion_args: ConversionArgs,
+
+    #[command(flatten)]
+    symbol_args: SymbolArgs,
+}
+
+#[derive(Debug, Args)]
+struct LoadArgs {
+    /// Paths to the files that should be loaded. For directories, all profile
+    /// files in the directory are loaded.
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+
     #[command(flatten)]
     conversion_args: ConversionArgs,
 
@@ -81,26 +153,63 @@ struct LoadArgs {
 
 #[cfg(any(target_os = "macos", target_os = "linux"))]
 #[derive(Debug, Args)]
+#[command(
+    mut_arg("top", |arg| arg.requires("report")),
+    mut_arg("threshold", |arg| arg.requires("report")),
+    mut_arg("thread", |arg| arg.requires("report")),
+    mut_arg("json", |arg| arg.requires("report"))
+)]
 struct RecordArgs {
     /// Do not run a local server after recording.
     #[arg(short, long)]
     save_only: bool,
 
-    /// Sampling rate, in Hz
-    #[arg(short, long, default_value = "1000")]
-    rate: f64,
+    /// Use the settings from this preset, which is defined in the `[presets]`
+    /// section of a samply.toml config file. Command line arguments take
+    /// precedence over the settings from the preset.
+    #[arg(long)]
+    preset: Option<String>,
+
+    /// Sampling rate, in Hz [default: 1000]
+    #[arg(short, long)]
+    rate: Option<f64>,
 
     /// Limit the recorded time to the specified number of seconds
     #[arg(short, long)]
     duration: Option<f64>,
 
-    /// Output filename.
-    #[arg(short, long, default_value = "profile.json")]
-    output: PathBuf,
+    /// Output filename. Files ending in .fxpc are written in the compact binary
+    /// profile format. [default: profile.json]
+    #[arg(short, long)]
+    output: Option<PathBuf>,
 
-    /// How many times to run the profiled command.
-    #[arg(long, default_value = "1")]
-    iteration_count: u32,
+    /// How many times to run the profiled command. [default: 1]
+    #[arg(long)]
+    iteration_count: Option<u32>,
+
+    /// The perf event which drives sampling (Linux only). By default, the CPU
+    /// cycles counter is used if it's available, and the CPU clock otherwise.
+    #[arg(long, value_enum)]
+    event_source: Option<config::EventSource>,
+
+    /// Run the local server while recording, and serve snapshots of the
+    /// profile recorded so far (Linux only).
+    #[arg(long, conflicts_with_all = ["save_only", "report"])]
+    live: bool,
+
+    /// With --live, take a snapshot every this many seconds. Loading the
+    /// profile in the profiler UI always takes a new snapshot. 0 means that
+    /// snapshots are only taken when the profile is loaded.
+    #[arg(long, default_value = "10", requires = "live")]
+    snapshot_interval: f64,
+
+    /// Print a report of the profile to the terminal after recording, instead
+    /// of opening the profiler UI. The profile is still saved.
+    #[arg(long, conflicts_with = "save_only")]
+    report: bool,
+
+    #[command(flatten)]
+    report_options: ReportOptions,
 
     #[command(flatten)]
     conversion_args: ConversionArgs,
@@ -135,78 +244,346 @@ struct ServerArgs {
     /// Print debugging output.
     #[arg(short, long)]
     verbose: bool,
+
+    /// Serve the profiler UI from this directory, which should contain a static
+    /// build of the Firefox Profiler front-end, instead of using
+    /// profiler.firefox.com. Can also be set with `ui-dir` in the `[server]`
+    /// section of the config file.
+    #[arg(long)]
+    ui_dir: Option<PathBuf>,
+
+    /// The address the local web server listens on. Use 0.0.0.0 to make it
+    /// reachable from other machines. The printed URLs use 127.0.0.1 in that case.
+    #[arg(long, default_value = "127.0.0.1")]
+    address: IpAddr,
+
+    /// The secret path prefix for the profile and symbol URLs. A random token
+    /// is generated if this is not specified.
+    #[arg(long, env = "SAMPLY_TOKEN", conflicts_with = "token_file")]
+    token: Option<String>,
+
+    /// Read the secret path prefix from this file.
+    #[arg(long)]
+    token_file: Option<PathBuf>,
+
+    /// Require HTTP basic auth with these credentials, in the form user:password.
+    /// Best combined with --ui-dir, because the profiler UI on a different
+    /// origin can't send the credentials.
+    #[arg(long, env = "SAMPLY_BASIC_AUTH", hide_env_values = true)]
+    basic_auth: Option<String>,
+
+    /// Serve over HTTPS, with the certificate chain from this PEM file.
+    #[arg(long, requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+
+    /// The PEM file with the private key for --tls-cert.
+    #[arg(long, requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+
+    #[command(flatten)]
+    symbol_args: SymbolArgs,
+}
+
+/// Symbol locations, in addition to the ones from the `[symbols]` section of
+/// the config file. The locations from the command line are searched first.
+#[derive(Debug, Args, Clone)]
+pub struct SymbolArgs {
+    /// Look up Windows symbol files in this local symbol store, which has the
+    /// directory layout of a symbol server. Can be specified multiple times.
+    #[arg(long = "symbol-dir", value_name = "DIR")]
+    symbol_dirs: Vec<PathBuf>,
+
+    /// Look up Breakpad symbol files in this directory. Can be specified
+    /// multiple times.
+    #[arg(long = "breakpad-dir", value_name = "DIR")]
+    breakpad_dirs: Vec<PathBuf>,
+
+    /// Download Breakpad symbol files from this server. Can be specified
+    /// multiple times.
+    #[arg(long = "breakpad-server", value_name = "URL")]
+    breakpad_servers: Vec<String>,
+
+    /// Download ELF binaries and debug info from this debuginfod server. Can be
+    /// specified multiple times.
+    #[arg(long = "debuginfod-server", value_name = "URL")]
+    debuginfod_servers: Vec<String>,
+
+    /// Download Windows binaries and symbol files from this symbol server, in
+    /// addition to the Microsoft symbol server. Can be specified multiple times.
+    #[arg(long = "windows-symbol-server", value_name = "URL")]
+    windows_symbol_servers: Vec<String>,
+
+    /// Also use the debuginfod servers from the DEBUGINFOD_URLS environment
+    /// variable. Can also be enabled with the SAMPLY_USE_DEBUGINFOD environment
+    /// variable.
+    #[arg(long)]
+    use_debuginfod: bool,
+
+    /// The directory for files downloaded from symbol servers. Defaults to ~/sym.
+    #[arg(long, value_name = "DIR")]
+    symbol_cache_dir: Option<PathBuf>,
+
+    /// Download source files from the srcsrv and SourceLink URLs in the debug
+    /// info, for the source view.
+    #[arg(long)]
+    download_source_files: bool,
+}
+
+#[derive(Debug, Args, Clone)]
+pub struct ReportOptions {
+    /// The number of functions in the report's function table.
+    #[arg(long, default_value = "20")]
+    top: usize,
+
+    /// Omit call tree nodes with fewer than this percentage of the samples
+    /// from the report.
+    #[arg(long, default_value = "1.0")]
+    threshold: f64,
+
+    /// Only include the threads in the report whose name contains this
+    /// string, or whose thread ID is this ID.
+    #[arg(long)]
+    thread: Option<String>,
+
+    /// Print the report as JSON.
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Debug, Args, Clone)]
+pub struct UploadOptions {
+    /// Symbolicate the profile before uploading it, so that it can be viewed
+    /// without access to the symbols. Inlined functions are not preserved:
+    /// each frame gets the name of its outermost function.
+    #[arg(long)]
+    symbolicate: bool,
+
+    /// Remove markers, counters and the local paths of libraries from the profile.
+    #[arg(long)]
+    strip: bool,
 }
 
 #[derive(Debug, Args, Clone)]
 pub struct ConversionArgs {
     /// Merge non-overlapping threads of the same name.
-    #[arg(long)]
+    #[arg(long, overrides_with = "no_merge_threads")]
     merge_threads: bool,
 
+    /// Don't merge threads, even if the preset merges them.
+    #[arg(long, overrides_with = "merge_threads")]
+    no_merge_threads: bool,
+
     /// Fold repeated frames at the base of the stack.
-    #[arg(long)]
+    #[arg(long, overrides_with = "no_fold_recursive_prefix")]
     fold_recursive_prefix: bool,
+
+    /// Don't fold repeated frames, even if the preset folds them.
+    #[arg(long, overrides_with = "fold_recursive_prefix")]
+    no_fold_recursive_prefix: bool,
+}
+
+impl ConversionArgs {
+    /// Uses the preset's settings for the flags which weren't given on the
+    /// command line.
+    fn with_preset(&self, preset: &config::Preset) -> Self {
+        let flag = |enabled: bool, disabled: bool, preset_value: Option<bool>| {
+            enabled || (!disabled && preset_value.unwrap_or(false))
+        };
+        Self {
+            merge_threads: flag(
+                self.merge_threads,
+                self.no_merge_threads,
+                preset.merge_threads,
+            ),
+            no_merge_threads: false,
+            fold_recursive_prefix: flag(
+                self.fold_recursive_prefix,
+                self.no_fold_recursive_prefix,
+                preset.fold_recursive_prefix,
+            ),
+            no_fold_recursive_prefix: false,
+        }
+    }
 }
 
 fn main() {
     let opt = Opt::parse();
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1)
+        }
+    };
     match opt.action {
         Action::Load(load_args) => {
-            let input_file = match File::open(&load_args.file) {
-                Ok(file) => file,
+            let mut profiles = Vec::new();
+            // The converted files are deleted when the temp files are dropped.
+            let mut converted_temp_files = Vec::new();
+            for file in expand_profile_paths(&load_args.files) {
+                let input_file = match File::open(&file) {
+                    Ok(input_file) => input_file,
+                    Err(err) => {
+                        eprintln!("Could not open file {:?}: {}", file, err);
+                        std::process::exit(1)
+                    }
+                };
+                let name = file.to_string_lossy().into_owned();
+                let path = match attempt_conversion(&file, &input_file, &load_args.conversion_args)
+                {
+                    Some(temp_file) => {
+                        let path = temp_file.path().to_owned();
+                        converted_temp_files.push(temp_file);
+                        path
+                    }
+                    None => file,
+                };
+                profiles.push(ServedProfile {
+                    path,
+                    name,
+                    live: None,
+                });
+            }
+            if profiles.is_empty() {
+                eprintln!("No profile files found in {:?}", load_args.files);
+                std::process::exit(1)
+            }
+            let server_props = load_args
+                .server_args
+                .server_props(&config, &SymbolsConfig::default());
+            start_server_main(&profiles, server_props);
+        }
+
+        Action::Report(report_args) => {
+            let file = &report_args.file;
+            let input_file = match File::open(file) {
+                Ok(input_file) => input_file,
+                Err(err) => {
+                    eprintln!("Could not open file {:?}: {}", file, err);
+                    std::process::exit(1)
+                }
+            };
+            // perf.data files are converted first. The converted file is deleted
+            // when the temp file is dropped.
+            let converted_temp_file =
+                attempt_conversion(file, &input_file, &report_args.conversion_args);
+            let path = match &converted_temp_file {
+                Some(temp_file) => temp_file.path(),
+                None => file.as_path(),
+            };
+            let symbols = report_args
+                .symbol_args
+                .symbols_config(&config, &SymbolsConfig::default());
+            print_report(path, &symbols, &report_args.report_options);
+        }
+
+        Action::Upload(upload_args) => {
+            let file = &upload_args.file;
+            let input_file = match File::open(file) {
+                Ok(input_file) => input_file,
                 Err(err) => {
-                    eprintln!("Could not open file {:?}: {}", load_args.file, err);
+                    eprintln!("Could not open file {:?}: {}", file, err);
                     std::process::exit(1)
                 }
             };
             let converted_temp_file =
-                attempt_conversion(&load_args.file, &input_file, &load_args.conversion_args);
-            let filename = match &converted_temp_file {
+                attempt_conversion(file, &input_file, &upload_args.conversion_args);
+            let path = match &converted_temp_file {
                 Some(temp_file) => temp_file.path(),
-                None => &load_args.file,
+                None => file.as_path(),
             };
-            start_server_main(filename, load_args.server_args.server_props());
+            let symbols = upload_args
+                .symbol_args
+                .symbols_config(&config, &SymbolsConfig::default());
+            match upload::upload_profile(
+                path,
+                &upload_args.to,
+                &symbols,
+                &upload_args.upload_options,
+            ) {
+                Ok(profiler_url) => println!("{profiler_url}"),
+                Err(err) => {
+                    eprintln!("{err}");
+                    std::process::exit(1)
+                }
+            }
         }
 
         #[cfg(any(target_os = "macos", target_os = "linux"))]
         Action::Record(record_args) => {
+            use shared::live_profile::LiveProfileRecorder;
             use std::time::Duration;
 
-            let server_props = if record_args.save_only {
+            let preset = record_args.preset(&config);
+            let server_props = if record_args.save_only || record_args.report {
                 None
             } else {
-                Some(record_args.server_args.server_props())
+                Some(
+                    record_args
+                        .server_args
+                        .server_props(&config, &preset.symbols),
+                )
             };
 
-            let time_limit = record_args.duration.map(Duration::from_secs_f64);
-            if record_args.rate <= 0.0 {
-                eprintln!(
-                    "Error: sampling rate must be greater than zero, got {}",
-                    record_args.rate
-                );
+            let time_limit = record_args
+                .duration
+                .or(preset.duration)
+                .map(Duration::from_secs_f64);
+            let rate = record_args.rate.or(preset.rate).unwrap_or(1000.0);
+            if rate <= 0.0 {
+                eprintln!("Error: sampling rate must be greater than zero, got {rate}");
                 std::process::exit(1);
             }
-            let interval = Duration::from_secs_f64(1.0 / record_args.rate);
+            let interval = Duration::from_secs_f64(1.0 / rate);
+            let output_file = record_args.output_file(&preset);
+            let iteration_count = record_args
+                .iteration_count
+                .or(preset.iteration_count)
+                .unwrap_or(1);
+            let event_source = record_args.event_source.or(preset.event_source);
+            let conversion_args = record_args.conversion_args.with_preset(&preset);
+
+            let live_profile = if record_args.live {
+                let snapshot_interval = if record_args.snapshot_interval > 0.0 {
+                    Some(Duration::from_secs_f64(record_args.snapshot_interval))
+                } else {
+                    None
+                };
+                Some(LiveProfileRecorder::new(snapshot_interval))
+            } else {
+                None
+            };
 
             if let Some(pid) = record_args.pid {
                 profiler::start_profiling_pid(
-                    &record_args.output,
+                    &output_file,
                     pid,
                     time_limit,
                     interval,
                     server_props,
-                    &record_args.conversion_args,
+                    &conversion_args,
+                    event_source,
+                    live_profile,
                 );
+                if record_args.report {
+                    let symbols = record_args
+                        .server_args
+                        .symbol_args
+                        .symbols_config(&config, &preset.symbols);
+                    print_report(&output_file, &symbols, &record_args.report_options);
+                }
             } else {
                 let exit_status = match profiler::start_recording(
-                    &record_args.output,
+                    &output_file,
                     record_args.command[0].clone(),
                     &record_args.command[1..],
                     time_limit,
                     interval,
                     server_props,
-                    &record_args.conversion_args,
-                    record_args.iteration_count,
+                    &conversion_args,
+                    iteration_count,
+                    event_source,
+                    live_profile,
                 ) {
                     Ok(exit_status) => exit_status,
                     Err(err) => {
@@ -214,14 +591,80 @@ fn main() {
                         std::process::exit(1);
                     }
                 };
+                if record_args.report {
+                    let symbols = record_args
+                        .server_args
+                        .symbol_args
+                        .symbols_config(&config, &preset.symbols);
+                    print_report(&output_file, &symbols, &record_args.report_options);
+                }
                 std::process::exit(exit_status.code().unwrap_or(0));
             }
         }
     }
 }
 
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+impl RecordArgs {
+    /// The preset selected with --preset, or an empty preset if there is none.
+    fn preset(&self, config: &Config) -> config::Preset {
+        let Some(name) = &self.preset else {
+            return config::Preset::default();
+        };
+        match config.presets.get(name) {
+            Some(preset) => preset.clone(),
+            None => {
+                eprintln!("Unknown preset {name:?}.");
+                let mut names: Vec<&String> = config.presets.keys().collect();
+                if names.is_empty() {
+                    eprintln!("No presets are defined. Presets are defined in the [presets] section of a samply.toml file.");
+                } else {
+                    names.sort();
+                    eprintln!("Available presets: {names:?}");
+                }
+                std::process::exit(1)
+            }
+        }
+    }
+
+    /// The output file from the command line, or from the preset's output
+    /// template. The parent directory is created for template output files.
+    fn output_file(&self, preset: &config::Preset) -> PathBuf {
+        if let Some(output) = &self.output {
+            return output.clone();
+        }
+        let Some(template) = &preset.output else {
+            return PathBuf::from("profile.json");
+        };
+        let command = match (self.command.first(), self.pid) {
+            (Some(command_name), _) => command_name.to_string_lossy().into_owned(),
+            (None, Some(pid)) => format!("pid-{pid}"),
+            (None, None) => String::new(),
+        };
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let output_file = config::expand_output_template(
+            template,
+            &command,
+            self.preset.as_deref().unwrap_or_default(),
+            timestamp,
+        );
+        if let Some(parent) = output_file.parent() {
+            if let Err(err) = std::fs::create_dir_all(parent) {
+                eprintln!("Could not create the output directory {parent:?}: {err}");
+                std::process::exit(1)
+            }
+        }
+        output_file
+    }
+}
+
 impl ServerArgs {
-    pub fn server_props(&self) -> ServerProps {
+    /// `preset_symbols` are the symbol locations from the recording preset,
+    /// which are searched after the ones from the command line and before the
+    /// ones from the `[symbols]` section of the config file.
+    pub fn server_props(&self, config: &Config, preset_symbols: &SymbolsConfig) -> ServerProps {
         let open_in_browser = !self.no_open;
         let port_selection = match PortSelection::try_from_str(&self.port) {
             Ok(p) => p,
@@ -233,12 +676,137 @@ impl ServerArgs {
                 std::process::exit(1)
             }
         };
+        let ui_dir = self.ui_dir.clone().or_else(|| config.server.ui_dir.clone());
+        if let Some(ui_dir) = &ui_dir {
+            if !ui_dir.join("index.html").is_file() {
+                eprintln!(
+                    "The UI directory {ui_dir:?} does not contain an index.html file. It needs to contain a build of the Firefox Profiler front-end."
+                );
+                std::process::exit(1)
+            }
+        }
+        let token = match (&self.token, &self.token_file) {
+            (Some(token), _) => Some(token.clone()),
+            (None, Some(token_file)) => match std::fs::read_to_string(token_file) {
+                Ok(token) => Some(token.trim().to_string()),
+                Err(e) => {
+                    eprintln!("Could not read the token file {token_file:?}: {e}");
+                    std::process::exit(1)
+                }
+            },
+            (None, None) => None,
+        };
+        if let Some(token) = &token {
+            if !is_valid_token(token) {
+                eprintln!("The token must be non-empty and only contain ASCII letters, digits, '-' and '_'.");
+                std::process::exit(1)
+            }
+        }
+        if let Some(basic_auth) = &self.basic_auth {
+            if !basic_auth.contains(':') {
+                eprintln!("The basic auth credentials must be in the form user:password.");
+                std::process::exit(1)
+            }
+        }
+        let tls_cert_and_key = match (&self.tls_cert, &self.tls_key) {
+            (Some(cert), Some(key)) => Some((cert.clone(), key.clone())),
+            _ => None,
+        };
+        let symbols = self.symbol_args.symbols_config(config, preset_symbols);
         ServerProps {
+            address: self.address,
             port_selection,
             verbose: self.verbose,
             open_in_browser,
+            ui_dir,
+            token,
+            basic_auth: self.basic_auth.clone(),
+            tls_cert_and_key,
+            symbols,
+        }
+    }
+}
+
+impl SymbolArgs {
+    /// The symbol locations from the command line, followed by the ones from
+    /// the recording preset and the ones from the `[symbols]` section of the
+    /// config file.
+    pub fn symbols_config(&self, config: &Config, preset_symbols: &SymbolsConfig) -> SymbolsConfig {
+        let mut symbols = SymbolsConfig {
+            symbol_dirs: self.symbol_dirs.clone(),
+            breakpad_dirs: self.breakpad_dirs.clone(),
+            breakpad_servers: self.breakpad_servers.clone(),
+            windows_symbol_servers: self.windows_symbol_servers.clone(),
+            debuginfod_servers: self.debuginfod_servers.clone(),
+            use_debuginfod: if self.use_debuginfod {
+                Some(true)
+            } else {
+                None
+            },
+            cache_dir: self.symbol_cache_dir.clone(),
+            download_source_files: if self.download_source_files {
+                Some(true)
+            } else {
+                None
+            },
+        };
+        symbols.append(preset_symbols.clone());
+        symbols.append(config.symbols.clone());
+        symbols
+    }
+}
+
+fn print_report(path: &Path, symbols: &SymbolsConfig, options: &ReportOptions) {
+    if let Err(err) = report::print_report(path, symbols, options) {
+        eprintln!("{err}");
+        std::process::exit(1)
+    }
+}
+
+fn is_valid_token(token: &str) -> bool {
+    !token.is_empty()
+        && token
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Replaces each directory in `paths` with the profile files it contains:
+/// files ending in `.json`, `.json.gz` or `.fxpc`, and files named `perf.data`
+/// or ending in `.perf.data`.
+fn expand_profile_paths(paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for path in paths {
+        if !path.is_dir() {
+            files.push(path.clone());
+            continue;
         }
+        let entries = match std::fs::read_dir(path) {
+            Ok(entries) => entries,
+            Err(err) => {
+                eprintln!("Could not read directory {:?}: {}", path, err);
+                std::process::exit(1)
+            }
+        };
+        let mut dir_files: Vec<PathBuf> = entries
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| path.is_file() && is_profile_file_name(path))
+            .collect();
+        dir_files.sort();
+        files.extend(dir_files);
     }
+    files
+}
+
+fn is_profile_file_name(path: &Path) -> bool {
+    let file_name = match path.file_name().and_then(|name| name.to_str()) {
+        Some(file_name) => file_name,
+        None => return false,
+    };
+    file_name.ends_with(".json")
+        || file_name.ends_with(".json.gz")
+        || file_name.ends_with(".fxpc")
+        || file_name == "perf.data"
+        || file_name.ends_with(".perf.data")
 }
 
 fn attempt_conversion(
@@ -249,7 +817,15 @@ fn attempt_conversion(
     let path = Path::new(filename)
         .canonicalize()
         .expect("Couldn't form absolute path");
-    let reader = BufReader::new(input_file);
+    let mut reader = BufReader::new(input_file);
+    match convert_compact_profile(&mut reader) {
+        Ok(Some(output_file)) => return Some(output_file),
+        Ok(None) => {}
+        Err(err) => {
+            eprintln!("Could not convert the compact profile {filename:?} to JSON: {err}");
+            std::process::exit(1)
+        }
+    }
     let output_file = tempfile::NamedTempFile::new().ok()?;
     let profile = import::perf::convert(
         reader,
@@ -273,6 +849,242 @@ mod test {
         Opt::command().debug_assert();
     }
 
+    #[cfg(any(target_os = "macos", target_os = "linux"))]
+    #[test]
+    fn verify_cli_record_live() {
+        let opt = Opt::parse_from(["samply", "record", "--live", "rustup"]);
+        assert!(
+            matches!(opt.action, Action::Record(record_args) if record_args.live && record_args.snapshot_interval == 10.0)
+        );
+
+        assert!(
+            Opt::try_parse_from(["samply", "record", "--live", "--save-only", "rustup"]).is_err()
+        );
+        assert!(
+            Opt::try_parse_from(["samply", "record", "--snapshot-interval", "5", "rustup"])
+                .is_err()
+        );
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "linux"))]
+    #[test]
+    fn verify_cli_record_preset() {
+        let config: Config = toml::from_str(
+            r#"
+            [presets.ci]
+            event-source = "cpu-clock"
+            rate = 4000
+            merge-threads = true
+            output = "{preset}-{command}.json"
+            "#,
+        )
+        .unwrap();
+
+        let opt = Opt::parse_from(["samply", "record", "--preset", "ci", "-r", "100", "rustup"]);
+        let Action::Record(record_args) = opt.action else {
+            panic!("Expected a record action")
+        };
+        let preset = record_args.preset(&config);
+        assert_eq!(preset.event_source, Some(config::EventSource::CpuClock));
+        assert_eq!(record_args.rate.or(preset.rate), Some(100.0));
+        assert_eq!(
+            record_args.output_file(&preset),
+            PathBuf::from("ci-rustup.json")
+        );
+        let conversion_args = record_args.conversion_args.with_preset(&preset);
+        assert!(conversion_args.merge_threads);
+
+        let opt = Opt::parse_from([
+            "samply",
+            "record",
+            "--preset",
+            "ci",
+            "--no-merge-threads",
+            "rustup",
+        ]);
+        let Action::Record(record_args) = opt.action else {
+            panic!("Expected a record action")
+        };
+        let preset = record_args.preset(&config);
+        let conversion_args = record_args.conversion_args.with_preset(&preset);
+        assert!(!conversion_args.merge_threads);
+
+        let opt = Opt::parse_from(["samply", "record", "--event-source", "cpu-cycles", "rustup"]);
+        let Action::Record(record_args) = opt.action else {
+            panic!("Expected a record action")
+        };
+        assert_eq!(
+            record_args.event_source,
+            Some(config::EventSource::CpuCycles)
+        );
+        let preset = record_args.preset(&config);
+        assert_eq!(preset, config::Preset::default());
+        assert_eq!(
+            record_args.output_file(&preset),
+            PathBuf::from("profile.json")
+        );
+    }
+
+    #[test]
+    fn verify_cli_report() {
+        let opt = Opt::parse_from(["samply", "report", "--json", "--top", "5", "prof.json"]);
+        let Action::Report(report_args) = opt.action else {
+            panic!("Expected a report action")
+        };
+        assert_eq!(report_args.file, PathBuf::from("prof.json"));
+        assert!(report_args.report_options.json);
+        assert_eq!(report_args.report_options.top, 5);
+        assert_eq!(report_args.report_options.threshold, 1.0);
+
+        #[cfg(any(target_os = "macos", target_os = "linux"))]
+        {
+            let opt = Opt::parse_from(["samply", "record", "--report", "rustup"]);
+            assert!(
+                matches!(opt.action, Action::Record(record_args) if record_args.report && record_args.command == ["rustup"])
+            );
+            assert!(
+                Opt::try_parse_from(["samply", "record", "--report", "--live", "rustup"]).is_err()
+            );
+            assert!(Opt::try_parse_from(["samply", "record", "--top", "5", "rustup"]).is_err());
+            assert!(Opt::try_parse_from(["samply", "record", "--json", "rustup"]).is_err());
+            assert!(
+                Opt::try_parse_from(["samply", "record", "--report", "--json", "rustup"]).is_ok()
+            );
+        }
+    }
+
+    #[test]
+    fn verify_cli_upload() {
+        let opt = Opt::parse_from([
+            "samply",
+            "upload",
+            "--symbolicate",
+            "--breakpad-dir",
+            "syms",
+            "prof.json",
+            "--to",
+            "http://store/profiles/",
+        ]);
+        let Action::Upload(upload_args) = opt.action else {
+            panic!("Expected an upload action")
+        };
+        assert_eq!(upload_args.file, PathBuf::from("prof.json"));
+        assert_eq!(upload_args.to, "http://store/profiles/");
+        assert!(upload_args.upload_options.symbolicate);
+        assert!(!upload_args.upload_options.strip);
+        assert_eq!(
+            upload_args.symbol_args.breakpad_dirs,
+            vec![PathBuf::from("syms")]
+        );
+
+        assert!(Opt::try_parse_from(["samply", "upload", "prof.json"]).is_err());
+    }
+
+    #[test]
+    fn verify_cli_load() {
+        let opt = Opt::parse_from(["samply", "load", "a.json", "profiles/"]);
+        assert!(
+            matches!(opt.action, Action::Load(load_args) if load_args.files == [PathBuf::from("a.json"), PathBuf::from("profiles/")])
+        );
+
+        assert!(Opt::try_parse_from(["samply", "load"]).is_err());
+    }
+
+    #[test]
+    fn verify_cli_server_args() {
+        let opt = Opt::parse_from([
+            "samply",
+            "load",
+            "--address",
+            "0.0.0.0",
+            "--token",
+            "my-token",
+            "--tls-cert",
+            "cert.pem",
+            "--tls-key",
+            "key.pem",
+            "profile.json",
+        ]);
+        let Action::Load(load_args) = opt.action else {
+            panic!("Expected a load action")
+        };
+        let props = load_args
+            .server_args
+            .server_props(&Config::default(), &SymbolsConfig::default());
+        assert_eq!(props.address, IpAddr::from([0, 0, 0, 0]));
+        assert_eq!(props.token.as_deref(), Some("my-token"));
+        assert!(props.tls_cert_and_key.is_some());
+
+        assert!(
+            Opt::try_parse_from(["samply", "load", "--tls-cert", "cert.pem", "profile.json"])
+                .is_err()
+        );
+        assert!(is_valid_token("abc_DEF-123"));
+        assert!(!is_valid_token(""));
+        assert!(!is_valid_token("a/b"));
+    }
+
+    #[test]
+    fn verify_cli_symbol_args() {
+        let config: Config = toml::from_str(
+            r#"
+            [symbols]
+            breakpad-servers = ["https://config/"]
+            cache-dir = "/config/sym"
+            "#,
+        )
+        .unwrap();
+        let opt = Opt::parse_from([
+            "samply",
+            "load",
+            "--breakpad-server",
+            "https://cli1/",
+            "--breakpad-server",
+            "https://cli2/",
+            "--symbol-dir",
+            "symbols",
+            "--use-debuginfod",
+            "--download-source-files",
+            "profile.json",
+        ]);
+        let Action::Load(load_args) = opt.action else {
+            panic!("Expected a load action")
+        };
+        let preset_symbols = SymbolsConfig {
+            breakpad_servers: vec!["https://preset/".to_string()],
+            ..Default::default()
+        };
+        let props = load_args.server_args.server_props(&config, &preset_symbols);
+        assert_eq!(
+            props.symbols.breakpad_servers,
+            [
+                "https://cli1/",
+                "https://cli2/",
+                "https://preset/",
+                "https://config/"
+            ]
+        );
+        assert_eq!(props.symbols.symbol_dirs, [PathBuf::from("symbols")]);
+        assert_eq!(props.symbols.use_debuginfod, Some(true));
+        assert_eq!(props.symbols.download_source_files, Some(true));
+        assert_eq!(
+            props.symbols.cache_dir.as_deref(),
+            Some(Path::new("/config/sym"))
+        );
+    }
+
+    #[test]
+    fn profile_file_names() {
+        assert!(is_profile_file_name(Path::new("dir/profile.json")));
+        assert!(is_profile_file_name(Path::new("profile.json.gz")));
+        assert!(is_profile_file_name(Path::new("prof.fxpc")));
+        assert!(is_profile_file_name(Path::new("perf.data")));
+        assert!(is_profile_file_name(Path::new("dir/run1.perf.data")));
+        assert!(!is_profile_file_name(Path::new("notes.txt")));
+        assert!(!is_profile_file_name(Path::new("perf.data.old")));
+        assert!(!is_profile_file_name(Path::new("myperf.data")));
+    }
+
     #[cfg(any(target_os = "macos", target_os = "linux"))]
     #[test]
     fn verify_cli_record() {
@@ -0,0 +1,691 @@
+use fxprof_processed_profile::{FrameView, ProfileReadError, ProfileView, ThreadView};
+use serde_derive::Serialize;
+use wholesym::debugid::DebugId;
+use wholesym::SymbolManager;
+
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use crate::config::SymbolsConfig;
+use crate::server::{library_info_for_profile_lib, symbol_manager_config};
+use crate::ReportOptions;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ReportError {
+    #[error("Could not open the profile {0:?}: {1}")]
+    Open(PathBuf, #[source] std::io::Error),
+
+    #[error("Could not read the profile {0:?}: {1}")]
+    Read(PathBuf, #[source] ProfileReadError),
+
+    #[error("Could not write the report: {0}")]
+    Write(#[source] std::io::Error),
+}
+
+/// Symbolicates the profile at `path` with the symbols from `symbols` and the
+/// default symbol locations, and prints a report to stdout: as text, or as
+/// JSON if `options.json` is set.
+#[tokio::main]
+pub async fn print_report(
+    path: &Path,
+    symbols: &SymbolsConfig,
+    options: &ReportOptions,
+) -> Result<(), ReportError> {
+    let file = File::open(path).map_err(|e| ReportError::Open(path.to_owned(), e))?;
+    let profile = ProfileView::from_reader(BufReader::new(file))
+        .map_err(|e| ReportError::Read(path.to_owned(), e))?;
+    let (function_names, _) = symbolicate(&profile, symbols).await;
+    let report = Report::new(&profile, &function_names, options);
+
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    if options.json {
+        serde_json::to_writer_pretty(&mut out, &report)
+            .map_err(|e| ReportError::Write(e.into()))?;
+        writeln!(out).map_err(ReportError::Write)
+    } else {
+        report
+            .write_text(&mut out, options.threshold)
+            .map_err(ReportError::Write)
+    }
+}
+
+/// The function names for the native frames in a profile, keyed by library
+/// debug ID and relative address. The names of inlined functions come first,
+/// followed by the name of the outer function.
+pub(crate) type FunctionNames = HashMap<(DebugId, u32), Vec<String>>;
+
+/// Looks up the function names for all addresses in the profile's native frames.
+/// Addresses in libraries without symbols keep their unsymbolicated names. Also
+/// returns whether symbols were found for all libraries.
+pub(crate) async fn symbolicate(
+    profile: &ProfileView,
+    symbols: &SymbolsConfig,
+) -> (FunctionNames, bool) {
+    let symbol_manager = SymbolManager::with_config(symbol_manager_config(false, symbols));
+    for lib in profile.libs() {
+        symbol_manager.add_known_library(library_info_for_profile_lib(lib));
+    }
+
+    let mut function_names = FunctionNames::new();
+    let mut found_all_libs = true;
+    for (debug_id, (lib, addresses)) in profile.native_frame_addresses() {
+        match symbol_manager
+            .lookup_function_names(&lib.debug_name, debug_id, addresses)
+            .await
+        {
+            Ok(names) => function_names.extend(
+                names
+                    .into_iter()
+                    .map(|(address, names)| ((debug_id, address), names)),
+            ),
+            Err(err) => {
+                eprintln!("Could not find symbols for {}: {err}", lib.debug_name);
+                found_all_libs = false;
+            }
+        }
+    }
+    (function_names, found_all_libs)
+}
+
+/// A function in the report, identified by its name and library.
+type FunctionKey<'a> = (&'a str, Option<&'a str>);
+
+/// The report for a profile. Sample counts are the sums of the sample weights.
+/// Percentages are relative to the samples of all threads in the report.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Report {
+    product: String,
+    total_samples: i64,
+    /// The functions with the most self samples, across all threads.
+    functions: Vec<FunctionSummary>,
+    threads: Vec<ThreadSummary>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct FunctionSummary {
+    name: String,
+    lib: Option<String>,
+    self_samples: i64,
+    self_percent: f64,
+    total_samples: i64,
+    total_percent: f64,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ThreadSummary {
+    name: String,
+    tid: String,
+    pid: String,
+    process_name: String,
+    samples: i64,
+    percent: f64,
+    /// The function with the most self samples in this thread.
+    top_function: Option<String>,
+    /// The nodes of the thread's call tree, without the nodes below the
+    /// threshold, in pre-order: each node comes before its children, and the
+    /// children are sorted by total samples. The tree is stored flat, with
+    /// parent indexes, because call trees of recursive code can be very deep.
+    call_tree: Vec<CallTreeNode>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct CallTreeNode {
+    name: String,
+    lib: Option<String>,
+    /// The index of the caller's node in the call tree, `None` for the roots.
+    parent: Option<usize>,
+    self_samples: i64,
+    total_samples: i64,
+    total_percent: f64,
+    /// The number of callees which were omitted because they are below the
+    /// threshold.
+    omitted_children: usize,
+}
+
+/// The sample counts of a thread, before they're converted into a report.
+#[derive(Default)]
+struct ThreadSamples<'a> {
+    samples: i64,
+    self_samples: HashMap<FunctionKey<'a>, i64>,
+    total_samples: HashMap<FunctionKey<'a>, i64>,
+    /// The call tree nodes, indexed by node index. Index 0 is a virtual root.
+    call_tree: Vec<CallTreeBuilderNode<'a>>,
+}
+
+struct CallTreeBuilderNode<'a> {
+    function: FunctionKey<'a>,
+    self_samples: i64,
+    total_samples: i64,
+    children: HashMap<FunctionKey<'a>, usize>,
+}
+
+impl<'a> ThreadSamples<'a> {
+    fn new(thread: &ThreadView<'a>, function_names: &'a FunctionNames) -> Self {
+        let mut thread_samples = ThreadSamples {
+            call_tree: vec![CallTreeBuilderNode {
+                function: ("", None),
+                self_samples: 0,
+                total_samples: 0,
+                children: HashMap::new(),
+            }],
+            ..Default::default()
+        };
+        // The functions of each stack, from the leaf to the root.
+        let mut stack_functions: HashMap<usize, Vec<FunctionKey<'a>>> = HashMap::new();
+        for sample in thread.samples() {
+            thread_samples.samples += sample.weight;
+            let Some(stack_index) = sample.stack_index else {
+                continue;
+            };
+            let functions = stack_functions.entry(stack_index).or_insert_with(|| {
+                let mut functions = Vec::new();
+                for frame in thread.stack_frames(Some(stack_index)) {
+                    push_frame_functions(&frame, function_names, &mut functions);
+                }
+                functions
+            });
+            thread_samples.add_stack(functions, sample.weight);
+        }
+        thread_samples
+    }
+
+    fn add_stack(&mut self, functions: &[FunctionKey<'a>], weight: i64) {
+        if let Some(leaf) = functions.first() {
+            *self.self_samples.entry(*leaf).or_default() += weight;
+        }
+        // Count recursive functions only once per sample.
+        let mut seen_functions = HashSet::new();
+        for function in functions {
+            if seen_functions.insert(function) {
+                *self.total_samples.entry(*function).or_default() += weight;
+            }
+        }
+
+        let mut node_index = 0;
+        self.call_tree[0].total_samples += weight;
+        for function in functions.iter().rev() {
+            node_index = match self.call_tree[node_index].children.get(function) {
+                Some(&child_index) => child_index,
+                None => {
+                    let child_index = self.call_tree.len();
+                    self.call_tree.push(CallTreeBuilderNode {
+                        function: *function,
+                        self_samples: 0,
+                        total_samples: 0,
+                        children: HashMap::new(),
+                    });
+                    self.call_tree[node_index]
+                        .children
+                        .insert(*function, child_index);
+                    child_index
+                }
+            };
+            self.call_tree[node_index].total_samples += weight;
+        }
+        self.call_tree[node_index].self_samples += weight;
+    }
+
+    /// Converts the call tree into report nodes, in pre-order, omitting the
+    /// nodes below `min_samples`. Also returns the number of omitted roots.
+    ///
+    /// Call trees of deeply recursive code can be very deep, so this uses an
+    /// explicit stack instead of recursion.
+    fn call_tree_nodes(
+        &self,
+        min_samples: f64,
+        percent: &impl Fn(i64) -> f64,
+    ) -> (Vec<CallTreeNode>, usize) {
+        let mut nodes = Vec::new();
+        let (roots, omitted_roots) = self.included_children(0, min_samples);
+        // (node index, parent index in `nodes`), pushed in reverse so that the
+        // children are visited in order.
+        let mut stack: Vec<(usize, Option<usize>)> =
+            roots.into_iter().rev().map(|root| (root, None)).collect();
+        while let Some((node_index, parent)) = stack.pop() {
+            let node = &self.call_tree[node_index];
+            let (children, omitted_children) = self.included_children(node_index, min_samples);
+            let index = nodes.len();
+            nodes.push(CallTreeNode {
+                name: node.function.0.to_string(),
+                lib: node.function.1.map(ToString::to_string),
+                parent,
+                self_samples: node.self_samples,
+                total_samples: node.total_samples,
+                total_percent: percent(node.total_samples),
+                omitted_children,
+            });
+            stack.extend(children.into_iter().rev().map(|child| (child, Some(index))));
+        }
+        (nodes, omitted_roots)
+    }
+
+    /// The children of the given node which have at least `min_samples`
+    /// samples, sorted by total samples, and the number of the other children.
+    fn included_children(&self, node_index: usize, min_samples: f64) -> (Vec<usize>, usize) {
+        let mut children: Vec<usize> = self.call_tree[node_index]
+            .children
+            .values()
+            .copied()
+            .collect();
+        children.sort_by_key(|&child_index| {
+            let child = &self.call_tree[child_index];
+            (std::cmp::Reverse(child.total_samples), child.function)
+        });
+        let child_count = children.len();
+        children.retain(|&child_index| {
+            let child = &self.call_tree[child_index];
+            (child.total_samples as f64) >= min_samples && child.total_samples != 0
+        });
+        let omitted_children = child_count - children.len();
+        (children, omitted_children)
+    }
+}
+
+/// Appends the functions of a frame: for symbolicated native frames, the
+/// inlined functions and the outer function, and otherwise the frame's name.
+fn push_frame_functions<'a>(
+    frame: &FrameView<'a>,
+    function_names: &'a FunctionNames,
+    functions: &mut Vec<FunctionKey<'a>>,
+) {
+    let lib_name = match frame.lib {
+        Some(lib) => Some(lib.name.as_str()),
+        None => frame.resource_name,
+    };
+    if let (Some(lib), Some(address)) = (frame.lib, frame.address) {
+        if let Some(names) = function_names.get(&(lib.debug_id, address)) {
+            functions.extend(names.iter().map(|name| (name.as_str(), lib_name)));
+            return;
+        }
+    }
+    functions.push((frame.name, lib_name));
+}
+
+impl Report {
+    fn new(profile: &ProfileView, function_names: &FunctionNames, options: &ReportOptions) -> Self {
+        let threads: Vec<(ThreadView, ThreadSamples)> = profile
+            .threads()
+            .filter(|thread| match &options.thread {
+                Some(filter) => thread.name().contains(filter.as_str()) || thread.tid() == filter,
+                None => true,
+            })
+            .map(|thread| {
+                let samples = ThreadSamples::new(&thread, function_names);
+                (thread, samples)
+            })
+            .collect();
+
+        let total_samples: i64 = threads.iter().map(|(_, samples)| samples.samples).sum();
+        let percent = |samples: i64| {
+            if total_samples == 0 {
+                0.0
+            } else {
+                samples as f64 * 100.0 / total_samples as f64
+            }
+        };
+        let min_samples = total_samples as f64 * options.threshold / 100.0;
+
+        let mut self_samples: HashMap<FunctionKey, i64> = HashMap::new();
+        let mut total_samples_per_function: HashMap<FunctionKey, i64> = HashMap::new();
+        for (_, samples) in &threads {
+            for (function, count) in &samples.self_samples {
+                *self_samples.entry(*function).or_default() += count;
+            }
+            for (function, count) in &samples.total_samples {
+                *total_samples_per_function.entry(*function).or_default() += count;
+            }
+        }
+        let mut functions: Vec<FunctionSummary> = total_samples_per_function
+            .iter()
+            .map(|(function, &total)| {
+                let self_count = self_samples.get(function).copied().unwrap_or(0);
+                FunctionSummary {
+                    name: function.0.to_string(),
+                    lib: function.1.map(ToString::to_string),
+                    self_samples: self_count,
+                    self_percent: percent(self_count),
+                    total_samples: total,
+                    total_percent: percent(total),
+                }
+            })
+            .collect();
+        functions.sort_by(|a, b| {
+            (b.self_samples, b.total_samples, &a.name).cmp(&(
+                a.self_samples,
+                a.total_samples,
+                &b.name,
+            ))
+        });
+        functions.truncate(options.top);
+
+        let threads = threads
+            .iter()
+            .map(|(thread, samples)| {
+                let top_function = samples
+                    .self_samples
+                    .iter()
+                    .max_by_key(|(function, &count)| (count, std::cmp::Reverse(**function)))
+                    .map(|(function, _)| function.0.to_string());
+                let (call_tree, _) = samples.call_tree_nodes(min_samples, &percent);
+                ThreadSummary {
+                    name: thread.name().to_string(),
+                    tid: thread.tid().to_string(),
+                    pid: thread.pid().to_string(),
+                    process_name: thread.process_name().to_string(),
+                    samples: samples.samples,
+                    percent: percent(samples.samples),
+                    top_function,
+                    call_tree,
+                }
+            })
+            .collect();
+
+        Report {
+            product: profile.product().to_string(),
+            total_samples,
+            functions,
+            threads,
+        }
+    }
+
+    fn write_text(&self, out: &mut impl Write, threshold: f64) -> std::io::Result<()> {
+        writeln!(
+            out,
+            "{}: {} samples in {} threads",
+            self.product,
+            self.total_samples,
+            self.threads.len()
+        )?;
+
+        writeln!(out)?;
+        writeln!(out, "Threads:")?;
+        writeln!(out, "{:>10} {:>7}  Thread", "Samples", "%")?;
+        for thread in &self.threads {
+            writeln!(
+                out,
+                "{:>10} {:>6.1}%  {} (tid {}) in {} (pid {}){}",
+                thread.samples,
+                thread.percent,
+                thread.name,
+                thread.tid,
+                thread.process_name,
+                thread.pid,
+                match &thread.top_function {
+                    Some(function) => format!(", mostly in {function}"),
+                    None => String::new(),
+                }
+            )?;
+        }
+
+        writeln!(out)?;
+        writeln!(
+            out,
+            "Top {} functions by self samples:",
+            self.functions.len()
+        )?;
+        writeln!(
+            out,
+            "{:>10} {:>7} {:>10} {:>7}  Function",
+            "Self", "%", "Total", "%"
+        )?;
+        for function in &self.functions {
+            writeln!(
+                out,
+                "{:>10} {:>6.1}% {:>10} {:>6.1}%  {}",
+                function.self_samples,
+                function.self_percent,
+                function.total_samples,
+                function.total_percent,
+                function_label(&function.name, function.lib.as_deref())
+            )?;
+        }
+
+        for thread in &self.threads {
+            if thread.call_tree.is_empty() {
+                continue;
+            }
+            writeln!(out)?;
+            writeln!(
+                out,
+                "Call tree of {} (tid {}), without calls below {threshold}%:",
+                thread.name, thread.tid
+            )?;
+            writeln!(out, "{:>10} {:>7}  Function", "Total", "%")?;
+            write_call_tree(out, &thread.call_tree)?;
+        }
+        Ok(())
+    }
+}
+
+/// A line of the text call tree which still needs to be written.
+enum CallTreeLine {
+    Node(usize, usize),
+    OmittedChildren(usize, usize),
+}
+
+/// Writes the call tree of a thread. Chains of nodes in which each node passes
+/// all of its samples on to its only child are collapsed into one line, with
+/// the function names separated by " > ".
+fn write_call_tree(out: &mut impl Write, nodes: &[CallTreeNode]) -> std::io::Result<()> {
+    let mut roots = Vec::new();
+    let mut children: Vec<Vec<usize>> = vec![Vec::new(); nodes.len()];
+    for (index, node) in nodes.iter().enumerate() {
+        match node.parent {
+            Some(parent) => children[parent].push(index),
+            None => roots.push(index),
+        }
+    }
+
+    let mut stack: Vec<CallTreeLine> = roots
+        .into_iter()
+        .rev()
+        .map(|root| CallTreeLine::Node(root, 0))
+        .collect();
+    while let Some(line) = stack.pop() {
+        let (index, depth) = match line {
+            CallTreeLine::Node(index, depth) => (index, depth),
+            CallTreeLine::OmittedChildren(omitted_children, depth) => {
+                writeln!(
+                    out,
+                    "{:>19}  {:indent$}[{} more below the threshold]",
+                    "",
+                    "",
+                    omitted_children,
+                    indent = depth * 2
+                )?;
+                continue;
+            }
+        };
+        let node = &nodes[index];
+        let mut labels = vec![function_label(&node.name, node.lib.as_deref())];
+        let mut last = index;
+        while let [only_child] = children[last].as_slice() {
+            let (last_node, child_node) = (&nodes[last], &nodes[*only_child]);
+            if last_node.self_samples != 0
+                || last_node.omitted_children != 0
+                || child_node.total_samples != last_node.total_samples
+            {
+                break;
+            }
+            labels.push(function_label(&child_node.name, child_node.lib.as_deref()));
+            last = *only_child;
+        }
+        writeln!(
+            out,
+            "{:>10} {:>6.1}%  {:indent$}{}",
+            node.total_samples,
+            node.total_percent,
+            "",
+            labels.join(" > "),
+            indent = depth * 2
+        )?;
+        // Pushed in reverse, so that the children are written in order,
+        // followed by the line about the omitted children.
+        if nodes[last].omitted_children != 0 {
+            stack.push(CallTreeLine::OmittedChildren(
+                nodes[last].omitted_children,
+                depth + 1,
+            ));
+        }
+        for child in children[last].iter().rev() {
+            stack.push(CallTreeLine::Node(*child, depth + 1));
+        }
+    }
+    Ok(())
+}
+
+fn function_label(name: &str, lib: Option<&str>) -> String {
+    match lib {
+        Some(lib) => format!("{name} ({lib})"),
+        None => name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use fxprof_processed_profile::{
+        CategoryHandle, CpuDelta, Frame, FrameFlags, FrameInfo, Profile, ReferenceTimestamp,
+        SamplingInterval, Timestamp,
+    };
+
+    fn test_profile() -> ProfileView {
+        let mut profile = Profile::new(
+            "test",
+            ReferenceTimestamp::from_millis_since_unix_epoch(0.0),
+            SamplingInterval::from_millis(1),
+        );
+        let process = profile.add_process("test", 123, Timestamp::from_millis_since_reference(0.0));
+        let thread = profile.add_thread(
+            process,
+            123,
+            Timestamp::from_millis_since_reference(0.0),
+            true,
+        );
+        profile.set_thread_name(thread, "main");
+        let stacks: &[&[&str]] = &[
+            &["main", "parse", "read"],
+            &["main", "parse", "read"],
+            &["main", "parse"],
+            &["main", "render"],
+            &["main", "fib", "fib", "fib"],
+        ];
+        for (i, stack) in stacks.iter().enumerate() {
+            let frames: Vec<FrameInfo> = stack
+                .iter()
+                .map(|name| FrameInfo {
+                    frame: Frame::Label(profile.intern_string(name)),
+                    category_pair: CategoryHandle::OTHER.into(),
+                    flags: FrameFlags::empty(),
+                })
+                .collect();
+            profile.add_sample(
+                thread,
+                Timestamp::from_millis_since_reference(i as f64),
+                frames.into_iter(),
+                CpuDelta::ZERO,
+                1,
+            );
+        }
+        ProfileView::from_slice(&serde_json::to_vec(&profile).unwrap()).unwrap()
+    }
+
+    fn options(threshold: f64) -> ReportOptions {
+        ReportOptions {
+            top: 3,
+            threshold,
+            thread: None,
+            json: false,
+        }
+    }
+
+    #[test]
+    fn function_table() {
+        let profile = test_profile();
+        let report = Report::new(&profile, &FunctionNames::new(), &options(1.0));
+        assert_eq!(report.total_samples, 5);
+        let functions: Vec<(&str, i64, i64)> = report
+            .functions
+            .iter()
+            .map(|f| (f.name.as_str(), f.self_samples, f.total_samples))
+            .collect();
+        assert_eq!(functions, [("read", 2, 2), ("parse", 1, 3), ("fib", 1, 1)]);
+        assert_eq!(report.threads[0].top_function.as_deref(), Some("read"));
+    }
+
+    #[test]
+    fn call_tree_threshold() {
+        let profile = test_profile();
+        let report = Report::new(&profile, &FunctionNames::new(), &options(30.0));
+        let call_tree = &report.threads[0].call_tree;
+        let nodes: Vec<(&str, Option<usize>, i64)> = call_tree
+            .iter()
+            .map(|node| (node.name.as_str(), node.parent, node.total_samples))
+            .collect();
+        assert_eq!(
+            nodes,
+            [
+                ("main", None, 5),
+                ("parse", Some(0), 3),
+                ("read", Some(1), 2)
+            ]
+        );
+        assert_eq!(call_tree[0].omitted_children, 2);
+
+        let mut text = Vec::new();
+        report.write_text(&mut text, 30.0).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        assert!(text.contains("  main\n"), "{text}");
+        assert!(text.contains("    parse\n"), "{text}");
+        assert!(text.contains("[2 more below the threshold]"), "{text}");
+    }
+
+    #[test]
+    fn very_deep_call_tree() {
+        const DEPTH: usize = 100_000;
+        let mut profile = Profile::new(
+            "test",
+            ReferenceTimestamp::from_millis_since_unix_epoch(0.0),
+            SamplingInterval::from_millis(1),
+        );
+        let process = profile.add_process("test", 123, Timestamp::from_millis_since_reference(0.0));
+        let thread = profile.add_thread(
+            process,
+            123,
+            Timestamp::from_millis_since_reference(0.0),
+            true,
+        );
+        let recurse = profile.intern_string("recurse");
+        let frames = std::iter::repeat(FrameInfo {
+            frame: Frame::Label(recurse),
+            category_pair: CategoryHandle::OTHER.into(),
+            flags: FrameFlags::empty(),
+        })
+        .take(DEPTH);
+        profile.add_sample(
+            thread,
+            Timestamp::from_millis_since_reference(0.0),
+            frames,
+            CpuDelta::ZERO,
+            1,
+        );
+        let profile = ProfileView::from_slice(&serde_json::to_vec(&profile).unwrap()).unwrap();
+
+        let report = Report::new(&profile, &FunctionNames::new(), &options(0.0));
+        assert_eq!(report.threads[0].call_tree.len(), DEPTH);
+        assert_eq!(report.functions[0].total_samples, 1);
+        let json = serde_json::to_string_pretty(&report).unwrap();
+        assert!(json.contains(&format!("\"parent\": {}", DEPTH - 2)));
+        let mut text = Vec::new();
+        report.write_text(&mut text, 0.0).unwrap();
+        drop(report);
+    }
+}
@@ -1,47 +1,125 @@
+use base64::Engine;
 use flate2::read::GzDecoder;
-use hyper::body::Bytes;
-use hyper::server::conn::AddrIncoming;
-use hyper::server::Builder;
-use hyper::service::{make_service_fn, service_fn};
-use hyper::{header, Body, Request, Response, Server};
+use fxprof_processed_profile::CompactProfileError;
+use hyper::body::{Bytes, HttpBody};
+use hyper::server::conn::Http;
+use hyper::service::service_fn;
+use hyper::{header, Body, Request, Response};
 use hyper::{Method, StatusCode};
-use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
 use rand::RngCore;
 use serde_derive::Deserialize;
-use tokio::io::AsyncReadExt;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite};
+use tokio::net::TcpListener;
+use tokio_rustls::rustls;
+use tokio_rustls::TlsAcceptor;
 use wholesym::debugid::DebugId;
 use wholesym::{CodeId, LibraryInfo, SymbolManager, SymbolManagerConfig};
 
 use std::collections::HashMap;
-use std::convert::Infallible;
 use std::ffi::{OsStr, OsString};
 use std::io::BufReader;
-use std::net::SocketAddr;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::ops::Range;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use std::str::FromStr;
 use std::sync::Arc;
 
+use crate::config::SymbolsConfig;
+use crate::shared::live_profile::LiveProfile;
+use crate::shared::profile_file::convert_compact_profile;
+use crate::symbolication_batcher::SymbolicationBatcher;
+
 #[derive(Clone, Debug)]
 pub struct ServerProps {
+    /// The address to listen on, 127.0.0.1 by default.
+    pub address: IpAddr,
     pub port_selection: PortSelection,
     pub verbose: bool,
     pub open_in_browser: bool,
+    /// A directory with a static build of the profiler front-end. If set, the
+    /// front-end is served by this server instead of profiler.firefox.com.
+    pub ui_dir: Option<PathBuf>,
+    /// The secret path prefix for the profile and symbol endpoints. A random
+    /// token is generated if this is `None`.
+    pub token: Option<String>,
+    /// Credentials in the form `user:password`. If set, all requests other
+    /// than CORS preflight requests need to supply them with HTTP basic auth.
+    pub basic_auth: Option<String>,
+    /// The paths of the PEM files with the certificate chain and the private
+    /// key. If set, the server uses HTTPS.
+    pub tls_cert_and_key: Option<(PathBuf, PathBuf)>,
+    /// Additional symbol directories and symbol servers.
+    pub symbols: SymbolsConfig,
+}
+
+/// A profile file which is served by the server.
+#[derive(Clone, Debug)]
+pub struct ServedProfile {
+    /// The path of the file on disk. Files ending in `.gz` are served with
+    /// gzip content encoding.
+    pub path: PathBuf,
+    /// The name under which the profile is listed on the index page.
+    pub name: String,
+    /// Some() if the profile is still being recorded. Snapshots of it are
+    /// served until the recording is finished.
+    pub live: Option<Arc<LiveProfile>>,
+}
+
+impl ServedProfile {
+    pub fn from_path(path: &Path) -> Self {
+        Self {
+            path: path.to_owned(),
+            name: path.to_string_lossy().into_owned(),
+            live: None,
+        }
+    }
+
+    /// A profile which is still being recorded into the file at `path`.
+    pub fn live(path: &Path, live_profile: Arc<LiveProfile>) -> Self {
+        Self {
+            path: path.to_owned(),
+            name: format!("{} (live)", path.to_string_lossy()),
+            live: Some(live_profile),
+        }
+    }
 }
 
 #[tokio::main]
-pub async fn start_server_main(file: &Path, props: ServerProps) {
-    start_server(
-        Some(file),
-        props.port_selection,
-        props.verbose,
-        props.open_in_browser,
-    )
-    .await;
+pub async fn start_server_main(profiles: &[ServedProfile], props: ServerProps) {
+    start_server(profiles, props).await;
 }
 
 const BAD_CHARS: &AsciiSet = &CONTROLS.add(b':').add(b'/');
 
+/// The origin of the profiler front-end: the `PROFILER_URL` environment
+/// variable, or profiler.firefox.com if it's not set.
+pub fn default_profiler_origin() -> String {
+    match std::env::var("PROFILER_URL") {
+        Ok(profiler_url) => profiler_url.trim_end_matches('/').to_string(),
+        Err(_) => "https://profiler.firefox.com".to_string(),
+    }
+}
+
+/// The URL which opens the profile at `profile_url` in the profiler, with
+/// symbols from `symbol_server_url` if given.
+pub fn profiler_url(
+    profiler_origin: &str,
+    profile_url: &str,
+    symbol_server_url: Option<&str>,
+) -> String {
+    let encoded_profile_url = utf8_percent_encode(profile_url, BAD_CHARS);
+    match symbol_server_url {
+        Some(symbol_server_url) => {
+            let encoded_symbol_server_url = utf8_percent_encode(symbol_server_url, BAD_CHARS);
+            format!(
+                "{profiler_origin}/from-url/{encoded_profile_url}/?symbolServer={encoded_symbol_server_url}"
+            )
+        }
+        None => format!("{profiler_origin}/from-url/{encoded_profile_url}/"),
+    }
+}
+
 #[test]
 fn test_is_send_and_sync() {
     use symsrv::FileContents;
@@ -69,102 +147,217 @@ impl PortSelection {
     }
 }
 
-async fn start_server(
-    profile_filename: Option<&Path>,
-    port_selection: PortSelection,
-    verbose: bool,
-    open_in_browser: bool,
-) {
-    let libinfo_map = if let Some(profile_filename) = profile_filename {
-        // Read the profile.json file and parse it as JSON.
-        // Build a map (debugName, breakpadID) -> debugPath from the information
-        // in profile(\.processes\[\d+\])*(\.threads\[\d+\])?\.libs.
-        let file = std::fs::File::open(profile_filename).expect("couldn't read file");
-        let reader = BufReader::new(file);
-
-        // Handle .gz profiles
-        if profile_filename.extension() == Some(&OsString::from("gz")) {
-            let decoder = GzDecoder::new(reader);
-            let reader = BufReader::new(decoder);
-            parse_libinfo_map_from_profile(reader).expect("couldn't parse json")
-        } else {
-            parse_libinfo_map_from_profile(reader).expect("couldn't parse json")
-        }
+/// The symbol manager config with the default symbol locations and the
+/// additional ones from `symbols`. Files downloaded from symbol servers are
+/// stored in subdirectories of `symbols.cache_dir`, or of `~/sym` by default.
+pub fn symbol_manager_config(verbose: bool, symbols: &SymbolsConfig) -> SymbolManagerConfig {
+    const DEFAULT_NT_SYMBOL_PATH: &str = "srv**https://msdl.microsoft.com/download/symbols";
+    let mut config = SymbolManagerConfig::new()
+        .verbose(verbose)
+        .use_debuginfod(should_use_debuginfod(symbols))
+        .use_spotlight(true);
+    if symbols.symbol_dirs.is_empty() {
+        config = config
+            .respect_nt_symbol_path(true)
+            .default_nt_symbol_path(DEFAULT_NT_SYMBOL_PATH);
     } else {
-        HashMap::new()
+        // Local directories in the symbol path are symbol stores with the
+        // layout of a symbol server. Search them before the other entries.
+        let mut symbol_path: Vec<String> = symbols
+            .symbol_dirs
+            .iter()
+            .map(|dir| dir.to_string_lossy().into_owned())
+            .collect();
+        symbol_path.push(
+            std::env::var("_NT_SYMBOL_PATH").unwrap_or_else(|_| DEFAULT_NT_SYMBOL_PATH.to_string()),
+        );
+        config = config
+            .respect_nt_symbol_path(false)
+            .default_nt_symbol_path(symbol_path.join(";"));
+    }
+    for dir in &symbols.breakpad_dirs {
+        config = config.breakpad_symbols_dir(dir);
+    }
+    let cache_dir = match &symbols.cache_dir {
+        Some(cache_dir) => cache_dir.clone(),
+        None => match dirs::home_dir() {
+            Some(home_dir) => home_dir.join("sym"),
+            None => return config,
+        },
+    };
+    config = config.debuginfod_cache_dir_if_not_installed(&cache_dir);
+    // Source file URLs in the debug info can point to any host, so source
+    // files are only downloaded when asked to.
+    let download_source_files = symbols
+        .download_source_files
+        .unwrap_or_else(|| std::env::var("SAMPLY_DOWNLOAD_SOURCE_FILES").is_ok());
+    if download_source_files {
+        config = config.source_file_cache_dir(cache_dir.join("sources"));
+    }
+    if !symbols.breakpad_servers.is_empty() {
+        config = config.breakpad_symindex_cache_dir(cache_dir.join("breakpad-symindex"));
+    }
+    for server in &symbols.breakpad_servers {
+        config = config.breakpad_symbols_server(server, cache_dir.join("breakpad"));
+    }
+    for server in &symbols.windows_symbol_servers {
+        config = config.windows_symbols_server(server, cache_dir.join("windows"));
+    }
+    for server in &symbols.debuginfod_servers {
+        config = config.extra_debuginfod_server(server, cache_dir.join("debuginfod"));
+    }
+    config
+}
+
+/// Whether the symbol manager should download files from debuginfod servers.
+/// The symbol manager only consults the servers which were added with
+/// `extra_debuginfod_server` if debuginfod is enabled, so configuring a
+/// server enables it.
+fn should_use_debuginfod(symbols: &SymbolsConfig) -> bool {
+    if !symbols.debuginfod_servers.is_empty() {
+        return true;
+    }
+    symbols
+        .use_debuginfod
+        .unwrap_or_else(|| std::env::var("SAMPLY_USE_DEBUGINFOD").is_ok())
+}
+
+async fn start_server(profiles: &[ServedProfile], props: ServerProps) {
+    let ServerProps {
+        address,
+        port_selection,
+        verbose,
+        open_in_browser,
+        ui_dir,
+        token,
+        basic_auth,
+        tls_cert_and_key,
+        symbols,
+    } = props;
+
+    // Read each profile file and parse it as JSON.
+    // Build a map (debugName, breakpadID) -> debugPath from the information
+    // in profile(\.processes\[\d+\])*(\.threads\[\d+\])?\.libs, merged
+    // across all profiles, and collect a summary of each profile for the index page.
+    // Files which can't be read or parsed are skipped, so that one bad file in a
+    // directory doesn't prevent serving the other profiles.
+    // Profiles in the compact binary format, e.g. recorded with `-o prof.fxpc`,
+    // are converted to JSON first. The converted files are deleted when the
+    // server stops.
+    let mut libinfo_map = HashMap::new();
+    let mut served_profiles = Vec::with_capacity(profiles.len());
+    let mut summaries = Vec::with_capacity(profiles.len());
+    let mut converted_temp_files = Vec::new();
+    for profile in profiles {
+        if profile.live.is_some() {
+            // The libraries of live profiles are added when a snapshot is served.
+            served_profiles.push(profile.clone());
+            summaries.push(ProfileSummary::default());
+            continue;
+        }
+        let mut profile = profile.clone();
+        match convert_compact_profile_file(&profile.path) {
+            Ok(Some(temp_file)) => {
+                profile.path = temp_file.path().to_owned();
+                converted_temp_files.push(temp_file);
+            }
+            Ok(None) => {}
+            Err(e) => {
+                eprintln!("Skipping {:?}: {e}", profile.path);
+                continue;
+            }
+        }
+        let profile_json = match read_profile_file(&profile.path) {
+            Ok(profile_json) => profile_json,
+            Err(e) => {
+                eprintln!("Skipping {:?}: {e}", profile.path);
+                continue;
+            }
+        };
+        add_to_libinfo_map_recursive(&profile_json, &mut libinfo_map);
+        served_profiles.push(profile);
+        summaries.push(ProfileSummary::from_profile(&profile_json));
+    }
+    if served_profiles.is_empty() && !profiles.is_empty() {
+        eprintln!("None of the profiles could be read.");
+        std::process::exit(1);
+    }
+    let profiles = &served_profiles[..];
+
+    let tls_acceptor = match &tls_cert_and_key {
+        Some((cert_path, key_path)) => match load_tls_config(cert_path, key_path) {
+            Ok(tls_config) => Some(TlsAcceptor::from(Arc::new(tls_config))),
+            Err(e) => {
+                eprintln!("Could not set up TLS: {e}");
+                std::process::exit(1)
+            }
+        },
+        None => None,
     };
 
-    let (builder, addr) = make_builder_at_port(port_selection);
+    let (listener, addr) = bind_at_port(address, port_selection);
 
-    let token = generate_token();
+    let token = token.unwrap_or_else(generate_token);
     let path_prefix = format!("/{token}");
-    let server_origin = format!("http://{addr}");
+    let scheme = match tls_acceptor {
+        Some(_) => "https",
+        None => "http",
+    };
+    let server_origin = format!("{scheme}://{}", advertised_addr(addr));
     let symbol_server_url = format!("{server_origin}{path_prefix}");
     let mut template_values: HashMap<&'static str, String> = HashMap::new();
     template_values.insert("SERVER_URL", server_origin.clone());
     template_values.insert("PATH_PREFIX", path_prefix.clone());
 
-    let profiler_url = if profile_filename.is_some() {
-        let profile_url = format!("{symbol_server_url}/profile.json");
+    let profiler_origin = match &ui_dir {
+        Some(_) => server_origin.clone(),
+        None => default_profiler_origin(),
+    };
 
-        let env_profiler_override = std::env::var("PROFILER_URL").ok();
-        let profiler_origin = match &env_profiler_override {
-            Some(s) => s.trim_end_matches('/'),
-            None => "https://profiler.firefox.com",
+    let mut profile_list = String::new();
+    let mut profiler_urls = Vec::with_capacity(profiles.len());
+    for (id, (profile, summary)) in profiles.iter().zip(&summaries).enumerate() {
+        let profile_url = match profile.live {
+            Some(_) => format!("{symbol_server_url}/profiles/{id}/snapshot.json"),
+            None => format!("{symbol_server_url}/profiles/{id}.json"),
         };
+        let profiler_url = profiler_url(&profiler_origin, &profile_url, Some(&symbol_server_url));
+        profile_list.push_str(&summary.to_html_table_row(
+            &profile.name,
+            &profiler_url,
+            &profile_url,
+        ));
+        profiler_urls.push(profiler_url);
+    }
+    template_values.insert("PROFILE_LIST", profile_list);
 
-        let encoded_profile_url = utf8_percent_encode(&profile_url, BAD_CHARS).to_string();
-        let encoded_symbol_server_url =
-            utf8_percent_encode(&symbol_server_url, BAD_CHARS).to_string();
-        let profiler_url = format!(
-            "{profiler_origin}/from-url/{encoded_profile_url}/?symbolServer={encoded_symbol_server_url}"
-        );
-        template_values.insert("PROFILER_URL", profiler_url.clone());
-        template_values.insert("PROFILE_URL", profile_url);
-        Some(profiler_url)
-    } else {
-        None
+    // With a single profile, open it in the profiler directly. With multiple
+    // profiles, open the index page, which links to each of them.
+    let profiler_url = match profiler_urls.len() {
+        0 => None,
+        1 => profiler_urls.pop(),
+        _ => Some(format!("{symbol_server_url}/")),
     };
 
-    let template_values = Arc::new(template_values);
-
-    let mut config = SymbolManagerConfig::new()
-        .verbose(verbose)
-        .respect_nt_symbol_path(true)
-        .default_nt_symbol_path("srv**https://msdl.microsoft.com/download/symbols")
-        .use_debuginfod(std::env::var("SAMPLY_USE_DEBUGINFOD").is_ok())
-        .use_spotlight(true);
-    if let Some(home_dir) = dirs::home_dir() {
-        config = config.debuginfod_cache_dir_if_not_installed(home_dir.join("sym"));
-    }
-    // TODO: Read breakpad symbol server config from some kind of config file, and call breakpad_symbols_server
-
-    let mut symbol_manager = SymbolManager::with_config(config);
+    let symbol_manager = SymbolManager::with_config(symbol_manager_config(verbose, &symbols));
     for lib_info in libinfo_map.into_values() {
         symbol_manager.add_known_library(lib_info);
     }
-    let symbol_manager = Arc::new(symbol_manager);
-    let new_service = make_service_fn(move |_conn| {
-        let symbol_manager = symbol_manager.clone();
-        let profile_filename = profile_filename.map(PathBuf::from);
-        let template_values = template_values.clone();
-        let path_prefix = path_prefix.clone();
-        async {
-            Ok::<_, Infallible>(service_fn(move |req| {
-                symbolication_service(
-                    req,
-                    template_values.clone(),
-                    symbol_manager.clone(),
-                    profile_filename.clone(),
-                    path_prefix.clone(),
-                )
-            }))
-        }
-    });
-
-    let server = builder.serve(new_service);
-
     eprintln!("Local server listening at {server_origin}");
+    if addr.ip().is_unspecified() {
+        eprintln!("  Listening on all interfaces. To connect from another machine, replace");
+        eprintln!(
+            "  {} with this machine's address.",
+            advertised_addr(addr).ip()
+        );
+    }
+    if let Some(ui_dir) = &ui_dir {
+        eprintln!("  Serving the profiler UI from {ui_dir:?}");
+    }
+    if basic_auth.is_some() && ui_dir.is_none() {
+        eprintln!("  Basic auth is enabled. Cross-origin requests from the profiler UI don't");
+        eprintln!("  send credentials, so you'll probably want to use --ui-dir as well.");
+    }
     if !open_in_browser {
         if let Some(profiler_url) = &profiler_url {
             eprintln!("  Open the profiler at {profiler_url}");
@@ -178,24 +371,153 @@ async fn start_server(
         }
     }
 
+    let context = Arc::new(ServiceContext {
+        template_values,
+        symbol_manager,
+        symbolication_batcher: SymbolicationBatcher::new(
+            std::thread::available_parallelism().map_or(4, |n| n.get()),
+        ),
+        profiles: profiles.to_vec(),
+        path_prefix,
+        ui_dir,
+        expected_authorization: basic_auth.map(|credentials| {
+            format!(
+                "Basic {}",
+                base64::engine::general_purpose::STANDARD.encode(credentials)
+            )
+        }),
+    });
+
     // Run this server for... forever!
-    if let Err(e) = server.await {
-        eprintln!("server error: {e}");
+    loop {
+        let (stream, _) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                eprintln!("server error: {e}");
+                continue;
+            }
+        };
+        let tls_acceptor = tls_acceptor.clone();
+        let context = context.clone();
+        tokio::spawn(async move {
+            match tls_acceptor {
+                Some(tls_acceptor) => match tls_acceptor.accept(stream).await {
+                    Ok(stream) => serve_connection(stream, context, verbose).await,
+                    Err(e) => {
+                        if verbose {
+                            eprintln!("TLS handshake failed: {e}");
+                        }
+                    }
+                },
+                None => serve_connection(stream, context, verbose).await,
+            }
+        });
     }
 }
 
-fn parse_libinfo_map_from_profile(
-    reader: impl std::io::Read,
-) -> Result<HashMap<(String, DebugId), LibraryInfo>, std::io::Error> {
-    let profile: ProfileJsonProcess = serde_json::from_reader(reader)?;
-    let mut libinfo_map = HashMap::new();
-    add_to_libinfo_map_recursive(&profile, &mut libinfo_map);
-    Ok(libinfo_map)
+/// The state which is shared by all requests.
+struct ServiceContext {
+    template_values: HashMap<&'static str, String>,
+    symbol_manager: SymbolManager,
+    /// Runs the symbolication queries, on at most one worker per CPU core.
+    symbolication_batcher: SymbolicationBatcher,
+    profiles: Vec<ServedProfile>,
+    path_prefix: String,
+    ui_dir: Option<PathBuf>,
+    /// The value of the Authorization header which requests need to have if
+    /// basic auth is enabled.
+    expected_authorization: Option<String>,
 }
 
-#[derive(Deserialize, Default, Clone, Debug, PartialEq, Eq)]
+async fn serve_connection<S>(stream: S, context: Arc<ServiceContext>, verbose: bool)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let service = service_fn(move |req| symbolication_service(req, context.clone()));
+    if let Err(e) = Http::new().serve_connection(stream, service).await {
+        if verbose {
+            eprintln!("connection error: {e}");
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+enum TlsConfigError {
+    #[error("Could not read {0:?}: {1}")]
+    Io(PathBuf, #[source] std::io::Error),
+
+    #[error("No certificates found in {0:?}")]
+    NoCertificates(PathBuf),
+
+    #[error("No private key found in {0:?}")]
+    NoPrivateKey(PathBuf),
+
+    #[error("Invalid certificate or private key: {0}")]
+    Rustls(#[from] rustls::Error),
+}
+
+fn load_tls_config(
+    cert_path: &Path,
+    key_path: &Path,
+) -> Result<rustls::ServerConfig, TlsConfigError> {
+    let cert_file =
+        std::fs::File::open(cert_path).map_err(|e| TlsConfigError::Io(cert_path.to_owned(), e))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(cert_file))
+        .map_err(|e| TlsConfigError::Io(cert_path.to_owned(), e))?;
+    if certs.is_empty() {
+        return Err(TlsConfigError::NoCertificates(cert_path.to_owned()));
+    }
+
+    let key_file =
+        std::fs::File::open(key_path).map_err(|e| TlsConfigError::Io(key_path.to_owned(), e))?;
+    let mut key_reader = BufReader::new(key_file);
+    let key = loop {
+        match rustls_pemfile::read_one(&mut key_reader)
+            .map_err(|e| TlsConfigError::Io(key_path.to_owned(), e))?
+        {
+            Some(rustls_pemfile::Item::PKCS8Key(key))
+            | Some(rustls_pemfile::Item::RSAKey(key))
+            | Some(rustls_pemfile::Item::ECKey(key)) => break key,
+            Some(_) => continue,
+            None => return Err(TlsConfigError::NoPrivateKey(key_path.to_owned())),
+        }
+    };
+
+    let config = rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(
+            certs.into_iter().map(rustls::Certificate).collect(),
+            rustls::PrivateKey(key),
+        )?;
+    Ok(config)
+}
+
+fn parse_profile(reader: impl std::io::Read) -> Result<ProfileJsonProcess, std::io::Error> {
+    Ok(serde_json::from_reader(reader)?)
+}
+
+fn convert_compact_profile_file(
+    path: &Path,
+) -> Result<Option<tempfile::NamedTempFile>, CompactProfileError> {
+    convert_compact_profile(BufReader::new(std::fs::File::open(path)?))
+}
+
+/// Reads and parses a profile file, which can be gzip-compressed.
+fn read_profile_file(path: &Path) -> Result<ProfileJsonProcess, std::io::Error> {
+    let reader = BufReader::new(std::fs::File::open(path)?);
+    if path.extension() == Some(&OsString::from("gz")) {
+        parse_profile(BufReader::new(GzDecoder::new(reader)))
+    } else {
+        parse_profile(reader)
+    }
+}
+
+#[derive(Deserialize, Default, Clone, Debug, PartialEq)]
 #[serde(rename_all = "camelCase")]
 struct ProfileJsonProcess {
+    #[serde(default)]
+    pub meta: ProfileJsonMeta,
     #[serde(default)]
     pub libs: Vec<ProfileJsonLib>,
     #[serde(default)]
@@ -204,11 +526,165 @@ struct ProfileJsonProcess {
     pub processes: Vec<ProfileJsonProcess>,
 }
 
-#[derive(Deserialize, Default, Clone, Debug, PartialEq, Eq)]
+#[derive(Deserialize, Default, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct ProfileJsonMeta {
+    pub product: Option<String>,
+    pub profiling_start_time: Option<f64>,
+    pub profiling_end_time: Option<f64>,
+}
+
+#[derive(Deserialize, Default, Clone, Debug, PartialEq)]
 #[serde(rename_all = "camelCase")]
 struct ProfileJsonThread {
     #[serde(default)]
     pub libs: Vec<ProfileJsonLib>,
+    #[serde(default)]
+    pub samples: ProfileJsonSamples,
+}
+
+/// The range of the sample times of a thread in the processed profile format.
+/// This is empty for profiles in the Gecko format, whose sample tables are
+/// stored with a schema.
+#[derive(Deserialize, Default, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct ProfileJsonSamples {
+    #[serde(default)]
+    pub time: SampleTimeRange,
+    #[serde(default)]
+    pub time_deltas: SampleTimeDeltaRange,
+}
+
+impl ProfileJsonSamples {
+    /// Returns the time of the first and of the last sample, in milliseconds.
+    fn time_range(&self) -> Option<(f64, f64)> {
+        self.time.0.or(self.time_deltas.0)
+    }
+}
+
+/// The smallest and the largest value of an array of sample times. The array is
+/// folded while it's parsed, so that the times don't need to be kept in memory.
+#[derive(Default, Clone, Copy, Debug, PartialEq)]
+struct SampleTimeRange(Option<(f64, f64)>);
+
+impl<'de> serde::Deserialize<'de> for SampleTimeRange {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let range = deserializer.deserialize_seq(FoldTimesVisitor(|range, time| match range {
+            Some((start, end)) => Some((f64::min(start, time), f64::max(end, time))),
+            None => Some((time, time)),
+        }))?;
+        Ok(Self(range))
+    }
+}
+
+/// The first value and the sum of an array of sample time deltas, i.e. the time
+/// of the first and of the last sample.
+#[derive(Default, Clone, Copy, Debug, PartialEq)]
+struct SampleTimeDeltaRange(Option<(f64, f64)>);
+
+impl<'de> serde::Deserialize<'de> for SampleTimeDeltaRange {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let range = deserializer.deserialize_seq(FoldTimesVisitor(|range, delta| match range {
+            Some((start, end)) => Some((start, end + delta)),
+            None => Some((delta, delta)),
+        }))?;
+        Ok(Self(range))
+    }
+}
+
+struct FoldTimesVisitor<F>(F);
+
+impl<'de, F> serde::de::Visitor<'de> for FoldTimesVisitor<F>
+where
+    F: Fn(Option<(f64, f64)>, f64) -> Option<(f64, f64)>,
+{
+    type Value = Option<(f64, f64)>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("an array of numbers")
+    }
+
+    fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut range = None;
+        while let Some(time) = seq.next_element::<f64>()? {
+            range = (self.0)(range, time);
+        }
+        Ok(range)
+    }
+}
+
+/// The information about a profile which is shown on the index page.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct ProfileSummary {
+    product: Option<String>,
+    duration_ms: Option<f64>,
+    thread_count: usize,
+}
+
+impl ProfileSummary {
+    fn from_profile(profile: &ProfileJsonProcess) -> Self {
+        let meta = &profile.meta;
+        let duration_ms = match (meta.profiling_start_time, meta.profiling_end_time) {
+            (Some(start), Some(end)) => Some(end - start),
+            _ => {
+                let mut range: Option<(f64, f64)> = None;
+                add_sample_time_range_recursive(profile, &mut range);
+                range.map(|(start, end)| end - start)
+            }
+        };
+        Self {
+            product: meta.product.clone(),
+            duration_ms,
+            thread_count: thread_count_recursive(profile),
+        }
+    }
+
+    fn to_html_table_row(&self, name: &str, profiler_url: &str, profile_url: &str) -> String {
+        let product = self.product.as_deref().unwrap_or("");
+        let duration = match self.duration_ms {
+            Some(duration_ms) => format!("{:.2}s", duration_ms / 1000.0),
+            None => String::new(),
+        };
+        format!(
+            "<tr><td><a href=\"{}\">{}</a></td><td>{}</td><td>{}</td><td>{}</td><td><a download href=\"{}\">JSON</a></td></tr>\n",
+            escape_html(profiler_url),
+            escape_html(name),
+            escape_html(product),
+            duration,
+            self.thread_count,
+            escape_html(profile_url),
+        )
+    }
+}
+
+fn add_sample_time_range_recursive(profile: &ProfileJsonProcess, range: &mut Option<(f64, f64)>) {
+    for thread in &profile.threads {
+        if let Some((start, end)) = thread.samples.time_range() {
+            *range = Some(match *range {
+                Some((range_start, range_end)) => (range_start.min(start), range_end.max(end)),
+                None => (start, end),
+            });
+        }
+    }
+    for process in &profile.processes {
+        add_sample_time_range_recursive(process, range);
+    }
+}
+
+fn thread_count_recursive(profile: &ProfileJsonProcess) -> usize {
+    profile.threads.len()
+        + profile
+            .processes
+            .iter()
+            .map(thread_count_recursive)
+            .sum::<usize>()
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
 }
 
 #[derive(Deserialize, Default, Clone, Debug, PartialEq, Eq)]
@@ -230,12 +706,12 @@ fn generate_token() -> String {
     nix_base32::to_nix_base32(&bytes)
 }
 
-fn make_builder_at_port(port_selection: PortSelection) -> (Builder<AddrIncoming>, SocketAddr) {
+fn bind_at_port(address: IpAddr, port_selection: PortSelection) -> (TcpListener, SocketAddr) {
     match port_selection {
         PortSelection::OnePort(port) => {
-            let addr = SocketAddr::from(([127, 0, 0, 1], port));
-            match Server::try_bind(&addr) {
-                Ok(builder) => (builder, addr),
+            let addr = SocketAddr::new(address, port);
+            match bind(addr) {
+                Ok(listener) => (listener, addr),
                 Err(e) => {
                     eprintln!("Could not bind to port {port}: {e}");
                     std::process::exit(1)
@@ -245,9 +721,9 @@ fn make_builder_at_port(port_selection: PortSelection) -> (Builder<AddrIncoming>
         PortSelection::TryMultiple(range) => {
             let mut error = None;
             for port in range.clone() {
-                let addr = SocketAddr::from(([127, 0, 0, 1], port));
-                match Server::try_bind(&addr) {
-                    Ok(builder) => return (builder, addr),
+                let addr = SocketAddr::new(address, port);
+                match bind(addr) {
+                    Ok(listener) => return (listener, addr),
                     Err(e) => {
                         error.get_or_insert(e);
                     }
@@ -266,6 +742,24 @@ fn make_builder_at_port(port_selection: PortSelection) -> (Builder<AddrIncoming>
     }
 }
 
+/// The address which is used in the URLs which the server prints and serves.
+/// Browsers can't connect to an unspecified address like 0.0.0.0, so the
+/// loopback address is used instead.
+fn advertised_addr(addr: SocketAddr) -> SocketAddr {
+    let ip = match addr.ip() {
+        IpAddr::V4(ip) if ip.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        IpAddr::V6(ip) if ip.is_unspecified() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+        ip => ip,
+    };
+    SocketAddr::new(ip, addr.port())
+}
+
+fn bind(addr: SocketAddr) -> std::io::Result<TcpListener> {
+    let listener = std::net::TcpListener::bind(addr)?;
+    listener.set_nonblocking(true)?;
+    TcpListener::from_std(listener)
+}
+
 const TEMPLATE_WITH_PROFILE: &str = r#"
 <!DOCTYPE html>
 <html lang="en">
@@ -275,8 +769,12 @@ const TEMPLATE_WITH_PROFILE: &str = r#"
 
 <p>This is the profiler symbol server, running at <code>SERVER_URL</code>. You can:</p>
 <ul>
-    <li><a href="PROFILER_URL">Open the profile in the profiler UI</a></li>
-    <li><a download href="PROFILE_URL">Download the raw profile JSON</a></li>
+    <li>Open a profile in the profiler UI, or download the raw profile JSON:
+        <table>
+        <tr><th>Profile</th><th>Product</th><th>Duration</th><th>Threads</th><th></th></tr>
+PROFILE_LIST
+        </table>
+    </li>
     <li>Obtain symbols by POSTing to <code>PATH_PREFIX/symbolicate/v5</code>, with the format specified by the <a href="https://tecken.readthedocs.io/en/latest/symbolication.html">Mozilla symbolication API documentation</a>.</li>
     <li>Obtain source code by POSTing to <code>PATH_PREFIX/source/v1</code>, with the format specified in this <a href="https://github.com/mstange/profiler-get-symbols/issues/24#issuecomment-989985588">github comment</a>.</li>
 </ul>
@@ -298,31 +796,55 @@ const TEMPLATE_WITHOUT_PROFILE: &str = r#"
 
 async fn symbolication_service(
     req: Request<Body>,
-    template_values: Arc<HashMap<&'static str, String>>,
-    symbol_manager: Arc<SymbolManager>,
-    profile_filename: Option<PathBuf>,
-    path_prefix: String,
+    context: Arc<ServiceContext>,
 ) -> Result<Response<Body>, hyper::Error> {
-    let has_profile = profile_filename.is_some();
+    let ServiceContext {
+        template_values,
+        symbol_manager,
+        symbolication_batcher,
+        profiles,
+        path_prefix,
+        ui_dir,
+        expected_authorization,
+    } = &*context;
+    let has_profile = !profiles.is_empty();
     let method = req.method();
     let path = req.uri().path();
     let mut response = Response::new(Body::empty());
 
-    let path_without_prefix = match path.strip_prefix(&path_prefix) {
+    // CORS preflight requests never carry credentials, so they're exempt from basic auth.
+    if let Some(expected_authorization) = expected_authorization {
+        let is_authorized = match req.headers().get(header::AUTHORIZATION) {
+            Some(authorization) => {
+                constant_time_eq(authorization.as_bytes(), expected_authorization.as_bytes())
+            }
+            None => false,
+        };
+        if !is_authorized && method != Method::OPTIONS {
+            *response.status_mut() = StatusCode::UNAUTHORIZED;
+            response.headers_mut().insert(
+                header::WWW_AUTHENTICATE,
+                header::HeaderValue::from_static("Basic realm=\"samply\""),
+            );
+            return Ok(response);
+        }
+    }
+
+    let path_without_prefix = match path.strip_prefix(path_prefix.as_str()) {
         None => {
             // The secret prefix was not part of the URL. Do not send CORS headers.
+            if let Some(ui_dir) = ui_dir {
+                // Everything outside the secret prefix belongs to the profiler UI.
+                if method == Method::GET {
+                    serve_ui_file(&mut response, ui_dir, path).await;
+                } else {
+                    *response.status_mut() = StatusCode::NOT_FOUND;
+                }
+                return Ok(response);
+            }
             match (method, path) {
                 (&Method::GET, "/") => {
-                    response.headers_mut().insert(
-                        header::CONTENT_TYPE,
-                        header::HeaderValue::from_static("text/html"),
-                    );
-                    let template = match has_profile {
-                        true => TEMPLATE_WITH_PROFILE,
-                        false => TEMPLATE_WITHOUT_PROFILE,
-                    };
-                    *response.body_mut() =
-                        Body::from(substitute_template(template, &template_values));
+                    serve_index_page(&mut response, has_profile, template_values);
                 }
                 _ => {
                     *response.status_mut() = StatusCode::NOT_FOUND;
@@ -342,7 +864,21 @@ async fn symbolication_service(
         header::HeaderValue::from_static("*"),
     );
 
-    match (method, path_without_prefix, profile_filename) {
+    // The first profile is also available as /profile.json.
+    let (requested_profile, fresh_snapshot) = match (method, path_without_prefix) {
+        (&Method::GET, "/profile.json") => (profiles.first(), false),
+        (&Method::GET, path) => match parse_profile_path(path) {
+            Some((id, fresh_snapshot)) => (profiles.get(id), fresh_snapshot),
+            None => (None, false),
+        },
+        _ => (None, false),
+    };
+    let profile_request = requested_profile.map(|profile| match &profile.live {
+        Some(live_profile) => ProfileRequest::LiveSnapshot(live_profile.clone(), fresh_snapshot),
+        None => ProfileRequest::File(profile.path.clone()),
+    });
+
+    match (method, path_without_prefix, profile_request) {
         (&Method::OPTIONS, _, _) => {
             // https://developer.mozilla.org/en-US/docs/Web/HTTP/Methods/OPTIONS
             *response.status_mut() = StatusCode::NO_CONTENT;
@@ -375,7 +911,31 @@ async fn symbolication_service(
                 );
             }
         }
-        (&Method::GET, "/profile.json", Some(profile_filename)) => {
+        (&Method::GET, "/", _) => {
+            serve_index_page(&mut response, has_profile, template_values);
+        }
+        (&Method::GET, _, Some(ProfileRequest::LiveSnapshot(live_profile, fresh_snapshot))) => {
+            let snapshot = match fresh_snapshot {
+                true => live_profile.fresh_snapshot().await,
+                false => live_profile.latest_snapshot().await,
+            };
+            match snapshot {
+                Some(snapshot) => {
+                    for lib in live_profile.libs() {
+                        symbol_manager.add_known_library(library_info_for_profile_lib(&lib));
+                    }
+                    response.headers_mut().insert(
+                        header::CONTENT_TYPE,
+                        header::HeaderValue::from_static("application/json; charset=UTF-8"),
+                    );
+                    *response.body_mut() = Body::from(snapshot);
+                }
+                None => {
+                    *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+                }
+            }
+        }
+        (&Method::GET, _, Some(ProfileRequest::File(profile_filename))) => {
             if profile_filename.extension() == Some(OsStr::new("gz")) {
                 response.headers_mut().insert(
                     header::CONTENT_ENCODING,
@@ -386,20 +946,29 @@ async fn symbolication_service(
                 header::CONTENT_TYPE,
                 header::HeaderValue::from_static("application/json; charset=UTF-8"),
             );
+            let mut file = match tokio::fs::File::open(&profile_filename).await {
+                Ok(file) => file,
+                Err(e) => {
+                    eprintln!("Could not open the profile {profile_filename:?}: {e}");
+                    *response.status_mut() = StatusCode::NOT_FOUND;
+                    return Ok(response);
+                }
+            };
             let (mut sender, body) = Body::channel();
             *response.body_mut() = body;
 
             // Stream the file out to the response body, asynchronously, after this function has returned.
             tokio::spawn(async move {
-                let mut file = tokio::fs::File::open(&profile_filename)
-                    .await
-                    .expect("couldn't open profile file");
                 let mut contents = vec![0; 1024 * 1024];
                 loop {
-                    let data_len = file
-                        .read(&mut contents)
-                        .await
-                        .expect("couldn't read profile file");
+                    let data_len = match file.read(&mut contents).await {
+                        Ok(data_len) => data_len,
+                        Err(e) => {
+                            eprintln!("Could not read the profile {profile_filename:?}: {e}");
+                            sender.abort();
+                            break;
+                        }
+                    };
                     if data_len == 0 {
                         break;
                     }
@@ -419,12 +988,51 @@ async fn symbolication_service(
                 header::HeaderValue::from_static("application/json"),
             );
             let path = path.to_string();
-            // Await the full body to be concatenated into a single `Bytes`...
-            let full_body = hyper::body::to_bytes(req.into_body()).await?;
-            let full_body = String::from_utf8(full_body.to_vec()).expect("invalid utf-8");
-            let response_json = symbol_manager.query_json_api(&path, &full_body).await;
+            if !matches!(
+                path.as_str(),
+                "/symbolicate/v5" | "/source/v1" | "/asm/v1" | "/symbols/v1"
+            ) {
+                set_error_response(&mut response, StatusCode::NOT_FOUND, "Unknown API");
+                return Ok(response);
+            }
+            let full_body = match read_body(req.into_body(), MAX_REQUEST_BODY_SIZE).await? {
+                Some(full_body) => full_body,
+                None => {
+                    let message = format!(
+                        "The request body is larger than the limit of {MAX_REQUEST_BODY_SIZE} bytes"
+                    );
+                    set_error_response(&mut response, StatusCode::PAYLOAD_TOO_LARGE, &message);
+                    return Ok(response);
+                }
+            };
+            let full_body = match String::from_utf8(full_body) {
+                Ok(full_body) => full_body,
+                Err(_) => {
+                    let message = "The request body is not valid UTF-8";
+                    set_error_response(&mut response, StatusCode::BAD_REQUEST, message);
+                    return Ok(response);
+                }
+            };
 
-            *response.body_mut() = response_json.into();
+            let query = |request: String| async move {
+                symbol_manager
+                    .query_json_api("/symbolicate/v5", &request)
+                    .await
+            };
+            let response_json = if path == "/symbolicate/v5" {
+                symbolication_batcher.symbolicate(&full_body, query).await
+            } else {
+                let response_json = symbolication_batcher
+                    .run(symbol_manager.query_json_api(&path, &full_body))
+                    .await;
+                Ok(response_json)
+            };
+            match response_json {
+                Ok(response_json) => *response.body_mut() = response_json.into(),
+                Err(e) => {
+                    set_error_response(&mut response, StatusCode::BAD_REQUEST, &e.to_string())
+                }
+            }
         }
         _ => {
             *response.status_mut() = StatusCode::NOT_FOUND;
@@ -434,6 +1042,168 @@ async fn symbolication_service(
     Ok(response)
 }
 
+/// The maximum size of a POST request body. Symbolication requests for very
+/// large profiles are a few megabytes.
+const MAX_REQUEST_BODY_SIZE: usize = 64 * 1024 * 1024;
+
+/// Reads the full request body. Returns `None` if it is larger than `limit`.
+async fn read_body(mut body: Body, limit: usize) -> Result<Option<Vec<u8>>, hyper::Error> {
+    if body.size_hint().lower() > limit as u64 {
+        return Ok(None);
+    }
+    let mut full_body = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        if full_body.len() + chunk.len() > limit {
+            return Ok(None);
+        }
+        full_body.extend_from_slice(&chunk);
+    }
+    Ok(Some(full_body))
+}
+
+/// Sets the status code and a JSON body with an error message, in the same
+/// format as the errors from the symbolication APIs.
+fn set_error_response(response: &mut Response<Body>, status: StatusCode, message: &str) {
+    *response.status_mut() = status;
+    *response.body_mut() = serde_json::json!({ "error": message }).to_string().into();
+}
+
+fn serve_index_page(
+    response: &mut Response<Body>,
+    has_profile: bool,
+    template_values: &HashMap<&'static str, String>,
+) {
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("text/html"),
+    );
+    let template = match has_profile {
+        true => TEMPLATE_WITH_PROFILE,
+        false => TEMPLATE_WITHOUT_PROFILE,
+    };
+    *response.body_mut() = Body::from(substitute_template(template, template_values));
+}
+
+/// What a GET request for a profile asks for.
+enum ProfileRequest {
+    /// The profile file at this path.
+    File(PathBuf),
+    /// A snapshot of a profile which is still being recorded. The bool is true
+    /// if a new snapshot should be taken, and false if the most recent one will do.
+    LiveSnapshot(Arc<LiveProfile>, bool),
+}
+
+/// Parses the profile index from a path of the form `/profiles/<id>.json` or
+/// `/profiles/<id>/snapshot.json`. The returned bool is true for the latter,
+/// which requests a fresh snapshot of a live profile.
+fn parse_profile_path(path: &str) -> Option<(usize, bool)> {
+    let rest = path.strip_prefix("/profiles/")?;
+    let (id, fresh_snapshot) = match rest.strip_suffix("/snapshot.json") {
+        Some(id) => (id, true),
+        None => (rest.strip_suffix(".json")?, false),
+    };
+    Some((id.parse().ok()?, fresh_snapshot))
+}
+
+/// Converts a library of a profile which was recorded by samply, so that the
+/// symbol manager can find its binary when symbols are requested.
+pub fn library_info_for_profile_lib(lib: &fxprof_processed_profile::LibraryInfo) -> LibraryInfo {
+    LibraryInfo {
+        debug_name: Some(lib.debug_name.clone()),
+        debug_id: Some(lib.debug_id),
+        debug_path: Some(lib.debug_path.clone()),
+        name: Some(lib.name.clone()),
+        code_id: lib
+            .code_id
+            .as_deref()
+            .and_then(|code_id| CodeId::from_str(code_id).ok()),
+        path: Some(lib.path.clone()),
+        arch: lib.arch.clone(),
+    }
+}
+
+/// Serves a file from the directory which contains the static build of the
+/// profiler front-end.
+///
+/// Requests for paths which don't correspond to a file, such as `/from-url/...`,
+/// are client-side routes of the front-end and get the front-end's index.html.
+async fn serve_ui_file(response: &mut Response<Body>, ui_dir: &Path, path: &str) {
+    let relative_path = match ui_relative_path(path) {
+        Some(relative_path) => relative_path,
+        None => {
+            *response.status_mut() = StatusCode::NOT_FOUND;
+            return;
+        }
+    };
+    let mut file_path = ui_dir.join(&relative_path);
+    let mut contents = tokio::fs::read(&file_path).await;
+    if contents.is_err() {
+        file_path = ui_dir.join("index.html");
+        contents = tokio::fs::read(&file_path).await;
+    }
+    match contents {
+        Ok(contents) => {
+            response.headers_mut().insert(
+                header::CONTENT_TYPE,
+                header::HeaderValue::from_static(ui_file_content_type(&file_path)),
+            );
+            *response.body_mut() = Body::from(contents);
+        }
+        Err(_) => {
+            *response.status_mut() = StatusCode::NOT_FOUND;
+        }
+    }
+}
+
+/// Converts the path of a request for a front-end file into a relative file
+/// path. Returns `None` for paths which would escape the UI directory.
+fn ui_relative_path(path: &str) -> Option<PathBuf> {
+    let path = percent_decode_str(path).decode_utf8().ok()?;
+    let mut relative_path = PathBuf::new();
+    for component in Path::new(path.trim_start_matches('/')).components() {
+        match component {
+            Component::Normal(component) => relative_path.push(component),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    if relative_path.as_os_str().is_empty() {
+        relative_path.push("index.html");
+    }
+    Some(relative_path)
+}
+
+fn ui_file_content_type(file_path: &Path) -> &'static str {
+    let extension = file_path.extension().and_then(OsStr::to_str).unwrap_or("");
+    match extension {
+        "html" => "text/html; charset=UTF-8",
+        "js" | "mjs" => "text/javascript; charset=UTF-8",
+        "css" => "text/css; charset=UTF-8",
+        "json" | "map" => "application/json; charset=UTF-8",
+        "webmanifest" => "application/manifest+json",
+        "txt" => "text/plain; charset=UTF-8",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "wasm" => "application/wasm",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Compares two byte strings in a time which doesn't depend on where they differ.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
 fn substitute_template(template: &str, template_values: &HashMap<&'static str, String>) -> String {
     let mut s = template.to_string();
     for (key, value) in template_values {
@@ -517,4 +1287,125 @@ mod test {
         assert_eq!(p.threads[0].libs[0], ProfileJsonLib::default());
         assert!(p.processes.is_empty());
     }
+
+    #[test]
+    fn profile_summary() {
+        let p: ProfileJsonProcess = serde_json::from_str(
+            r#"{
+                "meta": { "product": "dump_syms", "interval": 1 },
+                "threads": [
+                    { "samples": { "time": [10.0, 11.0, 12.5] } },
+                    { "samples": { "timeDeltas": [9.0, 1.0, 5.0] } }
+                ],
+                "processes": [{ "threads": [{ "samples": { "schema": {}, "data": [] } }] }]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            ProfileSummary::from_profile(&p),
+            ProfileSummary {
+                product: Some("dump_syms".to_string()),
+                duration_ms: Some(6.0),
+                thread_count: 3,
+            }
+        );
+
+        let p: ProfileJsonProcess = serde_json::from_str(
+            r#"{ "meta": { "profilingStartTime": 100.0, "profilingEndTime": 2100.0 } }"#,
+        )
+        .unwrap();
+        assert_eq!(ProfileSummary::from_profile(&p).duration_ms, Some(2000.0));
+        assert_eq!(ProfileSummary::from_profile(&p).thread_count, 0);
+    }
+
+    #[test]
+    fn unparsable_profile_file() {
+        let dir = std::env::temp_dir().join(format!("samply-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("broken.json");
+        std::fs::write(&path, "{ not json").unwrap();
+        assert!(read_profile_file(&path).is_err());
+        assert!(read_profile_file(&dir.join("missing.json")).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn profile_paths() {
+        assert_eq!(parse_profile_path("/profiles/0.json"), Some((0, false)));
+        assert_eq!(parse_profile_path("/profiles/12.json"), Some((12, false)));
+        assert_eq!(
+            parse_profile_path("/profiles/3/snapshot.json"),
+            Some((3, true))
+        );
+        assert_eq!(parse_profile_path("/profiles/x.json"), None);
+        assert_eq!(parse_profile_path("/profiles/1.json.gz"), None);
+        assert_eq!(parse_profile_path("/profiles/1/other.json"), None);
+        assert_eq!(parse_profile_path("/profile.json"), None);
+    }
+
+    #[test]
+    fn debuginfod_servers_enable_debuginfod() {
+        let mut symbols = SymbolsConfig {
+            use_debuginfod: Some(false),
+            ..Default::default()
+        };
+        assert!(!should_use_debuginfod(&symbols));
+        symbols
+            .debuginfod_servers
+            .push("https://debuginfod.elfutils.org/".to_string());
+        assert!(should_use_debuginfod(&symbols));
+    }
+
+    #[test]
+    fn advertised_addrs() {
+        let addr = |s: &str| s.parse::<SocketAddr>().unwrap();
+        assert_eq!(
+            advertised_addr(addr("0.0.0.0:3000")),
+            addr("127.0.0.1:3000")
+        );
+        assert_eq!(advertised_addr(addr("[::]:3000")), addr("[::1]:3000"));
+        assert_eq!(
+            advertised_addr(addr("10.0.0.2:3000")),
+            addr("10.0.0.2:3000")
+        );
+    }
+
+    #[test]
+    fn profiler_urls() {
+        assert_eq!(
+            profiler_url(
+                "https://profiler.firefox.com",
+                "http://127.0.0.1:3000/abc/profiles/0.json",
+                Some("http://127.0.0.1:3000/abc")
+            ),
+            "https://profiler.firefox.com/from-url/http%3A%2F%2F127.0.0.1%3A3000%2Fabc%2Fprofiles%2F0.json/?symbolServer=http%3A%2F%2F127.0.0.1%3A3000%2Fabc"
+        );
+        assert_eq!(
+            profiler_url(
+                "https://profiler.firefox.com",
+                "http://store/p.json.gz",
+                None
+            ),
+            "https://profiler.firefox.com/from-url/http%3A%2F%2Fstore%2Fp.json.gz/"
+        );
+    }
+
+    #[test]
+    fn ui_paths() {
+        assert_eq!(ui_relative_path("/"), Some(PathBuf::from("index.html")));
+        assert_eq!(
+            ui_relative_path("/static/app.js"),
+            Some(PathBuf::from("static/app.js"))
+        );
+        assert_eq!(
+            ui_relative_path("/from-url/http%3A%2F%2F127.0.0.1%3A3000%2Fprofile.json/"),
+            Some(PathBuf::from("from-url/http:/127.0.0.1:3000/profile.json"))
+        );
+        assert_eq!(ui_relative_path("/../secret"), None);
+        assert_eq!(ui_relative_path("/static/%2E%2E/%2E%2E/secret"), None);
+        assert_eq!(
+            ui_file_content_type(Path::new("index.html")),
+            "text/html; charset=UTF-8"
+        );
+    }
 }
@@ -0,0 +1,84 @@
+use std::ops::Index;
+use std::sync::Arc;
+
+const CHUNK_LEN: usize = 4096;
+
+/// A vector which stores its elements in fixed-size chunks behind `Arc`s, so
+/// that cloning it only copies the chunk pointers. The chunks are shared with
+/// the clones until they're modified, so pushing to a vector which has been
+/// cloned copies at most one chunk.
+///
+/// This is used for the data of profiles which are still being recorded, so
+/// that taking a snapshot doesn't copy all the data recorded so far.
+#[derive(Debug, Clone)]
+pub struct ChunkedVec<T> {
+    chunks: Vec<Arc<Vec<T>>>,
+    len: usize,
+}
+
+impl<T> Default for ChunkedVec<T> {
+    fn default() -> Self {
+        Self {
+            chunks: Vec::new(),
+            len: 0,
+        }
+    }
+}
+
+impl<T: Clone> ChunkedVec<T> {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn push(&mut self, value: T) {
+        if self.len % CHUNK_LEN == 0 {
+            self.chunks.push(Arc::new(Vec::with_capacity(CHUNK_LEN)));
+        }
+        Arc::make_mut(self.chunks.last_mut().unwrap()).push(value);
+        self.len += 1;
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        let chunk = self.chunks.get_mut(index / CHUNK_LEN)?;
+        Arc::make_mut(chunk).get_mut(index % CHUNK_LEN)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.chunks.iter().flat_map(|chunk| chunk.iter())
+    }
+}
+
+impl<T> Index<usize> for ChunkedVec<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        &self.chunks[index / CHUNK_LEN][index % CHUNK_LEN]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ChunkedVec, CHUNK_LEN};
+
+    #[test]
+    fn clones_share_chunks_until_modified() {
+        let mut vec = ChunkedVec::default();
+        for i in 0..CHUNK_LEN + 10 {
+            vec.push(i);
+        }
+        let snapshot = vec.clone();
+        vec.push(1_000_000);
+        *vec.get_mut(3).unwrap() = 7;
+
+        assert_eq!(snapshot.len(), CHUNK_LEN + 10);
+        assert_eq!(snapshot[3], 3);
+        assert!(snapshot.iter().copied().eq(0..CHUNK_LEN + 10));
+        assert_eq!(vec.len(), CHUNK_LEN + 11);
+        assert_eq!(vec[3], 7);
+        assert_eq!(vec[CHUNK_LEN + 10], 1_000_000);
+    }
+}
@@ -66,6 +66,15 @@ impl JitDumpManager {
         }
     }
 
+    /// The lib mapping ops which have been collected so far, for a snapshot
+    /// of a profile which is still being recorded.
+    pub fn snapshot_lib_mapping_ops(&self) -> Vec<LibMappingOpQueue> {
+        self.processors
+            .iter()
+            .map(|processor| processor.lib_mapping_ops.clone())
+            .collect()
+    }
+
     pub fn finish(
         mut self,
         jit_category_manager: &mut JitCategoryManager,
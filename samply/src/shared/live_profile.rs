@@ -0,0 +1,216 @@
+use fxprof_processed_profile::{LibraryInfo, Profile};
+use hyper::body::Bytes;
+use tokio::sync::watch;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{sync_channel, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+type SnapshotJob = Box<dyn FnOnce() -> Profile + Send>;
+
+/// Shares snapshots of a profile which is still being recorded with the server,
+/// so that the profile can be viewed without stopping the recording.
+///
+/// This is the server's side. The recording side is the [`LiveProfileRecorder`]
+/// which creates it. Snapshots are stored as serialized JSON.
+#[derive(Debug)]
+pub struct LiveProfile {
+    snapshot_requested: AtomicBool,
+    /// Whether the snapshot thread is still building the previous snapshot.
+    snapshot_in_progress: AtomicBool,
+    latest_snapshot: watch::Receiver<Option<Bytes>>,
+    /// The libraries of the most recent snapshot, so that the server can make
+    /// them known to its symbol manager without parsing the snapshot.
+    libs: Mutex<Vec<LibraryInfo>>,
+}
+
+/// The recording thread's side of a [`LiveProfile`].
+///
+/// The recording thread calls [`LiveProfileRecorder::snapshot_if_due`]
+/// regularly. It creates a snapshot when the snapshot interval has elapsed or
+/// when the server has asked for one. The snapshot is built and serialized on a
+/// separate thread, so that the recording thread can keep reading events.
+///
+/// When the recorder is dropped, either after [`LiveProfileRecorder::finish`] or
+/// because the recording failed, the server stops waiting for new snapshots.
+#[derive(Debug)]
+pub struct LiveProfileRecorder {
+    live_profile: Arc<LiveProfile>,
+    /// `None` if snapshots should only be taken on request.
+    snapshot_interval: Option<Duration>,
+    last_snapshot_time: Instant,
+    snapshot_jobs: SyncSender<SnapshotJob>,
+}
+
+impl LiveProfileRecorder {
+    pub fn new(snapshot_interval: Option<Duration>) -> Self {
+        let (latest_snapshot_sender, latest_snapshot) = watch::channel(None);
+        let live_profile = Arc::new(LiveProfile {
+            snapshot_requested: AtomicBool::new(false),
+            snapshot_in_progress: AtomicBool::new(false),
+            latest_snapshot,
+            libs: Mutex::new(Vec::new()),
+        });
+
+        // The snapshot thread owns the sender, so the watch channel closes once
+        // the recorder has been dropped and the last snapshot has been published.
+        let (snapshot_jobs, snapshot_job_receiver) = sync_channel::<SnapshotJob>(1);
+        thread::spawn({
+            let live_profile = live_profile.clone();
+            move || {
+                for job in snapshot_job_receiver {
+                    live_profile.publish(&job(), &latest_snapshot_sender);
+                    live_profile
+                        .snapshot_in_progress
+                        .store(false, Ordering::SeqCst);
+                }
+            }
+        });
+
+        Self {
+            live_profile,
+            snapshot_interval,
+            last_snapshot_time: Instant::now(),
+            snapshot_jobs,
+        }
+    }
+
+    /// The server's side of the live profile.
+    pub fn live_profile(&self) -> Arc<LiveProfile> {
+        self.live_profile.clone()
+    }
+
+    /// Called by the recording thread. If a snapshot is due, calls
+    /// `make_snapshot` and runs the function it returns on the snapshot thread
+    /// to create the profile. `make_snapshot` should only do the work which
+    /// needs the recording thread's data, and do it cheaply.
+    pub fn snapshot_if_due<F>(&mut self, make_snapshot: impl FnOnce() -> F)
+    where
+        F: FnOnce() -> Profile + Send + 'static,
+    {
+        let live_profile = &self.live_profile;
+        if live_profile.snapshot_in_progress.load(Ordering::SeqCst) {
+            // Leave any request pending until the current snapshot is done.
+            return;
+        }
+        let was_requested = live_profile
+            .snapshot_requested
+            .swap(false, Ordering::SeqCst);
+        let interval_elapsed = match self.snapshot_interval {
+            Some(interval) => self.last_snapshot_time.elapsed() >= interval,
+            None => false,
+        };
+        if !was_requested && !interval_elapsed {
+            return;
+        }
+        self.last_snapshot_time = Instant::now();
+        live_profile
+            .snapshot_in_progress
+            .store(true, Ordering::SeqCst);
+        // The snapshot thread is idle, so the channel has room for the job.
+        let _ = self.snapshot_jobs.try_send(Box::new(make_snapshot()));
+    }
+
+    /// Called by the recording thread once the recording has finished.
+    /// `profile` is the final profile, which is served from now on.
+    pub fn finish(self, profile: Profile) {
+        // Unlike snapshots, the final profile must not be skipped, so this
+        // waits for the snapshot thread if it's busy.
+        let _ = self.snapshot_jobs.send(Box::new(move || profile));
+    }
+}
+
+impl LiveProfile {
+    fn publish(&self, profile: &Profile, sender: &watch::Sender<Option<Bytes>>) {
+        // Update the libraries first, so that they're known by the time the
+        // snapshot which refers to them is served.
+        *self.libs.lock().unwrap() = profile.libs().cloned().collect();
+        match serde_json::to_vec(profile) {
+            Ok(json) => {
+                sender.send_replace(Some(Bytes::from(json)));
+            }
+            Err(err) => eprintln!("Could not serialize the profile snapshot: {err}"),
+        }
+    }
+
+    /// The libraries of the most recent snapshot.
+    pub fn libs(&self) -> Vec<LibraryInfo> {
+        self.libs.lock().unwrap().clone()
+    }
+
+    /// The most recent snapshot, or a new one if there isn't one yet.
+    pub async fn latest_snapshot(&self) -> Option<Bytes> {
+        if let Some(snapshot) = self.latest_snapshot.borrow().clone() {
+            return Some(snapshot);
+        }
+        self.fresh_snapshot().await
+    }
+
+    /// Asks the recording thread for a new snapshot and waits for it. Once the
+    /// recording has ended, this returns the final profile, or `None` if the
+    /// recording ended without producing a profile.
+    pub async fn fresh_snapshot(&self) -> Option<Bytes> {
+        let mut receiver = self.latest_snapshot.clone();
+        receiver.borrow_and_update();
+        self.snapshot_requested.store(true, Ordering::SeqCst);
+        // This fails once the recorder has been dropped and all its snapshots
+        // have been published. The latest snapshot is the final one then.
+        let _ = receiver.changed().await;
+        let snapshot = receiver.borrow().clone();
+        snapshot
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fxprof_processed_profile::{Profile, ReferenceTimestamp, SamplingInterval};
+
+    use super::LiveProfileRecorder;
+
+    fn profile(name: &str) -> Profile {
+        Profile::new(
+            name,
+            ReferenceTimestamp::from_millis_since_unix_epoch(0.0),
+            SamplingInterval::from_millis(1),
+        )
+    }
+
+    #[tokio::test]
+    async fn snapshots_and_final_profile() {
+        let mut recorder = LiveProfileRecorder::new(None);
+        let live_profile = recorder.live_profile();
+
+        let fresh_snapshot = tokio::spawn({
+            let live_profile = live_profile.clone();
+            async move { live_profile.fresh_snapshot().await }
+        });
+        while !live_profile
+            .snapshot_requested
+            .load(std::sync::atomic::Ordering::SeqCst)
+        {
+            tokio::task::yield_now().await;
+        }
+        recorder.snapshot_if_due(|| || profile("snapshot"));
+        let snapshot = fresh_snapshot.await.unwrap().unwrap();
+        assert!(std::str::from_utf8(&snapshot)
+            .unwrap()
+            .contains("\"snapshot\""));
+
+        recorder.finish(profile("final"));
+        let snapshot = live_profile.fresh_snapshot().await.unwrap();
+        assert!(std::str::from_utf8(&snapshot)
+            .unwrap()
+            .contains("\"final\""));
+    }
+
+    #[tokio::test]
+    async fn recording_ends_without_a_profile() {
+        let recorder = LiveProfileRecorder::new(None);
+        let live_profile = recorder.live_profile();
+        drop(recorder);
+        assert!(live_profile.fresh_snapshot().await.is_none());
+        assert!(live_profile.latest_snapshot().await.is_none());
+    }
+}
@@ -1,10 +1,15 @@
+pub mod chunked_vec;
 pub mod jit_category_manager;
 pub mod jit_function_add_marker;
 pub mod jit_function_recycler;
 pub mod jitdump_manager;
 pub mod lib_mappings;
+pub mod live_profile;
 pub mod perf_map;
 pub mod process_sample_data;
+pub mod profile_file;
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+pub mod recording_metadata;
 pub mod recycling;
 pub mod stack_converter;
 pub mod stack_depth_limiting_frame_iter;
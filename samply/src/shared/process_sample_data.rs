@@ -73,28 +73,27 @@ impl ProcessSampleData {
             lib_mappings_hierarchy.add_perf_map_mappings(perf_map_mappings);
         }
         let stack_converter = StackConverter::new(user_category, kernel_category);
-        let samples = unresolved_samples.into_inner();
-        for sample in samples {
+        for sample in unresolved_samples.iter() {
             lib_mappings_hierarchy.process_ops(sample.timestamp_mono);
             let UnresolvedSampleOrMarker {
                 thread_handle,
                 timestamp,
                 stack,
-                sample_or_marker,
-                extra_label_frame,
+                ref sample_or_marker,
+                ref extra_label_frame,
                 ..
-            } = sample;
+            } = *sample;
             stack_frame_scratch_buf.clear();
             stacks.convert_back(stack, stack_frame_scratch_buf);
             let frames = stack_converter.convert_stack(
                 stack_frame_scratch_buf,
                 &lib_mappings_hierarchy,
-                extra_label_frame,
+                extra_label_frame.clone(),
             );
             let frames = StackDepthLimitingFrameIter::new(profile, frames, user_category);
             match sample_or_marker {
                 SampleOrMarker::Sample(SampleData { cpu_delta, weight }) => {
-                    profile.add_sample(thread_handle, timestamp, frames, cpu_delta, weight);
+                    profile.add_sample(thread_handle, timestamp, frames, *cpu_delta, *weight);
                 }
                 SampleOrMarker::RssStatMarker(RssStatMarkerData {
                     size,
@@ -111,13 +110,13 @@ impl ProcessSampleData {
                     profile.add_marker_with_stack(
                         thread_handle,
                         name,
-                        RssStatMarker(size, delta),
+                        RssStatMarker(*size, *delta),
                         timing,
                         frames,
                     );
                 }
                 SampleOrMarker::OtherEventMarker(OtherEventMarkerData { attr_index }) => {
-                    if let Some(name) = event_names.get(attr_index) {
+                    if let Some(name) = event_names.get(*attr_index) {
                         let timing = MarkerTiming::Instant(timestamp);
                         profile.add_marker_with_stack(
                             thread_handle,
@@ -0,0 +1,93 @@
+use fxprof_processed_profile::{compact_profile_to_json, is_compact_profile, CompactProfileError};
+use tempfile::NamedTempFile;
+
+use std::io::{BufRead, BufWriter, Write};
+
+// Profiles are only saved on the platforms on which samply can record them.
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+use fxprof_processed_profile::{write_compact_profile, Profile};
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+use std::{ffi::OsStr, fs::File, path::Path};
+
+/// Writes the profile to `path`: in the compact binary format if the file name
+/// ends in `.fxpc`, and as processed profile JSON otherwise.
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+pub fn save_profile(profile: &Profile, path: &Path) -> Result<(), CompactProfileError> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    if path.extension() == Some(OsStr::new("fxpc")) {
+        write_compact_profile(profile, &mut writer)?;
+    } else {
+        serde_json::to_writer(&mut writer, profile)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// If `reader` contains a profile in the compact binary format, converts it
+/// into a temporary JSON file, because the profiler front-end only reads JSON.
+/// The file is deleted when the returned `NamedTempFile` is dropped.
+pub fn convert_compact_profile(
+    mut reader: impl BufRead,
+) -> Result<Option<NamedTempFile>, CompactProfileError> {
+    if !is_compact_profile(reader.fill_buf()?) {
+        return Ok(None);
+    }
+    let output_file = NamedTempFile::new()?;
+    let mut writer = BufWriter::new(output_file.as_file());
+    compact_profile_to_json(reader, &mut writer)?;
+    writer.flush()?;
+    drop(writer);
+    Ok(Some(output_file))
+}
+
+#[cfg(all(test, any(target_os = "macos", target_os = "linux")))]
+mod test {
+    use std::fs::File;
+    use std::io::BufReader;
+
+    use fxprof_processed_profile::{
+        CpuDelta, Profile, ReferenceTimestamp, SamplingInterval, Timestamp,
+    };
+
+    use super::{convert_compact_profile, save_profile};
+
+    #[test]
+    fn compact_profile_round_trip() {
+        let mut profile = Profile::new(
+            "test",
+            ReferenceTimestamp::from_millis_since_unix_epoch(1636162232627.0),
+            SamplingInterval::from_millis(1),
+        );
+        let process = profile.add_process("test", 123, Timestamp::from_millis_since_reference(0.0));
+        let thread = profile.add_thread(
+            process,
+            123,
+            Timestamp::from_millis_since_reference(0.0),
+            true,
+        );
+        profile.add_sample(
+            thread,
+            Timestamp::from_millis_since_reference(1.5),
+            std::iter::empty(),
+            CpuDelta::from_micros(700),
+            1,
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let compact_path = dir.path().join("profile.fxpc");
+        save_profile(&profile, &compact_path).unwrap();
+        let json_path = dir.path().join("profile.json");
+        save_profile(&profile, &json_path).unwrap();
+
+        // JSON files are left alone.
+        let json_file = BufReader::new(File::open(&json_path).unwrap());
+        assert!(convert_compact_profile(json_file).unwrap().is_none());
+
+        let compact_file = BufReader::new(File::open(&compact_path).unwrap());
+        let converted = convert_compact_profile(compact_file).unwrap().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(converted.path()).unwrap(),
+            std::fs::read_to_string(&json_path).unwrap()
+        );
+    }
+}
@@ -0,0 +1,72 @@
+use std::ffi::OsString;
+
+use fxprof_processed_profile::{ExtraProfileInfoSection, Profile};
+
+/// Stores information about a recording in the profile metadata: the profiled
+/// command line, the OS and CPU of this machine, and the samply version.
+///
+/// `command_line` is `None` when attaching to an existing process.
+pub fn add_recording_metadata(profile: &mut Profile, command_line: Option<&[OsString]>) {
+    if let Some(command_line) = command_line {
+        let arguments: Vec<_> = command_line
+            .iter()
+            .map(|arg| arg.to_string_lossy())
+            .collect();
+        profile.set_arguments(&arguments.join(" "));
+    }
+    if let Some(os_name_and_version) = os_name_and_version() {
+        profile.set_os_cpu(&os_name_and_version);
+    }
+    profile.set_platform(PLATFORM);
+    if let Some(cpu_name) = cpu_name() {
+        profile.set_cpu_name(&cpu_name);
+    }
+
+    let mut section = ExtraProfileInfoSection::new("Recording");
+    section.add_string_entry("samply version", env!("CARGO_PKG_VERSION"));
+    profile.add_extra_info_section(section);
+}
+
+#[cfg(target_os = "linux")]
+const PLATFORM: &str = "Linux";
+
+#[cfg(target_os = "macos")]
+const PLATFORM: &str = "Macintosh";
+
+#[cfg(target_os = "linux")]
+fn os_name_and_version() -> Option<String> {
+    let info = uname::uname().ok()?;
+    Some(format!("{} {}", info.sysname, info.release))
+}
+
+#[cfg(target_os = "linux")]
+fn cpu_name() -> Option<String> {
+    let cpuinfo = std::fs::read_to_string("/proc/cpuinfo").ok()?;
+    cpuinfo.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        if key.trim() == "model name" {
+            Some(value.trim().to_string())
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(target_os = "macos")]
+fn os_name_and_version() -> Option<String> {
+    use sysctl::Sysctl;
+    let version = sysctl::Ctl::new("kern.osproductversion")
+        .ok()?
+        .value_string()
+        .ok()?;
+    Some(format!("macOS {version}"))
+}
+
+#[cfg(target_os = "macos")]
+fn cpu_name() -> Option<String> {
+    use sysctl::Sysctl;
+    sysctl::Ctl::new("machdep.cpu.brand_string")
+        .ok()?
+        .value_string()
+        .ok()
+}
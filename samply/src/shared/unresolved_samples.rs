@@ -2,12 +2,13 @@ use std::collections::hash_map::Entry;
 
 use fxprof_processed_profile::{CpuDelta, FrameInfo, ThreadHandle, Timestamp};
 
+use super::chunked_vec::ChunkedVec;
 use super::process_sample_data::RssStatMember;
 use super::types::{FastHashMap, StackFrame, StackMode};
 
 #[derive(Debug, Clone, Default)]
 pub struct UnresolvedSamples {
-    samples_and_markers: Vec<UnresolvedSampleOrMarker>,
+    samples_and_markers: ChunkedVec<UnresolvedSampleOrMarker>,
     prev_sample_info_per_thread: FastHashMap<ThreadHandle, PreviousSampleInfo>,
 }
 
@@ -18,8 +19,8 @@ struct PreviousSampleInfo {
 }
 
 impl UnresolvedSamples {
-    pub fn into_inner(self) -> Vec<UnresolvedSampleOrMarker> {
-        self.samples_and_markers
+    pub fn iter(&self) -> impl Iterator<Item = &UnresolvedSampleOrMarker> {
+        self.samples_and_markers.iter()
     }
 
     pub fn is_empty(&self) -> bool {
@@ -34,7 +35,7 @@ impl UnresolvedSamples {
         timestamp_mono: u64,
         stack: UnresolvedStackHandle,
         cpu_delta: CpuDelta,
-        weight: i32,
+        weight: i64,
         extra_label_frame: Option<FrameInfo>,
     ) {
         let sample_index = self.samples_and_markers.len();
@@ -62,14 +63,14 @@ impl UnresolvedSamples {
         thread_handle: ThreadHandle,
         timestamp: Timestamp,
         timestamp_mono: u64,
-        weight: i32,
+        weight: i64,
         extra_label_frame: Option<FrameInfo>,
     ) {
         match self.prev_sample_info_per_thread.entry(thread_handle) {
             Entry::Occupied(mut entry) => {
                 let sample_info = entry.get_mut();
                 if let Some(sample_index) = sample_info.prev_sample_index_if_zero_cpu {
-                    let sample = &mut self.samples_and_markers[sample_index];
+                    let sample = self.samples_and_markers.get_mut(sample_index).unwrap();
                     sample.timestamp = timestamp;
                     let SampleOrMarker::Sample(ref mut data) = &mut sample.sample_or_marker else { panic!() };
                     data.weight += weight;
@@ -176,7 +177,7 @@ pub enum SampleOrMarker {
 #[derive(Debug, Clone)]
 pub struct SampleData {
     pub cpu_delta: CpuDelta,
-    pub weight: i32,
+    pub weight: i64,
 }
 
 #[derive(Debug, Clone)]
@@ -209,7 +210,7 @@ impl UnresolvedStackHandle {
 
 #[derive(Debug, Clone, Default)]
 pub struct UnresolvedStacks {
-    pub stacks: Vec<(UnresolvedStackHandle, StackFrame)>, // (prefix, frame)
+    pub stacks: ChunkedVec<(UnresolvedStackHandle, StackFrame)>, // (prefix, frame)
     pub stack_lookup: FastHashMap<(UnresolvedStackHandle, StackFrame), UnresolvedStackHandle>, // (prefix, frame) -> stack index
 }
 
@@ -254,6 +255,16 @@ impl UnresolvedStacks {
         prefix
     }
 
+    /// A cheap copy of the stacks for a profile snapshot, which only needs to
+    /// convert stacks back. The copy doesn't share the lookup table, so stacks
+    /// which are converted on it won't be deduplicated against existing ones.
+    pub fn snapshot(&self) -> Self {
+        Self {
+            stacks: self.stacks.clone(),
+            stack_lookup: FastHashMap::default(),
+        }
+    }
+
     // Appends the stack to `buf`, starting with the callee-most frame.
     pub fn convert_back(&self, mut stack_index: UnresolvedStackHandle, buf: &mut Vec<StackFrame>) {
         while stack_index != UnresolvedStackHandle::EMPTY {
@@ -0,0 +1,528 @@
+use serde_json::{json, Map, Value};
+use tokio::sync::{OnceCell, Semaphore};
+use wholesym::samply_api::{Job, Lib, Request};
+
+use std::collections::{BTreeSet, HashMap};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+/// Runs symbolication API queries on a bounded number of workers, and merges
+/// concurrent `/symbolicate/v5` requests for the same library.
+///
+/// The profiler front-end sends a burst of symbolication requests when it
+/// opens a large profile, and often several of them need the same library.
+/// Every `/symbolicate/v5` request is split up by library. Only one lookup
+/// runs per library at a time. Addresses which the running lookup already
+/// covers are taken from its result, and all other addresses are collected
+/// in the library's next lookup, which starts once the running one is done.
+/// This way, the library's symbols are only loaded once for all the requests
+/// which come in while it's being looked up. The per-library results are then
+/// put back together into one response per request.
+pub struct SymbolicationBatcher {
+    workers: Semaphore,
+    lookups: Mutex<HashMap<Lib, LibLookups>>,
+}
+
+/// The lookups of one library.
+#[derive(Default)]
+struct LibLookups {
+    /// Held while a lookup for the library runs.
+    running_lock: Arc<tokio::sync::Mutex<()>>,
+    running: Option<Arc<Batch>>,
+    /// The next lookup, which more addresses can be added to until it starts.
+    pending: Option<Arc<Batch>>,
+}
+
+/// A lookup of addresses in one library.
+#[derive(Default)]
+struct Batch {
+    addresses: Mutex<BTreeSet<u32>>,
+    result: OnceCell<Arc<LibResult>>,
+}
+
+/// The symbolication result for the requested addresses of one library.
+#[derive(Debug, Default)]
+struct LibResult {
+    found: bool,
+    errors: Option<Value>,
+    /// The response frame for each address, without the frame index.
+    frames: HashMap<u32, Value>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum SymbolicationRequestError {
+    #[error("Could not parse the symbolication request: {0}")]
+    Parse(#[from] serde_json::Error),
+
+    #[error("Stack frame module index {0} is beyond the memoryMap")]
+    ModuleIndexOutOfRange(u32),
+}
+
+impl SymbolicationBatcher {
+    /// Creates a batcher which runs at most `worker_count` queries at the same time.
+    pub fn new(worker_count: usize) -> Self {
+        Self {
+            workers: Semaphore::new(worker_count.max(1)),
+            lookups: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Runs a query for one of the other APIs, such as `/source/v1`, on a worker.
+    pub async fn run<F: Future<Output = String>>(&self, query: F) -> String {
+        let _permit = self.workers.acquire().await.unwrap();
+        query.await
+    }
+
+    /// Answers a `/symbolicate/v5` request. `query` runs a `/symbolicate/v5`
+    /// request for a single library and returns the JSON response.
+    pub async fn symbolicate<Q, F>(
+        &self,
+        request_json: &str,
+        query: Q,
+    ) -> Result<String, SymbolicationRequestError>
+    where
+        Q: Fn(String) -> F,
+        F: Future<Output = String>,
+    {
+        let request: Request = serde_json::from_str(request_json)?;
+        let jobs: Vec<&Job> = request.jobs().collect();
+
+        let mut addresses_per_lib: HashMap<&Lib, BTreeSet<u32>> = HashMap::new();
+        for job in &jobs {
+            for frame in job.stacks.iter().flat_map(|stack| &stack.0) {
+                let lib = job.memory_map.get(frame.module_index as usize).ok_or(
+                    SymbolicationRequestError::ModuleIndexOutOfRange(frame.module_index),
+                )?;
+                addresses_per_lib
+                    .entry(lib)
+                    .or_default()
+                    .insert(frame.address);
+            }
+        }
+
+        let query = &query;
+        let lookups = addresses_per_lib
+            .into_iter()
+            .map(|(lib, addresses)| async move {
+                let result = self.lookup(lib, addresses, query).await;
+                (lib, result)
+            });
+        let lib_results: HashMap<&Lib, Arc<LibResult>> = futures::future::join_all(lookups)
+            .await
+            .into_iter()
+            .collect();
+
+        let results: Vec<Value> = jobs
+            .iter()
+            .map(|job| result_for_job(job, &lib_results))
+            .collect();
+        Ok(json!({ "results": results }).to_string())
+    }
+
+    async fn lookup<Q, F>(
+        &self,
+        lib: &Lib,
+        mut addresses: BTreeSet<u32>,
+        query: &Q,
+    ) -> Arc<LibResult>
+    where
+        Q: Fn(String) -> F,
+        F: Future<Output = String>,
+    {
+        let (running, pending) = {
+            let mut lookups = self.lookups.lock().unwrap();
+            let lib_lookups = lookups.entry(lib.clone()).or_default();
+            let running = lib_lookups.running.clone();
+            if let Some(running) = &running {
+                let running_addresses = running.addresses.lock().unwrap();
+                addresses.retain(|address| !running_addresses.contains(address));
+            }
+            let pending = if addresses.is_empty() {
+                None
+            } else {
+                let batch = lib_lookups.pending.get_or_insert_with(Default::default);
+                batch.addresses.lock().unwrap().extend(addresses);
+                Some(batch.clone())
+            };
+            (running, pending)
+        };
+
+        let mut results = Vec::new();
+        for batch in running.iter().chain(&pending) {
+            let result = batch
+                .result
+                .get_or_init(|| self.run_batch(lib, batch, query))
+                .await;
+            results.push(result.clone());
+        }
+        match results.as_slice() {
+            [result] => result.clone(),
+            _ => Arc::new(LibResult::merge(&results)),
+        }
+    }
+
+    /// Looks up the addresses of `batch`, once no other lookup for `lib` is
+    /// running and a worker is available.
+    async fn run_batch<Q, F>(&self, lib: &Lib, batch: &Arc<Batch>, query: &Q) -> Arc<LibResult>
+    where
+        Q: Fn(String) -> F,
+        F: Future<Output = String>,
+    {
+        let running_lock = {
+            let mut lookups = self.lookups.lock().unwrap();
+            lookups.entry(lib.clone()).or_default().running_lock.clone()
+        };
+        let _running_guard = running_lock.lock().await;
+        let _permit = self.workers.acquire().await.unwrap();
+
+        // Start the lookup. Requests which come in from now on either use its
+        // result or start the next lookup.
+        let addresses: Vec<u32> = {
+            let mut lookups = self.lookups.lock().unwrap();
+            let lib_lookups = lookups.entry(lib.clone()).or_default();
+            if let Some(pending) = &lib_lookups.pending {
+                if Arc::ptr_eq(pending, batch) {
+                    lib_lookups.pending = None;
+                }
+            }
+            lib_lookups.running = Some(batch.clone());
+            let addresses = batch.addresses.lock().unwrap();
+            addresses.iter().copied().collect()
+        };
+
+        let stack: Vec<(u32, u32)> = addresses.iter().map(|&address| (0, address)).collect();
+        let request = json!({
+            "memoryMap": [[&lib.debug_name, &lib.breakpad_id]],
+            "stacks": [stack],
+        });
+        let response = query(request.to_string()).await;
+        let result = Arc::new(lib_result_from_response(lib, &addresses, &response));
+
+        let mut lookups = self.lookups.lock().unwrap();
+        if let Some(lib_lookups) = lookups.get_mut(lib) {
+            lib_lookups.running = None;
+            if lib_lookups.pending.is_none() {
+                lookups.remove(lib);
+            }
+        }
+        result
+    }
+}
+
+impl LibResult {
+    /// Combines the results of several lookups for the same library.
+    fn merge(results: &[Arc<LibResult>]) -> LibResult {
+        let mut merged = LibResult {
+            found: results.iter().all(|result| result.found),
+            ..Default::default()
+        };
+        for result in results {
+            if merged.errors.is_none() {
+                merged.errors = result.errors.clone();
+            }
+            merged.frames.extend(
+                result
+                    .frames
+                    .iter()
+                    .map(|(&address, frame)| (address, frame.clone())),
+            );
+        }
+        merged
+    }
+}
+
+/// Extracts the result for `lib` from the response to a single-library request
+/// whose only stack contains `addresses`.
+fn lib_result_from_response(lib: &Lib, addresses: &[u32], response: &str) -> LibResult {
+    let module_key = module_key(lib);
+    let invalid_response = |message: String| LibResult {
+        found: false,
+        errors: Some(json!([{ "name": "InvalidResponse", "message": message }])),
+        frames: HashMap::new(),
+    };
+    let mut response: Value = match serde_json::from_str(response) {
+        Ok(response) => response,
+        Err(e) => return invalid_response(e.to_string()),
+    };
+    let Some(mut result) = response.pointer_mut("/results/0").map(Value::take) else {
+        return invalid_response(format!("No results in the response: {response}"));
+    };
+    let mut frames = HashMap::new();
+    if let Some(Value::Array(stack)) = result.pointer_mut("/stacks/0").map(Value::take) {
+        for (&address, mut frame) in addresses.iter().zip(stack) {
+            if let Value::Object(frame) = &mut frame {
+                frame.remove("frame");
+            }
+            frames.insert(address, frame);
+        }
+    }
+    LibResult {
+        found: result["found_modules"][&module_key].as_bool() == Some(true),
+        errors: result
+            .get_mut("module_errors")
+            .and_then(|errors| errors.get_mut(&module_key))
+            .map(Value::take),
+        frames,
+    }
+}
+
+/// The key of a library in the `found_modules` and `module_errors` objects of
+/// a response.
+fn module_key(lib: &Lib) -> String {
+    format!("{}/{}", lib.debug_name, lib.breakpad_id)
+}
+
+fn result_for_job(job: &Job, lib_results: &HashMap<&Lib, Arc<LibResult>>) -> Value {
+    let mut found_modules = Map::new();
+    let mut module_errors = Map::new();
+    for lib in &job.memory_map {
+        if let Some(lib_result) = lib_results.get(lib) {
+            found_modules.insert(module_key(lib), Value::Bool(lib_result.found));
+            if let Some(errors) = &lib_result.errors {
+                module_errors.insert(module_key(lib), errors.clone());
+            }
+        }
+    }
+
+    let stacks: Vec<Value> = job
+        .stacks
+        .iter()
+        .map(|stack| {
+            let frames = stack
+                .0
+                .iter()
+                .enumerate()
+                .map(|(frame_index, stack_frame)| {
+                    let lib = &job.memory_map[stack_frame.module_index as usize];
+                    let address = stack_frame.address;
+                    let mut frame = match lib_results[lib].frames.get(&address) {
+                        Some(Value::Object(frame)) => frame.clone(),
+                        _ => {
+                            let mut frame = Map::new();
+                            frame.insert("module_offset".into(), format!("{address:#x}").into());
+                            frame.insert("module".into(), lib.debug_name.clone().into());
+                            frame
+                        }
+                    };
+                    frame.insert("frame".into(), frame_index.into());
+                    Value::Object(frame)
+                })
+                .collect();
+            Value::Array(frames)
+        })
+        .collect();
+
+    let mut result = json!({
+        "stacks": stacks,
+        "found_modules": found_modules,
+    });
+    if !module_errors.is_empty() {
+        result["module_errors"] = Value::Object(module_errors);
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::sync::Notify;
+
+    /// Answers a single-library request like the symbolication API does, with
+    /// a function name derived from the address.
+    fn fake_response(request: &str) -> String {
+        let request: Value = serde_json::from_str(request).unwrap();
+        let debug_name = request["memoryMap"][0][0].as_str().unwrap();
+        let module_key = format!(
+            "{}/{}",
+            debug_name,
+            request["memoryMap"][0][1].as_str().unwrap()
+        );
+        if debug_name == "missing.so" {
+            return json!({ "results": [{
+                "stacks": [request["stacks"][0].as_array().unwrap().iter().enumerate().map(|(i, frame)| json!({
+                    "frame": i,
+                    "module_offset": format!("{:#x}", frame[1].as_u64().unwrap()),
+                    "module": debug_name,
+                })).collect::<Vec<_>>()],
+                "found_modules": { module_key.clone(): false },
+                "module_errors": { module_key: [{ "name": "NotFound", "message": "not found" }] },
+            }]})
+            .to_string();
+        }
+        let frames: Vec<Value> = request["stacks"][0]
+            .as_array()
+            .unwrap()
+            .iter()
+            .enumerate()
+            .map(|(i, frame)| {
+                let address = frame[1].as_u64().unwrap();
+                json!({
+                    "frame": i,
+                    "module_offset": format!("{address:#x}"),
+                    "module": debug_name,
+                    "function": format!("func_{address:x}"),
+                    "function_offset": "0x0",
+                })
+            })
+            .collect();
+        json!({ "results": [{
+            "stacks": [frames],
+            "found_modules": { module_key: true },
+        }]})
+        .to_string()
+    }
+
+    #[tokio::test]
+    async fn reassemble_response() {
+        let batcher = SymbolicationBatcher::new(4);
+        let request = r#"{
+            "jobs": [{
+                "memoryMap": [["app", "AAAA0"], ["missing.so", "BBBB0"]],
+                "stacks": [[[0, 4096], [1, 32], [0, 8192]]]
+            }, {
+                "memoryMap": [["app", "AAAA0"]],
+                "stacks": [[[0, 8192]], []]
+            }]
+        }"#;
+        let response = batcher
+            .symbolicate(request, |request| async move { fake_response(&request) })
+            .await
+            .unwrap();
+        let response: Value = serde_json::from_str(&response).unwrap();
+        assert_eq!(
+            response,
+            json!({ "results": [{
+                "stacks": [[
+                    { "frame": 0, "module_offset": "0x1000", "module": "app", "function": "func_1000", "function_offset": "0x0" },
+                    { "frame": 1, "module_offset": "0x20", "module": "missing.so" },
+                    { "frame": 2, "module_offset": "0x2000", "module": "app", "function": "func_2000", "function_offset": "0x0" },
+                ]],
+                "found_modules": { "app/AAAA0": true, "missing.so/BBBB0": false },
+                "module_errors": { "missing.so/BBBB0": [{ "name": "NotFound", "message": "not found" }] },
+            }, {
+                "stacks": [[
+                    { "frame": 0, "module_offset": "0x2000", "module": "app", "function": "func_2000", "function_offset": "0x0" },
+                ], []],
+                "found_modules": { "app/AAAA0": true },
+            }]})
+        );
+
+        assert!(matches!(
+            batcher
+                .symbolicate("{\"jobs\": 5}", |_| async { String::new() })
+                .await,
+            Err(SymbolicationRequestError::Parse(_))
+        ));
+        assert!(matches!(
+            batcher
+                .symbolicate(
+                    r#"{"memoryMap": [["app", "AAAA0"]], "stacks": [[[1, 16]]]}"#,
+                    |_| async { String::new() }
+                )
+                .await,
+            Err(SymbolicationRequestError::ModuleIndexOutOfRange(1))
+        ));
+    }
+
+    #[tokio::test]
+    async fn coalesce_requests_for_the_same_library() {
+        let batcher = SymbolicationBatcher::new(1);
+        let unblock = Notify::new();
+        let query_count = AtomicUsize::new(0);
+        let query = |request: String| {
+            let unblock = &unblock;
+            let query_count = &query_count;
+            async move {
+                query_count.fetch_add(1, Ordering::SeqCst);
+                if request.contains("slow") {
+                    unblock.notified().await;
+                }
+                fake_response(&request)
+            }
+        };
+
+        // The first request occupies the only worker, so the other two have
+        // to wait and are merged into one lookup.
+        let (slow, first, second, ()) = tokio::join!(
+            batcher.symbolicate(
+                r#"{"memoryMap": [["slow", "0"]], "stacks": [[[0, 1]]]}"#,
+                query
+            ),
+            batcher.symbolicate(
+                r#"{"memoryMap": [["app", "0"]], "stacks": [[[0, 16]]]}"#,
+                query
+            ),
+            batcher.symbolicate(
+                r#"{"memoryMap": [["app", "0"]], "stacks": [[[0, 32]]]}"#,
+                query
+            ),
+            async {
+                tokio::task::yield_now().await;
+                unblock.notify_one();
+            }
+        );
+        assert!(slow.is_ok());
+        assert!(first.unwrap().contains("func_10"));
+        assert!(second.unwrap().contains("func_20"));
+        assert_eq!(query_count.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn join_the_running_lookup_for_a_library() {
+        let batcher = SymbolicationBatcher::new(4);
+        let unblock = Notify::new();
+        let query_count = AtomicUsize::new(0);
+        let running_queries = AtomicUsize::new(0);
+        let max_running_queries = AtomicUsize::new(0);
+        let query = |request: String| {
+            let unblock = &unblock;
+            let query_count = &query_count;
+            let running_queries = &running_queries;
+            let max_running_queries = &max_running_queries;
+            async move {
+                let running = running_queries.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running_queries.fetch_max(running, Ordering::SeqCst);
+                if query_count.fetch_add(1, Ordering::SeqCst) == 0 {
+                    unblock.notified().await;
+                }
+                running_queries.fetch_sub(1, Ordering::SeqCst);
+                fake_response(&request)
+            }
+        };
+
+        // The first request's lookup is running when the others come in. The
+        // second one only needs its result, and the addresses of the last two
+        // which it doesn't cover are looked up together once it's done.
+        let (first, second, third, fourth, ()) = tokio::join!(
+            batcher.symbolicate(
+                r#"{"memoryMap": [["app", "0"]], "stacks": [[[0, 16]]]}"#,
+                query
+            ),
+            batcher.symbolicate(
+                r#"{"memoryMap": [["app", "0"]], "stacks": [[[0, 16]]]}"#,
+                query
+            ),
+            batcher.symbolicate(
+                r#"{"memoryMap": [["app", "0"]], "stacks": [[[0, 32]]]}"#,
+                query
+            ),
+            batcher.symbolicate(
+                r#"{"memoryMap": [["app", "0"]], "stacks": [[[0, 16], [0, 48]]]}"#,
+                query
+            ),
+            async {
+                tokio::task::yield_now().await;
+                unblock.notify_one();
+            }
+        );
+        assert!(first.unwrap().contains("func_10"));
+        assert!(second.unwrap().contains("func_10"));
+        assert!(third.unwrap().contains("func_20"));
+        let fourth = fourth.unwrap();
+        assert!(fourth.contains("func_10") && fourth.contains("func_30"));
+        assert_eq!(query_count.load(Ordering::SeqCst), 2);
+        assert_eq!(max_running_queries.load(Ordering::SeqCst), 1);
+        assert!(batcher.lookups.lock().unwrap().is_empty());
+    }
+}
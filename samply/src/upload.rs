@@ -0,0 +1,396 @@
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use fxprof_processed_profile::{ProfileReadError, ProfileView};
+use hyper::{header, StatusCode};
+use serde_json::Value;
+use wholesym::debugid::DebugId;
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::config::SymbolsConfig;
+use crate::report::{symbolicate, FunctionNames};
+use crate::server::{default_profiler_origin, profiler_url};
+use crate::UploadOptions;
+
+#[derive(thiserror::Error, Debug)]
+pub enum UploadError {
+    #[error("Could not read the profile {0:?}: {1}")]
+    Read(PathBuf, #[source] std::io::Error),
+
+    #[error("Could not parse the profile {0:?}: {1}")]
+    Parse(PathBuf, #[source] serde_json::Error),
+
+    #[error("Could not read the profile {0:?} for symbolication: {1}")]
+    ReadForSymbolication(PathBuf, #[source] ProfileReadError),
+
+    #[error("Could not compress the profile: {0}")]
+    Compress(#[source] std::io::Error),
+
+    #[error("Invalid upload URL {0:?}: only http:// and https:// URLs are supported")]
+    InvalidUrl(String),
+
+    #[error("Could not upload the profile to {0}: {1}")]
+    Request(String, #[source] reqwest::Error),
+
+    #[error("Uploading the profile to {0} failed with status {1}")]
+    Status(String, StatusCode),
+}
+
+/// Uploads the profile at `path` with an HTTP PUT request, gzip-compressed,
+/// and returns the profiler URL which loads the uploaded profile.
+///
+/// If `to` ends with a slash, the file name of the profile is appended to it.
+#[tokio::main]
+pub async fn upload_profile(
+    path: &Path,
+    to: &str,
+    symbols: &SymbolsConfig,
+    options: &UploadOptions,
+) -> Result<String, UploadError> {
+    let json = read_profile_json(path)?;
+    let mut profile: Value =
+        serde_json::from_slice(&json).map_err(|e| UploadError::Parse(path.to_owned(), e))?;
+    if options.symbolicate {
+        let profile_view = ProfileView::from_slice(&json)
+            .map_err(|e| UploadError::ReadForSymbolication(path.to_owned(), e))?;
+        let (function_names, found_all_libs) = symbolicate(&profile_view, symbols).await;
+        apply_function_names(&mut profile, &function_names, found_all_libs);
+    }
+    if options.strip {
+        strip_profile(&mut profile);
+    }
+
+    let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+    serde_json::to_writer(&mut encoder, &profile).map_err(|e| UploadError::Compress(e.into()))?;
+    let body = encoder.finish().map_err(UploadError::Compress)?;
+
+    let url = upload_url(path, to);
+    put(&url, body).await?;
+    Ok(profiler_url(&default_profiler_origin(), &url, None))
+}
+
+/// Reads the profile JSON, and decompresses it if it is gzipped.
+fn read_profile_json(path: &Path) -> Result<Vec<u8>, UploadError> {
+    let bytes = std::fs::read(path).map_err(|e| UploadError::Read(path.to_owned(), e))?;
+    if !bytes.starts_with(&[0x1f, 0x8b]) {
+        return Ok(bytes);
+    }
+    let mut json = Vec::new();
+    GzDecoder::new(&bytes[..])
+        .read_to_end(&mut json)
+        .map_err(|e| UploadError::Read(path.to_owned(), e))?;
+    Ok(json)
+}
+
+/// The URL to upload to. If `to` is a "directory" URL ending in a slash, the
+/// profile's file name is appended, with a `.gz` extension.
+fn upload_url(path: &Path, to: &str) -> String {
+    if !to.ends_with('/') {
+        return to.to_string();
+    }
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "profile.json".to_string());
+    match file_name.ends_with(".gz") {
+        true => format!("{to}{file_name}"),
+        false => format!("{to}{file_name}.gz"),
+    }
+}
+
+async fn put(url: &str, body: Vec<u8>) -> Result<(), UploadError> {
+    let parsed_url =
+        reqwest::Url::parse(url).map_err(|_| UploadError::InvalidUrl(url.to_string()))?;
+    if !matches!(parsed_url.scheme(), "http" | "https") {
+        return Err(UploadError::InvalidUrl(url.to_string()));
+    }
+    let response = reqwest::Client::new()
+        .put(parsed_url)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::CONTENT_ENCODING, "gzip")
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| UploadError::Request(url.to_string(), e))?;
+    if !response.status().is_success() {
+        return Err(UploadError::Status(url.to_string(), response.status()));
+    }
+    Ok(())
+}
+
+/// Replaces the names of the native functions in the profile with their
+/// symbols. Frames in the same function end up with the same func, so that
+/// they're merged in the call tree. Inlined functions are not expanded; frames
+/// get the name of the outer function.
+///
+/// The profile is only marked as symbolicated if `found_all_libs` is true, so
+/// that the profiler still symbolicates the libraries which weren't resolved.
+fn apply_function_names(profile: &mut Value, function_names: &FunctionNames, found_all_libs: bool) {
+    let lib_debug_ids: Vec<Option<DebugId>> = match profile["libs"].as_array() {
+        Some(libs) => libs
+            .iter()
+            .map(|lib| DebugId::from_breakpad(lib["breakpadId"].as_str()?).ok())
+            .collect(),
+        None => return,
+    };
+    if let Some(threads) = profile["threads"].as_array_mut() {
+        for thread in threads {
+            symbolicate_thread(thread, &lib_debug_ids, function_names);
+        }
+    }
+    if !found_all_libs {
+        return;
+    }
+    if let Some(meta) = profile["meta"].as_object_mut() {
+        meta.insert("symbolicated".into(), Value::Bool(true));
+    }
+}
+
+/// Rebuilds the func table of a thread with the symbolicated function names.
+/// Returns `None` and leaves the thread unchanged if its tables don't have the
+/// expected format.
+fn symbolicate_thread(
+    thread: &mut Value,
+    lib_debug_ids: &[Option<DebugId>],
+    function_names: &FunctionNames,
+) -> Option<()> {
+    let frame_addresses = thread["frameTable"]["address"].as_array()?;
+    let frame_funcs = thread["frameTable"]["func"].as_array()?;
+    let func_resources = thread["funcTable"]["resource"].as_array()?;
+    let resource_libs = thread["resourceTable"]["lib"].as_array()?;
+    let mut strings: Vec<String> = serde_json::from_value(thread["stringArray"].clone()).ok()?;
+    let mut string_indexes: HashMap<String, usize> = strings
+        .iter()
+        .enumerate()
+        .map(|(i, s)| (s.clone(), i))
+        .collect();
+
+    // New funcs are keyed by the old func for unsymbolicated frames, and by
+    // resource and name for symbolicated frames.
+    #[derive(PartialEq, Eq, Hash)]
+    enum FuncKey {
+        Unchanged(usize),
+        Symbolicated(Option<u64>, usize),
+    }
+    let mut new_func_indexes: HashMap<FuncKey, usize> = HashMap::new();
+    // For each new func: the old func whose columns it copies, and the new name.
+    let mut new_funcs: Vec<(usize, Option<usize>)> = Vec::new();
+    let mut new_frame_funcs = Vec::with_capacity(frame_funcs.len());
+    for (address, func) in frame_addresses.iter().zip(frame_funcs) {
+        let func = func.as_u64()? as usize;
+        let resource = func_resources.get(func)?.as_u64();
+        let debug_id = resource
+            .and_then(|resource| resource_libs.get(resource as usize)?.as_u64())
+            .and_then(|lib| *lib_debug_ids.get(lib as usize)?);
+        let name = match (debug_id, address.as_i64()) {
+            (Some(debug_id), Some(address)) if address >= 0 => function_names
+                .get(&(debug_id, address as u32))
+                .and_then(|names| names.last()),
+            _ => None,
+        };
+        let (key, name_index) = match name {
+            Some(name) => {
+                let name_index = *string_indexes.entry(name.clone()).or_insert_with(|| {
+                    strings.push(name.clone());
+                    strings.len() - 1
+                });
+                (
+                    FuncKey::Symbolicated(resource, name_index),
+                    Some(name_index),
+                )
+            }
+            None => (FuncKey::Unchanged(func), None),
+        };
+        let new_func = *new_func_indexes.entry(key).or_insert_with(|| {
+            new_funcs.push((func, name_index));
+            new_funcs.len() - 1
+        });
+        new_frame_funcs.push(Value::from(new_func));
+    }
+
+    let func_table = thread["funcTable"].as_object_mut()?;
+    for (column_name, column) in func_table.iter_mut() {
+        let Value::Array(old_column) = column else {
+            continue;
+        };
+        let new_column = new_funcs
+            .iter()
+            .map(|&(old_func, name_index)| match name_index {
+                Some(name_index) if column_name == "name" => Value::from(name_index),
+                _ => old_column.get(old_func).cloned().unwrap_or(Value::Null),
+            })
+            .collect();
+        *column = Value::Array(new_column);
+    }
+    func_table.insert("length".into(), Value::from(new_funcs.len()));
+    thread["frameTable"]["func"] = Value::Array(new_frame_funcs);
+    thread["stringArray"] = Value::from(strings);
+    Some(())
+}
+
+/// Removes the data which isn't needed to look at the samples: markers,
+/// counters, the profiler overhead, and the local paths of the libraries.
+fn strip_profile(profile: &mut Value) {
+    if let Some(profile) = profile.as_object_mut() {
+        profile.remove("counters");
+        profile.remove("profilerOverhead");
+    }
+    if let Some(libs) = profile["libs"].as_array_mut() {
+        for lib in libs {
+            lib["path"] = lib["name"].clone();
+            lib["debugPath"] = lib["debugName"].clone();
+        }
+    }
+    if let Some(threads) = profile["threads"].as_array_mut() {
+        for thread in threads {
+            if let Some(markers) = thread["markers"].as_object_mut() {
+                for column in markers.values_mut() {
+                    *column = match column {
+                        Value::Array(_) => Value::Array(Vec::new()),
+                        _ => Value::from(0),
+                    };
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Method, Request};
+    use serde_json::json;
+    use std::convert::Infallible;
+    use std::sync::{Arc, Mutex};
+
+    fn test_profile() -> Value {
+        json!({
+            "meta": { "symbolicated": false },
+            "libs": [
+                { "name": "app", "path": "/bin/app", "debugName": "app", "debugPath": "/bin/app", "breakpadId": "11111111111111111111111111111111A" },
+                { "name": "libc.so", "path": "/lib/libc.so", "debugName": "libc.so", "debugPath": "/lib/libc.so", "breakpadId": "22222222222222222222222222222222B" },
+            ],
+            "counters": [],
+            "threads": [{
+                "frameTable": { "length": 4, "address": [16, 32, 48, -1], "func": [0, 1, 2, 3] },
+                "funcTable": {
+                    "length": 4,
+                    "name": [0, 1, 2, 4],
+                    "isJS": [false, false, false, false],
+                    "resource": [0, 0, 1, null],
+                },
+                "resourceTable": { "length": 2, "lib": [0, 1], "name": [5, 3] },
+                "markers": { "length": 1, "name": [4], "data": [null] },
+                "stringArray": ["0x10", "0x20", "0x30", "libc.so", "label", "app"],
+            }],
+        })
+    }
+
+    #[test]
+    fn symbolicate_and_strip() {
+        let app = DebugId::from_breakpad("11111111111111111111111111111111A").unwrap();
+        let mut function_names = FunctionNames::new();
+        function_names.insert((app, 16), vec!["inlined".to_string(), "main".to_string()]);
+        function_names.insert((app, 32), vec!["main".to_string()]);
+
+        let mut profile = test_profile();
+        apply_function_names(&mut profile, &function_names, false);
+        assert_eq!(profile["meta"]["symbolicated"], false);
+
+        let mut profile = test_profile();
+        apply_function_names(&mut profile, &function_names, true);
+        strip_profile(&mut profile);
+
+        assert_eq!(
+            profile,
+            json!({
+                "meta": { "symbolicated": true },
+                "libs": [
+                    { "name": "app", "path": "app", "debugName": "app", "debugPath": "app", "breakpadId": "11111111111111111111111111111111A" },
+                    { "name": "libc.so", "path": "libc.so", "debugName": "libc.so", "debugPath": "libc.so", "breakpadId": "22222222222222222222222222222222B" },
+                ],
+                "threads": [{
+                    "frameTable": { "length": 4, "address": [16, 32, 48, -1], "func": [0, 0, 1, 2] },
+                    "funcTable": {
+                        "length": 3,
+                        "name": [6, 2, 4],
+                        "isJS": [false, false, false],
+                        "resource": [0, 1, null],
+                    },
+                    "resourceTable": { "length": 2, "lib": [0, 1], "name": [5, 3] },
+                    "markers": { "length": 0, "name": [], "data": [] },
+                    "stringArray": ["0x10", "0x20", "0x30", "libc.so", "label", "app", "main"],
+                }],
+            })
+        );
+    }
+
+    #[test]
+    fn upload_urls() {
+        let path = Path::new("/tmp/profiles/prof.json");
+        assert_eq!(
+            upload_url(path, "http://store/bucket/"),
+            "http://store/bucket/prof.json.gz"
+        );
+        assert_eq!(
+            upload_url(path, "http://store/bucket/latest.json.gz"),
+            "http://store/bucket/latest.json.gz"
+        );
+        assert_eq!(
+            upload_url(Path::new("prof.json.gz"), "http://store/"),
+            "http://store/prof.json.gz"
+        );
+    }
+
+    #[tokio::test]
+    async fn upload_to_local_server() {
+        // A stand-in for the object store, which keeps the uploaded body.
+        let uploads = Arc::new(Mutex::new(Vec::new()));
+        let server_uploads = uploads.clone();
+        let make_service = make_service_fn(move |_| {
+            let uploads = server_uploads.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req: Request<Body>| {
+                    let uploads = uploads.clone();
+                    async move {
+                        let method = req.method().clone();
+                        let path = req.uri().path().to_string();
+                        let body = hyper::body::to_bytes(req.into_body()).await?;
+                        let status = match path.as_str() {
+                            "/forbidden.json.gz" => StatusCode::FORBIDDEN,
+                            _ => StatusCode::CREATED,
+                        };
+                        uploads.lock().unwrap().push((method, path, body));
+                        let mut response = hyper::Response::new(Body::empty());
+                        *response.status_mut() = status;
+                        Ok::<_, hyper::Error>(response)
+                    }
+                }))
+            }
+        });
+        let server = hyper::Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let origin = format!("http://{}", server.local_addr());
+        tokio::spawn(server);
+
+        put(&format!("{origin}/prof.json.gz"), b"contents".to_vec())
+            .await
+            .unwrap();
+        assert!(matches!(
+            put(&format!("{origin}/forbidden.json.gz"), Vec::new()).await,
+            Err(UploadError::Status(_, StatusCode::FORBIDDEN))
+        ));
+        assert!(matches!(
+            put("ftp://store/prof.json.gz", Vec::new()).await,
+            Err(UploadError::InvalidUrl(_))
+        ));
+
+        let uploads = uploads.lock().unwrap();
+        assert_eq!(uploads.len(), 2);
+        assert_eq!(uploads[0].0, Method::PUT);
+        assert_eq!(uploads[0].1, "/prof.json.gz");
+        assert_eq!(&uploads[0].2[..], b"contents");
+    }
+}
@@ -0,0 +1,532 @@
+use anyhow::Context;
+use fxprof_processed_profile::{ProfileView, ThreadView};
+use serde_derive::{Deserialize, Serialize};
+use wholesym::debugid::DebugId;
+use wholesym::{CodeId, LibraryInfo, SymbolManager, SymbolManagerConfig};
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::process::Command;
+use std::str::FromStr;
+
+/// The per-function sample distribution of a program, measured over several
+/// iterations. This is what gets stored as the baseline.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Measurement {
+    /// The number of samples in each iteration.
+    pub samples_per_iteration: Vec<u64>,
+    /// The functions, keyed by "name (library)".
+    pub functions: BTreeMap<String, FunctionSamples>,
+}
+
+/// The share of a function in the samples of each iteration, in percent.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct FunctionSamples {
+    /// Samples in which the function was the innermost frame.
+    pub self_percent: Vec<f64>,
+    /// Samples in which the function was anywhere on the stack.
+    pub total_percent: Vec<f64>,
+}
+
+/// The limits for `compare`, read from a TOML file. All increases are
+/// absolute, in percentage points of an iteration's samples, except for
+/// `max-sample-count-increase`, which is relative, in percent.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields, default)]
+pub struct Thresholds {
+    pub max_self_increase: f64,
+    pub max_total_increase: f64,
+    /// The allowed increase of the number of samples per iteration, i.e. of
+    /// the run time. `None` disables the check.
+    pub max_sample_count_increase: Option<f64>,
+    /// A change only counts if it is larger than this many standard errors of
+    /// the difference, so that noisy functions don't fail the check.
+    pub noise_factor: f64,
+    /// Overrides for individual functions, keyed like [`Measurement::functions`].
+    pub functions: HashMap<String, FunctionThresholds>,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self {
+            max_self_increase: 1.0,
+            max_total_increase: 2.0,
+            max_sample_count_increase: Some(10.0),
+            noise_factor: 2.0,
+            functions: HashMap::new(),
+        }
+    }
+}
+
+/// A `[functions."<name>"]` table of the thresholds file.
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct FunctionThresholds {
+    pub max_self_increase: Option<f64>,
+    pub max_total_increase: Option<f64>,
+    /// Don't check this function at all.
+    #[serde(default)]
+    pub ignore: bool,
+}
+
+impl Thresholds {
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Could not read the thresholds file {path:?}"))?;
+        toml::from_str(&contents)
+            .with_context(|| format!("Could not parse the thresholds file {path:?}"))
+    }
+}
+
+/// The mean and spread of a value over the iterations.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stats {
+    pub mean: f64,
+    pub std_dev: f64,
+    pub count: usize,
+}
+
+impl Stats {
+    pub fn new(values: &[f64]) -> Self {
+        let count = values.len();
+        if count == 0 {
+            return Self {
+                mean: 0.0,
+                std_dev: 0.0,
+                count,
+            };
+        }
+        let mean = values.iter().sum::<f64>() / count as f64;
+        let std_dev = if count > 1 {
+            let variance =
+                values.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / (count - 1) as f64;
+            variance.sqrt()
+        } else {
+            0.0
+        };
+        Self {
+            mean,
+            std_dev,
+            count,
+        }
+    }
+
+    /// The standard error of the difference between the means of `self` and `other`.
+    fn std_error_of_difference(&self, other: &Stats) -> f64 {
+        let variance_of_mean = |s: &Stats| {
+            if s.count == 0 {
+                0.0
+            } else {
+                s.std_dev * s.std_dev / s.count as f64
+            }
+        };
+        (variance_of_mean(self) + variance_of_mean(other)).sqrt()
+    }
+}
+
+/// What a [`Change`] measures.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Metric {
+    SampleCount,
+    SelfPercent(String),
+    TotalPercent(String),
+}
+
+impl std::fmt::Display for Metric {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Metric::SampleCount => write!(f, "samples per iteration"),
+            Metric::SelfPercent(function) => write!(f, "self time of {function}"),
+            Metric::TotalPercent(function) => write!(f, "total time of {function}"),
+        }
+    }
+}
+
+/// A change between the baseline and the current measurement which is larger
+/// than the threshold and the noise.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub metric: Metric,
+    pub baseline: Stats,
+    pub current: Stats,
+    /// The allowed increase, in the unit of the metric.
+    pub limit: f64,
+}
+
+impl Change {
+    /// The change of the mean. For [`Metric::SampleCount`], this is relative,
+    /// in percent, and otherwise it's in percentage points.
+    pub fn difference(&self) -> f64 {
+        let difference = self.current.mean - self.baseline.mean;
+        match self.metric {
+            Metric::SampleCount if self.baseline.mean > 0.0 => {
+                difference / self.baseline.mean * 100.0
+            }
+            _ => difference,
+        }
+    }
+}
+
+/// The result of [`compare`].
+#[derive(Debug, Clone, Default)]
+pub struct Comparison {
+    pub regressions: Vec<Change>,
+    pub improvements: Vec<Change>,
+    /// The number of functions which were compared.
+    pub checked_function_count: usize,
+}
+
+impl Comparison {
+    pub fn passed(&self) -> bool {
+        self.regressions.is_empty()
+    }
+
+    fn check(
+        &mut self,
+        metric: Metric,
+        baseline: Stats,
+        current: Stats,
+        limit: f64,
+        noise_factor: f64,
+    ) {
+        let difference = current.mean - baseline.mean;
+        let noise = noise_factor * baseline.std_error_of_difference(&current);
+        let change = Change {
+            metric,
+            baseline,
+            current,
+            limit,
+        };
+        if difference.abs() <= noise {
+            return;
+        }
+        if change.difference() > limit {
+            self.regressions.push(change);
+        } else if change.difference() < -limit {
+            self.improvements.push(change);
+        }
+    }
+}
+
+/// Compares the current measurement against the baseline. Changes are
+/// reported if they exceed the limit from `thresholds` and are larger than
+/// the noise between iterations. Regressions and improvements are sorted by
+/// decreasing size.
+pub fn compare(
+    baseline: &Measurement,
+    current: &Measurement,
+    thresholds: &Thresholds,
+) -> Comparison {
+    let mut comparison = Comparison::default();
+
+    if let Some(limit) = thresholds.max_sample_count_increase {
+        let to_f64 = |counts: &[u64]| counts.iter().map(|&c| c as f64).collect::<Vec<_>>();
+        comparison.check(
+            Metric::SampleCount,
+            Stats::new(&to_f64(&baseline.samples_per_iteration)),
+            Stats::new(&to_f64(&current.samples_per_iteration)),
+            limit,
+            thresholds.noise_factor,
+        );
+    }
+
+    let function_names: BTreeSet<&String> = baseline
+        .functions
+        .keys()
+        .chain(current.functions.keys())
+        .collect();
+    let empty = FunctionSamples::default();
+    for name in function_names {
+        let overrides = thresholds.functions.get(name).cloned().unwrap_or_default();
+        if overrides.ignore {
+            continue;
+        }
+        comparison.checked_function_count += 1;
+        let baseline_samples = baseline.functions.get(name).unwrap_or(&empty);
+        let current_samples = current.functions.get(name).unwrap_or(&empty);
+        comparison.check(
+            Metric::SelfPercent(name.clone()),
+            function_stats(
+                &baseline_samples.self_percent,
+                baseline.samples_per_iteration.len(),
+            ),
+            function_stats(
+                &current_samples.self_percent,
+                current.samples_per_iteration.len(),
+            ),
+            overrides
+                .max_self_increase
+                .unwrap_or(thresholds.max_self_increase),
+            thresholds.noise_factor,
+        );
+        comparison.check(
+            Metric::TotalPercent(name.clone()),
+            function_stats(
+                &baseline_samples.total_percent,
+                baseline.samples_per_iteration.len(),
+            ),
+            function_stats(
+                &current_samples.total_percent,
+                current.samples_per_iteration.len(),
+            ),
+            overrides
+                .max_total_increase
+                .unwrap_or(thresholds.max_total_increase),
+            thresholds.noise_factor,
+        );
+    }
+
+    let by_size = |a: &Change, b: &Change| {
+        b.difference()
+            .abs()
+            .partial_cmp(&a.difference().abs())
+            .unwrap_or(std::cmp::Ordering::Equal)
+    };
+    comparison.regressions.sort_by(by_size);
+    comparison.improvements.sort_by(by_size);
+    comparison
+}
+
+/// The stats for a function's percentages. Functions which don't appear in a
+/// measurement had 0% in all of its iterations.
+fn function_stats(percentages: &[f64], iteration_count: usize) -> Stats {
+    if percentages.is_empty() {
+        Stats::new(&vec![0.0; iteration_count])
+    } else {
+        Stats::new(percentages)
+    }
+}
+
+/// Runs `samply record` on `command` and saves the profile to `output`.
+/// samply runs the command `iteration_count` times, each in a new process.
+pub fn record_profile(
+    samply: &Path,
+    iteration_count: u32,
+    rate: Option<f64>,
+    command: &[OsString],
+    output: &Path,
+) -> anyhow::Result<()> {
+    let mut samply_command = Command::new(samply);
+    samply_command
+        .arg("record")
+        .arg("--save-only")
+        .arg("--iteration-count")
+        .arg(iteration_count.to_string())
+        .arg("--output")
+        .arg(output);
+    if let Some(rate) = rate {
+        samply_command.arg("--rate").arg(rate.to_string());
+    }
+    samply_command.arg("--").args(command);
+
+    let status = samply_command
+        .status()
+        .with_context(|| format!("Could not run {samply:?}"))?;
+    if !status.success() {
+        anyhow::bail!("samply record failed with {status}");
+    }
+    Ok(())
+}
+
+/// Reads and symbolicates the profile at `path`, and computes the sample
+/// distribution for each process in it. Every process is treated as one
+/// iteration, so the profiled command shouldn't launch subprocesses.
+pub async fn measure_profile(path: &Path) -> anyhow::Result<Measurement> {
+    let file = File::open(path).with_context(|| format!("Could not open the profile {path:?}"))?;
+    let profile = ProfileView::from_reader(BufReader::new(file))
+        .with_context(|| format!("Could not read the profile {path:?}"))?;
+    let function_names = symbolicate(&profile).await;
+    Ok(measure(&profile, &function_names))
+}
+
+/// The function names for the native frames in a profile, keyed by library
+/// debug ID and relative address. The names of inlined functions come first,
+/// followed by the name of the outer function.
+type FunctionNames = HashMap<(DebugId, u32), Vec<String>>;
+
+/// Looks up the function names for all addresses in the profile's native
+/// frames, with the symbols from the binaries on this machine.
+async fn symbolicate(profile: &ProfileView) -> FunctionNames {
+    let symbol_manager = SymbolManager::with_config(SymbolManagerConfig::new());
+    for lib in profile.libs() {
+        symbol_manager.add_known_library(LibraryInfo {
+            debug_name: Some(lib.debug_name.clone()),
+            debug_id: Some(lib.debug_id),
+            debug_path: Some(lib.debug_path.clone()),
+            name: Some(lib.name.clone()),
+            code_id: lib
+                .code_id
+                .as_deref()
+                .and_then(|code_id| CodeId::from_str(code_id).ok()),
+            path: Some(lib.path.clone()),
+            arch: lib.arch.clone(),
+        });
+    }
+
+    let mut function_names = FunctionNames::new();
+    for (debug_id, (lib, addresses)) in profile.native_frame_addresses() {
+        match symbol_manager
+            .lookup_function_names(&lib.debug_name, debug_id, addresses)
+            .await
+        {
+            Ok(names) => function_names.extend(
+                names
+                    .into_iter()
+                    .map(|(address, names)| ((debug_id, address), names)),
+            ),
+            Err(err) => eprintln!("Could not find symbols for {}: {err}", lib.debug_name),
+        }
+    }
+    function_names
+}
+
+/// Computes the sample distribution of each process in the profile, in the
+/// order in which the processes appear in the profile. Processes without
+/// samples are skipped.
+fn measure(profile: &ProfileView, function_names: &FunctionNames) -> Measurement {
+    let mut threads_per_process: Vec<(&str, Vec<ThreadView>)> = Vec::new();
+    for thread in profile.threads() {
+        match threads_per_process
+            .iter_mut()
+            .find(|(pid, _)| *pid == thread.pid())
+        {
+            Some((_, threads)) => threads.push(thread),
+            None => threads_per_process.push((thread.pid(), vec![thread])),
+        }
+    }
+
+    let mut iterations = Vec::new();
+    for (_, threads) in threads_per_process {
+        let mut sample_count = 0;
+        let mut self_counts: HashMap<String, u64> = HashMap::new();
+        let mut total_counts: HashMap<String, u64> = HashMap::new();
+        for thread in threads {
+            for sample in thread.samples() {
+                let weight = sample.weight.max(0) as u64;
+                sample_count += weight;
+                let mut stack_functions = Vec::new();
+                for frame in thread.stack_frames(sample.stack_index) {
+                    let lib_name = frame.resource_name.unwrap_or("unknown");
+                    let names = match (frame.lib, frame.address) {
+                        (Some(lib), Some(address)) => function_names.get(&(lib.debug_id, address)),
+                        _ => None,
+                    };
+                    match names {
+                        Some(names) => stack_functions
+                            .extend(names.iter().map(|name| format!("{name} ({lib_name})"))),
+                        None => stack_functions.push(format!("{} ({lib_name})", frame.name)),
+                    }
+                }
+                if let Some(leaf) = stack_functions.first() {
+                    *self_counts.entry(leaf.clone()).or_default() += weight;
+                }
+                let unique_functions: BTreeSet<String> = stack_functions.into_iter().collect();
+                for function in unique_functions {
+                    *total_counts.entry(function).or_default() += weight;
+                }
+            }
+        }
+        if sample_count > 0 {
+            iterations.push((sample_count, self_counts, total_counts));
+        }
+    }
+
+    let mut measurement = Measurement::default();
+    let iteration_count = iterations.len();
+    for (i, (sample_count, self_counts, total_counts)) in iterations.into_iter().enumerate() {
+        measurement.samples_per_iteration.push(sample_count);
+        let percent = |count: u64| count as f64 / sample_count as f64 * 100.0;
+        for (function, count) in total_counts {
+            let samples = measurement
+                .functions
+                .entry(function.clone())
+                .or_insert_with(|| FunctionSamples {
+                    self_percent: vec![0.0; iteration_count],
+                    total_percent: vec![0.0; iteration_count],
+                });
+            samples.total_percent[i] = percent(count);
+            samples.self_percent[i] = percent(self_counts.get(&function).copied().unwrap_or(0));
+        }
+    }
+    measurement
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn measurement(samples: &[u64], functions: &[(&str, &[f64], &[f64])]) -> Measurement {
+        Measurement {
+            samples_per_iteration: samples.to_vec(),
+            functions: functions
+                .iter()
+                .map(|(name, self_percent, total_percent)| {
+                    (
+                        name.to_string(),
+                        FunctionSamples {
+                            self_percent: self_percent.to_vec(),
+                            total_percent: total_percent.to_vec(),
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn compare_measurements() {
+        let baseline = measurement(
+            &[1000, 1010, 990],
+            &[
+                ("main (app)", &[0.0, 0.0, 0.0], &[100.0, 100.0, 100.0]),
+                ("parse (app)", &[20.0, 21.0, 19.0], &[50.0, 51.0, 49.0]),
+                ("noisy (app)", &[5.0, 15.0, 10.0], &[5.0, 15.0, 10.0]),
+                ("gone (app)", &[10.0, 10.0, 10.0], &[10.0, 10.0, 10.0]),
+            ],
+        );
+        let current = measurement(
+            &[1200, 1190, 1210],
+            &[
+                ("main (app)", &[0.0, 0.0, 0.0], &[100.0, 100.0, 100.0]),
+                ("parse (app)", &[25.0, 26.0, 24.0], &[51.0, 50.0, 52.0]),
+                ("noisy (app)", &[2.0, 20.0, 14.0], &[2.0, 20.0, 14.0]),
+            ],
+        );
+
+        let comparison = compare(&baseline, &current, &Thresholds::default());
+        assert!(!comparison.passed());
+        assert_eq!(comparison.checked_function_count, 4);
+        let regressions: Vec<_> = comparison.regressions.iter().map(|c| &c.metric).collect();
+        assert_eq!(
+            regressions,
+            vec![
+                &Metric::SampleCount,
+                &Metric::SelfPercent("parse (app)".to_string())
+            ]
+        );
+        let improvements: Vec<_> = comparison.improvements.iter().map(|c| &c.metric).collect();
+        assert_eq!(
+            improvements,
+            vec![
+                &Metric::SelfPercent("gone (app)".to_string()),
+                &Metric::TotalPercent("gone (app)".to_string())
+            ]
+        );
+
+        let thresholds: Thresholds = toml::from_str(
+            r#"
+            max-sample-count-increase = 25.0
+            [functions."parse (app)"]
+            max-self-increase = 6.0
+            "#,
+        )
+        .unwrap();
+        assert!(compare(&baseline, &current, &thresholds).passed());
+        assert!(toml::from_str::<Thresholds>("max-increase = 1.0").is_err());
+    }
+}
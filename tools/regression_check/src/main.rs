@@ -0,0 +1,266 @@
+use anyhow::{bail, Context};
+use clap::{Args, Parser, Subcommand};
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use regression_check::{
+    compare, measure_profile, record_profile, Comparison, Measurement, Metric, Thresholds,
+};
+
+#[derive(Parser)]
+#[command(
+    name = "regression-check",
+    about = "Compare the per-function sample distribution of a program against a baseline.",
+    after_help = "\
+EXAMPLES:
+    # Record a baseline measurement:
+    regression-check measure -o baseline.json -- ./target/profiling/my-benchmark
+
+    # Later, check for regressions. The exit code is 1 if there are regressions:
+    regression-check check --baseline baseline.json --thresholds thresholds.toml -- ./target/profiling/my-benchmark
+
+THRESHOLDS FILE:
+    max-self-increase = 1.0           # percentage points, default 1.0
+    max-total-increase = 2.0          # percentage points, default 2.0
+    max-sample-count-increase = 10.0  # percent, default 10.0
+    noise-factor = 2.0                # standard errors, default 2.0
+
+    [functions.\"my_crate::parse (my-benchmark)\"]
+    max-self-increase = 5.0
+    ignore = false"
+)]
+struct Opt {
+    #[command(subcommand)]
+    action: Action,
+}
+
+#[derive(Subcommand)]
+enum Action {
+    /// Record a profile with samply and save the measurement, e.g. as a new baseline.
+    Measure(MeasureArgs),
+
+    /// Compare a measurement against a baseline measurement.
+    Compare(CompareArgs),
+
+    /// Record a profile with samply and compare it against a baseline measurement.
+    Check(CheckArgs),
+}
+
+#[derive(Args)]
+struct MeasureArgs {
+    /// Save the measurement to this file.
+    #[arg(short, long)]
+    output: PathBuf,
+
+    #[command(flatten)]
+    source: SourceArgs,
+}
+
+#[derive(Args)]
+struct CompareArgs {
+    /// The baseline measurement.
+    baseline: PathBuf,
+
+    /// The measurement which is checked.
+    current: PathBuf,
+
+    #[command(flatten)]
+    threshold_args: ThresholdArgs,
+}
+
+#[derive(Args)]
+struct CheckArgs {
+    /// The baseline measurement.
+    #[arg(long)]
+    baseline: PathBuf,
+
+    /// Also save the new measurement to this file.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    #[command(flatten)]
+    threshold_args: ThresholdArgs,
+
+    #[command(flatten)]
+    source: SourceArgs,
+}
+
+#[derive(Args)]
+struct ThresholdArgs {
+    /// A TOML file with the allowed changes. See below for the format.
+    #[arg(long)]
+    thresholds: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct SourceArgs {
+    /// Measure this existing profile instead of recording a new one. Every
+    /// process in the profile is treated as one iteration.
+    #[arg(long, conflicts_with = "command")]
+    profile: Option<PathBuf>,
+
+    /// The samply binary.
+    #[arg(long, default_value = "samply")]
+    samply: PathBuf,
+
+    /// How many times to run the command. Each run needs to be a single
+    /// process, because the runs are told apart by their process ID.
+    #[arg(short = 'n', long, default_value_t = 5)]
+    iteration_count: u32,
+
+    /// Sampling rate, in Hz.
+    #[arg(short, long)]
+    rate: Option<f64>,
+
+    /// The command to profile, and its arguments.
+    #[arg(trailing_var_arg = true, required_unless_present = "profile")]
+    command: Vec<OsString>,
+}
+
+fn main() -> ExitCode {
+    match run(Opt::parse()) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(err) => {
+            eprintln!("Error: {err:#}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// Returns whether the check passed.
+fn run(opt: Opt) -> anyhow::Result<bool> {
+    match opt.action {
+        Action::Measure(args) => {
+            let measurement = args.source.measure()?;
+            save_measurement(&measurement, &args.output)?;
+            eprintln!(
+                "Saved the measurement of {} iterations to {:?}.",
+                measurement.samples_per_iteration.len(),
+                args.output
+            );
+            Ok(true)
+        }
+        Action::Compare(args) => {
+            let baseline = load_measurement(&args.baseline)?;
+            let current = load_measurement(&args.current)?;
+            let thresholds = args.threshold_args.thresholds()?;
+            let comparison = compare(&baseline, &current, &thresholds);
+            print_comparison(&comparison);
+            Ok(comparison.passed())
+        }
+        Action::Check(args) => {
+            let baseline = load_measurement(&args.baseline)?;
+            let thresholds = args.threshold_args.thresholds()?;
+            let current = args.source.measure()?;
+            if let Some(output) = &args.output {
+                save_measurement(&current, output)?;
+            }
+            let comparison = compare(&baseline, &current, &thresholds);
+            print_comparison(&comparison);
+            Ok(comparison.passed())
+        }
+    }
+}
+
+impl SourceArgs {
+    fn measure(&self) -> anyhow::Result<Measurement> {
+        let runtime = tokio::runtime::Runtime::new()?;
+        if let Some(profile) = &self.profile {
+            return runtime.block_on(measure_profile(profile));
+        }
+
+        let dir = tempfile::tempdir().context("Could not create a temporary directory")?;
+        let profile = dir.path().join("profile.json");
+        record_profile(
+            &self.samply,
+            self.iteration_count,
+            self.rate,
+            &self.command,
+            &profile,
+        )?;
+        let measurement = runtime.block_on(measure_profile(&profile))?;
+        // Iterations are told apart by their process, so a command which
+        // starts other processes can't be measured.
+        let measured_count = measurement.samples_per_iteration.len();
+        if measured_count != self.iteration_count as usize {
+            bail!(
+                "Expected {} iterations, but the profile has samples from {measured_count} processes. \
+                 Commands which start other processes are not supported.",
+                self.iteration_count
+            );
+        }
+        Ok(measurement)
+    }
+}
+
+impl ThresholdArgs {
+    fn thresholds(&self) -> anyhow::Result<Thresholds> {
+        match &self.thresholds {
+            Some(path) => Thresholds::from_file(path),
+            None => Ok(Thresholds::default()),
+        }
+    }
+}
+
+fn load_measurement(path: &Path) -> anyhow::Result<Measurement> {
+    let file = File::open(path).with_context(|| format!("Could not open {path:?}"))?;
+    serde_json::from_reader(BufReader::new(file))
+        .with_context(|| format!("Could not parse the measurement {path:?}"))
+}
+
+fn save_measurement(measurement: &Measurement, path: &Path) -> anyhow::Result<()> {
+    let file = File::create(path).with_context(|| format!("Could not create {path:?}"))?;
+    serde_json::to_writer_pretty(BufWriter::new(file), measurement)
+        .with_context(|| format!("Could not write {path:?}"))
+}
+
+fn print_comparison(comparison: &Comparison) {
+    for (label, changes) in [
+        ("Regression", &comparison.regressions),
+        ("Improvement", &comparison.improvements),
+    ] {
+        for change in changes {
+            let (baseline, current, unit) = if change.metric == Metric::SampleCount {
+                (
+                    format!("{:.0}", change.baseline.mean),
+                    format!("{:.0}", change.current.mean),
+                    "%",
+                )
+            } else {
+                (
+                    format!("{:.1}%", change.baseline.mean),
+                    format!("{:.1}%", change.current.mean),
+                    " points",
+                )
+            };
+            println!(
+                "{label}: {}: {baseline} -> {current} ({:+.1}{unit}, limit {:.1}{unit})",
+                change.metric,
+                change.difference(),
+                change.limit
+            );
+        }
+    }
+    if comparison.passed() {
+        println!(
+            "No regressions in the {} checked functions.",
+            comparison.checked_function_count
+        );
+    } else {
+        println!(
+            "Found {} regressions in the {} checked functions.",
+            comparison.regressions.len(),
+            comparison.checked_function_count
+        );
+    }
+}
+
+#[test]
+fn verify_cli() {
+    use clap::CommandFactory;
+    Opt::command().debug_assert()
+}
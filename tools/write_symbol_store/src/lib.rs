@@ -0,0 +1,73 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+pub use samply_symbols::SymbolStoreLayout;
+
+/// What happened to a file when it was added to the symbol store.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StoreAction {
+    /// The file was copied to this path.
+    Copied(PathBuf),
+    /// A file already exists at this path and was left alone.
+    AlreadyExists(PathBuf),
+    /// The file would be copied to this path, but we're doing a dry run.
+    WouldCopy(PathBuf),
+}
+
+/// Copies the file at `file_path` into the symbol store at `store_dir`, at the
+/// path(s) dictated by `layout`.
+///
+/// Returns an empty Vec if the file doesn't belong in this kind of symbol store.
+/// Existing files in the store are not overwritten: the paths in a symbol store
+/// are derived from the file's identifiers, so a file that is already present
+/// is expected to have the same contents.
+pub fn add_to_symbol_store(
+    store_dir: &Path,
+    file_path: &Path,
+    layout: SymbolStoreLayout,
+    dry_run: bool,
+) -> anyhow::Result<Vec<StoreAction>> {
+    let file_name = file_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| anyhow::anyhow!("Invalid file name: {file_path:?}"))?;
+    let file = File::open(file_path)?;
+    let mmap = unsafe { memmap2::MmapOptions::new().map(&file)? };
+    let rel_paths = samply_symbols::symbol_store_paths(mmap, file_name, layout)?;
+
+    let mut actions = Vec::new();
+    for rel_path in rel_paths {
+        if rel_path
+            .split('/')
+            .any(|component| matches!(component, "" | "." | "..") || component.contains('\\'))
+        {
+            anyhow::bail!("Refusing to store {file_path:?} at the path {rel_path:?}");
+        }
+        let dest_path = rel_path
+            .split('/')
+            .fold(store_dir.to_path_buf(), |path, component| {
+                path.join(component)
+            });
+        if dest_path.exists() {
+            actions.push(StoreAction::AlreadyExists(dest_path));
+        } else if dry_run {
+            actions.push(StoreAction::WouldCopy(dest_path));
+        } else {
+            if let Some(dir) = dest_path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            // Copy to a temporary file first and rename it into place, so that
+            // a partially copied file never shows up in the symbol store.
+            let mut temp_file_name = dest_path.file_name().unwrap_or_default().to_owned();
+            temp_file_name.push(format!(".{}.part", std::process::id()));
+            let temp_path = dest_path.with_file_name(temp_file_name);
+            std::fs::copy(file_path, &temp_path)?;
+            if let Err(err) = std::fs::rename(&temp_path, &dest_path) {
+                let _ = std::fs::remove_file(&temp_path);
+                return Err(err.into());
+            }
+            actions.push(StoreAction::Copied(dest_path));
+        }
+    }
+    Ok(actions)
+}
@@ -0,0 +1,75 @@
+use clap::{Parser, ValueEnum};
+use std::path::PathBuf;
+
+use write_symbol_store::{add_to_symbol_store, StoreAction, SymbolStoreLayout};
+
+#[derive(Parser)]
+#[command(
+    name = "write-symbol-store",
+    about = "Copy binaries and debug files into a symbol store directory, using the directory layout of a symsrv, Breakpad or debuginfod symbol server."
+)]
+struct Opt {
+    /// The root directory of the symbol store
+    store_dir: PathBuf,
+
+    /// The binaries and debug files to add to the store
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+
+    /// The directory layout of the store
+    #[arg(short, long, value_enum)]
+    layout: Layout,
+
+    /// Only print where the files would be stored, don't copy anything.
+    #[arg(short = 'n', long)]
+    dry_run: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Layout {
+    /// name.pdb/GUIDAGE/name.pdb and name.dll/TIMESTAMPSIZE/name.dll
+    Symsrv,
+    /// name.pdb/ID/name.sym
+    Breakpad,
+    /// buildid/<id>/debuginfo and buildid/<id>/executable
+    Debuginfod,
+}
+
+impl From<Layout> for SymbolStoreLayout {
+    fn from(layout: Layout) -> Self {
+        match layout {
+            Layout::Symsrv => SymbolStoreLayout::Symsrv,
+            Layout::Breakpad => SymbolStoreLayout::Breakpad,
+            Layout::Debuginfod => SymbolStoreLayout::Debuginfod,
+        }
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let opt = Opt::parse();
+    for file_path in &opt.files {
+        let actions =
+            add_to_symbol_store(&opt.store_dir, file_path, opt.layout.into(), opt.dry_run)?;
+        if actions.is_empty() {
+            eprintln!("Skipping {file_path:?}, it doesn't belong in this kind of symbol store.");
+        }
+        for action in actions {
+            match action {
+                StoreAction::Copied(path) => println!("Copied {file_path:?} to {path:?}"),
+                StoreAction::AlreadyExists(path) => {
+                    println!("Skipping {file_path:?}, {path:?} already exists")
+                }
+                StoreAction::WouldCopy(path) => {
+                    println!("Would copy {file_path:?} to {path:?}")
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn verify_cli() {
+    use clap::CommandFactory;
+    Opt::command().debug_assert()
+}
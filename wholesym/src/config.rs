@@ -1,5 +1,6 @@
 use std::{collections::HashMap, path::PathBuf};
 
+use samply_symbols::DemangleOptions;
 use symsrv::{parse_nt_symbol_path, NtSymbolPathEntry};
 
 /// The configuration of a [`SymbolManager`](crate::SymbolManager).
@@ -19,6 +20,8 @@ pub struct SymbolManagerConfig {
     pub(crate) use_spotlight: bool,
     pub(crate) debuginfod_cache_dir_if_not_installed: Option<PathBuf>,
     pub(crate) debuginfod_servers: Vec<(String, PathBuf)>,
+    pub(crate) demangle_options: DemangleOptions,
+    pub(crate) source_file_cache_dir: Option<PathBuf>,
 }
 
 impl SymbolManagerConfig {
@@ -175,4 +178,22 @@ impl SymbolManagerConfig {
         self.use_spotlight = use_spotlight;
         self
     }
+
+    /// Set a directory to store downloaded source files in, and allow downloading them.
+    ///
+    /// Source files are downloaded from the URLs which are found in the debug info,
+    /// i.e. from the srcsrv stream or the SourceLink mappings of Windows PDB files,
+    /// or from the `.debug_sourcelink` section of ELF files. Without this directory,
+    /// only local source files and source files from debuginfod servers are available.
+    pub fn source_file_cache_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.source_file_cache_dir = Some(dir.into());
+        self
+    }
+
+    /// Controls how function names are demangled, e.g. whether C++ return types
+    /// and template arguments or Rust symbol hashes are included.
+    pub fn demangle_options(mut self, demangle_options: DemangleOptions) -> Self {
+        self.demangle_options = demangle_options;
+        self
+    }
 }
@@ -64,6 +64,23 @@ impl DebuginfodSymbolCache {
             DebuginfodSymbolCacheInner::Manual(manual) => manual.get_file(buildid, file_type).await,
         }
     }
+
+    /// Gets a source file from the `/buildid/<buildid>/source/<path>` endpoint.
+    /// `source_path` is the absolute path of the source file, as found in the debug info.
+    pub async fn get_source_file(
+        &self,
+        buildid: &str,
+        source_path: &str,
+    ) -> Option<symsrv::FileContents> {
+        match &self.0 {
+            DebuginfodSymbolCacheInner::Official(official) => {
+                official.get_source_file(buildid, source_path).await
+            }
+            DebuginfodSymbolCacheInner::Manual(manual) => {
+                manual.get_source_file(buildid, source_path).await
+            }
+        }
+    }
 }
 
 /// Uses debuginfod-find on the shell maybe, not sure
@@ -81,6 +98,28 @@ impl OfficialDebuginfodSymbolCache {
     pub async fn get_file(&self, _buildid: &str, _file_type: &str) -> Option<symsrv::FileContents> {
         None // TODO
     }
+
+    /// `debuginfod-find source` downloads the file into the official cache and
+    /// prints its path.
+    pub async fn get_source_file(
+        &self,
+        buildid: &str,
+        source_path: &str,
+    ) -> Option<symsrv::FileContents> {
+        let output = tokio::process::Command::new("debuginfod-find")
+            .args(["source", buildid, source_path])
+            .output()
+            .await
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let path = std::str::from_utf8(&output.stdout).ok()?.trim_end();
+        let file = std::fs::File::open(path).ok()?;
+        Some(FileContents::Mmap(unsafe {
+            memmap2::MmapOptions::new().map(&file).ok()?
+        }))
+    }
 }
 
 /// Full reimplementation of a `debuginfod` client, used on non-Linux platforms or on Linux if debuginfod is not installed.
@@ -96,9 +135,40 @@ impl ManualDebuginfodSymbolCache {
         &self,
         buildid: &str,
         file_type: &str,
+    ) -> Option<symsrv::FileContents> {
+        self.get_cached_file(buildid, Path::new(file_type)).await
+    }
+
+    pub async fn get_file(&self, buildid: &str, file_type: &str) -> Option<symsrv::FileContents> {
+        self.get_file_impl(buildid, file_type, Path::new(file_type))
+            .await
+    }
+
+    pub async fn get_source_file(
+        &self,
+        buildid: &str,
+        source_path: &str,
+    ) -> Option<symsrv::FileContents> {
+        // Store the file at <cache_dir>/<buildid>/source/<source_path>, ignoring
+        // any path components which would escape that directory.
+        let mut cache_rel_path = PathBuf::from("source");
+        for component in source_path.split(['/', '\\']) {
+            if !component.is_empty() && component != "." && component != ".." {
+                cache_rel_path.push(component);
+            }
+        }
+        let url_path = format!("source{}", escape_source_path(source_path));
+        self.get_file_impl(buildid, &url_path, &cache_rel_path)
+            .await
+    }
+
+    async fn get_cached_file(
+        &self,
+        buildid: &str,
+        cache_rel_path: &Path,
     ) -> Option<symsrv::FileContents> {
         for (_server_base_url, cache_dir) in &self.servers_and_caches {
-            let cached_file_path = cache_dir.join(buildid).join(file_type);
+            let cached_file_path = cache_dir.join(buildid).join(cache_rel_path);
             if self.verbose {
                 eprintln!("Opening file {:?}", cached_file_path.to_string_lossy());
             }
@@ -111,14 +181,27 @@ impl ManualDebuginfodSymbolCache {
         None
     }
 
-    pub async fn get_file(&self, buildid: &str, file_type: &str) -> Option<symsrv::FileContents> {
-        if let Some(f) = self.get_file_only_cached(buildid, file_type).await {
+    /// Gets the file at `<server_base_url>/buildid/<buildid>/<url_path>` and stores
+    /// it at `<cache_dir>/<buildid>/<cache_rel_path>`.
+    async fn get_file_impl(
+        &self,
+        buildid: &str,
+        url_path: &str,
+        cache_rel_path: &Path,
+    ) -> Option<symsrv::FileContents> {
+        if let Some(f) = self.get_cached_file(buildid, cache_rel_path).await {
             return Some(f);
         }
 
         for (server_base_url, cache_dir) in &self.servers_and_caches {
             if let Ok(file) = self
-                .get_file_from_server(buildid, file_type, server_base_url, cache_dir)
+                .get_file_from_server(
+                    buildid,
+                    url_path,
+                    cache_rel_path,
+                    server_base_url,
+                    cache_dir,
+                )
                 .await
             {
                 return Some(file);
@@ -130,17 +213,18 @@ impl ManualDebuginfodSymbolCache {
     async fn get_file_from_server(
         &self,
         buildid: &str,
-        file_type: &str,
+        url_path: &str,
+        cache_rel_path: &Path,
         server_base_url: &str,
         cache_dir: &Path,
     ) -> Result<FileContents, Box<dyn std::error::Error>> {
-        let url = format!("{server_base_url}/buildid/{buildid}/{file_type}");
+        let url = format!("{server_base_url}/buildid/{buildid}/{url_path}");
         if self.verbose {
             eprintln!("Downloading {url}...");
         }
         let sym_file_response = reqwest::get(&url).await?.error_for_status()?;
         let mut stream = sym_file_response.bytes_stream();
-        let dest_path = cache_dir.join(buildid).join(file_type);
+        let dest_path = cache_dir.join(buildid).join(cache_rel_path);
         if let Some(dir) = dest_path.parent() {
             tokio::fs::create_dir_all(dir).await?;
         }
@@ -163,3 +247,18 @@ impl ManualDebuginfodSymbolCache {
         }))
     }
 }
+
+/// Percent-encodes the characters of a source file path which can't appear
+/// verbatim in a URL path, like the official debuginfod client does.
+fn escape_source_path(source_path: &str) -> String {
+    let mut escaped = String::with_capacity(source_path.len());
+    for b in source_path.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                escaped.push(b as char)
+            }
+            _ => escaped.push_str(&format!("%{b:02X}")),
+        }
+    }
+    escaped
+}
@@ -26,6 +26,8 @@ pub enum WholesymFileLocation {
     BreakpadSymindexFile(String),
     DebuginfodDebugFile(ElfBuildId),
     DebuginfodExecutable(ElfBuildId),
+    DebuginfodSourceFile(ElfBuildId, String),
+    SourceFileUrl(String),
 }
 
 impl FileLocation for WholesymFileLocation {
@@ -71,9 +73,17 @@ impl FileLocation for WholesymFileLocation {
                         .map(|base_path| Self::LocalFile(base_path.join(source_file_path)))
                 }
             }
-            Self::DebuginfodDebugFile(_build_id) | Self::DebuginfodExecutable(_build_id) => {
-                // TODO: load source file via debuginfod
-                None
+            Self::DebuginfodDebugFile(build_id) | Self::DebuginfodExecutable(build_id) => {
+                // Debuginfod servers only serve source files for absolute paths, as
+                // they appear in the debug info.
+                if source_file_path.starts_with('/') {
+                    Some(Self::DebuginfodSourceFile(
+                        build_id.clone(),
+                        source_file_path.to_owned(),
+                    ))
+                } else {
+                    None
+                }
             }
             _ => {
                 // We don't have local source files for debug files from symbol servers.
@@ -83,6 +93,10 @@ impl FileLocation for WholesymFileLocation {
         }
     }
 
+    fn location_for_source_file_url(&self, url: &str) -> Option<Self> {
+        Some(Self::SourceFileUrl(url.to_owned()))
+    }
+
     fn location_for_breakpad_symindex(&self) -> Option<Self> {
         match self {
             Self::BreakpadSymbolServerFile(rel_path) | Self::LocalBreakpadFile(_, rel_path) => {
@@ -290,7 +304,67 @@ impl Helper {
                 .get_file(&build_id.to_string(), "debuginfo")
                 .await
                 .ok_or_else(|| "Debuginfod could not find debuginfo".into()),
+            WholesymFileLocation::DebuginfodSourceFile(build_id, source_path) => self
+                .debuginfod_symbol_cache
+                .as_ref()
+                .ok_or("Debuginfod is not enabled")?
+                .get_source_file(&build_id.to_string(), &source_path)
+                .await
+                .ok_or_else(|| "Debuginfod could not find the source file".into()),
+            WholesymFileLocation::SourceFileUrl(url) => self.get_source_file_from_url(&url).await,
+        }
+    }
+
+    async fn get_source_file_from_url(&self, url: &str) -> FileAndPathHelperResult<FileContents> {
+        let cache_dir = self
+            .config
+            .source_file_cache_dir
+            .as_deref()
+            .ok_or("No source file cache dir configured, not downloading source files")?;
+
+        // Store the file at <cache_dir>/<host>/<path>, ignoring any path components
+        // which would escape that directory.
+        let url_without_scheme = url
+            .strip_prefix("https://")
+            .ok_or("Only https URLs are supported for source files")?;
+        let mut dest_path = cache_dir.to_owned();
+        for component in url_without_scheme.split('/') {
+            if !component.is_empty() && component != "." && component != ".." {
+                dest_path.push(component);
+            }
         }
+
+        if !dest_path.exists() {
+            if self.config.verbose {
+                eprintln!("Downloading {url}...");
+            }
+            let response = reqwest::get(url).await?.error_for_status()?;
+            let bytes = response.bytes().await?;
+            if let Some(dir) = dest_path.parent() {
+                tokio::fs::create_dir_all(dir).await?;
+            }
+            if self.config.verbose {
+                eprintln!("Saving bytes to {dest_path:?}.");
+            }
+            // Write to a temporary file first, so that a partially written file
+            // is never mistaken for a complete one.
+            let mut temp_file_name = dest_path.file_name().unwrap_or_default().to_owned();
+            temp_file_name.push(format!(".{}.part", std::process::id()));
+            let temp_path = dest_path.with_file_name(temp_file_name);
+            tokio::fs::write(&temp_path, &bytes).await?;
+            if let Err(err) = tokio::fs::rename(&temp_path, &dest_path).await {
+                let _ = tokio::fs::remove_file(&temp_path).await;
+                return Err(err.into());
+            }
+        }
+
+        if self.config.verbose {
+            eprintln!("Opening file {:?}", dest_path.to_string_lossy());
+        }
+        let file = File::open(&dest_path)?;
+        Ok(FileContents::Mmap(unsafe {
+            memmap2::MmapOptions::new().map(&file)?
+        }))
     }
 
     async fn get_bp_sym_file(&self, rel_path: &str) -> FileAndPathHelperResult<FileContents> {
@@ -149,10 +149,13 @@ mod moria_mac_spotlight;
 mod symbol_manager;
 
 pub use config::SymbolManagerConfig;
+#[cfg(feature = "api")]
+pub use samply_api;
 pub use samply_symbols;
 pub use samply_symbols::{
-    AddressInfo, CodeId, ElfBuildId, Error, ExternalFileAddressInFileRef, ExternalFileAddressRef,
-    ExternalFileRef, ExternalFileSymbolMap, FrameDebugInfo, FramesLookupResult, LibraryInfo,
-    MappedPath, MultiArchDisambiguator, PeCodeId, SourceFilePath, SymbolInfo,
+    AddressInfo, CodeId, DemangleOptions, ElfBuildId, Error, ExternalFileAddressInFileRef,
+    ExternalFileAddressRef, ExternalFileRef, ExternalFileSymbolMap, FrameDebugInfo,
+    FramesLookupResult, LibraryInfo, MappedPath, MultiArchDisambiguator, PeCodeId, SourceFilePath,
+    SymbolInfo,
 };
 pub use symbol_manager::{SymbolFileOrigin, SymbolManager, SymbolMap};
@@ -5,7 +5,7 @@ use std::{future::Future, pin::Pin};
 use debugid::DebugId;
 use samply_symbols::{
     self, AddressInfo, Error, ExternalFileAddressRef, ExternalFileRef, ExternalFileSymbolMap,
-    FrameDebugInfo, LibraryInfo, MultiArchDisambiguator,
+    FrameDebugInfo, FramesLookupResult, LibraryInfo, MultiArchDisambiguator,
 };
 use yoke::{Yoke, Yokeable};
 
@@ -94,10 +94,19 @@ impl SymbolMap {
 
     /// Iterate over all symbols in this `SymbolMap`.
     ///
-    /// This iterator yields the relative address and the name of each symbol.
+    /// This iterator yields the relative address and the raw (possibly mangled)
+    /// name of each symbol.
     pub fn iter_symbols(&self) -> Box<dyn Iterator<Item = (u32, Cow<'_, str>)> + '_> {
         self.0.iter_symbols()
     }
+
+    /// Iterate over all symbols in this `SymbolMap`, with demangled names.
+    ///
+    /// The names are demangled according to the [`DemangleOptions`](crate::DemangleOptions)
+    /// from the [`SymbolManagerConfig`].
+    pub fn iter_demangled_symbols(&self) -> Box<dyn Iterator<Item = (u32, Cow<'_, str>)> + '_> {
+        self.0.iter_demangled_symbols()
+    }
 }
 
 /// Allows obtaining [`SymbolMap`]s.
@@ -108,9 +117,11 @@ pub struct SymbolManager {
 impl SymbolManager {
     /// Create a new `SymbolManager` with the given config.
     pub fn with_config(config: SymbolManagerConfig) -> Self {
+        let demangle_options = config.demangle_options;
         let helper = Helper::with_config(config);
         let helper_with_symbol_manager = Yoke::attach_to_cart(Box::new(helper), |helper| {
-            let symbol_manager = samply_symbols::SymbolManager::with_helper(helper);
+            let mut symbol_manager = samply_symbols::SymbolManager::with_helper(helper);
+            symbol_manager.set_demangle_options(demangle_options);
             SymbolManagerWrapperTypeErased(Box::new(SymbolManagerWrapper(symbol_manager)))
         });
         Self {
@@ -192,9 +203,13 @@ impl SymbolManager {
     /// `(debug_name, debug_id)` pairs, so there needs to be some stored auxiliary
     /// information which allows us to find the right debug files for the request.
     /// The list of "known libraries" is this auxiliary information.
-    pub fn add_known_library(&mut self, lib_info: LibraryInfo) {
+    ///
+    /// Libraries can be added while queries are running.
+    pub fn add_known_library(&self, lib_info: LibraryInfo) {
         self.helper_with_symbol_manager
-            .with_mut(|manager| manager.0.add_known_lib(lib_info));
+            .get()
+            .0
+            .add_known_lib(lib_info);
     }
 
     /// Obtain a symbol map for the given `debug_name` and `debug_id`.
@@ -241,6 +256,50 @@ impl SymbolManager {
             .await
     }
 
+    /// Look up the function names for the given addresses, which are relative to
+    /// the base address of the library with the given `debug_name` and `debug_id`.
+    ///
+    /// For every address which has a symbol, this returns the names of the
+    /// inlined functions at the address, innermost first, followed by the name
+    /// of the outer function. If there is no debug info for the address, the
+    /// list only contains the symbol name. External debug info is looked up
+    /// with [`lookup_external`](SymbolManager::lookup_external), so passing the
+    /// addresses in sorted order makes the lookups faster.
+    pub async fn lookup_function_names(
+        &self,
+        debug_name: &str,
+        debug_id: DebugId,
+        addresses: impl IntoIterator<Item = u32>,
+    ) -> Result<Vec<(u32, Vec<String>)>, Error> {
+        let symbol_map = self.load_symbol_map(debug_name, debug_id).await?;
+        let symbol_file_origin = symbol_map.symbol_file_origin();
+        let mut function_names = Vec::new();
+        for address in addresses {
+            let address_info = match symbol_map.lookup_relative_address(address) {
+                Some(address_info) => address_info,
+                None => continue,
+            };
+            let frames = match address_info.frames {
+                FramesLookupResult::Available(frames) => Some(frames),
+                FramesLookupResult::External(external_address) => {
+                    self.lookup_external(&symbol_file_origin, &external_address)
+                        .await
+                }
+                FramesLookupResult::Unavailable => None,
+            };
+            let mut names: Vec<String> = frames
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|frame| frame.function)
+                .collect();
+            if names.is_empty() {
+                names.push(address_info.symbol.name);
+            }
+            function_names.push((address, names));
+        }
+        Ok(function_names)
+    }
+
     /// Manually load and return an external file with additional debug info.
     /// This is a lower-level alternative to [`lookup_external`](SymbolManager::lookup_external)
     /// and can be used if more control over caching is desired.
@@ -277,7 +336,7 @@ impl SymbolManager {
 struct SymbolManagerWrapperTypeErased<'h>(Box<dyn SymbolManagerTrait + 'h + Send + Sync>);
 
 trait SymbolManagerTrait {
-    fn add_known_lib(&mut self, lib_info: LibraryInfo);
+    fn add_known_lib(&self, lib_info: LibraryInfo);
 
     fn load_symbol_map<'a>(
         &'a self,
@@ -330,7 +389,7 @@ impl<'h> SymbolManagerWrapper<'h> {
 }
 
 impl<'h> SymbolManagerTrait for SymbolManagerWrapper<'h> {
-    fn add_known_lib(&mut self, lib_info: LibraryInfo) {
+    fn add_known_lib(&self, lib_info: LibraryInfo) {
         self.0.helper().add_known_lib(lib_info);
     }
 
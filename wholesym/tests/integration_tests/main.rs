@@ -181,7 +181,7 @@ fn disassemble_libcrypto() {
         ..Default::default()
     };
 
-    let mut symbol_manager = wholesym::SymbolManager::with_config(Default::default());
+    let symbol_manager = wholesym::SymbolManager::with_config(Default::default());
     symbol_manager.add_known_library(lib_info);
     let response_json = futures::executor::block_on(
         symbol_manager.query_json_api("/asm/v1", r#"{"debugName":"libcorecrypto.dylib","debugId":"6A5FFEB0E606324EB687DA95C362CE050","name":"libcorecrypto.dylib","codeId":null,"startAddress":"0x5844","size":"0x1c"}"#),